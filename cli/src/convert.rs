@@ -0,0 +1,59 @@
+//! The `convert` subcommand, which translates Lua files into Clue
+
+use crate::{files::lua_files, report};
+use clap::Args;
+use clue_core::{check, converter::convert_lua};
+use std::{fs, path::PathBuf};
+
+#[derive(Args)]
+pub struct ConvertArgs {
+	/// The path to the *.lua file to convert.
+	/// If the path points to a directory, every *.lua file inside it will be converted.
+	/// The Clue code is saved next to each file, in a *.clue file with the same name.
+	#[clap(default_value = ".")]
+	path: PathBuf,
+
+	/// Replace the *.clue files that already exist
+	#[clap(long)]
+	overwrite: bool,
+}
+
+/// Converts every Lua file of the given path, reporting what could not be translated
+pub fn convert(args: ConvertArgs) -> Result<(), String> {
+	let mut failed = 0;
+	let mut untranslated = 0;
+	for file in lua_files(args.path)? {
+		let name = file.to_string_lossy().into_owned();
+		let output = file.with_extension("clue");
+		if output.exists() && !args.overwrite {
+			println!(
+				"Skipped file \"{name}\": \"{}\" already exists, use --overwrite to replace it",
+				output.display()
+			);
+			continue;
+		}
+		let code = check!(fs::read_to_string(&file));
+		match convert_lua(&code, &name) {
+			Ok((converted, warnings)) => {
+				report(&warnings);
+				untranslated += warnings.len();
+				check!(fs::write(&output, converted));
+				println!("Converted file \"{name}\" to \"{}\"", output.display());
+			}
+			Err(diagnostics) => {
+				report(&diagnostics);
+				failed += 1;
+			}
+		}
+	}
+	match untranslated {
+		0 => {}
+		1 => println!("1 construct could not be translated and must be fixed by hand"),
+		n => println!("{n} constructs could not be translated and must be fixed by hand"),
+	}
+	match failed {
+		0 => Ok(()),
+		1 => Err(String::from("1 file failed to be converted!")),
+		n => Err(format!("{n} files failed to be converted!")),
+	}
+}
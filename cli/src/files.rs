@@ -0,0 +1,59 @@
+//! Helpers shared by the subcommands that work on every source file of a directory
+
+use clue_core::check;
+use serde::de::DeserializeOwned;
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+
+/// Searches the given path and its parents for a file named like one of the given names
+pub fn find_config(path: &Path, names: &[&str]) -> Option<PathBuf> {
+	fs::canonicalize(path)
+		.ok()?
+		.ancestors()
+		.flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+		.find(|config| config.is_file())
+}
+
+/// Reads a TOML config file
+pub fn read_config<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+	let config = check!(fs::read_to_string(path));
+	toml::from_str(&config).map_err(|e| format!("Invalid config file {}: {e}", path.display()))
+}
+
+fn find_files(path: &Path, extension: &str, files: &mut Vec<PathBuf>) -> Result<(), String> {
+	let mut entries = check!(check!(fs::read_dir(path)).collect::<Result<Vec<_>, _>>());
+	entries.sort_by_key(|entry| entry.file_name());
+	for entry in entries {
+		let path = entry.path();
+		if path.is_dir() {
+			find_files(&path, extension, files)?;
+		} else if path.extension().map_or(false, |found| found == extension) {
+			files.push(path);
+		}
+	}
+	Ok(())
+}
+
+fn source_files(path: PathBuf, extension: &str) -> Result<Vec<PathBuf>, String> {
+	let mut files = Vec::new();
+	if path.is_dir() {
+		find_files(&path, extension, &mut files)?;
+	} else if path.is_file() {
+		files.push(path);
+	} else {
+		return Err(format!("{} was not found!", path.display()));
+	}
+	Ok(files)
+}
+
+/// Returns the given path if it's a file, or every *.clue file inside it if it's a directory
+pub fn clue_files(path: PathBuf) -> Result<Vec<PathBuf>, String> {
+	source_files(path, "clue")
+}
+
+/// Returns the given path if it's a file, or every *.lua file inside it if it's a directory
+pub fn lua_files(path: PathBuf) -> Result<Vec<PathBuf>, String> {
+	source_files(path, "lua")
+}
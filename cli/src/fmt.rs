@@ -0,0 +1,74 @@
+//! The `fmt` subcommand, which formats *.clue files in place
+
+use crate::{
+	files::{clue_files, find_config, read_config},
+	report,
+};
+use clap::Args;
+use clue_core::{
+	check,
+	formatter::{format_code, FormatOptions},
+};
+use std::{fs, path::PathBuf};
+
+/// The config files searched in the formatted directory and its parents
+const CONFIG_FILES: [&str; 2] = ["cluefmt.toml", ".cluefmt.toml"];
+
+#[derive(Args)]
+pub struct FmtArgs {
+	/// The path to the *.clue file to format.
+	/// If the path points to a directory, every *.clue file inside it will be formatted.
+	#[clap(default_value = ".")]
+	path: PathBuf,
+
+	/// Don't save the formatted files, fail if any of them is not formatted
+	#[clap(long)]
+	check: bool,
+
+	/// Use the given config file instead of searching for cluefmt.toml
+	#[clap(long, value_name = "FILE NAME")]
+	config: Option<PathBuf>,
+}
+
+/// Formats every file of the given path, or checks that they are already formatted
+pub fn format(args: FmtArgs) -> Result<(), String> {
+	let options = match args
+		.config
+		.or_else(|| find_config(&args.path, &CONFIG_FILES))
+	{
+		Some(config) => read_config(&config)?,
+		None => FormatOptions::default(),
+	};
+	let files = clue_files(args.path)?;
+	let mut failed = 0;
+	let mut unformatted = 0;
+	for file in files {
+		let name = file.to_string_lossy().into_owned();
+		let code = check!(fs::read_to_string(&file));
+		let formatted = match format_code(&code, &name, &options) {
+			Ok(formatted) => formatted,
+			Err(diagnostics) => {
+				report(&diagnostics);
+				failed += 1;
+				continue;
+			}
+		};
+		if formatted == code {
+			continue;
+		}
+		if args.check {
+			println!("File \"{name}\" is not formatted");
+			unformatted += 1;
+		} else {
+			check!(fs::write(&file, formatted));
+			println!("Formatted file \"{name}\"");
+		}
+	}
+	match (failed, unformatted) {
+		(0, 0) => Ok(()),
+		(1, _) => Err(String::from("1 file failed to be formatted!")),
+		(0, 1) => Err(String::from("1 file is not formatted!")),
+		(0, n) => Err(format!("{n} files are not formatted!")),
+		(n, _) => Err(format!("{n} files failed to be formatted!")),
+	}
+}
@@ -0,0 +1,52 @@
+//! The `lint` subcommand, which reports code that is most likely a mistake
+
+use crate::{
+	build_options,
+	files::{clue_files, find_config, read_config},
+	manifest::apply_manifest,
+	report, BuildArgs,
+};
+use clap::{ArgMatches, Args};
+use clue_core::{
+	check,
+	linter::{lint_code, LintRules},
+};
+use std::{fs, path::PathBuf};
+
+/// The config files searched in the linted directory and its parents
+const CONFIG_FILES: [&str; 2] = ["cluelint.toml", ".cluelint.toml"];
+
+#[derive(Args)]
+pub struct LintArgs {
+	#[clap(flatten)]
+	build: BuildArgs,
+
+	/// Use the given config file instead of searching for cluelint.toml
+	#[clap(long, value_name = "FILE NAME")]
+	config: Option<PathBuf>,
+}
+
+/// Lints every file of the given path, failing if any problem was found
+/// Like building, the path and the options come from the flags and the clue.toml
+pub fn lint(mut args: LintArgs, matches: &ArgMatches) -> Result<(), String> {
+	apply_manifest(&mut args.build, matches)?;
+	let options = build_options(&args.build)?;
+	let path = args.build.path.unwrap_or_else(|| PathBuf::from("."));
+	let rules = match args.config.or_else(|| find_config(&path, &CONFIG_FILES)) {
+		Some(config) => read_config(&config)?,
+		None => LintRules::default(),
+	};
+	let mut problems = 0;
+	for file in clue_files(path)? {
+		let name = file.to_string_lossy().into_owned();
+		let code = check!(fs::read_to_string(&file));
+		let diagnostics = lint_code(&code, &name, &rules, &options).unwrap_or_else(|e| e);
+		report(&diagnostics);
+		problems += diagnostics.len();
+	}
+	match problems {
+		0 => Ok(()),
+		1 => Err(String::from("1 problem found!")),
+		n => Err(format!("{n} problems found!")),
+	}
+}
@@ -1,31 +1,65 @@
-#![allow(clippy::blocks_in_if_conditions)]
-
-use clap::{crate_version, Parser};
+use clap::{crate_version, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
 use clue_core::{
+	cache::Cache,
 	check,
 	compiler::*,
-	env::{BitwiseMode, ContinueMode, LuaVersion, Options},
+	diagnostic::Diagnostic,
+	env::{BitwiseMode, ContinueMode, LuaSTD, LuaVersion, Options, OutputMode, TypesMode},
 	format_clue,
 	parser::*,
 	preprocessor::*,
+	project::{compile_project, compile_project_cached, CompiledModule, ProjectOutput},
 	scanner::*,
+	sourcemap::SourceMap,
+};
+use convert::ConvertArgs;
+use fmt::FmtArgs;
+use lint::LintArgs;
+use new::NewArgs;
+use std::{
+	collections::BTreeMap,
+	fs,
+	num::NonZeroUsize,
+	path::{Path, PathBuf},
+	time::Instant,
 };
-use std::{fs, path::PathBuf, time::Instant};
-use threads::compile_folder;
 
-mod threads;
+mod convert;
+mod files;
+mod fmt;
+mod lint;
+mod manifest;
+mod new;
+#[cfg(feature = "mlua")]
+mod repl;
+mod watch;
 
 #[derive(Parser)]
 #[clap(
 	version,
 	about = "C/Rust like programming language that compiles into Lua code\nMade by Maiori\nhttps://github.com/ClueLang/Clue",
-	long_about = None
+	long_about = None,
+	args_conflicts_with_subcommands = true,
+	subcommand_negates_reqs = true
 )]
 struct Cli {
+	#[clap(subcommand)]
+	command: Option<Command>,
+
+	#[clap(flatten)]
+	build: BuildArgs,
+
+	/// Print license information
+	#[clap(short = 'L', long, display_order = 1000)]
+	license: bool,
+}
+
+#[derive(Args)]
+struct BuildArgs {
 	/// The path to the directory where the *.clue files are located.
 	/// Every directory inside the given directory will be checked too.
 	/// If the path points to a single *.clue file, only that file will be compiled.
-	#[clap(required_unless_present = "license")]
+	/// [default: the source of the clue.toml in the working directory or its parents]
 	path: Option<PathBuf>,
 
 	/// The name the output file will have
@@ -34,9 +68,13 @@ struct Cli {
 	#[clap(value_name = "OUTPUT FILE NAME")]
 	outputname: Option<PathBuf>,
 
-	/// Print license information
-	#[clap(short = 'L', long, display_order = 1000)]
-	license: bool,
+	/// Use the given profile of the clue.toml
+	#[clap(long, conflicts_with("pathiscode"), value_name = "NAME")]
+	profile: Option<String>,
+
+	/// Use the given manifest instead of searching for clue.toml
+	#[clap(long, conflicts_with("pathiscode"), value_name = "FILE NAME")]
+	manifest: Option<PathBuf>,
 
 	/// Print list of detected tokens in compiled files
 	#[clap(long)]
@@ -87,13 +125,34 @@ struct Cli {
 	r#continue: ContinueMode,
 
 	/// Don't save compiled code
-	#[clap(short = 'D', long)]
+	#[clap(long)]
 	dontsave: bool,
 
+	/// Define a preprocessor variable in every file (its value is true if not given)
+	#[clap(short = 'D', long, value_name = "NAME[=VALUE]")]
+	define: Vec<String>,
+
+	/// Remove a preprocessor variable defined by --define or by the clue.toml
+	#[clap(short = 'U', long, value_name = "NAME")]
+	undefine: Vec<String>,
+
 	/// Treat PATH not as a path but as Clue code
 	#[clap(short, long)]
 	pathiscode: bool,
 
+	/// Compile again every time a file of PATH changes, until stopped
+	#[clap(short, long, conflicts_with("pathiscode"))]
+	watch: bool,
+
+	/// Keep the compiled modules of the files of PATH in FILE to only compile the changed files next time
+	#[clap(
+		long,
+		conflicts_with("pathiscode"),
+		default_missing_value = ".cluecache",
+		value_name = "FILE"
+	)]
+	cache: Option<PathBuf>,
+
 	/// Use rawset to create globals
 	#[clap(short, long)]
 	rawsetglobals: bool,
@@ -102,6 +161,18 @@ struct Cli {
 	#[clap(short, long)]
 	debug: bool,
 
+	/// Change the way the files of a directory are written
+	/// (files: one Lua file per Clue file in the OUTPUT FILE NAME directory [default: PATH])
+	#[clap(
+		short,
+		long,
+		value_enum,
+		ignore_case(true),
+		default_value = "bundle",
+		value_name = "MODE"
+	)]
+	mode: OutputMode,
+
 	/// Use a custom Lua file as base for compiling the directory
 	#[clap(short = 'B', long, value_name = "FILE NAME")]
 	base: Option<String>,
@@ -122,30 +193,33 @@ struct Cli {
 	/// Change OS checked by @ifos
 	#[clap(long, default_value = std::env::consts::OS, value_name = "TARGET OS")]
 	targetos: String,
-	/*/// This is not yet supported (Coming out in 4.0)
-	#[clap(short, long, value_name = "MODE")]
-	types: Option<String>,*/
-
-	/*	/// Enable type checking (might slow down compilation)
-		#[clap(
-			short,
-			long,
-			value_enum,
-			default_value = "none",
-			value_name = "MODE"
-		)]
-		types: TypesMode,
-
-		/// Use the given Lua version's standard library (--types required)
-		#[clap(
-			long,
-			value_enum,
-			default_value = "luajit",
-			value_name = "LUA VERSION",
-			requires = "types"
-		)]
-		std: LuaSTD,
-	*/
+
+	/// Report reads of globals that were never declared with global, static or extern
+	#[clap(long)]
+	checkglobals: bool,
+
+	/// Write a source map of the output Lua code next to it
+	#[clap(long, conflicts_with("debug"))]
+	sourcemap: bool,
+
+	/// Stop reporting the errors of a file after the given amount
+	#[clap(long, value_name = "COUNT")]
+	maxerrors: Option<NonZeroUsize>,
+
+	/// Check the type annotations of the code (might slow down compilation)
+	#[clap(
+		long,
+		value_enum,
+		ignore_case(true),
+		default_value = "none",
+		value_name = "MODE"
+	)]
+	types: TypesMode,
+
+	/// Warn about uses of the standard library missing in the given Lua version (defaults to --target)
+	#[clap(long, value_enum, ignore_case(true), value_name = "LUA VERSION")]
+	std: Option<LuaSTD>,
+
 	#[cfg(feature = "mlua")]
 	/// Execute the output Lua code once it's compiled
 	#[clap(short, long)]
@@ -157,37 +231,77 @@ struct Cli {
 	symbols: bool,
 }
 
-pub fn compile_code(
+#[derive(Subcommand)]
+enum Command {
+	/// Compile a file or directory, like running clue without a subcommand
+	Build(BuildArgs),
+
+	/// Compile a file or directory without writing the output, reporting every error
+	Check(BuildArgs),
+
+	#[cfg(feature = "mlua")]
+	/// Compile a file or directory and run the output Lua code
+	Run(BuildArgs),
+
+	/// Create a new project with a clue.toml and a main.clue
+	New(NewArgs),
+
+	#[cfg(feature = "mlua")]
+	/// Run Clue code interactively, keeping the preprocessor and Lua state between inputs
+	Repl,
+
+	/// Translate *.lua files into *.clue files, reporting what can't be translated
+	Convert(ConvertArgs),
+
+	/// Format *.clue files in place, keeping their comments
+	Fmt(FmtArgs),
+
+	/// Report code that is most likely a mistake, like unused locals or unreachable code
+	Lint(LintArgs),
+}
+
+fn compile_code(
 	codes: PPCode,
 	variables: &PPVars,
 	name: &String,
 	scope: usize,
 	options: &Options,
-) -> Result<(String, String), String> {
+) -> Result<(String, SourceMap, String, Vec<Diagnostic>), Vec<Diagnostic>> {
 	let time = Instant::now();
 	let code = preprocess_codes(0, codes, variables, name)?;
 	if options.env_expand {
-		println!("Preprocessed file \"{name}\":\n{}", code.to_string());
+		println!("Preprocessed file \"{name}\":\n{code}");
 	}
 	let tokens: Vec<Token> = scan_code(code, name)?;
 	if options.env_tokens {
 		println!("Scanned tokens of file \"{name}\":\n{tokens:#?}");
 	}
-	let (ctokens, statics) = parse_tokens(
-		tokens,
-		/*if flag!(env_types) != TypesMode::NONE {
-			Some(AHashMap::default())
-		} else {
-			None
-		},*/
-		name, options,
-	)?;
+	#[cfg(feature = "lsp")]
+	let (ctokens, statics, warnings) = if options.env_symbols {
+		let (ctokens, statics, mut symbols, warnings) =
+			parse_tokens_with_symbols(tokens, name, options)?;
+		symbols.add_ppvars(variables);
+		println!("{}", symbols.to_json(name));
+		(ctokens, statics, warnings)
+	} else {
+		parse_tokens(tokens, name, options)?
+	};
+	#[cfg(not(feature = "lsp"))]
+	let (ctokens, statics, warnings) = parse_tokens(tokens, name, options)?;
 
 	if options.env_struct {
 		println!("Parsed structure of file \"{name}\":\n{ctokens:#?}");
 	}
 
-	let code = Compiler::new(options, name).compile_tokens(scope, ctokens)?;
+	let compiler = Compiler::new(options, name);
+	let (code, sourcemap) = if options.env_sourcemap {
+		compiler.compile_tokens_with_source_map(scope, ctokens)?
+	} else {
+		(
+			compiler.compile_tokens(scope, ctokens)?,
+			SourceMap::default(),
+		)
+	};
 
 	if options.env_output {
 		println!("Compiled Lua code of file \"{name}\":\n{code}");
@@ -197,18 +311,74 @@ pub fn compile_code(
 		name,
 		time.elapsed().as_secs_f32()
 	);
-	Ok((code, statics))
+	Ok((code, sourcemap, statics, warnings))
+}
+
+/// Reads the variables given with --define, without the ones given with --undefine
+fn defines(define: &[String], undefine: &[String]) -> Result<BTreeMap<String, String>, String> {
+	let mut defines = BTreeMap::new();
+	for define in define {
+		let (name, value) = define.split_once('=').unwrap_or((define, "true"));
+		if name.is_empty() || !name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_') {
+			return Err(format!(
+				"\"{name}\" is not a valid preprocessor variable name!"
+			));
+		}
+		defines.insert(name.to_owned(), value.to_owned());
+	}
+	for name in undefine {
+		defines.remove(name);
+	}
+	Ok(defines)
+}
+
+/// Compiles every file of the given directory in parallel, reporting the errors of every file
+/// If a cache is given, the files that didn't change are taken from it
+fn compile_folder(
+	path: impl AsRef<Path>,
+	options: Options,
+	cache: Option<&mut Cache>,
+) -> Result<ProjectOutput, String> {
+	let output = match cache {
+		Some(cache) => {
+			let output = compile_project_cached(path, String::new(), options, compile_code, cache)
+				.map_err(|e| reported(vec![*e]))?;
+			cache.save().map_err(|e| reported(vec![*e]))?;
+			if output.cached > 0 {
+				println!("Took {} unchanged files from the cache", output.cached);
+			}
+			output
+		}
+		None => compile_project(path, String::new(), options, compile_code)
+			.map_err(|e| reported(vec![*e]))?,
+	};
+	report(&output.diagnostics);
+	match output.failed {
+		0 => Ok(output),
+		1 => Err(String::from("1 file failed to compile!")),
+		n => Err(format!("{n} files failed to compile!")),
+	}
+}
+
+fn report(diagnostics: &[Diagnostic]) {
+	for diagnostic in diagnostics {
+		eprintln!("{diagnostic}");
+	}
+}
+
+fn reported(diagnostics: Vec<Diagnostic>) -> String {
+	report(&diagnostics);
+	String::from("Cannot continue until the above errors are fixed")
 }
 
 #[cfg(feature = "mlua")]
-fn execute_lua_code(code: &str) {
+fn execute_lua_code(code: &str) -> Result<(), String> {
 	println!("Running compiled code...");
 	let lua = mlua::Lua::new();
 	let time = Instant::now();
-	if let Err(error) = lua.load(code).exec() {
-		println!("{error}");
-	}
+	lua.load(code).exec().map_err(|error| error.to_string())?;
 	println!("Code ran in {} seconds!", time.elapsed().as_secs_f32());
+	Ok(())
 }
 
 fn finish(
@@ -227,13 +397,30 @@ fn finish(
 		}
 		#[cfg(feature = "mlua")]
 		if execute {
-			execute_lua_code(&new_output)
+			execute_lua_code(&new_output)?;
 		}
 		return Ok(());
 	}
 	#[cfg(feature = "mlua")]
 	if execute {
-		execute_lua_code(&code)
+		execute_lua_code(&code)?;
+	}
+	Ok(())
+}
+
+fn save_source_map(
+	output_path: &Path,
+	code: &mut String,
+	sourcemap: Option<SourceMap>,
+) -> Result<(), String> {
+	if let Some(sourcemap) = sourcemap {
+		let name = output_path.file_name().unwrap().to_string_lossy();
+		let map_name = format_clue!(name, ".map");
+		check!(fs::write(
+			output_path.with_file_name(&map_name),
+			sourcemap.to_json(&name)
+		));
+		*code += &format_clue!("\n--# sourceMappingURL=", map_name);
 	}
 	Ok(())
 }
@@ -241,7 +428,8 @@ fn finish(
 fn save_result(
 	dont_save: bool,
 	output_name: Option<PathBuf>,
-	code: String,
+	mut code: String,
+	sourcemap: Option<SourceMap>,
 ) -> Result<(Option<PathBuf>, String), String> {
 	Ok((
 		if !dont_save {
@@ -261,6 +449,7 @@ fn save_result(
 				}
 				None => PathBuf::from("main.lua"),
 			};
+			save_source_map(&output_path, &mut code, sourcemap)?;
 			check!(fs::write(&output_path, &code));
 			Some(output_path)
 		} else {
@@ -270,53 +459,139 @@ fn save_result(
 	))
 }
 
+fn save_modules(
+	dont_save: bool,
+	output_dir: PathBuf,
+	modules: Vec<CompiledModule>,
+	sourcemap: bool,
+) -> Result<(), String> {
+	if dont_save {
+		return Ok(());
+	}
+	for module in modules {
+		let sourcemap = sourcemap.then(|| {
+			let mut sourcemap = module.sourcemap;
+			sourcemap.move_after(&module.statics);
+			sourcemap
+		});
+		let mut code = module.statics + &module.code;
+		let mut output_path = output_dir.clone();
+		output_path.extend(module.name.split('.'));
+		output_path.set_extension("lua");
+		if let Some(parent) = output_path.parent() {
+			check!(fs::create_dir_all(parent));
+		}
+		save_source_map(&output_path, &mut code, sourcemap)?;
+		check!(fs::write(&output_path, &code));
+	}
+	Ok(())
+}
+
+/// Fails if a flag that needs a single output file is used with --mode files
+fn check_files_mode(args: &BuildArgs) -> Result<(), String> {
+	if args.debug {
+		return Err(String::from("--debug can't be used with --mode files"));
+	}
+	if args.base.is_some() {
+		return Err(String::from("--base can't be used with --mode files"));
+	}
+	#[cfg(feature = "mlua")]
+	if args.execute {
+		return Err(String::from("--execute can't be used with --mode files"));
+	}
+	Ok(())
+}
+
+/// Puts the output and the statics of a project in the base, moving the source map of the output with it
+fn bundle_code(
+	base: Option<&String>,
+	output: &str,
+	sourcemap: &mut SourceMap,
+	statics: &str,
+) -> Result<String, String> {
+	let base = match base {
+		Some(filename) => {
+			let base = match fs::read(filename) {
+				Ok(base) => base,
+				Err(_) => return Err(String::from("The given custom base was not found!")),
+			};
+			check!(std::str::from_utf8(&base)).to_owned()
+		}
+		None => include_str!("base.lua").to_owned(),
+	};
+	let base = base.replace("--STATICS\n", statics);
+	if let Some(index) = base.find('§') {
+		sourcemap.move_after(&base[..index]);
+	}
+	Ok(base.replace('§', output))
+}
+
+/// Adds the .clue extension to the path if it's missing, returning whether it points to a file
+fn find_clue_file(path: &mut PathBuf) -> bool {
+	match path.extension() {
+		Some(extension) if extension != "clue" => {
+			path.set_extension(format_clue!(extension.to_string_lossy(), ".clue"));
+		}
+		None => {
+			path.set_extension("clue");
+		}
+		_ => {}
+	}
+	path.is_file()
+}
+
 fn main() -> Result<(), String> {
 	std::env::set_var("CLUE_VERSION", crate_version!());
-	let cli = Cli::parse();
-	if cli.license {
-		print!(include_str!("../LICENSE"));
-		return Ok(());
-	} /*else if cli.types.is_some() {
-	  //TEMPORARY PLACEHOLDER UNTIL 4.0
-	  return Err(String::from("Type checking is not supported yet!"));
-  }*/
+	let matches = Cli::command().get_matches();
+	let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+	let Some(command) = cli.command else {
+		if cli.license {
+			print!(include_str!("../LICENSE"));
+			return Ok(());
+		}
+		return build(cli.build, &matches);
+	};
+	// the flags of the subcommand are needed to know which ones were given
+	let matches = matches
+		.subcommand()
+		.map_or(&matches, |(_, matches)| matches);
+	match command {
+		Command::Build(args) => build(args, matches),
+		Command::Check(mut args) => {
+			args.dontsave = true;
+			#[cfg(feature = "mlua")]
+			{
+				args.execute = false;
+			}
+			build(args, matches)
+		}
+		#[cfg(feature = "mlua")]
+		Command::Run(mut args) => {
+			args.execute = true;
+			build(args, matches)
+		}
+		Command::New(args) => new::new(args),
+		#[cfg(feature = "mlua")]
+		Command::Repl => repl::repl(),
+		Command::Convert(args) => convert::convert(args),
+		Command::Fmt(args) => fmt::format(args),
+		Command::Lint(args) => lint::lint(args, matches),
+	}
+}
+
+/// Compiles the given file, directory or code with the given flags and the ones of the manifest
+fn build(mut args: BuildArgs, matches: &ArgMatches) -> Result<(), String> {
+	if !args.pathiscode && !manifest::apply_manifest(&mut args, matches)? && args.path.is_none() {
+		return Err(String::from(
+			"PATH is required when there is no clue.toml, see --help",
+		));
+	}
 
-	if cli.r#continue == ContinueMode::LuaJIT {
+	if args.r#continue == ContinueMode::LuaJIT {
 		println!("Warning: \"LuaJIT continue mode was deprecated and replaced by goto mode\"")
 	}
 
-	let mut options = Options {
-		env_outputname: cli.outputname.clone(),
-		env_tokens: cli.tokens,
-		env_struct: cli.r#struct,
-		env_expand: cli.expand,
-		env_jitbit: {
-			if cli.jitbit.is_some() {
-				println!("Warning: \"--jitbit was deprecated and replaced by --bitwise\"");
-				cli.jitbit
-			} else if cli.bitwise == BitwiseMode::Library {
-				Some(String::from("bit"))
-			} else {
-				None
-			}
-		},
-		env_bitwise: cli.bitwise,
-		env_continue: cli.r#continue,
-		env_rawsetglobals: cli.rawsetglobals,
-		env_debug: cli.debug,
-		env_output: if cli.pathiscode {
-			cli.outputname.is_none()
-		} else {
-			cli.output
-		},
-		env_target: cli.target,
-		env_targetos: cli.targetos,
-		#[cfg(feature = "lsp")]
-		env_symbols: cli.symbols,
-        #[cfg(not(feature = "lsp"))]
-        env_symbols: false,
-	};
-	options.preset();
+	let options = build_options(&args)?;
 
 	//let mut code = String::with_capacity(512);
 
@@ -329,70 +604,116 @@ fn main() -> Result<(), String> {
 			_ => Some(AHashMap::default()),
 		};
 	}*/
-	let mut path = cli.path.unwrap();
-	if cli.pathiscode {
+	if args.watch {
+		return watch::watch(&args, options);
+	}
+	let path = args.path.clone().unwrap();
+	if args.pathiscode {
 		let filename = String::from("(command line)");
 		let mut code = path.to_string_lossy().into_owned();
 		let code = unsafe { code.as_bytes_mut() };
-		let preprocessed_code = preprocess_code(code, 1, false, &filename, &options)?;
-		let (code, statics) = compile_code(
-			preprocessed_code.0,
-			&preprocessed_code.1,
-			&filename,
-			0,
-			&options,
-		)?;
-		let code = code + &statics;
+		let (codes, variables, _, _, notes) =
+			preprocess_code(code, 1, false, &filename, &options).map_err(|e| reported(vec![*e]))?;
+		report(&notes);
+		let (code, sourcemap, statics, warnings) =
+			compile_code(codes, &variables, &filename, 0, &options).map_err(reported)?;
+		report(&warnings);
+		let mut code = code + &statics;
+		let sourcemap = options.env_sourcemap.then_some(sourcemap);
 		#[cfg(feature = "mlua")]
-		if cli.execute {
-			execute_lua_code(&code)
+		if args.execute {
+			execute_lua_code(&code)?;
 		}
-		return if let Some(outputname) = cli.outputname.clone() {
+		return if let Some(outputname) = args.outputname.clone() {
+			save_source_map(&outputname, &mut code, sourcemap)?;
 			check!(fs::write(&outputname, &code));
 			#[cfg(feature = "mlua")]
-			return finish(cli.debug, cli.execute, Some(outputname), code);
+			return finish(args.debug, args.execute, Some(outputname), code);
 			#[cfg(not(feature = "mlua"))]
-			finish(cli.debug, Some(outputname), code)
+			finish(args.debug, Some(outputname), code)
 		} else {
 			Ok(())
 		};
 	}
-	let (output_path, code) = if path.is_dir() {
-		let (output, statics) = compile_folder(path, String::new(), options)?;
-
-		let code = match cli.base {
-			Some(filename) => {
-				let base = match fs::read(filename) {
-					Ok(base) => base,
-					Err(_) => return Err(String::from("The given custom base was not found!")),
-				};
-				check!(std::str::from_utf8(&base))
-					.to_owned()
-					.replace("--STATICS\n", &statics)
-					.replace('§', &output)
-			}
-			None => include_str!("base.lua")
-				.replace("--STATICS\n", &statics)
-				.replace('§', &output),
-		};
-		save_result(cli.dontsave, cli.outputname, code)?
-	} else if {
-		match path.extension() {
-			Some(extension) if extension != "clue" => {
-				path.set_extension(format_clue!(extension.to_string_lossy(), ".clue"));
-			}
-			None => {
-				path.set_extension("clue");
+	let mut cache = args.cache.as_ref().map(|path| Cache::load(path, &options));
+	compile_path(&args, options, cache.as_mut())
+}
+
+/// Turns the flags, after applying the manifest to them, into the options of the compiler
+fn build_options(args: &BuildArgs) -> Result<Options, String> {
+	let mut options = Options {
+		env_outputname: args.outputname.clone(),
+		env_tokens: args.tokens,
+		env_struct: args.r#struct,
+		env_expand: args.expand,
+		env_jitbit: {
+			if args.jitbit.is_some() {
+				println!("Warning: \"--jitbit was deprecated and replaced by --bitwise\"");
+				args.jitbit.clone()
+			} else if args.bitwise == BitwiseMode::Library {
+				Some(String::from("bit"))
+			} else {
+				None
 			}
-			_ => {}
+		},
+		env_bitwise: args.bitwise,
+		env_continue: args.r#continue,
+		env_rawsetglobals: args.rawsetglobals,
+		env_debug: args.debug,
+		env_output: if args.pathiscode {
+			args.outputname.is_none()
+		} else {
+			args.output
+		},
+		env_target: args.target,
+		env_targetos: args.targetos.clone(),
+		env_maxerrors: args.maxerrors,
+		env_checkglobals: args.checkglobals,
+		env_types: args.types,
+		env_std: args.std,
+		env_defines: defines(&args.define, &args.undefine)?,
+		env_mode: args.mode,
+		env_sourcemap: args.sourcemap,
+		#[cfg(feature = "lsp")]
+		env_symbols: args.symbols,
+		#[cfg(not(feature = "lsp"))]
+		env_symbols: false,
+	};
+	options.preset();
+	Ok(options)
+}
+
+/// Compiles the file or directory given in `args` and saves the output
+/// The cache is only used by directories
+fn compile_path(
+	args: &BuildArgs,
+	options: Options,
+	cache: Option<&mut Cache>,
+) -> Result<(), String> {
+	let mut path = args.path.clone().expect("PATH is required");
+	let outputname = args.outputname.clone();
+	let sourcemap = options.env_sourcemap;
+	let (output_path, code) = if path.is_dir() {
+		if options.env_mode == OutputMode::Files {
+			check_files_mode(args)?;
+			let output_dir = outputname.unwrap_or_else(|| path.clone());
+			let modules = compile_folder(path, options, cache)?.modules;
+			return save_modules(args.dontsave, output_dir, modules, sourcemap);
 		}
-		path.is_file()
-	} {
+		let (output, mut map, statics) = compile_folder(path, options, cache)?.bundle();
+		let code = bundle_code(args.base.as_ref(), &output, &mut map, &statics)?;
+		save_result(args.dontsave, outputname, code, sourcemap.then_some(map))?
+	} else if find_clue_file(&mut path) {
 		let name = path.file_name().unwrap().to_string_lossy().into_owned();
-		let (rawcode, variables) = read_file(path, &name, &options)?;
-		let (output, statics) = compile_code(rawcode, &variables, &name, 0, &options)?;
+		let (rawcode, variables, notes) =
+			read_file(path, &name, &options).map_err(|e| reported(vec![*e]))?;
+		report(&notes);
+		let (output, mut map, statics, warnings) =
+			compile_code(rawcode, &variables, &name, 0, &options).map_err(reported)?;
+		report(&warnings);
+		map.move_after(&statics);
 		let code = statics + &output;
-		save_result(cli.dontsave, cli.outputname, code)?
+		save_result(args.dontsave, outputname, code, sourcemap.then_some(map))?
 	} else {
 		return Err(format!(
 			"{} was not found!",
@@ -401,18 +722,37 @@ fn main() -> Result<(), String> {
 	};
 
 	#[cfg(feature = "mlua")]
-	return finish(cli.debug, cli.execute, output_path, code);
+	return finish(args.debug, args.execute, output_path, code);
 	#[cfg(not(feature = "mlua"))]
-	finish(cli.debug, output_path, code)
+	finish(args.debug, output_path, code)
 }
 
 #[cfg(test)]
 mod tests {
-	use crate::compile_folder;
+	use crate::{compile_folder, defines};
 	use clue_core::env::Options;
 
 	#[test]
 	fn compilation_success() {
-		compile_folder("../examples/", String::new(), Options::default()).unwrap();
+		compile_folder("../examples/", Options::default(), None).unwrap();
+	}
+
+	#[test]
+	fn modules_are_sorted() {
+		let modules = compile_folder("../examples/", Options::default(), None)
+			.unwrap()
+			.modules;
+		assert!(modules.windows(2).all(|pair| pair[0].name < pair[1].name));
+	}
+
+	#[test]
+	fn defines_are_parsed() {
+		let define = ["DEBUG", "LEVEL=1", "NAME=a=b", "LEVEL=2"].map(String::from);
+		let defined = defines(&define, &[String::from("DEBUG")]).unwrap();
+		assert_eq!(defined.len(), 2);
+		assert_eq!(defined["LEVEL"], "2");
+		assert_eq!(defined["NAME"], "a=b");
+		assert_eq!(defines(&define, &[]).unwrap()["DEBUG"], "true");
+		assert!(defines(&[String::from("A B")], &[]).is_err());
 	}
 }
@@ -0,0 +1,250 @@
+//! The `clue.toml` manifest, which stores the build configuration of a project
+//!
+//! The `[build]` table is used by every build and the `[profile.NAME]` tables override it
+//! when the profile is selected with `--profile NAME`.
+//! Paths are relative to the directory of the manifest and the flags given in the command line
+//! always take precedence over it.
+
+use crate::{
+	files::{find_config, read_config},
+	BuildArgs,
+};
+use clap::{parser::ValueSource, ArgMatches, ValueEnum};
+use clue_core::env::{BitwiseMode, ContinueMode, LuaSTD, LuaVersion, OutputMode, TypesMode};
+use serde::{de::Error, Deserialize, Deserializer};
+use std::{
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+};
+
+/// The manifest files searched in the working directory and its parents
+const MANIFEST_FILES: [&str; 1] = ["clue.toml"];
+
+/// Reads enums with the same names the command line accepts
+fn value_enum<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+	D: Deserializer<'de>,
+	T: ValueEnum,
+{
+	let value = String::deserialize(deserializer)?;
+	T::from_str(&value, true)
+		.map(Some)
+		.map_err(D::Error::custom)
+}
+
+#[derive(Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+/// The options of a build, every missing one keeps the value of the command line
+pub struct Profile {
+	/// The directory or file to compile
+	source: Option<PathBuf>,
+
+	/// The output file, or directory when using `mode = "files"`
+	output: Option<PathBuf>,
+
+	/// The way the files of a directory are written
+	#[serde(deserialize_with = "value_enum")]
+	mode: Option<OutputMode>,
+
+	/// The custom Lua file used as base for compiling a directory
+	base: Option<PathBuf>,
+
+	/// The targeted Lua version, which overrides `bitwise` and `continue` like `--target`
+	#[serde(deserialize_with = "value_enum")]
+	target: Option<LuaVersion>,
+
+	/// The way bitwise operators are compiled
+	#[serde(deserialize_with = "value_enum")]
+	bitwise: Option<BitwiseMode>,
+
+	/// The way continue identifiers are compiled
+	#[serde(rename = "continue", deserialize_with = "value_enum")]
+	continue_mode: Option<ContinueMode>,
+
+	/// The mode used to check type annotations
+	#[serde(deserialize_with = "value_enum")]
+	types: Option<TypesMode>,
+
+	/// The standard library the code is checked against
+	#[serde(deserialize_with = "value_enum")]
+	std: Option<LuaSTD>,
+
+	/// The OS checked by `@ifos`
+	targetos: Option<String>,
+
+	/// Whether rawset is used to create globals
+	rawsetglobals: Option<bool>,
+
+	/// Whether reads of undeclared globals are reported
+	checkglobals: Option<bool>,
+
+	/// Whether a source map is written next to the output
+	sourcemap: Option<bool>,
+
+	/// The preprocessor variables defined in every file, like `--define NAME=VALUE`
+	defines: BTreeMap<String, String>,
+}
+
+impl Profile {
+	/// Returns this profile with every option set by `other` replaced
+	fn merge(mut self, other: Profile) -> Self {
+		macro_rules! merge {
+			($($field:ident),*) => {$(
+				if other.$field.is_some() {
+					self.$field = other.$field;
+				}
+			)*};
+		}
+		merge!(
+			source,
+			output,
+			mode,
+			base,
+			target,
+			bitwise,
+			continue_mode,
+			types,
+			std,
+			targetos,
+			rawsetglobals,
+			checkglobals,
+			sourcemap
+		);
+		self.defines.extend(other.defines);
+		self
+	}
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+/// The content of a `clue.toml` file
+pub struct Manifest {
+	/// The options used by every build
+	build: Profile,
+
+	/// The named profiles that can be selected with `--profile`
+	profile: BTreeMap<String, Profile>,
+}
+
+impl Manifest {
+	/// Returns the options of the given profile, or of `[build]` if no profile is given
+	fn profile(mut self, name: Option<&str>) -> Result<Profile, String> {
+		let Some(name) = name else {
+			return Ok(self.build);
+		};
+		match self.profile.remove(name) {
+			Some(profile) => Ok(self.build.merge(profile)),
+			None => Err(format!("Profile \"{name}\" was not found in the manifest!")),
+		}
+	}
+}
+
+/// Applies the manifest found in the working directory or its parents to the options of `args`
+/// Returns `false` if there is no manifest
+pub fn apply_manifest(args: &mut BuildArgs, matches: &ArgMatches) -> Result<bool, String> {
+	let Some(path) = args
+		.manifest
+		.clone()
+		.or_else(|| find_config(Path::new("."), &MANIFEST_FILES))
+	else {
+		return match &args.profile {
+			Some(_) => Err(String::from("--profile can't be used without a clue.toml")),
+			None => Ok(false),
+		};
+	};
+	let manifest: Manifest = read_config(&path)?;
+	let profile = manifest.profile(args.profile.as_deref())?;
+	let directory = path.parent().unwrap_or_else(|| Path::new("."));
+	let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+	if args.path.is_none() {
+		args.path = Some(directory.join(profile.source.unwrap_or_default()));
+	}
+	if args.outputname.is_none() {
+		args.outputname = profile.output.map(|output| directory.join(output));
+	}
+	if args.base.is_none() {
+		args.base = profile
+			.base
+			.map(|base| directory.join(base).to_string_lossy().into_owned());
+	}
+	if !(given("target") || given("bitwise") || given("continue") || given("jitbit")) {
+		args.target = profile.target;
+	}
+	macro_rules! apply {
+		($($field:ident: $id:literal = $value:expr),*) => {$(
+			if let (false, Some(value)) = (given($id), $value) {
+				args.$field = value;
+			}
+		)*};
+	}
+	apply!(
+		mode: "mode" = profile.mode,
+		bitwise: "bitwise" = profile.bitwise,
+		r#continue: "continue" = profile.continue_mode,
+		types: "types" = profile.types,
+		std: "std" = profile.std.map(Some),
+		targetos: "targetos" = profile.targetos,
+		rawsetglobals: "rawsetglobals" = profile.rawsetglobals,
+		checkglobals: "checkglobals" = profile.checkglobals,
+		sourcemap: "sourcemap" = profile.sourcemap
+	);
+	// the defines of the manifest go first so that --define can replace them
+	let defines = profile.defines.into_iter();
+	args.define
+		.splice(0..0, defines.map(|(name, value)| format!("{name}={value}")));
+	Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Manifest, Profile};
+	use clue_core::env::{BitwiseMode, LuaSTD, LuaVersion};
+
+	const MANIFEST: &str = r#"
+		[build]
+		source = "src"
+		bitwise = "vanilla"
+		defines = { DEBUG = "true" }
+
+		[profile.love]
+		target = "luajit"
+		std = "luajit"
+		output = "game.lua"
+		defines = { LOVE = "1" }
+
+		[profile.server]
+		target = "lua54"
+		defines = { DEBUG = "false" }
+	"#;
+
+	#[test]
+	fn profiles_override_build() {
+		let manifest = || toml::from_str::<Manifest>(MANIFEST).unwrap();
+		let build = manifest().profile(None).unwrap();
+		assert_eq!(build.bitwise, Some(BitwiseMode::Vanilla));
+		assert_eq!(build.target, None);
+
+		let love = manifest().profile(Some("love")).unwrap();
+		assert_eq!(love.source, build.source);
+		assert_eq!(love.target, Some(LuaVersion::LuaJIT));
+		assert_eq!(love.std, Some(LuaSTD::LUAJIT));
+		assert_eq!(love.defines.len(), 2);
+
+		let server = manifest().profile(Some("server")).unwrap();
+		assert_eq!(server.target, Some(LuaVersion::Lua54));
+		assert_eq!(server.defines["DEBUG"], "false");
+
+		assert!(manifest().profile(Some("web")).is_err());
+	}
+
+	#[test]
+	fn unknown_options_are_rejected() {
+		assert!(toml::from_str::<Manifest>("[build]\ntaget = \"lua54\"").is_err());
+		assert!(toml::from_str::<Manifest>("[build]\ntarget = \"lua6\"").is_err());
+		assert_eq!(
+			toml::from_str::<Manifest>("").unwrap().build,
+			Profile::default()
+		);
+	}
+}
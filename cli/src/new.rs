@@ -0,0 +1,41 @@
+//! The `new` subcommand, which creates the files of a new project
+
+use clap::Args;
+use clue_core::check;
+use std::{fs, path::PathBuf};
+
+/// The manifest of a new project
+const MANIFEST: &str = r#"[build]
+source = "src"
+output = "main.lua"
+
+# Select a profile with `clue build --profile NAME`
+# [profile.luajit]
+# target = "LuaJIT"
+"#;
+
+/// The main file of a new project
+const MAIN: &str = "print(\"Hello world!\")\n";
+
+#[derive(Args)]
+pub struct NewArgs {
+	/// The directory to create the project in, which must not exist or be empty
+	path: PathBuf,
+}
+
+/// Creates a project with a clue.toml and a src/main.clue in the given directory
+pub fn new(args: NewArgs) -> Result<(), String> {
+	let path = args.path;
+	if path.exists() && (!path.is_dir() || check!(fs::read_dir(&path)).next().is_some()) {
+		return Err(format!(
+			"{} already exists and is not empty!",
+			path.display()
+		));
+	}
+	let source = path.join("src");
+	check!(fs::create_dir_all(&source));
+	check!(fs::write(path.join("clue.toml"), MANIFEST));
+	check!(fs::write(source.join("main.clue"), MAIN));
+	println!("Created project \"{}\"", path.display());
+	Ok(())
+}
@@ -0,0 +1,208 @@
+//! The `repl` subcommand, which compiles and runs Clue code one input at a time
+//!
+//! Every input is preprocessed with the variables and macros defined by the previous ones
+//! and runs in the same Lua state, so globals are kept between inputs while locals are not.
+
+use crate::report;
+use clap::crate_version;
+use clue_core::{
+	check,
+	code::Code,
+	compiler::Compiler,
+	diagnostic::Diagnostic,
+	env::{LuaVersion, Options},
+	parser::parse_tokens,
+	preprocessor::{preprocess_code, preprocess_codes, PPVars},
+	scanner::scan_code,
+};
+use mlua::{Function, Lua, MultiValue};
+use std::io::{self, BufRead, Write};
+
+/// The file name used in the errors of the inputs
+const NAME: &str = "(repl)";
+
+const HELP: &str = "Type Clue code to run it, the values of expressions are printed
+Locals only last for their input, use global to keep a value
+:lua   Print the Lua code of the last input
+:help  Print this message
+:quit  Exit the REPL (Ctrl+D works too)";
+
+struct Repl {
+	lua: Lua,
+	options: Options,
+	variables: PPVars,
+	last: Option<String>,
+}
+
+impl Repl {
+	fn new() -> Self {
+		// the embedded Lua is LuaJIT
+		let mut options = Options {
+			env_target: Some(LuaVersion::LuaJIT),
+			..Default::default()
+		};
+		options.preset();
+		Self {
+			lua: Lua::new(),
+			options,
+			variables: PPVars::default(),
+			last: None,
+		}
+	}
+
+	/// Compiles the input as an expression whose values are returned if possible,
+	/// or as statements otherwise
+	fn compile(&mut self, input: &str) -> Result<String, Vec<Diagnostic>> {
+		let filename = String::from(NAME);
+		let mut input = input.to_owned();
+		let code = unsafe { input.as_bytes_mut() };
+		let (codes, variables, _, _, notes) =
+			preprocess_code(code, 1, false, &filename, &self.options)?;
+		report(&notes);
+		self.variables.extend(variables);
+		let code = preprocess_codes(0, codes, &self.variables, &filename)?;
+		let mut expression = Code::from(("return ", 1, 1));
+		expression.append(code.clone());
+		let parsed = scan_code(expression, &filename)
+			.and_then(|tokens| parse_tokens(tokens, &filename, &self.options));
+		let (ctokens, statics, warnings) = match parsed {
+			Ok(parsed) => parsed,
+			Err(_) => parse_tokens(scan_code(code, &filename)?, &filename, &self.options)?,
+		};
+		report(&warnings);
+		let code = Compiler::new(&self.options, &filename).compile_tokens(0, ctokens)?;
+		Ok(statics + &code)
+	}
+
+	/// Compiles and runs the input, printing the values it returns
+	fn run(&mut self, input: &str) {
+		let code = match self.compile(input) {
+			Ok(code) => code,
+			Err(diagnostics) => return report(&diagnostics),
+		};
+		let result = self
+			.lua
+			.load(&code)
+			.set_name(NAME)
+			.and_then(|chunk| chunk.eval::<MultiValue>())
+			.and_then(|values| {
+				if values.is_empty() {
+					return Ok(());
+				}
+				let tostring: Function = self.lua.globals().get("tostring")?;
+				let values = values
+					.into_iter()
+					.map(|value| tostring.call::<_, String>(value))
+					.collect::<Result<Vec<String>, _>>()?;
+				println!("{}", values.join("\t"));
+				Ok(())
+			});
+		if let Err(error) = result {
+			eprintln!("{error}");
+		}
+		self.last = Some(code);
+	}
+}
+
+/// Returns `true` if the input has brackets or a block comment that are still open,
+/// so it continues in the next line
+fn is_incomplete(input: &str) -> bool {
+	let mut depth = 0;
+	let mut quote = None;
+	let mut chars = input.chars().peekable();
+	while let Some(c) = chars.next() {
+		match (quote, c) {
+			(Some(_), '\\') => {
+				chars.next();
+			}
+			(Some(open), c) if c == open => quote = None,
+			(Some(_), _) => {}
+			(None, '/') if chars.next_if_eq(&'/').is_some() => {
+				chars.find(|&c| c == '\n');
+			}
+			(None, '/') if chars.next_if_eq(&'*').is_some() => loop {
+				match chars.next() {
+					None => return true,
+					Some('*') if chars.next_if_eq(&'/').is_some() => break,
+					Some(_) => {}
+				}
+			},
+			(None, '"' | '\'' | '`') => quote = Some(c),
+			(None, '(' | '{' | '[') => depth += 1,
+			(None, ')' | '}' | ']') => depth -= 1,
+			_ => {}
+		}
+	}
+	depth > 0
+}
+
+/// Reads Clue code from the standard input and runs it until it ends or `:quit` is typed
+pub fn repl() -> Result<(), String> {
+	let mut repl = Repl::new();
+	println!("Clue {} REPL, type :help for help", crate_version!());
+	let mut lines = io::stdin().lock().lines();
+	let mut input = String::new();
+	loop {
+		print!("{}", if input.is_empty() { "> " } else { "... " });
+		check!(io::stdout().flush());
+		let Some(line) = lines.next() else {
+			println!();
+			return Ok(());
+		};
+		let line = check!(line);
+		if input.is_empty() {
+			match line.trim() {
+				"" => continue,
+				":quit" | ":q" => return Ok(()),
+				":help" => {
+					println!("{HELP}");
+					continue;
+				}
+				":lua" => {
+					match &repl.last {
+						Some(code) => println!("{}", code.trim()),
+						None => println!("Nothing was compiled yet"),
+					}
+					continue;
+				}
+				_ => {}
+			}
+		}
+		input += &line;
+		input.push('\n');
+		if !is_incomplete(&input) {
+			repl.run(&input);
+			input.clear();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{is_incomplete, Repl};
+
+	#[test]
+	fn inputs_continue_until_closed() {
+		assert!(is_incomplete("fn f() {\n"));
+		assert!(is_incomplete("print(\"(\", {\n"));
+		assert!(!is_incomplete("print(\")\")\n"));
+		assert!(!is_incomplete("local t = {1, [2] = 3}\n"));
+		assert!(!is_incomplete("print(1) // (\n"));
+		assert!(!is_incomplete("print(/* { */ 1)\n"));
+		assert!(is_incomplete("/* print(1)\n"));
+		assert!(is_incomplete("fn f() { // }\n"));
+	}
+
+	#[test]
+	fn state_is_kept_between_inputs() {
+		let mut repl = Repl::new();
+		assert!(repl.compile("1 + 2").unwrap().contains("return 1+2"));
+		repl.compile("@define TWO 2\n@macro DOUBLE(x) { $x * $TWO }")
+			.unwrap();
+		assert!(repl.compile("$DOUBLE!(4)").unwrap().contains("return 4*2"));
+		repl.run("global x = $TWO");
+		let x: i32 = repl.lua.globals().get("x").unwrap();
+		assert_eq!(x, 2);
+		assert!(repl.last.unwrap().contains("x = 2"));
+	}
+}
@@ -0,0 +1,64 @@
+//! Watch mode, which recompiles a file or directory every time one of its files is saved
+//!
+//! Changes are found by polling the modification time of the files, so it works on any filesystem.
+//! Directories are compiled again with a [`Cache`], so only the files that changed are compiled
+//! again, together with the files that use a preprocessor variable or macro defined by one of them.
+//! The cache is kept in memory unless `--cache` is given.
+
+use crate::{compile_path, files::clue_files, find_clue_file, BuildArgs};
+use clue_core::{cache::Cache, env::Options};
+use std::{
+	collections::BTreeMap,
+	fs,
+	path::{Path, PathBuf},
+	thread,
+	time::{Duration, SystemTime},
+};
+
+/// How long to wait before checking the files again
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Returns the modification time of every file that is compiled
+fn modified_times(path: &Path) -> Result<BTreeMap<PathBuf, SystemTime>, String> {
+	let files = if path.is_dir() {
+		clue_files(path.to_path_buf())?
+	} else {
+		vec![path.to_path_buf()]
+	};
+	Ok(files
+		.into_iter()
+		.filter_map(|file| {
+			let modified = fs::metadata(&file).and_then(|metadata| metadata.modified());
+			Some((file, modified.ok()?))
+		})
+		.collect())
+}
+
+/// Compiles the given file or directory and compiles it again every time it changes, forever
+/// Errors are reported without stopping, so that they can be fixed while watching
+pub fn watch(args: &BuildArgs, options: Options) -> Result<(), String> {
+	let mut path = args.path.clone().expect("PATH is required");
+	if !path.is_dir() && !find_clue_file(&mut path) {
+		return Err(format!("{} was not found!", path.display()));
+	}
+	let mut cache = match &args.cache {
+		Some(cache_path) => Cache::load(cache_path, &options),
+		None => Cache::new(&options),
+	};
+	let mut times = BTreeMap::new();
+	println!("Watching \"{}\" for changes...", path.display());
+	loop {
+		match modified_times(&path) {
+			Ok(new_times) if new_times != times => {
+				times = new_times;
+				if let Err(e) = compile_path(args, options.clone(), Some(&mut cache)) {
+					eprintln!("Error: {e}");
+				}
+				println!("Waiting for changes...");
+			}
+			Ok(_) => {}
+			Err(e) => eprintln!("Error: {e}"),
+		}
+		thread::sleep(POLL_INTERVAL);
+	}
+}
@@ -0,0 +1,380 @@
+//! The cache stores the compiled code of every module of a project on disk, so that
+//! [`compile_project_cached`](crate::project::compile_project_cached) can skip the modules that didn't change.
+//!
+//! A module is taken from the cache only if its source code is the same and every preprocessor
+//! variable or macro it uses still has the same value.
+//! The whole cache is discarded when the version of the compiler or the [`Options`] change.
+
+use crate::{
+	code::Code,
+	diagnostic::{Diagnostic, Position, Span},
+	env::Options,
+	preprocessor::{PPCode, PPVar, PPVars},
+	sourcemap::{Mapping, SourceMap},
+};
+use ahash::AHashMap;
+use std::{
+	collections::BTreeSet,
+	env, fs,
+	hash::{Hash, Hasher},
+	path::PathBuf,
+};
+
+/// The first word of every cache file, followed by the key of the cache
+const HEADER: &str = "clue-cache";
+
+/// A 64-bit FNV-1a hasher, which unlike the hashers of the standard library
+/// gives the same hashes on every run, as they are stored on disk
+struct StableHasher(u64);
+
+impl Default for StableHasher {
+	fn default() -> Self {
+		Self(0xcbf2_9ce4_8422_2325)
+	}
+}
+
+impl Hasher for StableHasher {
+	fn finish(&self) -> u64 {
+		self.0
+	}
+
+	fn write(&mut self, bytes: &[u8]) {
+		for byte in bytes {
+			self.0 ^= *byte as u64;
+			self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+		}
+	}
+}
+
+/// Hashes the source code of a module
+///
+/// # Example
+/// ```rust
+/// use clue_core::cache::hash_source;
+///
+/// assert_eq!(hash_source(b"print(1)"), hash_source(b"print(1)"));
+/// assert_ne!(hash_source(b"print(1)"), hash_source(b"print(2)"));
+/// ```
+pub fn hash_source(code: &[u8]) -> u64 {
+	let mut hasher = StableHasher::default();
+	hasher.write(code);
+	hasher.finish()
+}
+
+fn hash_codes(codes: &PPCode, hasher: &mut StableHasher) {
+	for (code, uses_vars) in &codes.0 {
+		code.hash(hasher);
+		uses_vars.hash(hasher);
+	}
+}
+
+fn hash_variable(variable: &PPVar, hasher: &mut StableHasher) {
+	match variable {
+		PPVar::Simple(code) => {
+			0u8.hash(hasher);
+			code.hash(hasher);
+		}
+		PPVar::ToProcess(code) => {
+			1u8.hash(hasher);
+			code.hash(hasher);
+		}
+		PPVar::Macro {
+			code,
+			args,
+			ppvars,
+			vararg,
+		} => {
+			2u8.hash(hasher);
+			hash_codes(code, hasher);
+			args.hash(hasher);
+			vararg.hash(hasher);
+			let mut ppvars: Vec<(String, &PPVar)> = ppvars
+				.iter()
+				.map(|(name, variable)| (name.to_string(), variable))
+				.collect();
+			ppvars.sort_by(|(a, _), (b, _)| a.cmp(b));
+			for (name, variable) in ppvars {
+				name.hash(hasher);
+				hash_variable(variable, hasher);
+			}
+		}
+		PPVar::VarArgs(codes) => {
+			3u8.hash(hasher);
+			hash_codes(codes, hasher);
+		}
+	}
+}
+
+/// Returns `true` if both variables have the same value
+pub(crate) fn same_variable(a: &PPVar, b: &PPVar) -> bool {
+	let mut a_hasher = StableHasher::default();
+	let mut b_hasher = StableHasher::default();
+	hash_variable(a, &mut a_hasher);
+	hash_variable(b, &mut b_hasher);
+	a_hasher.finish() == b_hasher.finish()
+}
+
+/// Returns the hash of the value the preprocessor would use for the variable with the given name,
+/// which is an environment variable with that name if there is one
+fn hash_value(name: &str, variables: &PPVars) -> u64 {
+	let mut hasher = StableHasher::default();
+	if let Ok(value) = env::var(name) {
+		0u8.hash(&mut hasher);
+		value.hash(&mut hasher);
+	} else if let Some(variable) = variables.get(&Code::from((name, 0, 0))) {
+		1u8.hash(&mut hasher);
+		hash_variable(variable, &mut hasher);
+	}
+	hasher.finish()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A module stored in the cache
+pub struct CachedModule {
+	/// The hash of the source code of the module, see [`hash_source`]
+	pub source: u64,
+
+	/// Whether the module defines any preprocessor variable or macro,
+	/// in which case it has to be preprocessed even if it can be taken from the cache
+	pub defines: bool,
+
+	/// The preprocessor variables and macros used by the module and the hashes of their values
+	pub uses: Vec<(String, u64)>,
+
+	/// The compiled Lua code of the module
+	pub code: String,
+
+	/// The [`SourceMap`] of the code
+	pub sourcemap: SourceMap,
+
+	/// The statics defined in the module
+	pub statics: String,
+}
+
+impl CachedModule {
+	/// Creates a module to store in the cache, remembering the values of the given variables
+	/// `used` should contain every variable the module uses, see
+	/// [`used_variables`](crate::preprocessor::used_variables)
+	pub fn new(
+		source: u64,
+		defines: bool,
+		used: &BTreeSet<String>,
+		variables: &PPVars,
+		code: String,
+		sourcemap: SourceMap,
+		statics: String,
+	) -> Self {
+		Self {
+			source,
+			defines,
+			uses: used
+				.iter()
+				.map(|name| (name.clone(), hash_value(name, variables)))
+				.collect(),
+			code,
+			sourcemap,
+			statics,
+		}
+	}
+
+	/// Returns `true` if every variable used by the module still has the same value
+	pub fn is_valid(&self, variables: &PPVars) -> bool {
+		self.uses
+			.iter()
+			.all(|(name, hash)| hash_value(name, variables) == *hash)
+	}
+}
+
+/// Reads the next line, or the next `len` bytes followed by a new line if `len` is given
+fn read_field<'a>(data: &mut &'a str, len: Option<usize>) -> Option<&'a str> {
+	let (field, rest) = match len {
+		Some(len) => {
+			let field = data.get(..len)?;
+			(field, data.get(len..)?.strip_prefix('\n')?)
+		}
+		None => data.split_once('\n')?,
+	};
+	*data = rest;
+	Some(field)
+}
+
+fn parse_modules(mut data: &str, key: u64) -> Option<AHashMap<String, CachedModule>> {
+	let data = &mut data;
+	let header = read_field(data, None)?;
+	if header != format!("{HEADER} {key:016x}") {
+		return None;
+	}
+	let mut modules = AHashMap::default();
+	while !data.is_empty() {
+		let name = read_field(data, None)?.strip_prefix("module ")?.to_owned();
+		let mut fields = read_field(data, None)?.split(' ');
+		let source = u64::from_str_radix(fields.next()?, 16).ok()?;
+		let defines = fields.next()? == "1";
+		let uses_count: usize = fields.next()?.parse().ok()?;
+		let mappings_count: usize = fields.next()?.parse().ok()?;
+		let code_len: usize = fields.next()?.parse().ok()?;
+		let statics_len: usize = fields.next()?.parse().ok()?;
+		let mut uses = Vec::with_capacity(uses_count);
+		for _ in 0..uses_count {
+			let (name, hash) = read_field(data, None)?.split_once(' ')?;
+			uses.push((name.to_owned(), u64::from_str_radix(hash, 16).ok()?));
+		}
+		let mut mappings = Vec::with_capacity(mappings_count);
+		for _ in 0..mappings_count {
+			let mut fields = read_field(data, None)?.splitn(7, ' ');
+			let mut position = || -> Option<Position> {
+				Some(Position {
+					line: fields.next()?.parse().ok()?,
+					column: fields.next()?.parse().ok()?,
+					offset: fields.next()?.parse().ok()?,
+				})
+			};
+			let (generated, original) = (position()?, position()?);
+			mappings.push(Mapping {
+				generated,
+				source: fields.next()?.to_owned(),
+				original,
+			});
+		}
+		let code = read_field(data, Some(code_len))?.to_owned();
+		let statics = read_field(data, Some(statics_len))?.to_owned();
+		modules.insert(
+			name,
+			CachedModule {
+				source,
+				defines,
+				uses,
+				code,
+				sourcemap: SourceMap { mappings },
+				statics,
+			},
+		);
+	}
+	Some(modules)
+}
+
+#[derive(Debug, Clone)]
+/// The compiled modules of a project stored in a file, or only in memory
+pub struct Cache {
+	path: Option<PathBuf>,
+	key: u64,
+	modules: AHashMap<String, CachedModule>,
+}
+
+impl Cache {
+	/// Creates an empty cache for a project compiled with the given options,
+	/// which is only kept in memory and is never written by [`Cache::save`]
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{cache::*, env::Options};
+	///
+	/// let cache = Cache::new(&Options::default());
+	/// assert!(cache.get("main").is_none());
+	/// cache.save().unwrap();
+	/// ```
+	pub fn new(options: &Options) -> Self {
+		let mut hasher = StableHasher::default();
+		env!("CARGO_PKG_VERSION").hash(&mut hasher);
+		format!("{options:?}").hash(&mut hasher);
+		Self {
+			path: None,
+			key: hasher.finish(),
+			modules: AHashMap::default(),
+		}
+	}
+
+	/// Loads the cache stored in the given file for a project compiled with the given options
+	/// If the file doesn't exist, can't be read or was made by a different version of the compiler
+	/// or with different options, the cache will be empty
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{cache::*, env::Options};
+	///
+	/// let mut cache = Cache::load("cache_example", &Options::default());
+	/// assert!(cache.get("main").is_none());
+	/// ```
+	pub fn load(path: impl Into<PathBuf>, options: &Options) -> Self {
+		let path = path.into();
+		let mut cache = Self::new(options);
+		cache.modules = fs::read_to_string(&path)
+			.ok()
+			.and_then(|data| parse_modules(&data, cache.key))
+			.unwrap_or_default();
+		cache.path = Some(path);
+		cache
+	}
+
+	/// Returns the cached module with the given name
+	pub fn get(&self, name: &str) -> Option<&CachedModule> {
+		self.modules.get(name)
+	}
+
+	/// Stores a module in the cache, replacing the old one with the same name
+	pub fn insert(&mut self, name: String, module: CachedModule) {
+		self.modules.insert(name, module);
+	}
+
+	/// Removes every module whose name doesn't satisfy the given predicate
+	pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+		self.modules.retain(|name, _| keep(name))
+	}
+
+	/// Writes the cache to its file, if it has one
+	///
+	/// # Errors
+	/// If the file can't be written, an [`Err`] containing a [`Diagnostic`] will be returned
+	pub fn save(&self) -> Result<(), Box<Diagnostic>> {
+		let Some(path) = &self.path else {
+			return Ok(());
+		};
+		let mut names: Vec<&String> = self.modules.keys().collect();
+		names.sort();
+		let mut data = format!("{HEADER} {:016x}\n", self.key);
+		for name in names {
+			let module = &self.modules[name];
+			let mappings = &module.sourcemap.mappings;
+			data += &format!(
+				"module {name}\n{:016x} {} {} {} {} {}\n",
+				module.source,
+				module.defines as u8,
+				module.uses.len(),
+				mappings.len(),
+				module.code.len(),
+				module.statics.len()
+			);
+			for (name, hash) in &module.uses {
+				data += &format!("{name} {hash:016x}\n");
+			}
+			for Mapping {
+				generated,
+				source,
+				original,
+			} in mappings
+			{
+				data += &format!(
+					"{} {} {} {} {} {} {source}\n",
+					generated.line,
+					generated.column,
+					generated.offset,
+					original.line,
+					original.column,
+					original.offset
+				);
+			}
+			data += &module.code;
+			data.push('\n');
+			data += &module.statics;
+			data.push('\n');
+		}
+		fs::write(path, data).map_err(|e| {
+			Box::new(Diagnostic::error(
+				"io",
+				e.to_string(),
+				path.to_string_lossy().into_owned(),
+				Span::default(),
+			))
+		})
+	}
+}
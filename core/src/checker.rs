@@ -0,0 +1,428 @@
+//! The `checker` module contains the type checker, which runs after the parser when
+//! [`Options::env_types`] is not [`TypesMode::NONE`].
+//!
+//! Type annotations are optional: values whose type is not known are assumed to be [`LuaType::ANY`],
+//! so only code that uses annotations is checked. The annotations are stored in the AST but the
+//! [`Compiler`](crate::compiler::Compiler) ignores them, so they never reach the output.
+//!
+//! [`Options::env_types`]: crate::env::Options::env_types
+
+use crate::diagnostic::{Diagnostic, Severity, Span};
+use crate::env::TypesMode;
+use crate::parser::{
+	expr_span, walk_block, walk_match_case, walk_token, CodeBlock, ComplexToken, ComplexToken::*,
+	Expression, FunctionArgs, FunctionTypes, LuaType, MatchCase, Visitor,
+};
+use crate::scanner::TokenType::*;
+use ahash::AHashMap;
+
+struct Variable<'a> {
+	luatype: LuaType,
+	function: Option<(&'a FunctionArgs, &'a FunctionTypes)>,
+}
+
+struct TypeChecker<'a> {
+	filename: &'a str,
+	severity: Severity,
+	scopes: Vec<AHashMap<&'a str, Variable<'a>>>,
+	returns: Vec<(String, Option<&'a LuaType>)>,
+	diagnostics: Vec<Diagnostic>,
+}
+
+fn operator_rank(lexeme: &str) -> u8 {
+	match lexeme.trim() {
+		"and" | "or" => 4,
+		"not" | "==" | "~=" | "<" | ">" | "<=" | ">=" => 3,
+		".." => 2,
+		"+" | "-" | "*" | "/" | "%" | "^" | "#" | "&" | "|" | "~" | "<<" | ">>" => 1,
+		_ => 0,
+	}
+}
+
+fn function_name(name: &Expression) -> String {
+	name.iter()
+		.map(|t| match t {
+			SYMBOL(lexeme, _) => lexeme.as_str(),
+			_ => "",
+		})
+		.collect()
+}
+
+fn called_function(expr: &Expression) -> Option<(&String, &Vec<Expression>)> {
+	match (expr.front(), expr.get(1)) {
+		(Some(SYMBOL(name, _)), Some(CALL(args, _))) if expr.len() == 2 => Some((name, args)),
+		_ => None,
+	}
+}
+
+impl<'a> TypeChecker<'a> {
+	fn report(&mut self, code: &str, message: String, span: Span) {
+		self.diagnostics.push(Diagnostic::new(
+			self.severity,
+			code,
+			message,
+			self.filename,
+			span,
+		))
+	}
+
+	fn declare(
+		&mut self,
+		name: &'a str,
+		luatype: LuaType,
+		function: Option<(&'a FunctionArgs, &'a FunctionTypes)>,
+	) {
+		if let Some(scope) = self.scopes.last_mut() {
+			scope.insert(name, Variable { luatype, function });
+		}
+	}
+
+	fn lookup(&self, name: &str) -> Option<&Variable<'a>> {
+		self.scopes.iter().rev().find_map(|scope| scope.get(name))
+	}
+
+	fn infer_symbol(&self, lexeme: &str) -> LuaType {
+		match lexeme.chars().next() {
+			Some('0'..='9' | '.') if lexeme != "..." => LuaType::NUMBER,
+			Some('"' | '\'' | '`' | '[') => LuaType::STRING,
+			_ => match lexeme {
+				"true" | "false" => LuaType::BOOLEAN,
+				"nil" => LuaType::NIL,
+				name => self
+					.lookup(name)
+					.map_or(LuaType::ANY, |variable| variable.luatype.clone()),
+			},
+		}
+	}
+
+	fn infer(&self, expr: &Expression) -> LuaType {
+		let rank = expr
+			.iter()
+			.map(|t| match t {
+				SYMBOL(lexeme, _) => operator_rank(lexeme),
+				_ => 0,
+			})
+			.max()
+			.unwrap_or_default();
+		match rank {
+			4 => return LuaType::ANY,
+			3 => return LuaType::BOOLEAN,
+			2 => return LuaType::STRING,
+			1 => return LuaType::NUMBER,
+			_ => {}
+		}
+		let mut values = expr
+			.iter()
+			.filter(|t| !matches!(t, IDENT { expr, .. } if expr.is_empty()));
+		let (Some(value), None) = (values.next(), values.next()) else {
+			return LuaType::ANY;
+		};
+		match value {
+			SYMBOL(lexeme, _) => self.infer_symbol(lexeme),
+			IDENT { expr, .. } => match (expr.front(), called_function(expr)) {
+				(Some(SYMBOL(lexeme, _)), None) if expr.len() == 1 => self.infer_symbol(lexeme),
+				(_, Some((name, _))) => self
+					.lookup(name)
+					.and_then(|variable| variable.function)
+					.and_then(|(_, (_, returns))| returns.clone())
+					.unwrap_or(LuaType::ANY),
+				_ => LuaType::ANY,
+			},
+			TABLE { .. } => LuaType::TABLE,
+			LAMBDA { .. } => LuaType::FUNCTION,
+			EXPR(expr, _) => self.infer(expr),
+			_ => LuaType::ANY,
+		}
+	}
+
+	fn check_value(&mut self, name: &str, expected: &LuaType, value: &Expression) {
+		let found = self.infer(value);
+		if !expected.accepts(&found) {
+			self.report(
+				"type-mismatch",
+				format!("Cannot assign a value of type '{found}' to '{name}' of type '{expected}'"),
+				expr_span(value),
+			)
+		}
+	}
+
+	fn check_call(&mut self, name: &str, args: &[Expression], span: Span) {
+		let Some((fargs, (types, _))) = self.lookup(name).and_then(|variable| variable.function)
+		else {
+			return;
+		};
+		let varargs = fargs.last().map_or(false, |(arg, _)| arg == "...");
+		if !varargs && args.len() > fargs.len() {
+			self.report(
+				"argument-count",
+				format!(
+					"'{name}' takes {} argument(s) but was given {}",
+					fargs.len(),
+					args.len()
+				),
+				span,
+			);
+		}
+		for (((arg, _), expected), value) in fargs.iter().zip(types).zip(args) {
+			let Some(expected) = expected else {
+				continue;
+			};
+			let found = self.infer(value);
+			if !expected.accepts(&found) {
+				self.report(
+					"type-mismatch",
+					format!(
+						"Argument '{arg}' of '{name}' expects type '{expected}' but was given '{found}'"
+					),
+					expr_span(value),
+				)
+			}
+		}
+	}
+
+	fn check_block(&mut self, block: &'a CodeBlock, declared: &[(&'a str, LuaType)]) {
+		self.scopes.push(AHashMap::default());
+		for (name, luatype) in declared {
+			self.declare(name, luatype.clone(), None);
+		}
+		walk_block(self, block);
+		self.scopes.pop();
+	}
+
+	fn check_function(
+		&mut self,
+		name: String,
+		args: &'a FunctionArgs,
+		(types, returns): &'a FunctionTypes,
+		code: &'a CodeBlock,
+	) {
+		self.scopes.push(AHashMap::default());
+		for (i, (arg, default)) in args.iter().enumerate() {
+			let luatype = types.get(i).cloned().flatten();
+			if let Some((default, _)) = default {
+				self.visit_expression(default);
+				if let Some(luatype) = &luatype {
+					self.check_value(arg, luatype, default);
+				}
+			}
+			self.declare(arg, luatype.unwrap_or(LuaType::ANY), None);
+		}
+		self.returns.push((name, returns.as_ref()));
+		self.visit_block(code);
+		self.returns.pop();
+		self.scopes.pop();
+	}
+
+	fn check_return(&mut self, values: Option<&Expression>, span: Span) {
+		let Some((name, Some(expected))) = self.returns.last() else {
+			return;
+		};
+		let found = values.map_or(LuaType::NIL, |value| self.infer(value));
+		if !expected.accepts(&found) {
+			let message = format!("'{name}' should return '{expected}' but returns '{found}'");
+			self.report("type-mismatch", message, span)
+		}
+	}
+}
+
+impl<'a> Visitor<'a> for TypeChecker<'a> {
+	fn visit_token(&mut self, t: &'a ComplexToken) {
+		match t {
+			VARIABLE {
+				names,
+				values,
+				types,
+				..
+			} => {
+				walk_token(self, t);
+				for (i, name) in names.iter().enumerate() {
+					let luatype = types.get(i).cloned().flatten();
+					if let (Some(luatype), Some(value)) = (&luatype, values.get(i)) {
+						self.check_value(name, luatype, value);
+					}
+					let function = match values.get(i).and_then(|value| value.front()) {
+						Some(LAMBDA { args, types, .. }) if luatype.is_none() => {
+							Some((args, types))
+						}
+						_ => None,
+					};
+					self.declare(name, luatype.unwrap_or(LuaType::ANY), function);
+				}
+			}
+			ALTER {
+				kind,
+				names,
+				values,
+				span,
+				..
+			} => {
+				walk_token(self, t);
+				for (i, name) in names.iter().enumerate() {
+					let Some(SYMBOL(name, _)) = name.front().filter(|_| name.len() == 1) else {
+						continue;
+					};
+					let Some(expected) = self.lookup(name).map(|variable| variable.luatype.clone())
+					else {
+						continue;
+					};
+					let found = match kind {
+						DEFINE => match values.get(i) {
+							Some(value) => self.infer(value),
+							None => LuaType::NIL,
+						},
+						INCREASE | DECREASE | MULTIPLY | DIVIDE | EXPONENTIATE | MODULATE => {
+							LuaType::NUMBER
+						}
+						CONCATENATE => LuaType::STRING,
+						_ => continue,
+					};
+					if !expected.accepts(&found) {
+						self.report(
+							"type-mismatch",
+							format!(
+								"Cannot assign a value of type '{found}' to '{name}' of type '{expected}'"
+							),
+							values.get(i).map_or(*span, expr_span),
+						)
+					}
+				}
+			}
+			FUNCTION {
+				name,
+				args,
+				types,
+				code,
+				..
+			} => {
+				let fname = function_name(name);
+				if let Some(SYMBOL(name, _)) = name.front().filter(|_| name.len() == 1) {
+					self.declare(name, LuaType::FUNCTION, Some((args, types)));
+				}
+				self.check_function(fname, args, types, code)
+			}
+			LAMBDA {
+				args, types, code, ..
+			} => self.check_function(String::from("fn"), args, types, code),
+			MATCH_BLOCK {
+				name,
+				value,
+				branches,
+				..
+			} => {
+				self.visit_expression(value);
+				self.declare(name, LuaType::ANY, None);
+				branches.iter().for_each(|case| self.visit_match_case(case))
+			}
+			LOOP_UNTIL {
+				condition, code, ..
+			} => {
+				self.scopes.push(AHashMap::default());
+				self.visit_expression(&code.code);
+				self.visit_expression(condition);
+				self.scopes.pop();
+			}
+			FOR_LOOP {
+				iterator,
+				start,
+				end,
+				alter,
+				code,
+				..
+			} => {
+				self.visit_expression(start);
+				self.visit_expression(end);
+				self.visit_expression(alter);
+				self.check_block(code, &[(iterator, LuaType::NUMBER)]);
+			}
+			FOR_FUNC_LOOP {
+				iterators,
+				expr,
+				code,
+				..
+			} => {
+				self.visit_expression(expr);
+				let iterators: Vec<(&str, LuaType)> = iterators
+					.iter()
+					.map(|iterator| (iterator.as_str(), LuaType::ANY))
+					.collect();
+				self.check_block(code, &iterators);
+			}
+			TRY_CATCH {
+				totry,
+				error,
+				catch,
+				..
+			} => {
+				self.visit_block(totry);
+				if let Some(catch) = catch {
+					match error {
+						Some(error) => self.check_block(catch, &[(error, LuaType::ANY)]),
+						None => self.visit_block(catch),
+					}
+				}
+			}
+			IDENT { expr, span, .. } => {
+				walk_token(self, t);
+				if let Some((name, args)) = called_function(expr) {
+					self.check_call(name, args, *span)
+				}
+			}
+			RETURN_EXPR(exprs, span) => {
+				walk_token(self, t);
+				self.check_return(exprs.as_ref().and_then(|exprs| exprs.first()), *span)
+			}
+			EXTERN_GLOBAL { name, args, .. } => self.declare(
+				name,
+				match args {
+					Some(_) => LuaType::FUNCTION,
+					None => LuaType::ANY,
+				},
+				None,
+			),
+			_ => walk_token(self, t),
+		}
+	}
+
+	fn visit_block(&mut self, block: &'a CodeBlock) {
+		self.check_block(block, &[])
+	}
+
+	fn visit_match_case(&mut self, case: &'a MatchCase) {
+		self.scopes.push(AHashMap::default());
+		walk_match_case(self, case);
+		self.scopes.pop();
+	}
+}
+
+/// Checks the type annotations of the given AST, returning every mismatch found
+/// The [`Diagnostic`]s are warnings if `mode` is [`TypesMode::WARN`] and errors if it's [`TypesMode::STRICT`],
+/// while [`TypesMode::NONE`] skips the check entirely
+///
+/// # Example
+/// ```rust
+/// use clue_core::{checker::check_types, env::TypesMode, Clue};
+///
+/// let clue = Clue::new();
+/// let (expr, _) = clue.parse_code("local x: number = \"one\"".to_owned()).unwrap();
+/// let diagnostics = check_types(&expr, "main.clue", TypesMode::STRICT);
+///
+/// assert_eq!(diagnostics.len(), 1);
+/// assert!(diagnostics[0].is_error());
+/// assert_eq!(diagnostics[0].span.start.column, 19);
+/// ```
+pub fn check_types(expr: &Expression, filename: &str, mode: TypesMode) -> Vec<Diagnostic> {
+	let severity = match mode {
+		TypesMode::NONE => return Vec::new(),
+		TypesMode::WARN => Severity::Warning,
+		TypesMode::STRICT => Severity::Error,
+	};
+	let mut checker = TypeChecker {
+		filename,
+		severity,
+		scopes: vec![AHashMap::default()],
+		returns: Vec::new(),
+		diagnostics: Vec::new(),
+	};
+	checker.visit_expression(expr);
+	checker.diagnostics
+}
@@ -9,6 +9,7 @@ use std::{
 		VecDeque,
 	},
 	ffi::OsString,
+	fmt::{self, Write},
 	hash::Hash,
 };
 
@@ -17,8 +18,8 @@ use utf8_decode::decode;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-/// A tuple containing a byte, the line it was on, and the column it was on.
-pub type CodeChar = (u8, usize, usize);
+/// A tuple containing a byte, the line it was on, the column it was on and its byte offset in the source file.
+pub type CodeChar = (u8, usize, usize, usize);
 
 #[derive(Debug, Clone, Default)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -35,6 +36,7 @@ pub struct CodeBytes {
 	code: Code,
 	line: usize,
 	column: usize,
+	offset: usize,
 	read: usize,
 }
 
@@ -44,6 +46,7 @@ pub struct CodeBytes {
 /// If the character is invalid, it will return the Unicode replacement character (U+FFFD)
 pub struct CodeChars {
 	code: CodeBytes,
+	offset: usize,
 }
 
 impl Iterator for CodeBytes {
@@ -51,10 +54,11 @@ impl Iterator for CodeBytes {
 
 	/// Returns an [`Option`] with the next byte in the [`Code`].
 	fn next(&mut self) -> Option<Self::Item> {
-		self.code.pop_start().map(|(c, line, column)| {
+		self.code.pop_start().map(|(c, line, column, offset)| {
 			self.read += 1;
 			self.line = line;
 			self.column = column;
+			self.offset = offset + 1;
 			c
 		})
 	}
@@ -68,6 +72,9 @@ impl Iterator for CodeChars {
 	/// Returns [`None`] when iteration is finished.
 	/// If the next character is invalid, it will return the Unicode replacement character (U+FFFD)
 	fn next(&mut self) -> Option<Self::Item> {
+		// the offset of a character is the one of its first byte
+		let first = self.code.code.list.front();
+		self.offset = first.map_or(self.code.offset, |(.., offset)| *offset);
 		match decode(&mut self.code) {
 			None => None,
 			Some(Err(_)) => Some('\u{FFFD}'), // U+FFFD Unicode replacement character
@@ -93,6 +100,12 @@ impl CodeChars {
 		self.code.column
 	}
 
+	/// Returns the byte offset of the last character in the source file,
+	/// or the one right after the last character if there are no characters left.
+	pub const fn offset(&self) -> usize {
+		self.offset
+	}
+
 	/// Returns the number of bytes read by the iterator since the last time this function was called.
 	pub fn bytes_read(&mut self) -> usize {
 		let read = self.code.read;
@@ -119,28 +132,34 @@ impl IntoIterator for Code {
 	}
 }
 
-impl ToString for Code {
-	fn to_string(&self) -> String {
-		let mut result = String::with_capacity(self.len());
+impl fmt::Display for Code {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		for c in self.clone().chars() {
-			result.push(c);
+			f.write_char(c)?;
 		}
-		result
+		Ok(())
 	}
 }
 
-impl<'a> From<(&'a [u8], usize, usize)> for Code {
-	fn from(value: (&'a [u8], usize, usize)) -> Self {
-		let (iter, line, mut column) = value;
+impl<'a> From<(&'a [u8], usize, usize, usize)> for Code {
+	fn from(value: (&'a [u8], usize, usize, usize)) -> Self {
+		let (iter, line, mut column, mut offset) = value;
 		let mut result = Code::with_capacity(iter.len());
 		for c in iter {
-			result.push((*c, line, column));
+			result.push((*c, line, column, offset));
 			column += 1;
+			offset += 1;
 		}
 		result
 	}
 }
 
+impl<'a> From<(&'a [u8], usize, usize)> for Code {
+	fn from(value: (&'a [u8], usize, usize)) -> Self {
+		Code::from((value.0, value.1, value.2, 0))
+	}
+}
+
 impl<'a, const N: usize> From<(&'a [u8; N], usize, usize)> for Code {
 	fn from(value: (&'a [u8; N], usize, usize)) -> Self {
 		Code::from((value.0 as &[u8], value.1, value.2))
@@ -159,6 +178,18 @@ impl From<(String, usize, usize)> for Code {
 	}
 }
 
+impl<'a> From<(&'a str, usize, usize, usize)> for Code {
+	fn from(value: (&'a str, usize, usize, usize)) -> Self {
+		Code::from((value.0.as_bytes(), value.1, value.2, value.3))
+	}
+}
+
+impl From<(String, usize, usize, usize)> for Code {
+	fn from(value: (String, usize, usize, usize)) -> Self {
+		Code::from((value.0.as_bytes(), value.1, value.2, value.3))
+	}
+}
+
 impl PartialEq for Code {
 	fn eq(&self, other: &Self) -> bool {
 		self.len() == other.len() && {
@@ -281,7 +312,7 @@ impl Code {
 	}
 
 	/// Returns an iterator over the characters in the [`Code`] without consuming it.
-	pub fn iter(&self) -> Iter<CodeChar> {
+	pub fn iter(&self) -> Iter<'_, CodeChar> {
 		self.list.iter()
 	}
 
@@ -291,13 +322,17 @@ impl Code {
 			code: self,
 			line: 0,
 			column: 0,
+			offset: 0,
 			read: 0,
 		}
 	}
 
 	/// Returns an iterator over the characters in the [`Code`] consuming it.
 	pub const fn chars(self) -> CodeChars {
-		CodeChars { code: self.bytes() }
+		CodeChars {
+			code: self.bytes(),
+			offset: 0,
+		}
 	}
 
 	/// Trims whitespaces from the start and end of the [`Code`].
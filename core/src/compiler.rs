@@ -3,23 +3,26 @@
 //! The compiler module handles the compilation of a list of [`ComplexToken`] ([`Expression`]) into a Lua code.
 //! It exposes the [`Compiler`] struct which is used to compile to Lua.
 
+use std::cell::RefCell;
 use std::fmt::Write;
 use std::iter::{Iterator, Peekable};
 
 use crate::{
+	diagnostic::{Diagnostic, Span},
 	env::{ContinueMode, Options},
 	format_clue,
 	parser::{CodeBlock, ComplexToken, ComplexToken::*, Expression, FunctionArgs},
 	scanner::TokenType::*,
+	sourcemap::SourceMap,
 };
 
 /// The Compiler struct is used to compile a list of [`ComplexToken`] ([`Expression`]) into a lua code.
 ///
 /// # Example
 /// ```rust
-/// use clue_core::{compiler::*, env::Options, parser::*, scanner::*, Clue};
+/// use clue_core::{compiler::*, diagnostic::Diagnostic, env::Options, parser::*, scanner::*, Clue};
 ///
-/// fn main() -> Result<(), String> {
+/// fn main() -> Result<(), Vec<Diagnostic>> {
 ///     let options = Options::default();
 ///     let filename = String::from("file.clue");
 ///     let compiler = Compiler::new(&options, &filename);
@@ -35,6 +38,7 @@ use crate::{
 pub struct Compiler<'a> {
 	options: &'a Options,
 	filename: &'a String,
+	mappings: RefCell<Vec<(usize, Span)>>,
 }
 
 impl<'a> Compiler<'a> {
@@ -47,7 +51,24 @@ impl<'a> Compiler<'a> {
 	/// let compiler = Compiler::new(&options, &String::from("file.clue"));
 	/// ```
 	pub const fn new(options: &'a Options, filename: &'a String) -> Self {
-		Self { options, filename }
+		Self {
+			options,
+			filename,
+			mappings: RefCell::new(Vec::new()),
+		}
+	}
+
+	fn internal_error(&self, msg: impl Into<String>) -> Box<Diagnostic> {
+		Box::new(Diagnostic::error(
+			"internal",
+			msg,
+			self.filename.as_str(),
+			Span::default(),
+		))
+	}
+
+	fn map(&self, result: &str, span: Span) {
+		self.mappings.borrow_mut().push((result.len(), span))
 	}
 
 	fn indentate(&self, scope: usize) -> String {
@@ -69,47 +90,57 @@ impl<'a> Compiler<'a> {
 
 	fn compile_list<T>(
 		&self,
+		result: &mut String,
 		list: Vec<T>,
 		separator: &str,
-		tostring: &mut impl FnMut(T) -> Result<String, String>,
-	) -> Result<String, String> {
-		let mut result = String::new();
+		tostring: &mut impl FnMut(&mut String, T) -> Result<(), Box<Diagnostic>>,
+	) -> Result<(), Box<Diagnostic>> {
 		let end = list.len();
 		let mut start = 0usize;
 		for element in list {
-			result += &(tostring(element)?);
+			tostring(result, element)?;
 			start += 1;
 			if start < end {
-				result += separator
+				*result += separator
 			}
 		}
-		Ok(result)
+		Ok(())
 	}
 
-	fn compile_identifiers(&self, names: Vec<String>) -> Result<String, String> {
-		self.compile_list(names, ", ", &mut Ok)
+	fn compile_identifiers(&self, result: &mut String, names: Vec<String>) {
+		*result += &names.join(", ")
 	}
 
-	fn compile_expressions(&self, scope: usize, values: Vec<Expression>) -> Result<String, String> {
-		self.compile_list(values, ", ", &mut |expr| {
-			self.compile_expression(scope, expr)
+	fn compile_expressions(
+		&self,
+		result: &mut String,
+		scope: usize,
+		values: Vec<Expression>,
+	) -> Result<(), Box<Diagnostic>> {
+		self.compile_list(result, values, ", ", &mut |result, expr| {
+			self.compile_expression(result, scope, expr)
 		})
 	}
 
 	fn compile_function(
 		&self,
+		result: &mut String,
 		scope: usize,
 		args: FunctionArgs,
 		code: CodeBlock,
-	) -> Result<(String, String), String> {
-		let mut code = self.compile_code_block(scope + self.options.env_debug as usize, "", code)?;
-		let args = self.compile_list(args, ", ", &mut |(arg, default)| {
+	) -> Result<(), Box<Diagnostic>> {
+		let names: Vec<&str> = args.iter().map(|(arg, _)| arg.as_str()).collect();
+		let names = names.join(", ");
+		*result += &format_clue!(names, ")");
+		let pre = self.indentate(scope);
+		if self.options.env_debug {
+			*result += &format_clue!("\n", pre, "\tlocal _result = {xpcall(function(", names, ")");
+		}
+		for (arg, default) in args.into_iter().rev() {
 			if let Some((default, line)) = default {
-				let default = self.compile_expression(scope + 2, default)?;
 				let pre = self.indentate(scope + 1);
 				let debug = self.compile_debug_line(line, scope + 2, true);
-				let line = self.compile_debug_comment(line);
-				code = format_clue!(
+				*result += &format_clue!(
 					"\n",
 					pre,
 					"if ",
@@ -119,26 +150,15 @@ impl<'a> Compiler<'a> {
 					"\t",
 					debug,
 					arg,
-					" = ",
-					default,
-					line,
-					"\n",
-					pre,
-					"end",
-					code
+					" = "
 				);
+				self.compile_expression(result, scope + 2, default)?;
+				*result += &format_clue!(self.compile_debug_comment(line), "\n", pre, "end");
 			}
-			Ok(arg)
-		})?;
+		}
+		self.compile_code_block(result, scope + self.options.env_debug as usize, "", code)?;
 		if self.options.env_debug {
-			let pre = self.indentate(scope);
-			code = format_clue!(
-				"\n",
-				pre,
-				"\tlocal _result = {xpcall(function(",
-				args,
-				")",
-				code,
+			*result += &format_clue!(
 				"end, function(err)\n",
 				pre,
 				"\t\t_errored_file = \"",
@@ -148,10 +168,10 @@ impl<'a> Compiler<'a> {
 				"\t\t_clue_error(err)\n",
 				pre,
 				"\tend",
-				if args.is_empty() {
+				if names.is_empty() {
 					String::new()
 				} else {
-					format_clue!(", ", args)
+					format_clue!(", ", names)
 				},
 				")}\n",
 				pre,
@@ -171,26 +191,31 @@ impl<'a> Compiler<'a> {
 				pre
 			)
 		}
-		Ok((code, args))
+		Ok(())
 	}
 
 	fn compile_code_block(
 		&self,
+		result: &mut String,
 		scope: usize,
 		start: &str,
 		block: CodeBlock,
-	) -> Result<String, String> {
+	) -> Result<(), Box<Diagnostic>> {
 		let pre = self.indentate(scope);
-		let code = self.compile_tokens(scope + 1, block.code)?;
-		let debug = self.compile_debug_line(block.start, scope + 1, true);
-		Ok(if self.options.env_debug {
-			format!(
-				"{}\n{}\t{}--{}->{}\n{}\n{}",
-				start, pre, debug, block.start, block.end, code, pre
+		if self.options.env_debug {
+			let debug = self.compile_debug_line(block.start, scope + 1, true);
+			write!(
+				result,
+				"{}\n{}\t{}--{}->{}\n",
+				start, pre, debug, block.start, block.end
 			)
+			.map_err(|e| self.internal_error(e.to_string()))?;
 		} else {
-			format_clue!(start, "\n", code, "\n", pre)
-		})
+			*result += &format_clue!(start, "\n");
+		}
+		self.compile_statements(result, scope + 1, block.code)?;
+		*result += &format_clue!("\n", pre);
+		Ok(())
 	}
 
 	fn compile_debug_comment(&self, line: usize) -> String {
@@ -214,117 +239,181 @@ impl<'a> Compiler<'a> {
 		}
 	}
 
-	fn compile_identifier(&self, scope: usize, expr: Expression) -> Result<String, String> {
-		let mut result = String::with_capacity(32);
+	fn compile_identifier(
+		&self,
+		result: &mut String,
+		scope: usize,
+		expr: Expression,
+	) -> Result<(), Box<Diagnostic>> {
 		for t in expr {
-			result += &match t {
-				SYMBOL(lexeme) => lexeme,
-				EXPR(expr) => self.compile_expression(scope, expr)?,
-				CALL(args) => {
-					format_clue!("(", self.compile_expressions(scope, args.clone())?, ")")
+			match t {
+				SYMBOL(lexeme, _) => *result += &lexeme,
+				EXPR(expr, _) => self.compile_expression(result, scope, expr)?,
+				CALL(args, _) => {
+					result.push('(');
+					self.compile_expressions(result, scope, args)?;
+					result.push(')');
 				}
-				_ => return Err(String::from("Unexpected ComplexToken found")),
+				_ => return Err(self.internal_error("Unexpected ComplexToken found")),
 			}
 		}
-		Ok(result)
+		Ok(())
+	}
+
+	fn compile_table_entries<T>(
+		&self,
+		result: &mut String,
+		scope: usize,
+		entries: Vec<(T, Expression, usize)>,
+		name: &mut impl FnMut(&mut String, T) -> Result<(), Box<Diagnostic>>,
+	) -> Result<(), Box<Diagnostic>> {
+		let mut prevline = 0;
+		let pre = self.indentate(scope);
+		self.compile_list(result, entries, ", ", &mut |result, (key, value, line)| {
+			if prevline != 0 {
+				*result += &self.compile_debug_comment(prevline);
+			}
+			prevline = line;
+			*result += &format_clue!("\n", pre);
+			name(result, key)?;
+			self.compile_expression(result, scope, value)
+		})?;
+		*result += &self.compile_debug_comment(prevline);
+		Ok(())
 	}
 
-	fn compile_expression(&self, mut scope: usize, expr: Expression) -> Result<String, String> {
-		let mut result = String::with_capacity(64);
+	fn compile_expression(
+		&self,
+		result: &mut String,
+		scope: usize,
+		expr: Expression,
+	) -> Result<(), Box<Diagnostic>> {
 		for t in expr {
-			result += &match t {
-				SYMBOL(lexeme) => lexeme,
+			match t {
+				SYMBOL(lexeme, _) => *result += &lexeme,
 				TABLE {
 					values,
 					metas,
 					metatable,
+					..
 				} => {
-					scope += 1;
-					let mut prevline = 0;
-					let pre1 = self.indentate(scope);
-					let values = if values.is_empty() {
-						String::new()
-					} else {
-						self.compile_list(values, ", ", &mut |(name, value, line)| {
-							let value = self.compile_expression(scope, value)?;
-							let l = if prevline != 0 {
-								self.compile_debug_comment(prevline)
-							} else {
-								String::new()
-							};
-							prevline = line;
-							if let Some(name) = name {
-								let name = self.compile_expression(scope, name)?;
-								Ok(format_clue!(l, "\n", pre1, name, " = ", value))
-							} else {
-								Ok(format_clue!(l, "\n", pre1, value))
-							}
-						})? + &self.compile_debug_comment(prevline)
-							+ "\n"
-					};
-					prevline = 0;
-					let pre2 = self.indentate(scope - 1);
-					if metas.is_empty() {
-						scope -= 1;
-						if let Some(metatable) = metatable {
-							format!("setmetatable({{{values}{pre2}}}, {metatable})")
-						} else {
-							format!("{{{values}{pre2}}}")
-						}
-					} else {
-						let metas =
-							self.compile_list(metas, ", ", &mut |(name, value, line)| {
-								let value = self.compile_expression(scope, value)?;
-								let l = if prevline != 0 {
-									self.compile_debug_comment(prevline)
-								} else {
-									String::new()
-								};
-								prevline = line;
-								Ok(format_clue!(l, "\n", pre1, name, " = ", value))
-							})?;
-						scope -= 1;
-						let line = self.compile_debug_comment(prevline);
-						format!("setmetatable({{{values}{pre2}}}, {{{metas}{line}\n{pre2}}})",)
+					let pre = self.indentate(scope);
+					let setmetatable = !metas.is_empty() || metatable.is_some();
+					if setmetatable {
+						*result += "setmetatable(";
+					}
+					result.push('{');
+					if !values.is_empty() {
+						self.compile_table_entries(
+							result,
+							scope + 1,
+							values,
+							&mut |result, name| {
+								if let Some(name) = name {
+									self.compile_expression(result, scope + 1, name)?;
+									*result += " = ";
+								}
+								Ok(())
+							},
+						)?;
+						result.push('\n');
+					}
+					*result += &format_clue!(pre, "}");
+					if !metas.is_empty() {
+						*result += ", {";
+						self.compile_table_entries(
+							result,
+							scope + 1,
+							metas,
+							&mut |result, name| {
+								*result += &format_clue!(name, " = ");
+								Ok(())
+							},
+						)?;
+						*result += &format_clue!("\n", pre, "})");
+					} else if let Some(metatable) = metatable {
+						*result += &format_clue!(", ", metatable, ")");
 					}
 				}
-				LAMBDA { args, code } => {
-					let (code, args) = self.compile_function(scope, args, code)?;
-					format_clue!("function(", args, ")", code, "end")
+				LAMBDA { args, code, .. } => {
+					*result += "function(";
+					self.compile_function(result, scope, args, code)?;
+					*result += "end";
 				}
-				IDENT { expr, .. } => self.compile_identifier(scope, expr)?,
-				CALL(args) => format!("({})", self.compile_expressions(scope, args)?),
-				EXPR(expr) => format!("({})", self.compile_expression(scope, expr)?),
-				_ => return Err(String::from("Unexpected ComplexToken found")),
+				IDENT { expr, .. } => self.compile_identifier(result, scope, expr)?,
+				CALL(args, _) => {
+					result.push('(');
+					self.compile_expressions(result, scope, args)?;
+					result.push(')');
+				}
+				EXPR(expr, _) => {
+					result.push('(');
+					self.compile_expression(result, scope, expr)?;
+					result.push(')');
+				}
+				_ => return Err(self.internal_error("Unexpected ComplexToken found")),
 			}
 		}
-		Ok(result)
+		Ok(())
 	}
 
 	fn compile_elseif_chain(
 		&self,
+		result: &mut String,
 		scope: usize,
 		condition: Expression,
 		code: CodeBlock,
 		next: Option<Box<ComplexToken>>,
-	) -> Result<String, String> {
-		let condition = self.compile_expression(scope, condition)?;
-		let code = self.compile_code_block(scope, "then", code)?;
-		let next = if let Some(next) = next {
-			String::from("else")
-				+ &match *next {
-					IF_STATEMENT {
-						condition,
-						code,
-						next,
-					} => self.compile_elseif_chain(scope, condition, code, next)?,
-					DO_BLOCK(code) => self.compile_code_block(scope, "", code)?,
-					_ => return Err(String::from("Unexpected ComplexToken found")),
-				}
-		} else {
-			String::new()
-		};
-		Ok(format_clue!("if ", condition, " ", code, next))
+	) -> Result<(), Box<Diagnostic>> {
+		*result += "if ";
+		self.compile_expression(result, scope, condition)?;
+		result.push(' ');
+		self.compile_code_block(result, scope, "then", code)?;
+		if let Some(next) = next {
+			*result += "else";
+			match *next {
+				IF_STATEMENT {
+					condition,
+					code,
+					next,
+					..
+				} => self.compile_elseif_chain(result, scope, condition, code, next)?,
+				DO_BLOCK(code, _) => self.compile_code_block(result, scope, "", code)?,
+				_ => return Err(self.internal_error("Unexpected ComplexToken found")),
+			}
+		}
+		Ok(())
+	}
+
+	fn compile_match_condition(
+		&self,
+		result: &mut String,
+		scope: usize,
+		name: &str,
+		conditions: Vec<Expression>,
+		extraif: Option<Expression>,
+	) -> Result<(), Box<Diagnostic>> {
+		*result += "if ";
+		let empty = conditions.is_empty();
+		if !empty && extraif.is_some() {
+			result.push('(');
+		}
+		self.compile_list(result, conditions, "or ", &mut |result, expr| {
+			*result += &format_clue!("(", name, " == ");
+			self.compile_expression(result, scope, expr)?;
+			*result += ") ";
+			Ok(())
+		})?;
+		if let Some(extraif) = extraif {
+			if !empty {
+				result.pop();
+				*result += ") and ";
+			}
+			self.compile_expression(result, scope, extraif)?;
+			result.push(' ');
+		}
+		*result += "then";
+		Ok(())
 	}
 
 	/// Compiles an [`Expression`] into a [`String`] of Lua.
@@ -334,9 +423,9 @@ impl<'a> Compiler<'a> {
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::{compiler::*, env::Options, parser::*, scanner::*, Clue};
+	/// use clue_core::{compiler::*, diagnostic::Diagnostic, env::Options, parser::*, scanner::*, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///     let options = Options::default();
 	///     let filename = String::from("file.clue");
 	///     let compiler = Compiler::new(&options, &filename);
@@ -349,30 +438,85 @@ impl<'a> Compiler<'a> {
 	///     Ok(())
 	/// }
 	/// ```
-	pub fn compile_tokens(&self, scope: usize, ctokens: Expression) -> Result<String, String> {
-		let mut result = self.indentate(scope);
+	pub fn compile_tokens(
+		&self,
+		scope: usize,
+		ctokens: Expression,
+	) -> Result<String, Box<Diagnostic>> {
+		let mut result = String::with_capacity(1024);
+		self.mappings.borrow_mut().clear();
+		self.compile_statements(&mut result, scope, ctokens)?;
+		Ok(result)
+	}
+
+	/// Compiles an [`Expression`] into a [`String`] of Lua like [`Compiler::compile_tokens`],
+	/// also returning the [`SourceMap`] that maps every statement of the output to where it is in the file.
+	///
+	/// # Errors
+	/// Returns an error if an unexpected [`ComplexToken`] is found.
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{compiler::*, diagnostic::{Diagnostic, Position}, env::Options, Clue};
+	///
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
+	///     let options = Options::default();
+	///     let filename = String::from("file.clue");
+	///     let compiler = Compiler::new(&options, &filename);
+	///     let (ctokens, _) = Clue::new().parse_code("if true {\n\tprint(1)\n}".to_owned())?;
+	///     let (code, map) = compiler.compile_tokens_with_source_map(0, ctokens)?;
+	///
+	///     assert_eq!(code, "if true then\n\tprint(1);\nend");
+	///     let mapping = map.lookup(Position::new(2, 2)).unwrap();
+	///     assert_eq!(mapping.source, "file.clue");
+	///     assert_eq!((mapping.original.line, mapping.original.column), (2, 2));
+	///
+	///     Ok(())
+	/// }
+	/// ```
+	pub fn compile_tokens_with_source_map(
+		&self,
+		scope: usize,
+		ctokens: Expression,
+	) -> Result<(String, SourceMap), Box<Diagnostic>> {
+		let code = self.compile_tokens(scope, ctokens)?;
+		let sourcemap = SourceMap::new(&code, self.filename, &self.mappings.borrow());
+		Ok((code, sourcemap))
+	}
+
+	fn compile_statements(
+		&self,
+		result: &mut String,
+		scope: usize,
+		ctokens: Expression,
+	) -> Result<(), Box<Diagnostic>> {
+		*result += &self.indentate(scope);
 		let ctokens = &mut ctokens.into_iter().peekable();
 		while let Some(t) = ctokens.next() {
-			result += &match t {
-				SYMBOL(lexeme) => lexeme,
+			if !matches!(t, SYMBOL(..) | EXTERN_GLOBAL { .. }) {
+				self.map(result, t.span());
+			}
+			match t {
+				SYMBOL(lexeme, _) => *result += &lexeme,
 				VARIABLE {
 					local,
 					names,
 					values,
 					line,
+					..
 				} => {
-					let debug = self.compile_debug_line(line, scope, true);
+					*result += &self.compile_debug_line(line, scope, true);
 					let line = self.compile_debug_comment(line);
 					if !local && self.options.env_rawsetglobals {
-						let mut result = debug;
-						let mut valuesit = values.iter();
+						let mut valuesit = values.into_iter();
 						let namesit = &mut names.iter().peekable();
 						while let Some(name) = namesit.next() {
-							let value = if let Some(value) = valuesit.next() {
-								self.compile_expression(scope, value.clone())?
+							*result += &format_clue!("rawset(_G, \"", name, "\", ");
+							if let Some(value) = valuesit.next() {
+								self.compile_expression(result, scope, value)?;
 							} else {
-								String::from("nil")
-							};
+								*result += "nil";
+							}
 							let end = {
 								let pend = self.indentate_if(namesit, scope);
 								if !pend.is_empty() {
@@ -381,21 +525,18 @@ impl<'a> Compiler<'a> {
 									self.indentate_if(ctokens, scope)
 								}
 							};
-							write!(result, "rawset(_G, \"{name}\", {value});{line}{end}")
-								.map_err(|e| e.to_string())?
+							*result += &format_clue!(");", line, end);
 						}
-						result
 					} else {
-						let end = self.indentate_if(ctokens, scope);
-						let pre = if local { "local " } else { "" };
-						if values.is_empty() {
-							let ident = self.compile_identifiers(names)?;
-							format_clue!(debug, pre, ident, ";", line, end)
-						} else {
-							let values = self.compile_expressions(scope, values)?;
-							let names = self.compile_identifiers(names)?;
-							format_clue!(debug, pre, names, " = ", values, ";", line, end)
+						if local {
+							*result += "local ";
+						}
+						self.compile_identifiers(result, names);
+						if !values.is_empty() {
+							*result += " = ";
+							self.compile_expressions(result, scope, values)?;
 						}
+						*result += &format_clue!(";", line, self.indentate_if(ctokens, scope));
 					}
 				}
 				ALTER {
@@ -403,24 +544,28 @@ impl<'a> Compiler<'a> {
 					names,
 					values,
 					line,
+					..
 				} => {
-					let iter = names.into_iter();
-					let mut names: Vec<String> = Vec::new();
-					for name in iter {
-						names.push(self.compile_expression(scope, name)?)
+					*result += &self.compile_debug_line(line, scope, true);
+					let mut targets = Vec::with_capacity(names.len());
+					for (i, name) in names.into_iter().enumerate() {
+						if i > 0 {
+							*result += ", ";
+						}
+						let start = result.len();
+						self.compile_expression(result, scope, name)?;
+						targets.push(start..result.len());
 					}
+					*result += " = ";
 					let mut i = 0usize;
-					let values = self.compile_list(values, ", ", &mut |expr| {
-						let name = if let Some(name) = names.get(i) {
-							name.clone()
-						} else {
-							String::from("nil")
-						};
-						i += 1;
-						Ok((if kind == DEFINE {
-							String::new()
-						} else {
-							name + match kind {
+					self.compile_list(result, values, ", ", &mut |result, expr| {
+						if kind != DEFINE {
+							let name = match targets.get(i) {
+								Some(target) => result[target.clone()].to_owned(),
+								None => String::from("nil"),
+							};
+							*result += &name;
+							*result += match kind {
 								DEFINE_AND => " and ",
 								DEFINE_OR => " or ",
 								INCREASE => " + ",
@@ -430,178 +575,132 @@ impl<'a> Compiler<'a> {
 								EXPONENTIATE => " ^ ",
 								CONCATENATE => " .. ",
 								MODULATE => " % ",
-								_ => return Err(String::from("Unexpected alter type found")),
-							}
-						}) + &self.compile_expression(scope, expr)?)
+								_ => return Err(self.internal_error("Unexpected alter type found")),
+							};
+						}
+						i += 1;
+						self.compile_expression(result, scope, expr)
 					})?;
-					let names = self.compile_identifiers(names)?;
-					let debug = self.compile_debug_line(line, scope, true);
-					let line = self.compile_debug_comment(line);
-					format_clue!(
-						debug,
-						names,
-						" = ",
-						values,
+					*result += &format_clue!(
 						";",
-						line,
+						self.compile_debug_comment(line),
 						self.indentate_if(ctokens, scope)
-					)
+					);
 				}
 				FUNCTION {
 					local,
 					name,
 					args,
 					code,
+					..
 				} => {
-					let pre = if local { "local " } else { "" };
-					let end = self.indentate_if(ctokens, scope);
-					let name = self.compile_expression(scope, name)?;
-					let (code, args) = self.compile_function(scope, args, code)?;
-					format_clue!(pre, "function ", name, "(", args, ")", code, "end", end)
+					if local {
+						*result += "local ";
+					}
+					*result += "function ";
+					self.compile_expression(result, scope, name)?;
+					result.push('(');
+					self.compile_function(result, scope, args, code)?;
+					*result += &format_clue!("end", self.indentate_if(ctokens, scope));
 				}
 				IF_STATEMENT {
 					condition,
 					code,
 					next,
+					..
 				} => {
-					let code = self.compile_elseif_chain(scope, condition, code, next)?;
-					format_clue!(code, "end", self.indentate_if(ctokens, scope))
+					self.compile_elseif_chain(result, scope, condition, code, next)?;
+					*result += &format_clue!("end", self.indentate_if(ctokens, scope));
 				}
 				MATCH_BLOCK {
 					name,
 					value,
 					branches,
 					line,
+					..
 				} => {
-					let value = self.compile_expression(scope, value)?;
-					let debug = self.compile_debug_line(line, scope, true);
-					let line = self.compile_debug_comment(line);
-					let branches = {
-						let mut result = self.indentate(scope);
-						let last = branches.len() - 1;
-						let branches = branches.into_iter().enumerate();
-						for (i, (conditions, internal_expr, extraif, code)) in branches {
-							let empty = conditions.is_empty();
-							let default = empty && extraif.is_none();
-							let condition = {
-								let mut condition =
-									self.compile_list(conditions, "or ", &mut |expr| {
-										let expr = self.compile_expression(scope, expr)?;
-										Ok(format_clue!("(", name, " == ", expr, ") "))
-									})?;
-								format_clue!("if ", if let Some(extraif) = extraif {
-									condition.pop();
-									let extraif = self.compile_expression(scope, extraif)?;
-									if empty {
-										extraif + " "
-									} else {
-										format_clue!("(", condition, ") and ", extraif, " ")
-									}
-								} else {
-									condition
-								}, "then")
-							};
-							let end = if i >= last {
-								"end"
-							} else {
-								"else"
-							};
-							let pre = self.indentate(scope + i);
-							let internal_code = if internal_expr.is_empty() {
-								None
-							} else {
-								Some(self.compile_tokens(scope + i, internal_expr)?)
-							};
-							let code = if i == 0 {
-								let code = self.compile_code_block(
-									scope,
-									&condition,
-									code,
-								)? + end;
-								if let Some(internal_code) = internal_code {
-									format_clue!(
-										internal_code,
-										'\n',
-										pre,
-										code
-									)
-								} else {
-									code
-								}
-							} else if default {
-								self.compile_code_block(
-									scope + i - 1,
-									"",
-									code,
-								)? + end
-							} else {
-								let code = self.compile_code_block(
-									scope + i,
-									&condition,
-									code,
-								)?;
-								let mut code = format_clue!(
-									'\n',
-									pre,
-									code,
-									end
-								);
-								if let Some(internal_code) = internal_code {
-									code = format_clue!(
-										'\n',
-										internal_code,
-										code
-									)
-								}
-								if i >= last {
-									code += &format_clue!('\n', self.indentate(scope + i - 1), "end");
-								}
-								code
-							};
-							result += &code;
-						}
-						if last > 1 {
-							result.push('\n');
-							for i in (1..last - 1).rev() {
-								result += &(self.indentate(scope + i) + "end\n");
+					*result += &format_clue!(
+						self.compile_debug_line(line, scope, true),
+						"local ",
+						name,
+						" = "
+					);
+					self.compile_expression(result, scope, value)?;
+					*result += &format_clue!(
+						';',
+						self.compile_debug_comment(line),
+						'\n',
+						self.indentate(scope)
+					);
+					let last = branches.len() - 1;
+					let branches = branches.into_iter().enumerate();
+					for (i, (conditions, internal_expr, extraif, code)) in branches {
+						let default = conditions.is_empty() && extraif.is_none();
+						let end = if i >= last { "end" } else { "else" };
+						let pre = self.indentate(scope + i);
+						if i == 0 {
+							if !internal_expr.is_empty() {
+								self.compile_statements(result, scope + i, internal_expr)?;
+								*result += &format_clue!('\n', pre);
 							}
-							format_clue!(result, self.indentate(scope), "end")
+							self.compile_match_condition(
+								result, scope, &name, conditions, extraif,
+							)?;
+							self.compile_code_block(result, scope, "", code)?;
+							*result += end;
+						} else if default {
+							self.compile_code_block(result, scope + i - 1, "", code)?;
+							*result += end;
 						} else {
-							result
+							if !internal_expr.is_empty() {
+								result.push('\n');
+								self.compile_statements(result, scope + i, internal_expr)?;
+							}
+							*result += &format_clue!('\n', pre);
+							self.compile_match_condition(
+								result, scope, &name, conditions, extraif,
+							)?;
+							self.compile_code_block(result, scope + i, "", code)?;
+							*result += end;
+							if i >= last {
+								*result +=
+									&format_clue!('\n', self.indentate(scope + i - 1), "end");
+							}
 						}
-					};
-					let end = self.indentate_if(ctokens, scope);
-					format_clue!(
-						debug, "local ", name, " = ", value, ';', line, '\n', branches, end
-					)
+					}
+					if last > 1 {
+						result.push('\n');
+						for i in (1..last - 1).rev() {
+							*result += &(self.indentate(scope + i) + "end\n");
+						}
+						*result += &format_clue!(self.indentate(scope), "end");
+					}
+					*result += &self.indentate_if(ctokens, scope);
 				}
-				WHILE_LOOP { condition, code, line } => {
-					let condition = self.compile_expression(scope, condition)?;
+				WHILE_LOOP {
+					condition,
+					code,
+					line,
+					..
+				} => {
 					let debug = self.compile_debug_line(line, scope, true);
-					let code = self.compile_code_block(scope, "do", code)?;
-					format_clue!(
-						debug,
-						"while ",
-						condition,
-						" ",
-						code,
-						debug,
-						"end",
-						self.indentate_if(ctokens, scope)
-					)
+					*result += &format_clue!(debug, "while ");
+					self.compile_expression(result, scope, condition)?;
+					result.push(' ');
+					self.compile_code_block(result, scope, "do", code)?;
+					*result += &format_clue!(debug, "end", self.indentate_if(ctokens, scope));
 				}
-				LOOP_UNTIL { condition, code, line } => {
-					let condition = self.compile_expression(scope, condition)?;
-					let debug = self.compile_debug_line(line, scope, true);
-					let code = self.compile_code_block(scope, "", code)?;
-					format_clue!(
-						"repeat ",
-						code,
-						debug,
-						"until ",
-						condition,
-						self.indentate_if(ctokens, scope)
-					)
+				LOOP_UNTIL {
+					condition,
+					code,
+					line,
+					..
+				} => {
+					*result += "repeat ";
+					self.compile_code_block(result, scope, "", code)?;
+					*result += &format_clue!(self.compile_debug_line(line, scope, true), "until ");
+					self.compile_expression(result, scope, condition)?;
+					*result += &self.indentate_if(ctokens, scope);
 				}
 				FOR_LOOP {
 					iterator,
@@ -610,132 +709,102 @@ impl<'a> Compiler<'a> {
 					alter,
 					code,
 					line,
+					..
 				} => {
-					let start = self.compile_expression(scope, start)?;
-					let endexpr = self.compile_expression(scope, end)?;
-					let alter = self.compile_expression(scope, alter)?;
 					let debug = self.compile_debug_line(line, scope, true);
-					let code = self.compile_code_block(scope, "do", code)?;
-					let end = self.indentate_if(ctokens, scope);
-					format_clue!(
-						debug,
-						"for ",
-						iterator,
-						" = ",
-						start,
-						", ",
-						endexpr,
-						", ",
-						alter,
-						" ",
-						code,
-						debug,
-						"end",
-						end
-					)
+					*result += &format_clue!(debug, "for ", iterator, " = ");
+					self.compile_expression(result, scope, start)?;
+					*result += ", ";
+					self.compile_expression(result, scope, end)?;
+					*result += ", ";
+					self.compile_expression(result, scope, alter)?;
+					result.push(' ');
+					self.compile_code_block(result, scope, "do", code)?;
+					*result += &format_clue!(debug, "end", self.indentate_if(ctokens, scope));
 				}
 				FOR_FUNC_LOOP {
 					iterators,
 					expr,
 					code,
 					line,
+					..
 				} => {
-					let expr = self.compile_expression(scope, expr)?;
-					let iterators = self.compile_identifiers(iterators)?;
 					let debug = self.compile_debug_line(line, scope, true);
-					let code = self.compile_code_block(scope, "do", code)?;
-					format_clue!(
-						debug,
-						"for ",
-						iterators,
-						" in ",
-						expr,
-						" ",
-						code,
-						debug,
-						"end",
-						self.indentate_if(ctokens, scope)
-					)
+					*result += &format_clue!(debug, "for ");
+					self.compile_identifiers(result, iterators);
+					*result += " in ";
+					self.compile_expression(result, scope, expr)?;
+					result.push(' ');
+					self.compile_code_block(result, scope, "do", code)?;
+					*result += &format_clue!(debug, "end", self.indentate_if(ctokens, scope));
 				}
 				TRY_CATCH {
 					totry,
 					error,
 					catch,
+					..
 				} => {
-					let i = self.indentate_if(ctokens, scope);
-					let totry = self.compile_code_block(scope, "function()", totry)?;
-					if let Some(catch) = catch {
-						let catch = self.compile_code_block(scope, "if not _check then", catch)?;
-						let i2 = self.indentate(scope);
-						if let Some(error) = error {
-							format_clue!(
-								"local _check, ",
-								error,
-								" = pcall(",
-								totry,
-								"end)\n",
-								i2,
-								catch,
-								"end",
-								i
-							)
-						} else {
-							format_clue!(
-								"local _check = pcall(",
-								totry,
-								"end)\n",
-								i2,
-								catch,
-								"end",
-								i
-							)
+					match (&catch, error) {
+						(Some(_), Some(error)) => {
+							*result += &format_clue!("local _check, ", error, " = pcall(")
 						}
-					} else {
-						format_clue!("pcall(", totry, "end)", i)
+						(Some(_), None) => *result += "local _check = pcall(",
+						(None, _) => *result += "pcall(",
 					}
+					self.compile_code_block(result, scope, "function()", totry)?;
+					*result += "end)";
+					if let Some(catch) = catch {
+						*result += &format_clue!("\n", self.indentate(scope));
+						self.compile_code_block(result, scope, "if not _check then", catch)?;
+						*result += "end";
+					}
+					*result += &self.indentate_if(ctokens, scope);
 				}
-				IDENT { expr, line } => {
-					let expr = self.compile_identifier(scope, expr)?;
-					let debug = self.compile_debug_line(line, scope, true);
-					let line = self.compile_debug_comment(line);
-					format_clue!(debug, expr, ";", line, self.indentate_if(ctokens, scope))
+				IDENT { expr, line, .. } => {
+					*result += &self.compile_debug_line(line, scope, true);
+					self.compile_identifier(result, scope, expr)?;
+					*result += &format_clue!(
+						";",
+						self.compile_debug_comment(line),
+						self.indentate_if(ctokens, scope)
+					);
 				}
-				EXPR(expr) => {
-					format!("({})", self.compile_expression(scope, expr)?)
+				EXPR(expr, _) => {
+					result.push('(');
+					self.compile_expression(result, scope, expr)?;
+					result.push(')');
 				}
-				DO_BLOCK(code) => {
-					format!(
-						"{}end{}",
-						self.compile_code_block(scope, "do", code)?,
-						self.indentate_if(ctokens, scope)
-					)
+				DO_BLOCK(code, _) => {
+					self.compile_code_block(result, scope, "do", code)?;
+					*result += &format_clue!("end", self.indentate_if(ctokens, scope));
 				}
-				RETURN_EXPR(exprs) => {
+				RETURN_EXPR(exprs, _) => {
 					if let Some(exprs) = exprs {
-						format!("return {};", self.compile_expressions(scope, exprs)?)
+						*result += "return ";
+						self.compile_expressions(result, scope, exprs)?;
+						result.push(';');
 					} else {
-						String::from("return;")
+						*result += "return;";
 					}
 				}
-				CONTINUE_LOOP => {
-					let end = self.indentate_if(ctokens, scope);
-					format!(
-						"{};{}",
-						if matches!(
-							self.options.env_continue,
-							ContinueMode::LuaJIT | ContinueMode::Goto
-						) {
-							"goto continue"
-						} else {
-							"continue"
-						},
-						end
-					)
+				CONTINUE_LOOP(_) => {
+					*result += if matches!(
+						self.options.env_continue,
+						ContinueMode::LuaJIT | ContinueMode::Goto
+					) {
+						"goto continue;"
+					} else {
+						"continue;"
+					};
+					*result += &self.indentate_if(ctokens, scope);
 				}
-				BREAK_LOOP => String::from("break;") + &self.indentate_if(ctokens, scope),
-				_ => return Err(String::from("Unexpected ComplexToken found")),
+				BREAK_LOOP(_) => {
+					*result += &format_clue!("break;", self.indentate_if(ctokens, scope))
+				}
+				EXTERN_GLOBAL { .. } => {}
+				_ => return Err(self.internal_error("Unexpected ComplexToken found")),
 			}
 		}
-		Ok(result)
+		Ok(())
 	}
 }
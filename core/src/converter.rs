@@ -0,0 +1,1289 @@
+//! The `converter` module converts Lua code into Clue code, to help moving existing projects to Clue.
+//!
+//! Lua 5.1 to 5.4 code is parsed into the same AST the Clue parser builds, which is then turned
+//! into Clue by the [`printer`](crate::printer). Comments are kept, and `goto`s that jump to a label
+//! at the end of a loop become `continue`s.
+//!
+//! Some constructs can't be written in Clue, like any other `goto` or variables named like a Clue
+//! keyword: they are reported with an `untranslatable` warning and need to be fixed by hand.
+
+use crate::{
+	diagnostic::{Diagnostic, Position, Span},
+	parser::{CodeBlock, ComplexToken, ComplexToken::*, Expression, FunctionArgs},
+	printer::print_ast,
+	scanner::{keywords, TokenType::DEFINE},
+};
+use std::collections::VecDeque;
+
+const LUA_KEYWORDS: [&str; 22] = [
+	"and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+	"local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+const SYMBOLS: [&str; 33] = [
+	"...", "..", "==", "~=", "<=", ">=", "<<", ">>", "//", "::", "+", "-", "*", "/", "%", "^", "#",
+	"&", "~", "|", "<", ">", "=", "(", ")", "{", "}", "[", "]", ";", ":", ",", ".",
+];
+
+/// The comments of the code with their span, which are put back in the AST between statements
+type Comments = VecDeque<(Span, String)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LuaKind {
+	Name,
+	Keyword,
+	Number,
+	String,
+	Symbol,
+	Eof,
+}
+
+#[derive(Debug, Clone)]
+struct LuaToken {
+	kind: LuaKind,
+	text: String,
+	span: Span,
+}
+
+struct Lexer<'a> {
+	code: &'a [u8],
+	current: usize,
+	line: usize,
+	column: usize,
+	filename: &'a String,
+	tokens: Vec<LuaToken>,
+	comments: Comments,
+	errors: Vec<Diagnostic>,
+}
+
+impl<'a> Lexer<'a> {
+	fn position(&self) -> Position {
+		Position {
+			line: self.line,
+			column: self.column,
+			offset: self.current,
+		}
+	}
+
+	fn peek(&self, n: usize) -> u8 {
+		self.code.get(self.current + n).copied().unwrap_or(0)
+	}
+
+	fn ended(&self) -> bool {
+		self.current >= self.code.len()
+	}
+
+	fn advance(&mut self) {
+		let c = self.peek(0);
+		self.current += 1;
+		if c == b'\n' {
+			self.line += 1;
+			self.column = 1;
+		} else if c & 0xC0 != 0x80 {
+			self.column += 1;
+		}
+	}
+
+	fn text(&self, start: Position) -> String {
+		String::from_utf8_lossy(&self.code[start.offset..self.current]).into_owned()
+	}
+
+	fn error(&mut self, code: &str, message: &str, start: Position) {
+		let span = Span::range(start, self.position());
+		self.errors.push(Diagnostic::error(
+			code,
+			message,
+			self.filename.as_str(),
+			span,
+		));
+	}
+
+	fn long_bracket_level(&self) -> Option<usize> {
+		let mut level = 0;
+		while self.peek(level + 1) == b'=' {
+			level += 1;
+		}
+		(self.peek(0) == b'[' && self.peek(level + 1) == b'[').then_some(level)
+	}
+
+	fn read_long_bracket(&mut self, level: usize) -> bool {
+		for _ in 0..level + 2 {
+			self.advance();
+		}
+		while !self.ended() {
+			if self.peek(0) == b']'
+				&& (1..=level).all(|i| self.peek(i) == b'=')
+				&& self.peek(level + 1) == b']'
+			{
+				for _ in 0..level + 2 {
+					self.advance();
+				}
+				return true;
+			}
+			self.advance();
+		}
+		false
+	}
+
+	fn read_comment(&mut self, start: Position) {
+		self.advance();
+		self.advance();
+		match self.long_bracket_level() {
+			Some(level) => {
+				if !self.read_long_bracket(level) {
+					self.error("unterminated-comment", "Unterminated comment", start);
+					return;
+				}
+			}
+			None => {
+				while !matches!(self.peek(0), b'\n' | 0) {
+					self.advance();
+				}
+			}
+		}
+		let text = self.text(start);
+		self.comments
+			.push_back((Span::range(start, self.position()), text));
+	}
+
+	fn read_string(&mut self, quote: u8, start: Position) -> bool {
+		self.advance();
+		loop {
+			if self.ended() || self.peek(0) == b'\n' {
+				self.error("unterminated-string", "Unterminated string", start);
+				return false;
+			}
+			let c = self.peek(0);
+			self.advance();
+			if c == quote {
+				return true;
+			} else if c == b'\\' {
+				let escape = self.peek(0);
+				self.advance();
+				if escape == b'z' {
+					while self.peek(0).is_ascii_whitespace() {
+						self.advance();
+					}
+				} else if escape == b'\r' && self.peek(0) == b'\n' {
+					self.advance();
+				}
+			}
+		}
+	}
+
+	fn read_number(&mut self) {
+		let hex = self.peek(0) == b'0' && matches!(self.peek(1), b'x' | b'X');
+		let exponent: &[u8] = if hex {
+			self.advance();
+			self.advance();
+			b"pP"
+		} else {
+			b"eE"
+		};
+		loop {
+			let c = self.peek(0);
+			if exponent.contains(&c) {
+				self.advance();
+				if matches!(self.peek(0), b'+' | b'-') {
+					self.advance();
+				}
+			} else if c.is_ascii_alphanumeric() || c == b'.' {
+				self.advance();
+			} else {
+				break;
+			}
+		}
+	}
+
+	fn scan(mut self) -> Result<(Vec<LuaToken>, Comments), Vec<Diagnostic>> {
+		if self.peek(0) == b'#' {
+			let start = self.position();
+			while !matches!(self.peek(0), b'\n' | 0) {
+				self.advance();
+			}
+			let text = self.text(start);
+			self.comments
+				.push_back((Span::range(start, self.position()), text));
+		}
+		while !self.ended() {
+			let start = self.position();
+			let c = self.peek(0);
+			let kind = match c {
+				b' ' | b'\t' | b'\r' | b'\n' | 0x0B | 0x0C => {
+					self.advance();
+					continue;
+				}
+				b'-' if self.peek(1) == b'-' => {
+					self.read_comment(start);
+					continue;
+				}
+				b'"' | b'\'' => {
+					if !self.read_string(c, start) {
+						continue;
+					}
+					LuaKind::String
+				}
+				b'[' if self.long_bracket_level().is_some() => {
+					let level = self.long_bracket_level().unwrap_or_default();
+					if !self.read_long_bracket(level) {
+						self.error("unterminated-string", "Unterminated string", start);
+						continue;
+					}
+					LuaKind::String
+				}
+				b'0'..=b'9' => {
+					self.read_number();
+					LuaKind::Number
+				}
+				b'.' if self.peek(1).is_ascii_digit() => {
+					self.read_number();
+					LuaKind::Number
+				}
+				c if c.is_ascii_alphabetic() || c == b'_' => {
+					while self.peek(0).is_ascii_alphanumeric() || self.peek(0) == b'_' {
+						self.advance();
+					}
+					if LUA_KEYWORDS.contains(&self.text(start).as_str()) {
+						LuaKind::Keyword
+					} else {
+						LuaKind::Name
+					}
+				}
+				_ => {
+					let symbol = SYMBOLS
+						.iter()
+						.find(|symbol| self.code[self.current..].starts_with(symbol.as_bytes()));
+					match symbol {
+						Some(symbol) => {
+							for _ in 0..symbol.len() {
+								self.advance();
+							}
+							LuaKind::Symbol
+						}
+						None => {
+							self.advance();
+							let message = format!("Unexpected character '{}'", self.text(start));
+							self.error("unexpected-character", &message, start);
+							continue;
+						}
+					}
+				}
+			};
+			self.tokens.push(LuaToken {
+				kind,
+				text: self.text(start),
+				span: Span::range(start, self.position()),
+			});
+		}
+		self.tokens.push(LuaToken {
+			kind: LuaKind::Eof,
+			text: String::from("<end>"),
+			span: Span::range(self.position(), self.position()),
+		});
+		if self.errors.is_empty() {
+			Ok((self.tokens, self.comments))
+		} else {
+			Err(self.errors)
+		}
+	}
+}
+
+/// Turns a Lua comment into a Clue comment
+fn convert_comment(text: &str) -> String {
+	let Some(body) = text.strip_prefix("--") else {
+		return format!("// {text}");
+	};
+	let level = body.strip_prefix('[').and_then(|rest| {
+		rest.find('[')
+			.filter(|&level| rest[..level].bytes().all(|c| c == b'='))
+	});
+	match level {
+		Some(level) => {
+			let content = &body[level + 2..body.len() - level - 2];
+			if content.contains("*/") {
+				content
+					.lines()
+					.map(|line| format!("//{line}"))
+					.collect::<Vec<String>>()
+					.join("\n")
+			} else {
+				format!("/*{content}*/")
+			}
+		}
+		None => {
+			let dashes = body.len() - body.trim_start_matches('-').len();
+			format!("//{}{}", "/".repeat(dashes), &body[dashes..])
+		}
+	}
+}
+
+/// Rewrites the escapes that would need a line break inside a quoted string, which Clue doesn't allow
+fn convert_string(text: &str) -> String {
+	if text.starts_with('[') {
+		return text.to_owned();
+	}
+	let mut string = String::with_capacity(text.len());
+	let mut chars = text.chars().peekable();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			string.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('\r' | '\n') => {
+				chars.next_if(|&c| c == '\n');
+				string += "\\n";
+			}
+			Some('z') => while chars.next_if(char::is_ascii_whitespace).is_some() {},
+			Some(escape) => {
+				string.push(c);
+				string.push(escape);
+			}
+			None => string.push(c),
+		}
+	}
+	string
+}
+
+/// Rewrites the numbers that Lua accepts but Clue doesn't, like `.5`, `5.` or `1e+5`
+fn convert_number(text: &str) -> String {
+	let mut number = text.replace("e+", "e").replace("E+", "E");
+	if number.starts_with('.') {
+		number.insert(0, '0');
+	}
+	if let Some(dot) = number.find('.') {
+		if !number[dot + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+			number.insert(dot + 1, '0');
+		}
+	}
+	number
+}
+
+fn binary_priority(token: &LuaToken) -> Option<(u8, u8)> {
+	if !matches!(token.kind, LuaKind::Symbol | LuaKind::Keyword) {
+		return None;
+	}
+	Some(match token.text.as_str() {
+		"or" => (1, 1),
+		"and" => (2, 2),
+		"<" | ">" | "<=" | ">=" | "~=" | "==" => (3, 3),
+		"|" => (4, 4),
+		"~" => (5, 5),
+		"&" => (6, 6),
+		"<<" | ">>" => (7, 7),
+		".." => (9, 8),
+		"+" | "-" => (10, 10),
+		"*" | "/" | "//" | "%" => (11, 11),
+		"^" => (14, 13),
+		_ => return None,
+	})
+}
+
+const UNARY_PRIORITY: u8 = 12;
+
+struct LuaParser<'a> {
+	tokens: Vec<LuaToken>,
+	current: usize,
+	filename: &'a String,
+	comments: Comments,
+	keywords: Vec<&'static str>,
+	gotos: Vec<(String, Span)>,
+	labels: Vec<(String, Span)>,
+	warnings: Vec<Diagnostic>,
+}
+
+impl<'a> LuaParser<'a> {
+	fn peek(&self, n: usize) -> &LuaToken {
+		let last = self.tokens.len() - 1;
+		&self.tokens[(self.current + n).min(last)]
+	}
+
+	fn advance(&mut self) -> LuaToken {
+		let t = self.peek(0).clone();
+		if t.kind != LuaKind::Eof {
+			self.current += 1;
+		}
+		t
+	}
+
+	fn span_from(&self, start: Span) -> Span {
+		let end = self.tokens[self.current.saturating_sub(1)].span.end;
+		Span::range(start.start, end.max(start.end))
+	}
+
+	fn check(&self, text: &str) -> bool {
+		let t = self.peek(0);
+		matches!(t.kind, LuaKind::Symbol | LuaKind::Keyword) && t.text == text
+	}
+
+	fn accept(&mut self, text: &str) -> bool {
+		let found = self.check(text);
+		if found {
+			self.current += 1;
+		}
+		found
+	}
+
+	fn expected(&self, expected: &str, t: &LuaToken) -> Box<Diagnostic> {
+		Box::new(Diagnostic::error(
+			"expected",
+			format!("Expected '{expected}', got '{}'", t.text),
+			self.filename.as_str(),
+			t.span,
+		))
+	}
+
+	fn expect(&mut self, text: &str) -> Result<LuaToken, Box<Diagnostic>> {
+		if self.check(text) {
+			Ok(self.advance())
+		} else {
+			Err(self.expected(text, self.peek(0)))
+		}
+	}
+
+	fn untranslatable(&mut self, message: impl Into<String>, span: Span) {
+		self.warnings.push(Diagnostic::warning(
+			"untranslatable",
+			message,
+			self.filename.as_str(),
+			span,
+		));
+	}
+
+	fn expect_label(&mut self) -> Result<LuaToken, Box<Diagnostic>> {
+		if self.peek(0).kind == LuaKind::Name {
+			Ok(self.advance())
+		} else {
+			Err(self.expected("<name>", self.peek(0)))
+		}
+	}
+
+	fn expect_name(&mut self) -> Result<LuaToken, Box<Diagnostic>> {
+		let t = self.expect_label()?;
+		if self.keywords.contains(&t.text.as_str()) {
+			let message = format!(
+				"'{}' is a keyword in Clue and can't be used as a name",
+				t.text
+			);
+			self.untranslatable(message, t.span);
+		}
+		Ok(t)
+	}
+
+	fn flush_comments(&mut self, code: &mut Expression, before: Position) {
+		while let Some((span, _)) = self.comments.front() {
+			if span.start > before {
+				break;
+			}
+			if let Some((span, text)) = self.comments.pop_front() {
+				code.push_back(SYMBOL(convert_comment(&text), span));
+			}
+		}
+	}
+
+	fn parse_block(&mut self) -> Result<Expression, Box<Diagnostic>> {
+		let mut code = Expression::new();
+		loop {
+			let t = self.peek(0);
+			let start = t.span.start;
+			if t.kind == LuaKind::Eof
+				|| t.kind == LuaKind::Keyword
+					&& matches!(t.text.as_str(), "end" | "else" | "elseif" | "until")
+			{
+				break;
+			}
+			self.flush_comments(&mut code, start);
+			if self.check("return") {
+				let keyword = self.advance().span;
+				let exprs = if self.block_ends() || self.check(";") {
+					None
+				} else {
+					Some(self.parse_expressions()?)
+				};
+				self.accept(";");
+				code.push_back(RETURN_EXPR(exprs, self.span_from(keyword)));
+				if !self.block_ends() {
+					return Err(self.expected("<end>", self.peek(0)));
+				}
+				break;
+			}
+			self.parse_statement(&mut code)?;
+		}
+		let end = self.peek(0).span.start;
+		self.flush_comments(&mut code, end);
+		Ok(code)
+	}
+
+	fn block_ends(&self) -> bool {
+		let t = self.peek(0);
+		t.kind == LuaKind::Eof
+			|| t.kind == LuaKind::Keyword
+				&& matches!(t.text.as_str(), "end" | "else" | "elseif" | "until")
+	}
+
+	fn parse_code_block(&mut self, start: usize, end: &str) -> Result<CodeBlock, Box<Diagnostic>> {
+		let code = self.parse_block()?;
+		let end = self.expect(end)?.span.start.line;
+		Ok(CodeBlock { start, code, end })
+	}
+
+	fn parse_loop_block(&mut self, start: usize, end: &str) -> Result<CodeBlock, Box<Diagnostic>> {
+		let mut block = self.parse_code_block(start, end)?;
+		let Some(SYMBOL(_, span)) = block.code.back() else {
+			return Ok(block);
+		};
+		let Some(index) = self.labels.iter().position(|(_, label)| label == span) else {
+			return Ok(block);
+		};
+		let name = &self.labels[index].0;
+		let gotos: Vec<Span> = self
+			.gotos
+			.iter()
+			.filter(|(goto, _)| goto == name)
+			.map(|(_, span)| *span)
+			.collect();
+		let mut replaced = Vec::new();
+		replace_gotos(&mut block.code, &gotos, &mut replaced);
+		if !replaced.is_empty() {
+			block.code.pop_back();
+			self.labels.remove(index);
+			self.gotos.retain(|(_, span)| !replaced.contains(span));
+		}
+		Ok(block)
+	}
+
+	fn parse_statement(&mut self, code: &mut Expression) -> Result<(), Box<Diagnostic>> {
+		let t = self.advance();
+		let start = t.span;
+		let line = start.start.line;
+		if t.kind == LuaKind::Name || t.kind == LuaKind::Symbol && t.text == "(" {
+			self.current -= 1;
+			return self.parse_expression_statement(code);
+		}
+		let statement = match t.text.as_str() {
+			";" => return Ok(()),
+			"::" => {
+				let name = self.expect_label()?.text;
+				self.expect("::")?;
+				let span = self.span_from(start);
+				self.labels.push((name.clone(), span));
+				SYMBOL(format!("// ::{name}::"), span)
+			}
+			"goto" => {
+				let name = self.expect_label()?.text;
+				let span = self.span_from(start);
+				self.gotos.push((name.clone(), span));
+				SYMBOL(format!("// goto {name}"), span)
+			}
+			"break" => BREAK_LOOP(start),
+			"do" => {
+				let block = self.parse_code_block(line, "end")?;
+				DO_BLOCK(block, self.span_from(start))
+			}
+			"while" => {
+				let condition = self.parse_expression()?;
+				self.expect("do")?;
+				let code = self.parse_loop_block(line, "end")?;
+				WHILE_LOOP {
+					condition,
+					code,
+					line,
+					span: self.span_from(start),
+				}
+			}
+			"repeat" => {
+				let code = self.parse_loop_block(line, "until")?;
+				let condition = self.parse_expression()?;
+				LOOP_UNTIL {
+					condition,
+					code,
+					line,
+					span: self.span_from(start),
+				}
+			}
+			"if" => self.parse_if(start)?,
+			"for" => self.parse_for(start)?,
+			"function" => {
+				let name = self.parse_function_name()?;
+				let (args, code) = self.parse_function_body(line)?;
+				FUNCTION {
+					local: false,
+					name,
+					args,
+					types: Default::default(),
+					code,
+					span: self.span_from(start),
+				}
+			}
+			"local" if self.accept("function") => {
+				let name = self.expect_name()?;
+				let (args, code) = self.parse_function_body(line)?;
+				FUNCTION {
+					local: true,
+					name: VecDeque::from([SYMBOL(name.text, name.span)]),
+					args,
+					types: Default::default(),
+					code,
+					span: self.span_from(start),
+				}
+			}
+			"local" => {
+				let mut names = Vec::new();
+				loop {
+					names.push(self.expect_name()?.text);
+					if self.accept("<") {
+						let attribute = self.expect_label()?;
+						self.expect(">")?;
+						if attribute.text != "const" {
+							let message = format!(
+								"'<{}>' variables can't be translated to Clue",
+								attribute.text
+							);
+							self.untranslatable(message, attribute.span);
+						}
+					}
+					if !self.accept(",") {
+						break;
+					}
+				}
+				let values = if self.accept("=") {
+					self.parse_expressions()?
+				} else {
+					Vec::new()
+				};
+				VARIABLE {
+					local: true,
+					names,
+					values,
+					types: Vec::new(),
+					line,
+					span: self.span_from(start),
+				}
+			}
+			_ => return Err(self.expected("<statement>", &t)),
+		};
+		code.push_back(statement);
+		Ok(())
+	}
+
+	fn parse_expression_statement(&mut self, code: &mut Expression) -> Result<(), Box<Diagnostic>> {
+		let start = self.peek(0).span;
+		let line = start.start.line;
+		let first = self.parse_primary()?;
+		if self.check("=") || self.check(",") {
+			let mut names = VecDeque::from([assignment_target(first)]);
+			while self.accept(",") {
+				let name = self.parse_primary()?;
+				names.push_back(assignment_target(name));
+			}
+			self.expect("=")?;
+			let values = self.parse_expressions()?;
+			code.push_back(ALTER {
+				kind: DEFINE,
+				names,
+				values,
+				line,
+				span: self.span_from(start),
+			});
+			return Ok(());
+		}
+		let is_call = match first.back() {
+			Some(IDENT { expr, .. }) => matches!(expr.back(), Some(CALL(..))),
+			_ => false,
+		};
+		if !is_call {
+			return Err(self.expected("=", self.peek(0)));
+		}
+		code.extend(first);
+		Ok(())
+	}
+
+	fn parse_if(&mut self, start: Span) -> Result<ComplexToken, Box<Diagnostic>> {
+		let condition = self.parse_expression()?;
+		let line = self.expect("then")?.span.start.line;
+		let code = self.parse_block()?;
+		let t = self.advance();
+		let end = t.span.start.line;
+		let code = CodeBlock {
+			start: line,
+			code,
+			end,
+		};
+		let next = match t.text.as_str() {
+			"elseif" if t.kind == LuaKind::Keyword => Some(Box::new(self.parse_if(t.span)?)),
+			"else" if t.kind == LuaKind::Keyword => {
+				let block = self.parse_code_block(end, "end")?;
+				Some(Box::new(DO_BLOCK(block, self.span_from(t.span))))
+			}
+			"end" if t.kind == LuaKind::Keyword => None,
+			_ => return Err(self.expected("end", &t)),
+		};
+		Ok(IF_STATEMENT {
+			condition,
+			code,
+			next,
+			span: self.span_from(start),
+		})
+	}
+
+	fn parse_for(&mut self, start: Span) -> Result<ComplexToken, Box<Diagnostic>> {
+		let line = start.start.line;
+		let first = self.expect_name()?.text;
+		if self.accept("=") {
+			let begin = self.parse_expression()?;
+			self.expect(",")?;
+			let end = self.parse_expression()?;
+			let alter = if self.accept(",") {
+				self.parse_expression()?
+			} else {
+				VecDeque::from([SYMBOL(String::from("1"), start)])
+			};
+			self.expect("do")?;
+			let code = self.parse_loop_block(line, "end")?;
+			return Ok(FOR_LOOP {
+				iterator: first,
+				start: begin,
+				end,
+				alter,
+				code,
+				line,
+				span: self.span_from(start),
+			});
+		}
+		let mut iterators = vec![first];
+		while self.accept(",") {
+			iterators.push(self.expect_name()?.text);
+		}
+		self.expect("in")?;
+		let exprs_start = self.peek(0).span;
+		let mut exprs = self.parse_expressions()?;
+		let expr = if exprs.len() > 1 {
+			let span = self.span_from(exprs_start);
+			self.untranslatable(
+				"'for' loops with more than one iterator expression can't be translated to Clue",
+				span,
+			);
+			let mut expr = Expression::new();
+			for (i, mut value) in exprs.into_iter().enumerate() {
+				if i > 0 {
+					expr.push_back(SYMBOL(String::from(", "), span));
+				}
+				expr.append(&mut value);
+			}
+			expr
+		} else {
+			let expr = exprs.pop().unwrap_or_default();
+			match iterator_call(&expr) {
+				Some((function, mut arg, span)) if !matches!(arg.front(), Some(TABLE { .. })) => {
+					arg.push_front(SYMBOL(format!("{function}("), span));
+					arg.push_back(SYMBOL(String::from(")"), span));
+					arg
+				}
+				_ => expr,
+			}
+		};
+		self.expect("do")?;
+		let code = self.parse_loop_block(line, "end")?;
+		Ok(FOR_FUNC_LOOP {
+			iterators,
+			expr,
+			code,
+			line,
+			span: self.span_from(start),
+		})
+	}
+
+	fn parse_function_name(&mut self) -> Result<Expression, Box<Diagnostic>> {
+		let t = self.expect_name()?;
+		let mut name = VecDeque::from([SYMBOL(t.text, t.span)]);
+		loop {
+			let t = self.peek(0).clone();
+			if !self.accept(".") && !self.accept(":") {
+				break;
+			}
+			name.push_back(SYMBOL(t.text.clone(), t.span));
+			let t = self.peek(0).clone();
+			if t.kind != LuaKind::Name {
+				return Err(self.expected("<name>", &t));
+			}
+			name.push_back(SYMBOL(self.advance().text, t.span));
+			if t.text == ":" {
+				break;
+			}
+		}
+		Ok(name)
+	}
+
+	fn parse_function_body(
+		&mut self,
+		start: usize,
+	) -> Result<(FunctionArgs, CodeBlock), Box<Diagnostic>> {
+		self.expect("(")?;
+		let mut args = FunctionArgs::new();
+		if !self.check(")") {
+			loop {
+				if self.accept("...") {
+					args.push((String::from("..."), None));
+					break;
+				}
+				args.push((self.expect_name()?.text, None));
+				if !self.accept(",") {
+					break;
+				}
+			}
+		}
+		self.expect(")")?;
+		let code = self.parse_code_block(start, "end")?;
+		Ok((args, code))
+	}
+
+	fn parse_expressions(&mut self) -> Result<Vec<Expression>, Box<Diagnostic>> {
+		let mut exprs = vec![self.parse_expression()?];
+		while self.accept(",") {
+			exprs.push(self.parse_expression()?);
+		}
+		Ok(exprs)
+	}
+
+	fn parse_expression(&mut self) -> Result<Expression, Box<Diagnostic>> {
+		self.parse_subexpression(0)
+	}
+
+	fn parse_subexpression(&mut self, limit: u8) -> Result<Expression, Box<Diagnostic>> {
+		let t = self.peek(0).clone();
+		let mut expr = if matches!(t.kind, LuaKind::Symbol | LuaKind::Keyword)
+			&& matches!(t.text.as_str(), "not" | "-" | "#" | "~")
+		{
+			self.advance();
+			let after_minus = self.current >= 2 && {
+				let previous = &self.tokens[self.current - 2];
+				previous.kind == LuaKind::Symbol && previous.text == "-"
+			};
+			let lexeme = match t.text.as_str() {
+				"not" => String::from("not "),
+				"-" if after_minus => String::from(" -"),
+				operator => operator.to_owned(),
+			};
+			let mut operand = parenthesize_lambda(self.parse_subexpression(UNARY_PRIORITY)?);
+			if lexeme == "#" {
+				operand = parenthesize_length(operand);
+			}
+			let mut expr = VecDeque::from([SYMBOL(lexeme, t.span)]);
+			expr.append(&mut operand);
+			expr
+		} else {
+			self.parse_simple()?
+		};
+		while let Some((left, right)) = binary_priority(self.peek(0)) {
+			if left <= limit {
+				break;
+			}
+			let t = self.advance();
+			let mut rhs = parenthesize_lambda(self.parse_subexpression(right)?);
+			if t.text == "//" {
+				let mut division = Expression::with_capacity(expr.len() + rhs.len() + 1);
+				division.append(&mut expr);
+				division.push_back(SYMBOL(String::from("/"), t.span));
+				division.append(&mut rhs);
+				let span = Span::range(
+					division.front().map_or(t.span, ComplexToken::span).start,
+					division.back().map_or(t.span, ComplexToken::span).end,
+				);
+				expr.push_back(SYMBOL(String::from("math.floor"), t.span));
+				expr.push_back(CALL(vec![division], span));
+				continue;
+			}
+			let lexeme = match t.text.as_str() {
+				"and" => String::from(" and "),
+				"or" => String::from(" or "),
+				operator => operator.to_owned(),
+			};
+			expr.push_back(SYMBOL(lexeme, t.span));
+			expr.append(&mut rhs);
+		}
+		Ok(expr)
+	}
+
+	fn parse_simple(&mut self) -> Result<Expression, Box<Diagnostic>> {
+		let t = self.peek(0).clone();
+		let token = match (t.kind, t.text.as_str()) {
+			(LuaKind::Number, text) => {
+				if (text.starts_with("0x") || text.starts_with("0X"))
+					&& text.contains(['.', 'p', 'P'])
+				{
+					self.untranslatable("Hexadecimal floats can't be translated to Clue", t.span);
+				}
+				SYMBOL(convert_number(text), t.span)
+			}
+			(LuaKind::String, text) => {
+				if text.starts_with('[') && text.trim_end_matches([']', '=']).ends_with('\\') {
+					self.untranslatable(
+						"Long strings ending with '\\' can't be translated to Clue",
+						t.span,
+					);
+				}
+				SYMBOL(convert_string(text), t.span)
+			}
+			(LuaKind::Keyword, "nil" | "true" | "false") | (LuaKind::Symbol, "...") => {
+				SYMBOL(t.text.clone(), t.span)
+			}
+			(LuaKind::Keyword, "function") => {
+				self.advance();
+				let (args, code) = self.parse_function_body(t.span.start.line)?;
+				return Ok(VecDeque::from([LAMBDA {
+					args,
+					types: Default::default(),
+					code,
+					span: self.span_from(t.span),
+				}]));
+			}
+			(LuaKind::Symbol, "{") => return Ok(VecDeque::from([self.parse_table()?])),
+			_ => return self.parse_primary(),
+		};
+		self.advance();
+		Ok(VecDeque::from([token]))
+	}
+
+	fn parse_primary(&mut self) -> Result<Expression, Box<Diagnostic>> {
+		let t = self.peek(0).clone();
+		let mut result = Expression::new();
+		let mut expr = Expression::new();
+		if t.kind == LuaKind::Name {
+			let t = self.expect_name()?;
+			expr.push_back(SYMBOL(t.text, t.span));
+		} else if self.accept("(") {
+			let inner = self.parse_expression()?;
+			self.expect(")")?;
+			result.push_back(EXPR(inner, self.span_from(t.span)));
+		} else {
+			return Err(self.expected("<expr>", &t));
+		}
+		loop {
+			let t = self.peek(0).clone();
+			match (t.kind, t.text.as_str()) {
+				(LuaKind::Symbol, ".") => {
+					self.advance();
+					let name = self.peek(0).clone();
+					if name.kind != LuaKind::Name {
+						return Err(self.expected("<name>", &name));
+					}
+					self.advance();
+					expr.push_back(SYMBOL(String::from("."), t.span));
+					expr.push_back(SYMBOL(name.text, name.span));
+				}
+				(LuaKind::Symbol, "[") => {
+					self.advance();
+					let index = self.parse_expression()?;
+					let close = self.expect("]")?;
+					let span = Span::range(t.span.end, close.span.start);
+					expr.push_back(SYMBOL(String::from("[("), t.span));
+					expr.push_back(EXPR(index, span));
+					expr.push_back(SYMBOL(String::from(")]"), close.span));
+				}
+				(LuaKind::Symbol, ":") => {
+					self.advance();
+					let name = self.peek(0).clone();
+					if name.kind != LuaKind::Name {
+						return Err(self.expected("<name>", &name));
+					}
+					self.advance();
+					expr.push_back(SYMBOL(String::from(":"), t.span));
+					expr.push_back(SYMBOL(name.text, name.span));
+					let args = self.parse_call_args()?;
+					expr.push_back(CALL(args, self.span_from(t.span)));
+				}
+				(LuaKind::Symbol, "(" | "{") | (LuaKind::String, _) => {
+					let args = self.parse_call_args()?;
+					expr.push_back(CALL(args, self.span_from(t.span)));
+				}
+				_ => break,
+			}
+		}
+		if !expr.is_empty() {
+			let span = Span::range(
+				expr.front().map_or(t.span, ComplexToken::span).start,
+				expr.back().map_or(t.span, ComplexToken::span).end,
+			);
+			result.push_back(IDENT {
+				expr,
+				line: t.span.start.line,
+				span,
+			});
+		}
+		Ok(result)
+	}
+
+	fn parse_call_args(&mut self) -> Result<Vec<Expression>, Box<Diagnostic>> {
+		if self.accept("(") {
+			if self.accept(")") {
+				return Ok(Vec::new());
+			}
+			let args = self.parse_expressions()?;
+			self.expect(")")?;
+			Ok(args)
+		} else {
+			Ok(vec![self.parse_simple()?])
+		}
+	}
+
+	fn parse_table(&mut self) -> Result<ComplexToken, Box<Diagnostic>> {
+		let open = self.expect("{")?.span;
+		let mut values = Vec::new();
+		while !self.check("}") {
+			let t = self.peek(0).clone();
+			let line = t.span.start.line;
+			if t.kind == LuaKind::Name && {
+				let next = self.peek(1);
+				next.kind == LuaKind::Symbol && next.text == "="
+			} {
+				self.advance();
+				self.advance();
+				let key = if self.keywords.contains(&t.text.as_str()) {
+					let key = VecDeque::from([SYMBOL(format!("\"{}\"", t.text), t.span)]);
+					VecDeque::from([
+						SYMBOL(String::from("["), t.span),
+						EXPR(key, t.span),
+						SYMBOL(String::from("]"), t.span),
+					])
+				} else {
+					VecDeque::from([SYMBOL(t.text, t.span)])
+				};
+				values.push((Some(key), self.parse_expression()?, line));
+			} else if self.accept("[") {
+				let key = self.parse_expression()?;
+				let close = self.expect("]")?;
+				self.expect("=")?;
+				let key = VecDeque::from([
+					SYMBOL(String::from("["), t.span),
+					EXPR(key, Span::range(t.span.end, close.span.start)),
+					SYMBOL(String::from("]"), close.span),
+				]);
+				values.push((Some(key), self.parse_expression()?, line));
+			} else {
+				values.push((None, self.parse_expression()?, line));
+			}
+			if !self.accept(",") && !self.accept(";") {
+				break;
+			}
+		}
+		self.expect("}")?;
+		Ok(TABLE {
+			values,
+			metas: Vec::new(),
+			metatable: None,
+			span: self.span_from(open),
+		})
+	}
+}
+
+/// Turns an identifier parsed as an expression into the name of an assignment
+fn assignment_target(mut expr: Expression) -> Expression {
+	match (expr.len(), expr.pop_back()) {
+		(1, Some(IDENT { expr, .. })) => expr,
+		(_, Some(last)) => {
+			expr.push_back(last);
+			expr
+		}
+		(_, None) => expr,
+	}
+}
+
+/// Wraps a function that is the operand of an operator in parentheses, since Clue doesn't allow `fn` there
+fn parenthesize_lambda(mut expr: Expression) -> Expression {
+	if let Some(LAMBDA { span, .. }) = expr.front() {
+		let span = *span;
+		if let Some(lambda) = expr.pop_front() {
+			expr.push_front(EXPR(VecDeque::from([lambda]), span));
+		}
+	}
+	expr
+}
+
+/// Wraps the operand of `#` in parentheses unless it starts with a name or a table,
+/// since Clue doesn't accept anything else after it
+fn parenthesize_length(expr: Expression) -> Expression {
+	fn is_operand(t: Option<&ComplexToken>) -> bool {
+		match t {
+			Some(IDENT { expr, .. }) => is_operand(expr.front()),
+			Some(SYMBOL(lexeme, _)) => {
+				lexeme.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+					&& !matches!(lexeme.as_str(), "nil" | "true" | "false" | "not ")
+			}
+			Some(TABLE { .. } | EXPR(..)) => true,
+			_ => false,
+		}
+	}
+	if is_operand(expr.front()) {
+		return expr;
+	}
+	let span = Span::range(
+		expr.front()
+			.map_or_else(Span::default, ComplexToken::span)
+			.start,
+		expr.back()
+			.map_or_else(Span::default, ComplexToken::span)
+			.end,
+	);
+	VecDeque::from([EXPR(expr, span)])
+}
+
+/// Returns the function and the argument of a `pairs(t)` or `ipairs(t)` call
+fn iterator_call(expr: &Expression) -> Option<(&str, Expression, Span)> {
+	let (Some(IDENT { expr, span, .. }), 1) = (expr.front(), expr.len()) else {
+		return None;
+	};
+	match (expr.front(), expr.get(1), expr.len()) {
+		(Some(SYMBOL(function, _)), Some(CALL(args, _)), 2)
+			if matches!(function.as_str(), "pairs" | "ipairs") && args.len() == 1 =>
+		{
+			Some((function, args[0].clone(), *span))
+		}
+		_ => None,
+	}
+}
+
+/// Turns the `goto`s with the given spans into `continue`s, without entering nested loops or functions
+fn replace_gotos(code: &mut Expression, gotos: &[Span], replaced: &mut Vec<Span>) {
+	for t in code {
+		replace_goto(t, gotos, replaced);
+	}
+}
+
+fn replace_goto(t: &mut ComplexToken, gotos: &[Span], replaced: &mut Vec<Span>) {
+	match t {
+		SYMBOL(_, span) if gotos.contains(span) => {
+			replaced.push(*span);
+			*t = CONTINUE_LOOP(*span);
+		}
+		IF_STATEMENT { code, next, .. } => {
+			replace_gotos(&mut code.code, gotos, replaced);
+			if let Some(next) = next {
+				replace_goto(next, gotos, replaced);
+			}
+		}
+		DO_BLOCK(code, _) => replace_gotos(&mut code.code, gotos, replaced),
+		_ => {}
+	}
+}
+
+/// Parses Lua code into the AST the Clue parser would build for the same program
+/// Returns the AST and a warning for every construct that can't be translated to Clue
+/// Comments are kept as [`SYMBOL`]s holding a Clue comment
+///
+/// # Errors
+/// If the code is not valid Lua, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
+pub fn parse_lua(
+	code: &str,
+	filename: &String,
+) -> Result<(Expression, Vec<Diagnostic>), Vec<Diagnostic>> {
+	let lexer = Lexer {
+		code: code.as_bytes(),
+		current: 0,
+		line: 1,
+		column: 1,
+		filename,
+		tokens: Vec::new(),
+		comments: VecDeque::new(),
+		errors: Vec::new(),
+	};
+	let (tokens, comments) = lexer.scan()?;
+	let mut parser = LuaParser {
+		tokens,
+		current: 0,
+		filename,
+		comments,
+		keywords: keywords(),
+		gotos: Vec::new(),
+		labels: Vec::new(),
+		warnings: Vec::new(),
+	};
+	let expr = parser.parse_block()?;
+	if parser.peek(0).kind != LuaKind::Eof {
+		return Err(parser.expected("<end>", parser.peek(0)).into());
+	}
+	for (name, span) in std::mem::take(&mut parser.gotos) {
+		parser.untranslatable(format!("'goto {name}' can't be translated to Clue"), span);
+	}
+	for (name, span) in std::mem::take(&mut parser.labels) {
+		parser.untranslatable(
+			format!("The label '{name}' can't be translated to Clue"),
+			span,
+		);
+	}
+	parser
+		.warnings
+		.sort_by_key(|warning| (warning.span.start.line, warning.span.start.column));
+	Ok((expr, parser.warnings))
+}
+
+/// Converts Lua code into Clue code, keeping its comments
+/// Returns the Clue code and a warning for every construct that couldn't be translated, which is
+/// left in the code as a comment or as it was
+///
+/// # Errors
+/// If the code is not valid Lua, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
+///
+/// # Example
+/// ```rust
+/// use clue_core::{converter::convert_lua, diagnostic::Diagnostic};
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let filename = String::from("main.lua");
+///     let code = "local function f(t) -- sum\n  for i, v in ipairs(t) do\n    if not v or v ~= 0 then goto continue end\n    print(i)\n    ::continue::\n  end\nend";
+///
+///     let (clue, warnings) = convert_lua(code, &filename)?;
+///     assert_eq!(
+///         clue,
+///         "local fn f(t) {\n\t// sum\n\tfor i, v in t {\n\t\tif !v || v != 0 {\n\t\t\tcontinue\n\t\t}\n\t\tprint(i)\n\t}\n}\n"
+///     );
+///     assert!(warnings.is_empty());
+///
+///     Ok(())
+/// }
+/// ```
+pub fn convert_lua(
+	code: &str,
+	filename: &String,
+) -> Result<(String, Vec<Diagnostic>), Vec<Diagnostic>> {
+	let (expr, warnings) = parse_lua(code, filename)?;
+	Ok((print_ast(&expr), warnings))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::convert_lua;
+	use crate::Clue;
+
+	/// Converts the Lua code and compiles the converted code back into Lua
+	fn round_trip(lua: &str) -> String {
+		let filename = String::from("test.lua");
+		let (clue, _) = convert_lua(lua, &filename).unwrap();
+		Clue::new()
+			.compile_code(clue.clone())
+			.unwrap_or_else(|e| panic!("{lua}\n--- converted to ---\n{clue}\n{e:?}"))
+	}
+
+	#[test]
+	fn converted_code_compiles() {
+		for lua in [
+			"local a, b = 1, 2\nprint(a + b * 2, a .. b, #t, not a)",
+			"local function f(x, ...) return x, ... end\nlocal g = function() end",
+			"for i = 1, 10, 2 do print(i) end\nfor k, v in pairs(t) do print(k, v) end",
+			"while a do a = a - 1 end\nrepeat a = a + 1 until a > 10",
+			"if a then print(1) elseif b then print(2) else print(3) end",
+			"local t = {1, 2, x = 3, [\"y\"] = {}, f = function(self) return self.x end}\nt:f()",
+			"local s = [[long\nstring]] -- comment\n--[[ long\ncomment ]]\nprint(s)",
+			"print(#\"abc\", #'a' .. 'b', #{1, 2}, #t.x, #(t), ##t, #-t, #...)",
+		] {
+			round_trip(lua);
+		}
+	}
+
+	#[test]
+	fn floor_division() {
+		assert!(round_trip("print(7 // 2)").contains("print(math.floor(7/2));"));
+		let code = round_trip("local x = 1 + 7 // 2 * 3\nprint(x)");
+		assert!(code.contains("local x = 1+math.floor(7/2)*3;\nprint(x);"));
+		assert!(round_trip("x = (a + b) // c").contains("math.floor((a+b)/c)"));
+	}
+}
@@ -0,0 +1,214 @@
+//! The `diagnostic` module contains the [`Diagnostic`] struct, which is what every step of the
+//! compilation process uses to report errors and warnings.
+//!
+//! A [`Diagnostic`] carries its [`Severity`], a short machine readable code, the message, the file
+//! it comes from and the [`Span`] of the source code it refers to, so that it can be inspected
+//! programmatically instead of being parsed out of the compiler's output.
+
+use std::{error::Error, fmt};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// How serious a [`Diagnostic`] is
+pub enum Severity {
+	/// The code cannot be compiled
+	Error,
+
+	/// The code can be compiled but it is likely to contain a mistake
+	Warning,
+
+	/// Additional information that does not point to a problem
+	Note,
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Severity::Error => "Error",
+			Severity::Warning => "Warning",
+			Severity::Note => "Note",
+		})
+	}
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A position in the source code
+pub struct Position {
+	/// The line of the position, starting from 1
+	pub line: usize,
+
+	/// The column of the position, starting from 1
+	pub column: usize,
+
+	/// The byte offset of the position from the start of the source file, starting from 0
+	/// It is 0 for positions that do not come from the scanner, like most preprocessor errors
+	pub offset: usize,
+}
+
+impl Position {
+	/// Creates a new [`Position`] at the given line and column, with no byte offset
+	pub const fn new(line: usize, column: usize) -> Self {
+		Self {
+			line,
+			column,
+			offset: 0,
+		}
+	}
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A range of the source code, going from `start` to `end`
+pub struct Span {
+	/// Where the range starts
+	pub start: Position,
+
+	/// Where the range ends
+	pub end: Position,
+}
+
+impl Span {
+	/// Creates a new [`Span`] that starts and ends at the given line and column
+	pub const fn new(line: usize, column: usize) -> Self {
+		let position = Position::new(line, column);
+		Self {
+			start: position,
+			end: position,
+		}
+	}
+
+	/// Creates a new [`Span`] going from `start` to `end`
+	pub const fn range(start: Position, end: Position) -> Self {
+		Self { start, end }
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// An error or a warning found while compiling Clue code
+///
+/// # Example
+/// ```rust
+/// use clue_core::{diagnostic::Severity, Clue};
+///
+/// let clue = Clue::new();
+/// let diagnostics = clue.compile_code("local x = ".to_owned()).unwrap_err();
+/// let error = &diagnostics[0];
+///
+/// assert_eq!(error.severity, Severity::Error);
+/// assert_eq!(error.file, "(library)");
+/// assert_eq!(error.span.start.line, 1);
+/// ```
+pub struct Diagnostic {
+	/// How serious the diagnostic is
+	pub severity: Severity,
+
+	/// A short code identifying the kind of diagnostic, e.g. `"expected"` or `"unterminated-string"`
+	pub code: String,
+
+	/// The message describing the diagnostic
+	pub message: String,
+
+	/// The name of the file the diagnostic comes from
+	pub file: String,
+
+	/// The part of the source code the diagnostic refers to
+	pub span: Span,
+
+	/// Additional notes explaining the diagnostic
+	pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+	/// Creates a new [`Diagnostic`] with the given [`Severity`], code, message, file and [`Span`]
+	pub fn new(
+		severity: Severity,
+		code: impl Into<String>,
+		message: impl Into<String>,
+		file: impl Into<String>,
+		span: Span,
+	) -> Self {
+		Self {
+			severity,
+			code: code.into(),
+			message: message.into(),
+			file: file.into(),
+			span,
+			notes: Vec::new(),
+		}
+	}
+
+	/// Creates a new [`Diagnostic`] with [`Severity::Error`]
+	pub fn error(
+		code: impl Into<String>,
+		message: impl Into<String>,
+		file: impl Into<String>,
+		span: Span,
+	) -> Self {
+		Self::new(Severity::Error, code, message, file, span)
+	}
+
+	/// Creates a new [`Diagnostic`] with [`Severity::Warning`]
+	pub fn warning(
+		code: impl Into<String>,
+		message: impl Into<String>,
+		file: impl Into<String>,
+		span: Span,
+	) -> Self {
+		Self::new(Severity::Warning, code, message, file, span)
+	}
+
+	/// Creates a new [`Diagnostic`] with [`Severity::Note`]
+	pub fn note(
+		code: impl Into<String>,
+		message: impl Into<String>,
+		file: impl Into<String>,
+		span: Span,
+	) -> Self {
+		Self::new(Severity::Note, code, message, file, span)
+	}
+
+	/// Adds a note to the [`Diagnostic`] and returns it
+	pub fn with_note(mut self, note: impl Into<String>) -> Self {
+		self.notes.push(note.into());
+		self
+	}
+
+	/// Returns `true` if the [`Diagnostic`] is an error
+	pub fn is_error(&self) -> bool {
+		self.severity == Severity::Error
+	}
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let Position { line, column, .. } = self.span.start;
+		write!(
+			f,
+			"{} in {}:{line}:{column}!\n{}: \"{}\"",
+			self.severity, self.file, self.severity, self.message
+		)?;
+		for note in &self.notes {
+			write!(f, "\nNote: \"{note}\"")?;
+		}
+		Ok(())
+	}
+}
+
+impl Error for Diagnostic {}
+
+impl From<Diagnostic> for Vec<Diagnostic> {
+	fn from(diagnostic: Diagnostic) -> Self {
+		vec![diagnostic]
+	}
+}
+
+impl From<Box<Diagnostic>> for Vec<Diagnostic> {
+	fn from(diagnostic: Box<Diagnostic>) -> Self {
+		vec![*diagnostic]
+	}
+}
@@ -2,9 +2,9 @@
 //!
 //! It contains [`Options`] struct is the main struct used to store the compiler options,
 //! and is used by the [`Compiler`](crate::compiler::Compiler) to determine how to compile the code
-//! and also other helpful enums such as [`LuaVersion`], [`BitwiseMode`] and [`ContinueMode`]
+//! and also other helpful enums such as [`LuaVersion`], [`BitwiseMode`], [`ContinueMode`], [`OutputMode`], [`TypesMode`] and [`LuaSTD`]
 
-use std::path::PathBuf;
+use std::{collections::BTreeMap, fmt, num::NonZeroUsize, path::PathBuf};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -38,22 +38,101 @@ pub enum ContinueMode {
 	MoonScript,
 }
 
-/*
-#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[clap(rename_all = "verbatim")]
+/// The way the files of a directory are written once they are compiled
+pub enum OutputMode {
+	#[default]
+	#[clap(name = "bundle")]
+	/// Bundle: Every file is wrapped into a module of a single Lua file,
+	/// and `@import` loads them with the `import` function of the bundle
+	Bundle,
+
+	#[clap(name = "files")]
+	/// Files: Every file is written to its own Lua file, mirroring the directory tree,
+	/// and `@import` loads them with `require`
+	Files,
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[clap(rename_all = "verbatim")]
+/// The mode to use when checking the type annotations of the code
 pub enum TypesMode {
+	#[default]
+	#[clap(name = "none")]
+	/// None: Type annotations are erased without being checked
 	NONE,
+
+	#[clap(name = "warn")]
+	/// Warn: Type mismatches are reported as warnings and the code is still compiled
 	WARN,
+
+	#[clap(name = "strict")]
+	/// Strict: Type mismatches are reported as errors
 	STRICT,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[clap(rename_all = "verbatim")]
+/// The standard library that references to Lua's standard library are checked against
 pub enum LuaSTD {
+	#[clap(name = "none")]
+	/// Don't check references to the standard library
 	NONE,
+
+	#[clap(name = "LuaJIT")]
+	/// The standard library of LuaJIT
 	LUAJIT,
+
+	#[clap(name = "Lua54")]
+	/// The standard library of Lua 5.4
 	LUA54,
-	//ADD MORE LATER
+
+	#[clap(name = "Lua53")]
+	/// The standard library of Lua 5.3
+	LUA53,
+
+	#[clap(name = "Lua52")]
+	/// The standard library of Lua 5.2
+	LUA52,
+
+	#[clap(name = "Lua51")]
+	/// The standard library of Lua 5.1
+	LUA51,
+
+	/// The standard library of BLUA
+	BLUA,
+}
+
+impl From<LuaVersion> for LuaSTD {
+	fn from(version: LuaVersion) -> Self {
+		match version {
+			LuaVersion::LuaJIT => LuaSTD::LUAJIT,
+			LuaVersion::Lua54 => LuaSTD::LUA54,
+			LuaVersion::Lua53 => LuaSTD::LUA53,
+			LuaVersion::Lua52 => LuaSTD::LUA52,
+			LuaVersion::Lua51 => LuaSTD::LUA51,
+			LuaVersion::BLUA => LuaSTD::BLUA,
+		}
+	}
+}
+
+impl fmt::Display for LuaSTD {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			LuaSTD::NONE => "none",
+			LuaSTD::LUAJIT => "LuaJIT",
+			LuaSTD::LUA54 => "Lua 5.4",
+			LuaSTD::LUA53 => "Lua 5.3",
+			LuaSTD::LUA52 => "Lua 5.2",
+			LuaSTD::LUA51 => "Lua 5.1",
+			LuaSTD::BLUA => "BLUA",
+		})
+	}
 }
-*/
 
 #[derive(Default, Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -143,14 +222,41 @@ pub struct Options {
 	/// The path to the output file
 	pub env_targetos: String,
 
-	/// Whether to print the symbol table
+	/// Whether to print the symbol table of every file as JSON
 	pub env_symbols: bool,
 
-	//pub env_types: TypesMode,
-	//pub env_std: LuaSTD,
+	/// The maximum number of errors to report for each file, if any
+	pub env_maxerrors: Option<NonZeroUsize>,
+
+	/// Whether to report reads of globals that were never declared
+	pub env_checkglobals: bool,
+
+	/// The way the files of a directory are written once they are compiled
+	pub env_mode: OutputMode,
+
+	/// Whether to build a [`SourceMap`](crate::sourcemap::SourceMap) of the output of every file of a project
+	pub env_sourcemap: bool,
+
+	/// The mode to use when checking the type annotations of the code
+	pub env_types: TypesMode,
+
+	/// The standard library to check references against, if it's not the one of `env_target`
+	pub env_std: Option<LuaSTD>,
+
+	/// The preprocessor variables defined in every file before it's preprocessed,
+	/// which `@define` can override
+	pub env_defines: BTreeMap<String, String>,
 }
 
 impl Options {
+	/// Returns the standard library references should be checked against, which is `env_std` if set
+	/// or the standard library of `env_target` otherwise
+	pub fn std(&self) -> LuaSTD {
+		self.env_std
+			.or_else(|| self.env_target.map(LuaSTD::from))
+			.unwrap_or(LuaSTD::NONE)
+	}
+
 	/// Applies the chosen preset to the options
 	/// This should be called after `env_jitbit` or `env_target` is set to reflect the chosen preset
 	pub fn preset(&mut self) {
@@ -0,0 +1,358 @@
+//! The formatter re-emits Clue code in a consistent style, keeping every comment and directive.
+//!
+//! It works on the tokens returned by [`scan_code_lossless`] instead of the syntax tree, so the
+//! code doesn't need to be preprocessed and only the whitespace between the tokens can change:
+//! formatted code always compiles to the same Lua code as the original.
+//!
+//! It exposes a single function, [`format_code`], configured by [`FormatOptions`].
+
+use crate::{
+	diagnostic::Diagnostic,
+	scanner::{scan_code_lossless, Token, TokenType, TokenType::*, Trivia, TriviaKind},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+/// How [`format_code`] formats the code
+/// The fields are named like the keys of a `cluefmt.toml` file
+pub struct FormatOptions {
+	/// Indent with tabs instead of spaces
+	pub hard_tabs: bool,
+
+	/// How many spaces make an indentation level when `hard_tabs` is `false`
+	pub tab_spaces: usize,
+
+	/// The maximum amount of consecutive blank lines
+	pub blank_lines_upper_bound: usize,
+}
+
+impl Default for FormatOptions {
+	fn default() -> Self {
+		Self {
+			hard_tabs: true,
+			tab_spaces: 4,
+			blank_lines_upper_bound: 1,
+		}
+	}
+}
+
+const fn is_opening(kind: TokenType) -> bool {
+	matches!(
+		kind,
+		ROUND_BRACKET_OPEN
+			| SQUARE_BRACKET_OPEN
+			| CURLY_BRACKET_OPEN
+			| SAFE_CALL
+			| SAFE_SQUARE_BRACKET
+	)
+}
+
+const fn is_closing(kind: TokenType) -> bool {
+	matches!(
+		kind,
+		ROUND_BRACKET_CLOSED | SQUARE_BRACKET_CLOSED | CURLY_BRACKET_CLOSED
+	)
+}
+
+/// Tokens that are always surrounded by spaces
+const fn is_spaced(kind: TokenType) -> bool {
+	matches!(
+		kind,
+		DEFINE
+			| DEFINE_AND
+			| DEFINE_OR
+			| INCREASE
+			| DECREASE
+			| MULTIPLY
+			| DIVIDE | DEFINE_COALESCE
+			| EXPONENTIATE
+			| CONCATENATE
+			| MODULATE
+			| EQUAL | NOT_EQUAL
+			| BIGGER | BIGGER_EQUAL
+			| SMALLER | SMALLER_EQUAL
+			| AND | OR
+			| ARROW | COALESCE
+			| TWODOTS | PLUS
+			| STAR | SLASH
+			| FLOOR_DIVISION
+			| PERCENTUAL
+			| CARET | BIT_AND
+			| BIT_OR | BIT_XOR
+			| LEFT_SHIFT
+			| RIGHT_SHIFT
+	)
+}
+
+/// Tokens that continue the expression of the previous line when a line starts with them
+const fn continues_line(kind: TokenType) -> bool {
+	is_spaced(kind)
+		|| matches!(
+			kind,
+			QUESTION_MARK | COLON | DOT | DOUBLE_COLON | SAFE_DOT | SAFE_DOUBLE_COLON
+		)
+}
+
+/// Tokens that need something after them when a line ends with them
+/// `?` is not one of them because it also ends optional types (e.g. `string?`)
+const fn continues_next_line(kind: TokenType) -> bool {
+	match kind {
+		QUESTION_MARK => false,
+		MINUS => true,
+		_ => continues_line(kind),
+	}
+}
+
+/// Tokens after which a `{` on the next line opens the body of a block
+const fn starts_header(kind: TokenType) -> bool {
+	matches!(
+		kind,
+		IF | ELSEIF
+			| ELSE | FOR
+			| WHILE | LOOP
+			| TRY | CATCH
+			| FN | METHOD
+			| MATCH | STRUCT
+			| ENUM | CONSTRUCTOR
+			| ARROW
+	)
+}
+
+fn needs_space(last: TokenType, kind: TokenType, space: bool) -> bool {
+	match (last, kind) {
+		(_, COMMA | SEMICOLON) => false,
+		(COMMA, _) => true,
+		(ROUND_BRACKET_OPEN | SQUARE_BRACKET_OPEN | SAFE_CALL | SAFE_SQUARE_BRACKET, _) => false,
+		(_, ROUND_BRACKET_CLOSED | SQUARE_BRACKET_CLOSED) => false,
+		(CURLY_BRACKET_CLOSED, ELSE | ELSEIF | CATCH | UNTIL) => true,
+		_ => is_spaced(last) || is_spaced(kind) || space,
+	}
+}
+
+struct Formatter<'a> {
+	options: &'a FormatOptions,
+	output: String,
+	brackets: Vec<usize>,
+	indent: usize,
+	newlines: usize,
+	space: bool,
+	last: Option<TokenType>,
+	previous: Option<TokenType>,
+	header: bool,
+}
+
+impl<'a> Formatter<'a> {
+	fn new(options: &'a FormatOptions, capacity: usize) -> Self {
+		Self {
+			options,
+			output: String::with_capacity(capacity),
+			brackets: Vec::new(),
+			indent: 0,
+			newlines: 0,
+			space: false,
+			last: None,
+			previous: None,
+			header: false,
+		}
+	}
+
+	fn inner_indent(&self) -> usize {
+		self.brackets.last().copied().unwrap_or(0)
+	}
+
+	fn start_line(&mut self, indent: usize, closing: bool) {
+		if !self.output.is_empty() {
+			let newlines = if closing || self.last.map_or(false, is_opening) {
+				1
+			} else {
+				self.newlines.min(self.options.blank_lines_upper_bound + 1)
+			};
+			for _ in 0..newlines {
+				self.output.push('\n');
+			}
+		}
+		self.indent = indent;
+		if self.options.hard_tabs {
+			for _ in 0..indent {
+				self.output.push('\t');
+			}
+		} else {
+			for _ in 0..indent * self.options.tab_spaces {
+				self.output.push(' ');
+			}
+		}
+	}
+
+	fn write_token(&mut self, token: &Token) {
+		let kind = token.kind;
+		if self.newlines > 0 {
+			if let Some(last) = self.last {
+				let joins = match kind {
+					CURLY_BRACKET_OPEN => {
+						self.header
+							&& (last == ARROW || !continues_next_line(last))
+							&& !is_opening(last) && last != COMMA
+					}
+					ELSE | ELSEIF | CATCH | UNTIL => last == CURLY_BRACKET_CLOSED,
+					_ => false,
+				};
+				if joins {
+					self.newlines = 0;
+					self.space = true;
+				}
+			}
+		}
+		if self.newlines > 0 || self.output.is_empty() {
+			let closing = is_closing(kind);
+			let indent = if closing {
+				self.inner_indent().saturating_sub(1)
+			} else if continues_line(kind) || self.previous.map_or(false, continues_next_line) {
+				self.inner_indent() + 1
+			} else {
+				self.inner_indent()
+			};
+			self.start_line(indent, closing);
+		} else if self.last.map_or(true, |last| {
+			// the brace that opens a block is always spaced, like in `else {`
+			let space = self.space || (kind == CURLY_BRACKET_OPEN && self.header);
+			needs_space(last, kind, space)
+		}) {
+			self.output.push(' ');
+		}
+		self.output += &token.lexeme;
+		if is_opening(kind) {
+			self.brackets.push(self.indent + 1);
+		} else if is_closing(kind) {
+			self.brackets.pop();
+		}
+		if starts_header(kind) {
+			self.header = true;
+		} else if matches!(kind, CURLY_BRACKET_OPEN | CURLY_BRACKET_CLOSED) {
+			self.header = false;
+		}
+		self.last = Some(kind);
+		self.previous = Some(kind);
+		self.space = false;
+		self.newlines = 0;
+	}
+
+	fn write_trivia_list(&mut self, trivia: &[Trivia]) {
+		for trivia in trivia {
+			match trivia.kind {
+				TriviaKind::Newline => self.newlines += 1,
+				TriviaKind::Whitespace => self.space = true,
+				TriviaKind::Comment => self.write_trivia(trivia.text.trim_end()),
+				TriviaKind::Directive => self.write_directive(trivia.text.trim_end()),
+			}
+		}
+	}
+
+	fn write_trivia(&mut self, text: &str) {
+		if self.newlines > 0 || self.output.is_empty() {
+			self.start_line(self.inner_indent(), false);
+		} else if self.space || self.last.is_none() || text.starts_with("//") {
+			self.output.push(' ');
+		}
+		self.output += text;
+		self.last = None;
+		self.space = false;
+		self.newlines = 0;
+	}
+
+	fn write_directive(&mut self, text: &str) {
+		self.write_trivia(text);
+		self.previous = None;
+		self.header = false;
+		let mut quote = None;
+		for c in text.chars() {
+			match (quote, c) {
+				(None, '"' | '\'' | '`') => quote = Some(c),
+				(Some(q), c) if q == c => quote = None,
+				(None, '{') => self.brackets.push(self.indent + 1),
+				(None, '}') => {
+					self.brackets.pop();
+				}
+				_ => {}
+			}
+		}
+	}
+}
+
+/// Formats Clue code that was not preprocessed, keeping its comments and directives
+///
+/// Every line is indented by how many brackets are open, with one more level for lines that
+/// continue the expression of the previous one, the opening braces of blocks and the keywords after
+/// a closing brace (like `} else {`) are moved to the end of the previous line, spaces are normalized
+/// around commas, brackets and binary operators and consecutive blank lines are limited
+///
+/// # Errors
+/// If the code contains something that can't be scanned, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
+///
+/// # Example
+/// ```rust
+/// use clue_core::{diagnostic::Diagnostic, formatter::*};
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let options = FormatOptions::default();
+///     let filename = String::from("main.clue");
+///     let code = "if x==1\n{\n    print(x,2) // two\n}\nelse {\n  print( 3 )\n}";
+///
+///     let formatted = format_code(code, &filename, &options)?;
+///     assert_eq!(formatted, "if x == 1 {\n\tprint(x, 2) // two\n} else {\n\tprint(3)\n}\n");
+///     assert_eq!(format_code(&formatted, &filename, &options)?, formatted);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn format_code(
+	code: &str,
+	filename: &String,
+	options: &FormatOptions,
+) -> Result<String, Vec<Diagnostic>> {
+	let mut formatter = Formatter::new(options, code.len());
+	for token in scan_code_lossless(code, filename)? {
+		formatter.write_trivia_list(&token.leading);
+		if token.token.kind != EOF {
+			formatter.write_token(&token.token);
+		}
+		formatter.write_trivia_list(&token.trailing);
+	}
+	let mut output = formatter.output;
+	if !output.is_empty() {
+		output.push('\n');
+	}
+	Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{format_code, FormatOptions};
+
+	fn format(code: &str) -> String {
+		let filename = String::from("test.clue");
+		let options = FormatOptions::default();
+		let formatted = format_code(code, &filename, &options).unwrap();
+		assert_eq!(
+			format_code(&formatted, &filename, &options).unwrap(),
+			formatted,
+			"formatting {code:?} again changed it"
+		);
+		formatted
+	}
+
+	#[test]
+	fn formatting_is_idempotent() {
+		assert_eq!(format("if x {} else{ }"), "if x {} else { }\n");
+		assert_eq!(format("if x {}else{}"), "if x {} else {}\n");
+		assert_eq!(
+			format("fn f(x){\nreturn x\n}"),
+			"fn f(x) {\n\treturn x\n}\n"
+		);
+		assert_eq!(format("local t = {1,2}"), "local t = {1, 2}\n");
+		assert_eq!(format("try {\n}\ncatch e{\n}"), "try {\n} catch e {\n}\n");
+	}
+}
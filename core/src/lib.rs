@@ -7,27 +7,43 @@ use std::{
 	ffi::OsStr,
 	fmt::Display,
 	fs,
+	num::NonZeroUsize,
 	path::{Path, PathBuf},
 };
 
 use code::Code;
 use compiler::Compiler;
-use env::{BitwiseMode, ContinueMode, LuaVersion, Options};
-use parser::{parse_tokens, Expression};
+use diagnostic::{Diagnostic, Span};
+use env::{BitwiseMode, ContinueMode, LuaSTD, LuaVersion, Options, TypesMode};
+use parser::{parse_tokens, parse_tokens_with_symbols, Expression};
 use preprocessor::{preprocess_code, preprocess_codes, read_file};
-use scanner::{scan_code, Token};
+use project::ProjectOutput;
+use scanner::{scan_code, LosslessToken, Token};
+use sourcemap::SourceMap;
+use symbols::SymbolTable;
 
 #[cfg(feature = "rpmalloc")]
 #[global_allocator]
 /// The best memory allocator available for Clue
 static ALLOC: rpmalloc::RpMalloc = rpmalloc::RpMalloc;
 
+pub mod cache;
+pub mod checker;
 pub mod code;
 pub mod compiler;
+pub mod converter;
+pub mod diagnostic;
 pub mod env;
+pub mod formatter;
+pub mod linter;
 pub mod parser;
 pub mod preprocessor;
+pub mod printer;
+pub mod project;
 pub mod scanner;
+pub mod sourcemap;
+pub mod stdlib;
+pub mod symbols;
 
 #[macro_export]
 /// Check whether `tocheck` is `Ok` or `Err`
@@ -65,6 +81,15 @@ macro_rules! format_clue {
     }};
 }
 
+fn invalid_path(path: &impl Display) -> Diagnostic {
+	Diagnostic::error(
+		"io",
+		format!("Invalid path: {path}"),
+		path.to_string(),
+		Span::default(),
+	)
+}
+
 /// The main Clue library API
 /// This is the API that you will use to interact with Clue for most use cases
 /// It's recommended to use this API instead of the lower level APIs unless you need to
@@ -150,6 +175,90 @@ impl Clue {
 	pub fn target_os(&mut self, os: String) {
 		self.options.env_targetos = os;
 	}
+
+	/// Sets the `maxerrors` option
+	/// The `maxerrors` option is used to limit how many errors are reported for each file
+	/// To report every error found, set `max` to `None`
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::Clue;
+	/// use std::num::NonZeroUsize;
+	///
+	/// let mut clue = Clue::new();
+	/// let code = "local a = }\nlocal b = }\nlocal c = }";
+	/// assert_eq!(clue.compile_code(code.to_owned()).unwrap_err().len(), 3);
+	///
+	/// clue.max_errors(NonZeroUsize::new(2));
+	/// assert_eq!(clue.compile_code(code.to_owned()).unwrap_err().len(), 2);
+	///
+	/// clue.max_errors(NonZeroUsize::new(1));
+	/// let code = "local a = 1\nlocal b = 2\nlocal c = }";
+	/// assert_eq!(clue.compile_code(code.to_owned()).unwrap_err().len(), 1);
+	/// ```
+	pub fn max_errors(&mut self, max: Option<NonZeroUsize>) {
+		self.options.env_maxerrors = max;
+	}
+
+	/// Sets the `checkglobals` option
+	/// When the `checkglobals` option is enabled, reading a global that was never declared with
+	/// `global`, `static` or `extern` or assigned in the same file will be reported as an error
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::Clue;
+	///
+	/// let mut clue = Clue::new();
+	/// clue.check_globals(true);
+	///
+	/// assert!(clue.compile_code("extern fn spawn(x, y)\nspawn(1, 2)".to_owned()).is_ok());
+	/// assert!(clue.compile_code("spwan(1, 2)".to_owned()).is_err());
+	///
+	/// let errors = clue.compile_code("local x = 1\nprint(x, spwan)".to_owned()).unwrap_err();
+	/// assert_eq!((errors[0].span.start.line, errors[0].span.start.column), (2, 10));
+	/// ```
+	pub fn check_globals(&mut self, check: bool) {
+		self.options.env_checkglobals = check;
+	}
+
+	/// Sets the [`TypesMode`] to use
+	/// [`TypesMode::WARN`] reports type mismatches as warnings, which are returned by [`Clue::check_tokens`],
+	/// while [`TypesMode::STRICT`] makes them errors
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{env::TypesMode, Clue};
+	///
+	/// let mut clue = Clue::new();
+	/// clue.types_mode(TypesMode::STRICT);
+	///
+	/// let code = "local fn half(x: number): number { return x / 2 }\nlocal y: number = half(4)";
+	/// assert_eq!(
+	///     clue.compile_code(code.to_owned()).unwrap(),
+	///     "local function half(x)\n\treturn x/2;\nend\nlocal y = half(4);"
+	/// );
+	/// assert!(clue.compile_code("local s: string = 1".to_owned()).is_err());
+	/// ```
+	pub fn types_mode(&mut self, mode: TypesMode) {
+		self.options.env_types = mode;
+	}
+
+	/// Sets the standard library to check references against
+	/// If it's `None` then the standard library of the target is used, if any
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{env::LuaSTD, Clue};
+	///
+	/// let mut clue = Clue::new();
+	/// clue.std(Some(LuaSTD::LUA54));
+	///
+	/// // Using a member of the standard library missing in Lua 5.4 is only a warning
+	/// assert!(clue.compile_code("print(unpack({1, 2}))".to_owned()).is_ok());
+	/// ```
+	pub fn std(&mut self, std: Option<LuaSTD>) {
+		self.options.env_std = std;
+	}
 }
 
 impl Clue {
@@ -160,19 +269,19 @@ impl Clue {
 	/// If the code was successfully preprocessed, the [`Result`] will return a [`Code`] containing the preprocessed code
 	///
 	/// # Errors
-	/// If an error occurs while preprocessing the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while preprocessing the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///     let clue = Clue::new();
 	///     let code = clue.preprocess_code("print(\"Hello World!\")".to_owned())?;
 	///
 	///     Ok(())
 	/// }
-	pub fn preprocess_code(&self, code: String) -> Result<Code, String> {
+	pub fn preprocess_code(&self, code: String) -> Result<Code, Vec<Diagnostic>> {
 		let mut code = code;
 		let filename = String::from("(library)");
 		let (codes, variables, ..) = preprocess_code(
@@ -183,7 +292,7 @@ impl Clue {
 			&filename,
 			&self.options,
 		)?;
-		preprocess_codes(0, codes, &variables, &filename)
+		Ok(preprocess_codes(0, codes, &variables, &filename)?)
 	}
 
 	/// Preprocesses the given file
@@ -193,14 +302,14 @@ impl Clue {
 	/// If the code was successfully preprocessed, the [`Result`] will return a [`Code`] containing the preprocessed code
 	///
 	/// # Errors
-	/// If an error occurs while preprocessing the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while preprocessing the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///     let clue = Clue::new();
 	///     let code = clue.preprocess_file("../examples/fizzbuzz.clue")?;
 	///
@@ -210,15 +319,15 @@ impl Clue {
 	pub fn preprocess_file<P: AsRef<Path> + AsRef<OsStr> + Display>(
 		&self,
 		path: P,
-	) -> Result<Code, String> {
+	) -> Result<Code, Vec<Diagnostic>> {
 		let filepath = PathBuf::from(path.to_string());
 		let filename = filepath
 			.file_name()
-			.ok_or_else(|| format!("Invalid path: {}", path))?
+			.ok_or_else(|| invalid_path(&path))?
 			.to_string_lossy()
 			.into_owned();
-		let (codes, variables) = read_file(filepath, &filename, &self.options)?;
-		preprocess_codes(0, codes, &variables, &filename)
+		let (codes, variables, _) = read_file(filepath, &filename, &self.options)?;
+		Ok(preprocess_codes(0, codes, &variables, &filename)?)
 	}
 }
 
@@ -233,17 +342,17 @@ impl Clue {
 	/// If the code was successfully scanned, the [`Result`] will return a [`Vec<Token>`] containing the scanned tokens
 	///
 	/// # Errors
-	/// If an error occurs while scanning the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while scanning the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	pub fn scan_preprocessed_file<P: AsRef<Path> + AsRef<OsStr> + Display>(
 		&self,
 		code: Code,
 		path: P,
-	) -> Result<Vec<Token>, String> {
+	) -> Result<Vec<Token>, Vec<Diagnostic>> {
 		let filepath: &Path = path.as_ref();
 		let filename = filepath
 			.file_name()
-			.ok_or_else(|| format!("Invalid path: {}", path))?
+			.ok_or_else(|| invalid_path(&path))?
 			.to_string_lossy()
 			.into_owned();
 		scan_code(code, &filename)
@@ -257,20 +366,20 @@ impl Clue {
 	/// If the code was successfully scanned, the [`Result`] will return a [`Vec<Token>`] containing the scanned tokens
 	///
 	/// # Errors
-	/// If an error occurs while scanning the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while scanning the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///   let clue = Clue::new();
 	///   let code = clue.preprocess_code("print(\"Hello World!\")".to_owned())?;
 	///   let tokens = clue.scan_preprocessed(code)?;
 	///
 	///   Ok(())
 	/// }
-	pub fn scan_preprocessed(&self, code: Code) -> Result<Vec<Token>, String> {
+	pub fn scan_preprocessed(&self, code: Code) -> Result<Vec<Token>, Vec<Diagnostic>> {
 		scan_code(code, &String::from("(library)"))
 	}
 
@@ -281,23 +390,47 @@ impl Clue {
 	/// If the code was successfully scanned, the [`Result`] will return a [`Vec<Token>`] containing the scanned tokens
 	///
 	/// # Errors
-	/// If an error occurs while scanning the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while scanning the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let tokens = clue.scan_code("print(\"Hello World!\")".to_owned())?;
 	///
 	///   Ok(())
 	/// }
-	pub fn scan_code(&self, code: String) -> Result<Vec<Token>, String> {
+	pub fn scan_code(&self, code: String) -> Result<Vec<Token>, Vec<Diagnostic>> {
 		let code = self.preprocess_code(code)?;
 		self.scan_preprocessed(code)
 	}
 
+	/// Scans the given code for tokens without preprocessing it, keeping its whitespace, comments and directives
+	/// Takes a [`String`] containing the code to scan
+	/// Returns a [`Result`] containing the scanned tokens
+	///
+	/// If the code was successfully scanned, the [`Result`] will return a [`Vec<LosslessToken>`] containing the scanned tokens
+	///
+	/// # Errors
+	/// If an error occurs while scanning the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
+	///
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
+	///    let clue = Clue::new();
+	///    let tokens = clue.scan_code_lossless("print(\"Hello World!\") // hi".to_owned())?;
+	///    assert_eq!(tokens[3].trailing[1].text, "// hi");
+	///
+	///   Ok(())
+	/// }
+	pub fn scan_code_lossless(&self, code: String) -> Result<Vec<LosslessToken>, Vec<Diagnostic>> {
+		scanner::scan_code_lossless(&code, &String::from("(library)"))
+	}
+
 	/// Scans the given file for tokens
 	/// Takes any type that implements [`AsRef<Path>`] and [`AsRef<OsStr>`] and [`Display`] containing the path to the file to scan
 	/// Returns a [`Result`] containing the scanned tokens
@@ -305,13 +438,13 @@ impl Clue {
 	/// If the code was successfully scanned, the [`Result`] will return a [`Vec<Token>`] containing the scanned tokens
 	///
 	/// # Errors
-	/// If an error occurs while scanning the file, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while scanning the file, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///   let clue = Clue::new();
 	///   let tokens = clue.scan_file("../examples/fizzbuzz.clue")?;
 	///
@@ -320,7 +453,7 @@ impl Clue {
 	pub fn scan_file<P: AsRef<Path> + AsRef<OsStr> + Display>(
 		&self,
 		filename: P,
-	) -> Result<Vec<Token>, String> {
+	) -> Result<Vec<Token>, Vec<Diagnostic>> {
 		let code = self.preprocess_file(&filename)?;
 		self.scan_preprocessed_file(code, &filename)
 	}
@@ -334,20 +467,20 @@ impl Clue {
 	/// If the code was successfully parsed, the [`Result`] will return a `(Expression, String)` containing the parsed expression and the static variables
 	///
 	/// # Errors
-	/// If an error occurs while parsing the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while parsing the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///  let clue = Clue::new();
 	///  let code = clue.preprocess_code("print(\"Hello World!\")".to_owned())?;
 	///  let (expression, statics) = clue.parse_preprocessed(code)?;
 	///
 	///  Ok(())
 	/// }
-	pub fn parse_preprocessed(&self, code: Code) -> Result<(Expression, String), String> {
+	pub fn parse_preprocessed(&self, code: Code) -> Result<(Expression, String), Vec<Diagnostic>> {
 		let tokens = self.scan_preprocessed(code)?;
 		self.parse_tokens(tokens)
 	}
@@ -359,21 +492,25 @@ impl Clue {
 	/// If the code was successfully parsed, the [`Result`] will return a `(Expression, String)` containing the parsed expression and the static variables
 	///
 	/// # Errors
-	/// If an error occurs while parsing the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while parsing the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let tokens = clue.scan_code("print(\"Hello World!\")".to_owned())?;
 	///    let (expression, statics) = clue.parse_tokens(tokens)?;
 	///
 	///    Ok(())
 	/// }
-	pub fn parse_tokens(&self, tokens: Vec<Token>) -> Result<(Expression, String), String> {
-		parse_tokens(tokens, &String::from("(library)"), &self.options)
+	pub fn parse_tokens(
+		&self,
+		tokens: Vec<Token>,
+	) -> Result<(Expression, String), Vec<Diagnostic>> {
+		let (expr, statics, _) = parse_tokens(tokens, &String::from("(library)"), &self.options)?;
+		Ok((expr, statics))
 	}
 
 	/// Parses the given code
@@ -383,23 +520,61 @@ impl Clue {
 	/// If the code was successfully parsed, the [`Result`] will return a `(Expression, String)` containing the parsed expression and the static variables
 	///
 	/// # Errors
-	/// If an error occurs while parsing the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while parsing the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///   let clue = Clue::new();
 	///   let (expression, statics) = clue.parse_code("print(\"Hello World!\")".to_owned())?;
 	///
 	///   Ok(())
 	/// }
-	pub fn parse_code(&self, code: String) -> Result<(Expression, String), String> {
+	pub fn parse_code(&self, code: String) -> Result<(Expression, String), Vec<Diagnostic>> {
 		let tokens = self.scan_code(code)?;
 		self.parse_tokens(tokens)
 	}
 
+	/// Returns the [`SymbolTable`] of the given code
+	/// Takes a [`String`] containing the code to analyze
+	///
+	/// The table contains the variables, functions, methods, structs and enum entries declared in the code,
+	/// followed by its preprocessor variables and macros
+	///
+	/// # Errors
+	/// If an error occurs while parsing the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
+	///
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
+	///   let clue = Clue::new();
+	///   let table = clue.symbols("@define NAME \"Clue\"\nglobal greeting = $NAME".to_owned())?;
+	///   assert_eq!(table.get("NAME").unwrap().value.as_deref(), Some("\"Clue\""));
+	///
+	///   Ok(())
+	/// }
+	pub fn symbols(&self, code: String) -> Result<SymbolTable, Vec<Diagnostic>> {
+		let mut code = code;
+		let filename = String::from("(library)");
+		let (codes, variables, ..) = preprocess_code(
+			// SAFETY: This is safe because the preprocessor will never output anything other than UTF-8
+			unsafe { code.as_bytes_mut() },
+			1,
+			false,
+			&filename,
+			&self.options,
+		)?;
+		let code = preprocess_codes(0, codes, &variables, &filename)?;
+		let tokens = scan_code(code, &filename)?;
+		let (_, _, mut table, _) = parse_tokens_with_symbols(tokens, &filename, &self.options)?;
+		table.add_ppvars(&variables);
+		Ok(table)
+	}
+
 	/// Parses the given file
 	/// Takes any type that implements [`AsRef<Path>`] and [`AsRef<OsStr>`] and [`Display`] containing the path to the file to parse
 	/// Returns a [`Result`] containing the parsed expression
@@ -407,13 +582,13 @@ impl Clue {
 	/// If the code was successfully parsed, the [`Result`] will return a `(Expression, String)` containing the parsed expression and the static variables
 	///
 	/// # Errors
-	/// If an error occurs while parsing the file, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while parsing the file, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///   let clue = Clue::new();
 	///   let (expression, statics) = clue.parse_file("../examples/fizzbuzz.clue")?;
 	///
@@ -422,16 +597,17 @@ impl Clue {
 	pub fn parse_file<P: AsRef<Path> + AsRef<OsStr> + Display>(
 		&self,
 		path: P,
-	) -> Result<(Expression, String), String> {
+	) -> Result<(Expression, String), Vec<Diagnostic>> {
 		let filepath: &Path = path.as_ref();
 		let filename = filepath
 			.file_name()
-			.ok_or_else(|| format!("Invalid path: {}", path))?
+			.ok_or_else(|| invalid_path(&path))?
 			.to_string_lossy()
 			.into_owned();
 		let tokens = self.scan_file(&path)?;
 
-		parse_tokens(tokens, &filename, &self.options)
+		let (expr, statics, _) = parse_tokens(tokens, &filename, &self.options)?;
+		Ok((expr, statics))
 	}
 }
 
@@ -443,26 +619,59 @@ impl Clue {
 	/// If the code was successfully compiled, the [`Result`] will return a [`String`] containing the compiled code
 	///
 	/// # Errors
-	/// If an error occurs while compiling the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while compiling the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rustrust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let tokens = clue.scan_code("print(\"Hello World!\")".to_owned())?;
 	///    let code = clue.compile_tokens(tokens)?;
 	///
 	///    Ok(())
 	/// }
-	pub fn compile_tokens(&self, tokens: Vec<Token>) -> Result<String, String> {
+	pub fn compile_tokens(&self, tokens: Vec<Token>) -> Result<String, Vec<Diagnostic>> {
 		let (ctokens, statics) = self.parse_tokens(tokens)?;
 		let filename = String::from("(library)");
 		let compiler = Compiler::new(&self.options, &filename);
 		Ok(statics + &compiler.compile_tokens(0, ctokens)?)
 	}
 
+	/// Compiles the given [`Vec`] of [`Token`] only to find its problems
+	/// Takes a [`Vec<Token>`] containing the tokens to check
+	/// Returns the errors found, or the warnings of the global and type checks if there are no errors
+	///
+	/// The other methods only return the errors, so this is how the warnings can be read
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{diagnostic::Diagnostic, env::TypesMode, Clue};
+	///
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
+	///    let mut clue = Clue::new();
+	///    clue.types_mode(TypesMode::WARN);
+	///    let tokens = clue.scan_code("local x: number = \"one\"".to_owned())?;
+	///    let diagnostics = clue.check_tokens(tokens);
+	///    assert_eq!(diagnostics.len(), 1);
+	///    assert!(!diagnostics[0].is_error());
+	///
+	///    Ok(())
+	/// }
+	pub fn check_tokens(&self, tokens: Vec<Token>) -> Vec<Diagnostic> {
+		let filename = String::from("(library)");
+		match parse_tokens(tokens, &filename, &self.options) {
+			Ok((ctokens, _, warnings)) => {
+				match Compiler::new(&self.options, &filename).compile_tokens(0, ctokens) {
+					Ok(_) => warnings,
+					Err(error) => vec![*error],
+				}
+			}
+			Err(errors) => errors,
+		}
+	}
+
 	/// Compiles the given preprocessed code
 	/// Takes a [`Code`] containing the preprocessed code to compile
 	/// Returns a [`Result`] containing the compiled code
@@ -470,20 +679,20 @@ impl Clue {
 	/// If the code was successfully compiled, the [`Result`] will return a [`String`] containing the compiled code
 	///
 	/// # Errors
-	/// If an error occurs while compiling the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while compiling the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///     let clue = Clue::new();
 	///     let code = clue.preprocess_code("print(\"Hello World!\")".to_owned())?;
 	///     let compiled = clue.compile_preprocessed(code)?;
 	///
 	///     Ok(())
 	/// }
-	pub fn compile_preprocessed(&self, code: Code) -> Result<String, String> {
+	pub fn compile_preprocessed(&self, code: Code) -> Result<String, Vec<Diagnostic>> {
 		let tokens = self.scan_preprocessed(code)?;
 		self.compile_tokens(tokens)
 	}
@@ -495,20 +704,23 @@ impl Clue {
 	/// If the code was successfully compiled, the [`Result`] will return a [`String`] containing the compiled code
 	///
 	/// # Errors
-	/// If an error occurs while compiling the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while compiling the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let parse_result = clue.parse_code("print(\"Hello World!\")".to_owned())?;
 	///    let code = clue.compile_ast(parse_result)?;
 	///
 	///    Ok(())
 	/// }
-	pub fn compile_ast(&self, (ctokens, statics): (Expression, String)) -> Result<String, String> {
+	pub fn compile_ast(
+		&self,
+		(ctokens, statics): (Expression, String),
+	) -> Result<String, Vec<Diagnostic>> {
 		let filename = String::from("(library)");
 		let compiler = Compiler::new(&self.options, &filename);
 		Ok(statics + &compiler.compile_tokens(0, ctokens)?)
@@ -521,23 +733,54 @@ impl Clue {
 	/// If the code was successfully compiled, the [`Result`] will return a [`String`] containing the compiled code
 	///
 	/// # Errors
-	/// If an error occurs while compiling the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while compiling the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let code = clue.compile_code("print(\"Hello World!\")".to_owned())?;
 	///
 	///    Ok(())
 	/// }
-	pub fn compile_code(&self, code: String) -> Result<String, String> {
+	pub fn compile_code(&self, code: String) -> Result<String, Vec<Diagnostic>> {
 		let tokens = self.scan_code(code)?;
 		self.compile_tokens(tokens)
 	}
 
+	/// Compiles the given code and builds a [`SourceMap`] of the output
+	/// Takes a [`String`] containing the code to compile
+	/// Returns a [`Result`] containing the compiled code and its [`SourceMap`]
+	///
+	/// # Errors
+	/// If an error occurs while compiling the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
+	///
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
+	///    let clue = Clue::new();
+	///    let (code, map) = clue.compile_code_with_source_map("print(\"Hello World!\")".to_owned())?;
+	///    let json = map.to_json("main.lua");
+	///
+	///    Ok(())
+	/// }
+	pub fn compile_code_with_source_map(
+		&self,
+		code: String,
+	) -> Result<(String, SourceMap), Vec<Diagnostic>> {
+		let tokens = self.scan_code(code)?;
+		let (ctokens, statics) = self.parse_tokens(tokens)?;
+		let filename = String::from("(library)");
+		let compiler = Compiler::new(&self.options, &filename);
+		let (code, mut sourcemap) = compiler.compile_tokens_with_source_map(0, ctokens)?;
+		sourcemap.move_after(&statics);
+		Ok((statics + &code, sourcemap))
+	}
+
 	/// Compiles the given file
 	/// Takes any type that implements [`AsRef<Path>`] and [`AsRef<OsStr>`] and [`Display`] containing the path to the file to compile
 	/// Returns a [`Result`] containing the compiled code
@@ -545,13 +788,13 @@ impl Clue {
 	/// If the code was successfully compiled, the [`Result`] will return a [`String`] containing the compiled code
 	///
 	/// # Errors
-	/// If an error occurs while compiling the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while compiling the code, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let code = clue.compile_file("../examples/fizzbuzz.clue")?;
 	///
@@ -560,21 +803,58 @@ impl Clue {
 	pub fn compile_file<P: AsRef<Path> + AsRef<OsStr> + Display>(
 		&self,
 		path: P,
-	) -> Result<String, String> {
+	) -> Result<String, Vec<Diagnostic>> {
 		let tokens = self.scan_file(&path)?;
 		let result = self.compile_tokens(tokens)?;
 		if self.options.env_output {
-			fs::write(path, &result).map_err(|e| e.to_string())?;
+			fs::write(&path, &result).map_err(|e| {
+				Diagnostic::error("io", e.to_string(), path.to_string(), Span::default())
+			})?;
 		}
 		Ok(result)
 	}
+
+	/// Compiles every file of the given directory in parallel
+	/// Takes any type that implements [`AsRef<Path>`] containing the path to the directory to compile
+	/// Returns a [`Result`] containing the [`ProjectOutput`]
+	///
+	/// The [`ProjectOutput`] contains the code and statics of every module and the errors of the files that failed to compile
+	///
+	/// # Errors
+	/// If the directory can't be read, an [`Err`] containing a [`Diagnostic`] will be returned
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
+	///
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
+	///    let clue = Clue::new();
+	///    let output = clue.compile_project("../examples/")?;
+	///    assert!(output.is_ok());
+	///    assert!(output.modules.iter().any(|module| module.name == "fizzbuzz"));
+	///
+	///    Ok(())
+	/// }
+	/// ```
+	pub fn compile_project<P: AsRef<Path>>(
+		&self,
+		path: P,
+	) -> Result<ProjectOutput, Vec<Diagnostic>> {
+		project::compile_project(
+			path,
+			String::new(),
+			self.options.clone(),
+			project::compile_code,
+		)
+		.map_err(Vec::from)
+	}
 }
 
 /// Creates a new [`Clue`] instance with the default options
 ///
 /// # Example
 /// ```rust
-/// use clue_core::Clue;
+/// use clue_core::{diagnostic::Diagnostic, Clue};
 ///
 /// let clue = Clue::default();
 /// ```
@@ -0,0 +1,644 @@
+//! The `linter` module contains the linter, which looks for code that compiles but is most likely
+//! a mistake, like locals that are never used or code that can never run.
+//!
+//! Every rule is named like the code of the [`Diagnostic`]s it reports and can be turned off with
+//! [`LintRules`], which `clue lint` reads from a `cluelint.toml` file, or with comments in the code:
+//! - `// clue-lint-disable <rules>` disables the rules until a `// clue-lint-enable <rules>`
+//! - `// clue-lint-disable-next-line <rules>` disables the rules for the next line only
+//! - `// clue-lint-disable-line <rules>` disables the rules for the line of the comment only
+//!
+//! The rules are separated by spaces or commas, and every rule is affected if none is given.
+
+use crate::diagnostic::{Diagnostic, Position, Span};
+use crate::env::Options;
+use crate::parser::{
+	expr_span, parse_tokens_with_symbols, walk_expression, walk_token, CodeBlock, ComplexToken,
+	ComplexToken::*, Expression, FunctionArgs, Visitor,
+};
+use crate::preprocessor::{preprocess_code, preprocess_codes};
+use crate::scanner::{scan_code, scan_code_lossless, TokenType::DEFINE, TriviaKind};
+use crate::symbols::{SymbolKind, SymbolTable};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The names of every rule of the linter
+pub const RULES: [&str; 7] = [
+	"unused-local",
+	"shadowed-local",
+	"unreachable-code",
+	"duplicate-case",
+	"empty-catch",
+	"enum-assignment",
+	"nil-comparison",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+	feature = "serde",
+	serde(default, deny_unknown_fields, rename_all = "kebab-case")
+)]
+/// Which rules the linter checks, all of them are enabled by default
+/// The fields are named like the keys of a `cluelint.toml` file
+pub struct LintRules {
+	/// Locals and local functions that are never used
+	pub unused_local: bool,
+
+	/// Locals with the same name as another variable that is still visible
+	pub shadowed_local: bool,
+
+	/// Code after a `return`, `break` or `continue`
+	pub unreachable_code: bool,
+
+	/// Cases of a `match` block that were already matched by a previous case
+	pub duplicate_case: bool,
+
+	/// `catch` blocks that silently ignore the error
+	pub empty_catch: bool,
+
+	/// Assignments to the entries of an enum
+	pub enum_assignment: bool,
+
+	/// Comparisons with `nil` using `==` or `!=` that could use `?` instead
+	pub nil_comparison: bool,
+}
+
+impl Default for LintRules {
+	fn default() -> Self {
+		Self {
+			unused_local: true,
+			shadowed_local: true,
+			unreachable_code: true,
+			duplicate_case: true,
+			empty_catch: true,
+			enum_assignment: true,
+			nil_comparison: true,
+		}
+	}
+}
+
+impl LintRules {
+	/// Returns whether the given rule is enabled, unknown rules are never enabled
+	pub fn is_enabled(&self, rule: &str) -> bool {
+		match rule {
+			"unused-local" => self.unused_local,
+			"shadowed-local" => self.shadowed_local,
+			"unreachable-code" => self.unreachable_code,
+			"duplicate-case" => self.duplicate_case,
+			"empty-catch" => self.empty_catch,
+			"enum-assignment" => self.enum_assignment,
+			"nil-comparison" => self.nil_comparison,
+			_ => false,
+		}
+	}
+
+	/// Enables or disables the given rule, returning `false` if it doesn't exist
+	pub fn set(&mut self, rule: &str, enabled: bool) -> bool {
+		let rule = match rule {
+			"unused-local" => &mut self.unused_local,
+			"shadowed-local" => &mut self.shadowed_local,
+			"unreachable-code" => &mut self.unreachable_code,
+			"duplicate-case" => &mut self.duplicate_case,
+			"empty-catch" => &mut self.empty_catch,
+			"enum-assignment" => &mut self.enum_assignment,
+			"nil-comparison" => &mut self.nil_comparison,
+			_ => return false,
+		};
+		*rule = enabled;
+		true
+	}
+}
+
+struct Local<'a> {
+	name: &'a str,
+	span: Span,
+	used: bool,
+	reportable: bool,
+	constant: bool,
+}
+
+struct Linter<'a> {
+	filename: &'a str,
+	rules: &'a LintRules,
+	enum_entries: Vec<Position>,
+	constants: Vec<&'a str>,
+	scopes: Vec<Vec<Local<'a>>>,
+	diagnostics: Vec<Diagnostic>,
+}
+
+fn case_key(expr: &Expression) -> Option<String> {
+	let mut key = String::new();
+	for t in expr {
+		match t {
+			SYMBOL(lexeme, _) => key += lexeme,
+			IDENT { expr, .. } => key += &case_key(expr)?,
+			_ => return None,
+		}
+	}
+	Some(key)
+}
+
+impl<'a> Linter<'a> {
+	fn report(&mut self, rule: &str, message: String, span: Span) {
+		self.report_with_note(rule, message, span, None)
+	}
+
+	fn report_with_note(&mut self, rule: &str, message: String, span: Span, note: Option<&str>) {
+		if self.rules.is_enabled(rule) {
+			let diagnostic = Diagnostic::warning(rule, message, self.filename, span);
+			self.diagnostics.push(match note {
+				Some(note) => diagnostic.with_note(note),
+				None => diagnostic,
+			});
+		}
+	}
+
+	fn lookup(&mut self, name: &str) -> Option<&mut Local<'a>> {
+		self.scopes
+			.iter_mut()
+			.rev()
+			.find_map(|scope| scope.iter_mut().rev().find(|local| local.name == name))
+	}
+
+	fn use_name(&mut self, name: &str) {
+		if let Some(local) = self.lookup(name) {
+			local.used = true;
+		}
+	}
+
+	fn declare(&mut self, name: &'a str, span: Span, reportable: bool, constant: bool) {
+		if name == "..." {
+			return;
+		}
+		let hidden = !name.starts_with('_');
+		if hidden && self.lookup(name).is_some() {
+			self.report(
+				"shadowed-local",
+				format!("'{name}' shadows a variable with the same name"),
+				span,
+			);
+		}
+		if let Some(scope) = self.scopes.last_mut() {
+			scope.push(Local {
+				name,
+				span,
+				used: false,
+				reportable: reportable && hidden,
+				constant,
+			});
+		}
+	}
+
+	fn is_constant(&mut self, name: &str) -> bool {
+		match self.lookup(name) {
+			Some(local) => local.constant,
+			None => self.constants.contains(&name),
+		}
+	}
+
+	fn pop_scope(&mut self) {
+		if let Some(scope) = self.scopes.pop() {
+			for local in scope {
+				if local.reportable && !local.used {
+					self.report(
+						"unused-local",
+						format!("'{}' is never used", local.name),
+						local.span,
+					);
+				}
+			}
+		}
+	}
+
+	fn lint_block(&mut self, block: &'a CodeBlock, declared: &[(&'a str, Span)]) {
+		self.scopes.push(Vec::new());
+		for (name, span) in declared {
+			self.declare(name, *span, false, false);
+		}
+		self.lint_statements(&block.code);
+		self.pop_scope();
+	}
+
+	fn lint_function(&mut self, args: &'a FunctionArgs, code: &'a CodeBlock, span: Span) {
+		self.scopes.push(Vec::new());
+		for (arg, default) in args {
+			if let Some((default, _)) = default {
+				self.visit_expression(default);
+			}
+			self.declare(arg, span, false, false);
+		}
+		self.visit_block(code);
+		self.pop_scope();
+	}
+
+	fn lint_statements(&mut self, code: &'a Expression) {
+		let mut exit = None;
+		for t in code {
+			if let Some(keyword) = exit.take() {
+				self.report(
+					"unreachable-code",
+					format!("This code can never run because of the '{keyword}' before it"),
+					t.span(),
+				);
+			}
+			self.visit_token(t);
+			exit = match t {
+				RETURN_EXPR(..) => Some("return"),
+				BREAK_LOOP(_) => Some("break"),
+				CONTINUE_LOOP(_) => Some("continue"),
+				_ => None,
+			};
+		}
+	}
+
+	fn lint_name(&mut self, name: &'a Expression) {
+		if let Some(SYMBOL(base, _)) = name.front() {
+			self.use_name(base);
+		}
+		self.visit_expression(name);
+	}
+
+	fn lint_cases(&mut self, conditions: &'a [Expression], seen: &mut Vec<String>) {
+		for condition in conditions {
+			let Some(key) = case_key(condition) else {
+				continue;
+			};
+			if seen.contains(&key) {
+				self.report(
+					"duplicate-case",
+					format!("'{key}' was already matched by a previous case"),
+					expr_span(condition),
+				);
+			} else {
+				seen.push(key);
+			}
+		}
+	}
+}
+
+impl<'a> Visitor<'a> for Linter<'a> {
+	fn visit_expression(&mut self, expr: &'a Expression) {
+		for (a, b) in expr.iter().zip(expr.iter().skip(1)) {
+			if let (SYMBOL(a, span), SYMBOL(b, _)) = (a, b) {
+				let operator = match (a.trim(), b.trim()) {
+					(operator @ ("==" | "~="), "nil") | ("nil", operator @ ("==" | "~=")) => {
+						operator
+					}
+					_ => continue,
+				};
+				let operator = if operator == "~=" { "!=" } else { "==" };
+				self.report_with_note(
+					"nil-comparison",
+					format!("Comparison with nil using '{operator}'"),
+					*span,
+					Some(
+						"use '?.', '?::', '?[' or '??' to handle nil values, or '!' to check them",
+					),
+				);
+			}
+		}
+		walk_expression(self, expr)
+	}
+
+	fn visit_token(&mut self, t: &'a ComplexToken) {
+		match t {
+			VARIABLE {
+				local,
+				names,
+				values,
+				span,
+				..
+			} => {
+				walk_token(self, t);
+				if *local {
+					let constant = self.enum_entries.contains(&span.start);
+					// the constructor arguments renamed for the defaults of the fields are arguments too
+					let restored = !values.is_empty()
+						&& values.iter().all(|value| {
+							matches!(value.iter().collect::<Vec<_>>()[..],
+							[SYMBOL(name, _)] if name.starts_with("_internal"))
+						});
+					for name in names {
+						self.declare(name, *span, !constant && !restored, constant);
+					}
+				}
+			}
+			ALTER {
+				kind,
+				names,
+				values,
+				span,
+				..
+			} => {
+				for name in names {
+					match name.front() {
+						Some(SYMBOL(variable, _)) if name.len() == 1 => {
+							if *kind != DEFINE {
+								self.use_name(variable);
+							}
+							if self.is_constant(variable) {
+								self.report(
+									"enum-assignment",
+									format!(
+										"'{variable}' is an enum entry and should not be changed"
+									),
+									*span,
+								);
+							}
+						}
+						_ => self.lint_name(name),
+					}
+				}
+				values.iter().for_each(|value| self.visit_expression(value));
+			}
+			FUNCTION {
+				local,
+				name,
+				args,
+				code,
+				span,
+				..
+			} => {
+				match name.front() {
+					Some(SYMBOL(function, _)) if *local && name.len() == 1 => {
+						self.declare(function, *span, true, false)
+					}
+					_ => self.lint_name(name),
+				}
+				self.lint_function(args, code, *span);
+			}
+			LAMBDA {
+				args, code, span, ..
+			} => self.lint_function(args, code, *span),
+			MATCH_BLOCK {
+				value, branches, ..
+			} => {
+				self.visit_expression(value);
+				let mut seen = Vec::new();
+				for case in branches {
+					let (conditions, _, extraif, _) = case;
+					if extraif.is_none() {
+						self.lint_cases(conditions, &mut seen)
+					}
+					self.visit_match_case(case)
+				}
+			}
+			LOOP_UNTIL {
+				condition, code, ..
+			} => {
+				self.scopes.push(Vec::new());
+				self.lint_statements(&code.code);
+				self.visit_expression(condition);
+				self.pop_scope();
+			}
+			FOR_LOOP {
+				iterator,
+				start,
+				end,
+				alter,
+				code,
+				span,
+				..
+			} => {
+				self.visit_expression(start);
+				self.visit_expression(end);
+				self.visit_expression(alter);
+				self.lint_block(code, &[(iterator, *span)]);
+			}
+			FOR_FUNC_LOOP {
+				iterators,
+				expr,
+				code,
+				span,
+				..
+			} => {
+				self.visit_expression(expr);
+				let iterators: Vec<(&str, Span)> = iterators
+					.iter()
+					.map(|iterator| (iterator.as_str(), *span))
+					.collect();
+				self.lint_block(code, &iterators);
+			}
+			TRY_CATCH {
+				totry,
+				catch,
+				error,
+				span,
+			} => {
+				self.visit_block(totry);
+				if let Some(catch) = catch {
+					if catch.code.is_empty() {
+						self.report(
+							"empty-catch",
+							String::from(
+								"This catch block is empty, so errors are silently ignored",
+							),
+							*span,
+						);
+					}
+					match error {
+						Some(error) => self.lint_block(catch, &[(error, *span)]),
+						None => self.visit_block(catch),
+					}
+				}
+			}
+			IDENT { expr, .. } => self.lint_name(expr),
+			SYMBOL(lexeme, _) => self.use_name(lexeme),
+			_ => walk_token(self, t),
+		}
+	}
+
+	fn visit_block(&mut self, block: &'a CodeBlock) {
+		self.lint_block(block, &[])
+	}
+}
+
+/// Lints the given AST, returning a warning for every problem found by the enabled rules, sorted by position
+/// `symbols` must be the [`SymbolTable`] of the same code, which is used to find the entries of enums
+///
+/// # Example
+/// ```rust
+/// use clue_core::{diagnostic::Diagnostic, linter::*, Clue};
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let clue = Clue::new();
+///     let code = "local fn f() {\n\tlocal x = 1\n\twhile true {\n\t\tbreak\n\t\tprint(x)\n\t}\n}\nf()";
+///     let symbols = clue.symbols(code.to_owned())?;
+///     let (expr, _) = clue.parse_code(code.to_owned())?;
+///
+///     let diagnostics = lint(&expr, &symbols, "main.clue", &LintRules::default());
+///     let codes: Vec<&str> = diagnostics.iter().map(|d| d.code.as_str()).collect();
+///     assert_eq!(codes, ["unreachable-code"]);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn lint(
+	expr: &Expression,
+	symbols: &SymbolTable,
+	filename: &str,
+	rules: &LintRules,
+) -> Vec<Diagnostic> {
+	let entries = symbols
+		.symbols
+		.iter()
+		.filter(|symbol| symbol.kind == SymbolKind::EnumEntry);
+	let mut linter = Linter {
+		filename,
+		rules,
+		enum_entries: entries.clone().map(|symbol| symbol.span.start).collect(),
+		constants: entries
+			.filter(|symbol| !symbol.local || symbol.is_static)
+			.map(|symbol| symbol.name.as_str())
+			.collect(),
+		scopes: vec![Vec::new()],
+		diagnostics: Vec::new(),
+	};
+	linter.lint_statements(expr);
+	linter.pop_scope();
+	sort_diagnostics(&mut linter.diagnostics);
+	linter.diagnostics
+}
+
+fn sort_diagnostics(diagnostics: &mut [Diagnostic]) {
+	diagnostics
+		.sort_by_key(|diagnostic| (diagnostic.span.start.line, diagnostic.span.start.column));
+}
+
+#[derive(PartialEq, Eq)]
+enum InlineKind {
+	Disable,
+	Enable,
+	DisableNextLine,
+	DisableLine,
+}
+
+struct InlineRules {
+	kind: InlineKind,
+	line: usize,
+	rules: Vec<String>,
+}
+
+fn read_inline_rules(
+	code: &str,
+	filename: &String,
+	diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<InlineRules>, Vec<Diagnostic>> {
+	let mut comments = Vec::new();
+	for token in scan_code_lossless(code, filename)? {
+		let trivia = token.leading.iter().chain(token.trailing.iter());
+		for comment in trivia.filter(|trivia| trivia.kind == TriviaKind::Comment) {
+			let text = comment.text.trim();
+			let text = match text.strip_prefix("/*") {
+				Some(text) => text.strip_suffix("*/").unwrap_or(text),
+				None => text.trim_start_matches('/'),
+			};
+			let text = text.trim();
+			let Some(text) = text.strip_prefix("clue-lint-") else {
+				continue;
+			};
+			let (kind, text) = if let Some(text) = text.strip_prefix("disable-next-line") {
+				(InlineKind::DisableNextLine, text)
+			} else if let Some(text) = text.strip_prefix("disable-line") {
+				(InlineKind::DisableLine, text)
+			} else if let Some(text) = text.strip_prefix("disable") {
+				(InlineKind::Disable, text)
+			} else if let Some(text) = text.strip_prefix("enable") {
+				(InlineKind::Enable, text)
+			} else {
+				continue;
+			};
+			if !text.is_empty() && !text.starts_with(char::is_whitespace) {
+				continue;
+			}
+			let rules: Vec<String> = text
+				.split(|c: char| c == ',' || c.is_whitespace())
+				.filter(|rule| !rule.is_empty())
+				.map(String::from)
+				.collect();
+			for rule in &rules {
+				if !RULES.contains(&rule.as_str()) {
+					diagnostics.push(Diagnostic::warning(
+						"unknown-lint-rule",
+						format!("Unknown lint rule '{rule}'"),
+						filename,
+						comment.span,
+					));
+				}
+			}
+			comments.push(InlineRules {
+				kind,
+				line: comment.span.start.line,
+				rules,
+			});
+		}
+	}
+	Ok(comments)
+}
+
+fn is_enabled_at(rules: &LintRules, comments: &[InlineRules], rule: &str, line: usize) -> bool {
+	let mut enabled = rules.is_enabled(rule);
+	for comment in comments {
+		let applies = match comment.kind {
+			InlineKind::Disable | InlineKind::Enable => comment.line <= line,
+			InlineKind::DisableNextLine => comment.line + 1 == line,
+			InlineKind::DisableLine => comment.line == line,
+		};
+		if applies && (comment.rules.is_empty() || comment.rules.iter().any(|r| r == rule)) {
+			enabled = comment.kind == InlineKind::Enable;
+		}
+	}
+	enabled
+}
+
+/// Preprocesses, parses and lints the given code, skipping the rules disabled by its comments
+///
+/// # Errors
+/// If the code can't be compiled, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
+///
+/// # Example
+/// ```rust
+/// use clue_core::{diagnostic::Diagnostic, env::Options, linter::*};
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let filename = String::from("main.clue");
+///     let code = "local a = 1\nlocal b = 2 // clue-lint-disable-line unused-local\nif c == nil {}";
+///
+///     let diagnostics = lint_code(code, &filename, &LintRules::default(), &Options::default())?;
+///     let codes: Vec<&str> = diagnostics.iter().map(|d| d.code.as_str()).collect();
+///     assert_eq!(codes, ["unused-local", "nil-comparison"]);
+///     assert_eq!(diagnostics[0].span.start.line, 1);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn lint_code(
+	code: &str,
+	filename: &String,
+	rules: &LintRules,
+	options: &Options,
+) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+	let mut diagnostics = Vec::new();
+	let comments = read_inline_rules(code, filename, &mut diagnostics)?;
+	let mut code = code.to_owned();
+	let (codes, variables, ..) = preprocess_code(
+		// SAFETY: This is safe because the preprocessor will never output anything other than UTF-8
+		unsafe { code.as_bytes_mut() },
+		1,
+		false,
+		filename,
+		options,
+	)?;
+	let code = preprocess_codes(0, codes, &variables, filename)?;
+	let tokens = scan_code(code, filename)?;
+	let (expr, _, symbols, mut warnings) = parse_tokens_with_symbols(tokens, filename, options)?;
+	diagnostics.append(&mut warnings);
+	diagnostics.extend(
+		lint(&expr, &symbols, filename, &LintRules::default())
+			.into_iter()
+			.filter(|d| is_enabled_at(rules, &comments, &d.code, d.span.start.line)),
+	);
+	sort_diagnostics(&mut diagnostics);
+	Ok(diagnostics)
+}
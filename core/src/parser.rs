@@ -7,12 +7,18 @@
 #![allow(non_camel_case_types)]
 
 use self::ComplexToken::*;
+use crate::checker::check_types;
 use crate::compiler::Compiler;
-use crate::env::{BitwiseMode, ContinueMode, LuaVersion, Options};
+use crate::diagnostic::{Diagnostic, Position, Span};
+use crate::env::{BitwiseMode, ContinueMode, LuaSTD, LuaVersion, Options};
+use crate::format_clue;
 use crate::scanner::{BorrowedToken, TokenType::*};
 use crate::scanner::{Token, TokenType};
-use crate::{check, format_clue};
+use crate::stdlib;
+use crate::symbols::{Symbol, SymbolKind, SymbolTable};
+use ahash::AHashSet;
 use std::cell::Cell;
+use std::fmt;
 use std::vec;
 use std::{cmp, collections::VecDeque};
 
@@ -40,8 +46,15 @@ pub type Expression = VecDeque<ComplexToken>;
 /// used in function signatures.
 pub type FunctionArgs = Vec<(String, Option<(Expression, usize)>)>;
 
-//pub type LocalsList = Option<AHashMap<String, LuaType>>;
-//pub type ArgsAndTypes = (FunctionArgs, Option<Vec<(String, LuaType)>>);
+/// The type annotations of a function: the types of its arguments, in the same order as
+/// [`FunctionArgs`], and the type it returns.
+pub type FunctionTypes = (Vec<Option<LuaType>>, Option<LuaType>);
+
+/// Function arguments together with their type annotations.
+type ArgsAndTypes = (FunctionArgs, FunctionTypes);
+
+/// A list of identifiers together with their optional type annotations.
+type TypedIdentifiers = (Vec<String>, Vec<Option<LuaType>>);
 
 /// An optional end token, which is used to check if the end token is present.
 /// It is a tuple of the token type and the token lexeme.
@@ -54,14 +67,19 @@ type OptionalEnd = Option<(TokenType, &'static str)>;
 ///   1 if z == 0 => {foo()},
 /// }
 /// ```
-/// the first element of the tuple would be `1`, the second element would be `z == 0`
-/// and the third element would be `{foo()}`.
-type MatchCase = (Vec<Expression>, Expression, Option<Expression>, CodeBlock);
+/// the first element of the tuple would be `1`, the third element would be `z == 0`
+/// and the fourth element would be `{foo()}`.
+/// The second element is the code the parser generated to compute the things to match, if any.
+pub type MatchCase = (Vec<Expression>, Expression, Option<Expression>, CodeBlock);
 
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// An enum representing all the possible complex tokens that can be parsed
 /// such as functions, if statements, variables, tables etc.
+///
+/// Every complex token carries the [`Span`] of the code it was parsed from, in the file given to
+/// [`parse_tokens`]. Code generated by the parser, like the internal variables of `a ? b : c`,
+/// uses the span of the code that caused it to be generated.
 pub enum ComplexToken {
 	/// A variable declaration.
 	VARIABLE {
@@ -74,8 +92,14 @@ pub enum ComplexToken {
 		/// The values of the variable(s).
 		values: Vec<Expression>,
 
+		/// The type annotations of the variable(s), empty if none of them has one.
+		types: Vec<Option<LuaType>>,
+
 		/// The line number of the variable declaration.
 		line: usize,
+
+		/// Where the variable declaration is in the source code.
+		span: Span,
 	},
 
 	/// An assignment to a variable or a list of variables.
@@ -91,6 +115,9 @@ pub enum ComplexToken {
 
 		/// The line number of the assignment.
 		line: usize,
+
+		/// Where the assignment is in the source code.
+		span: Span,
 	},
 
 	/// A table.
@@ -103,6 +130,9 @@ pub enum ComplexToken {
 
 		/// the table's metatable.
 		metatable: Option<String>,
+
+		/// Where the table is in the source code.
+		span: Span,
 	},
 
 	/// A function declaration.
@@ -116,8 +146,14 @@ pub enum ComplexToken {
 		/// The arguments of the function.
 		args: FunctionArgs,
 
+		/// The type annotations of the function.
+		types: FunctionTypes,
+
 		/// The code block of the function.
 		code: CodeBlock,
+
+		/// Where the function is in the source code.
+		span: Span,
 	},
 
 	/// A lambda function.
@@ -125,8 +161,14 @@ pub enum ComplexToken {
 		/// The arguments of the function.
 		args: FunctionArgs,
 
+		/// The type annotations of the function.
+		types: FunctionTypes,
+
 		/// The code block of the function.
 		code: CodeBlock,
+
+		/// Where the lambda function is in the source code.
+		span: Span,
 	},
 
 	/// An if statement.
@@ -139,6 +181,9 @@ pub enum ComplexToken {
 
 		/// The next elseif/else statement.
 		next: Option<Box<ComplexToken>>,
+
+		/// Where the if statement is in the source code.
+		span: Span,
 	},
 
 	/// A match statement.
@@ -154,6 +199,9 @@ pub enum ComplexToken {
 
 		/// The line number of the match statement.
 		line: usize,
+
+		/// Where the match statement is in the source code.
+		span: Span,
 	},
 
 	/// A while loop.
@@ -166,6 +214,9 @@ pub enum ComplexToken {
 
 		/// The line number of the while loop.
 		line: usize,
+
+		/// Where the while loop is in the source code.
+		span: Span,
 	},
 
 	/// An until loop.
@@ -178,6 +229,9 @@ pub enum ComplexToken {
 
 		/// The line number of the loop.
 		line: usize,
+
+		/// Where the loop is in the source code.
+		span: Span,
 	},
 
 	/// A for loop over a range of number e.g. `for i = 0, 10, 1 {...}`.
@@ -199,6 +253,9 @@ pub enum ComplexToken {
 
 		/// The line number of the for loop.
 		line: usize,
+
+		/// Where the for loop is in the source code.
+		span: Span,
 	},
 
 	/// A for loop over a some iterator which can be either a for..in loop, a for..of loop or a for..with loop.
@@ -217,18 +274,24 @@ pub enum ComplexToken {
 
 		/// The line number of the for loop.
 		line: usize,
+
+		/// Where the for loop is in the source code.
+		span: Span,
 	},
 
 	/// A try catch block.
 	TRY_CATCH {
 		/// The code block of the try block.
 		totry: CodeBlock,
-		
+
 		/// An optional code block of the catch block.
 		catch: Option<CodeBlock>,
-		
+
 		/// The name of the error variable in the catch block.
 		error: Option<String>,
+
+		/// Where the try catch block is in the source code.
+		span: Span,
 	},
 
 	/// An identifier.
@@ -238,28 +301,94 @@ pub enum ComplexToken {
 
 		/// The line number of the identifier.
 		line: usize,
+
+		/// Where the identifier is in the source code.
+		span: Span,
 	},
 
-	/// Any symbol.
-	SYMBOL(String),
+	/// Any symbol and where it is in the source code.
+	SYMBOL(String, Span),
+
+	/// A function call and where its arguments are in the source code.
+	CALL(Vec<Expression>, Span),
+
+	/// An expression and where it is in the source code.
+	EXPR(Expression, Span),
+
+	/// A do block and where it is in the source code.
+	DO_BLOCK(CodeBlock, Span),
 
-	/// A function call.
-	CALL(Vec<Expression>),
+	/// A return statement and where it is in the source code.
+	RETURN_EXPR(Option<Vec<Expression>>, Span),
 
-	/// An expression.
-	EXPR(Expression),
+	/// A global provided by the host environment, which produces no output.
+	EXTERN_GLOBAL {
+		/// The name of the global.
+		name: String,
+
+		/// The arguments of the global if it was declared as a function.
+		args: Option<FunctionArgs>,
+
+		/// The line number of the declaration.
+		line: usize,
 
-	/// A do block.
-	DO_BLOCK(CodeBlock),
+		/// Where the declaration is in the source code.
+		span: Span,
+	},
 
-	/// A return statement.
-	RETURN_EXPR(Option<Vec<Expression>>),
+	/// A continue keyword and where it is in the source code.
+	CONTINUE_LOOP(Span),
 
-	/// A continue keyword.
-	CONTINUE_LOOP,
+	/// A break keyword and where it is in the source code.
+	BREAK_LOOP(Span),
+}
 
-	/// A break keyword.
-	BREAK_LOOP,
+impl ComplexToken {
+	/// Returns where the complex token is in the source code.
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{diagnostic::Diagnostic, env::Options, parser::*, scanner::*, Clue};
+	///
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
+	///     let options = Options::default();
+	///     let filename = String::from("main.clue");
+	///     let code = Clue::new().preprocess_code("local x = 1\nx += 2".to_owned())?;
+	///     let tokens = scan_code(code, &filename)?;
+	///     let (expr, ..) = parse_tokens(tokens, &filename, &options)?;
+	///
+	///     let span = expr[1].span();
+	///     assert_eq!((span.start.line, span.start.column, span.start.offset), (2, 1, 12));
+	///     assert_eq!((span.end.line, span.end.column, span.end.offset), (2, 7, 18));
+	///
+	///     Ok(())
+	/// }
+	/// ```
+	pub const fn span(&self) -> Span {
+		match self {
+			VARIABLE { span, .. }
+			| ALTER { span, .. }
+			| TABLE { span, .. }
+			| FUNCTION { span, .. }
+			| LAMBDA { span, .. }
+			| IF_STATEMENT { span, .. }
+			| MATCH_BLOCK { span, .. }
+			| WHILE_LOOP { span, .. }
+			| LOOP_UNTIL { span, .. }
+			| FOR_LOOP { span, .. }
+			| FOR_FUNC_LOOP { span, .. }
+			| TRY_CATCH { span, .. }
+			| IDENT { span, .. }
+			| EXTERN_GLOBAL { span, .. }
+			| SYMBOL(_, span)
+			| CALL(_, span)
+			| EXPR(_, span)
+			| DO_BLOCK(_, span)
+			| RETURN_EXPR(_, span)
+			| CONTINUE_LOOP(span)
+			| BREAK_LOOP(span) => *span,
+		}
+	}
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -276,14 +405,569 @@ pub struct CodeBlock {
 	pub end: usize,
 }
 
-/*
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A type annotation, e.g. `number`, `string?` or `number | string`.
 pub enum LuaType {
+	/// Any type, which is also used for values whose type is unknown.
 	ANY,
+
+	/// `nil`.
 	NIL,
+
+	/// `bool` or `boolean`.
+	BOOLEAN,
+
+	/// `number`.
 	NUMBER,
+
+	/// `string`.
+	STRING,
+
+	/// `table`.
+	TABLE,
+
+	/// `fn`.
+	FUNCTION,
+
+	/// `userdata`.
+	USERDATA,
+
+	/// `thread`.
+	THREAD,
+
+	/// Any of the given types, e.g. `number | string`, or `number | nil` for `number?`.
+	UNION(Vec<LuaType>),
+}
+
+impl LuaType {
+	/// Returns whether a value of type `other` can be used where a value of this type is expected.
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::parser::LuaType;
+	///
+	/// let optional = LuaType::UNION(vec![LuaType::NUMBER, LuaType::NIL]);
+	/// assert!(optional.accepts(&LuaType::NIL));
+	/// assert!(!LuaType::NUMBER.accepts(&optional));
+	/// assert!(LuaType::NUMBER.accepts(&LuaType::ANY));
+	/// ```
+	pub fn accepts(&self, other: &LuaType) -> bool {
+		match (self, other) {
+			(LuaType::ANY, _) | (_, LuaType::ANY) => true,
+			(_, LuaType::UNION(others)) => others.iter().all(|other| self.accepts(other)),
+			(LuaType::UNION(types), _) => types.iter().any(|luatype| luatype.accepts(other)),
+			_ => self == other,
+		}
+	}
+}
+
+impl fmt::Display for LuaType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let name = match self {
+			LuaType::ANY => "any",
+			LuaType::NIL => "nil",
+			LuaType::BOOLEAN => "boolean",
+			LuaType::NUMBER => "number",
+			LuaType::STRING => "string",
+			LuaType::TABLE => "table",
+			LuaType::FUNCTION => "function",
+			LuaType::USERDATA => "userdata",
+			LuaType::THREAD => "thread",
+			LuaType::UNION(types) => {
+				for (i, luatype) in types.iter().enumerate() {
+					if i > 0 {
+						f.write_str(" | ")?;
+					}
+					write!(f, "{luatype}")?;
+				}
+				return Ok(());
+			}
+		};
+		f.write_str(name)
+	}
+}
+
+/// A read-only traversal of the AST.
+///
+/// Every method has a default implementation that calls the matching `walk_` function,
+/// which visits everything inside the node, so an implementation only needs to override the
+/// methods of the nodes it cares about and call the `walk_` function to keep going deeper.
+///
+/// # Example
+/// ```rust
+/// use clue_core::{diagnostic::Diagnostic, parser::*, Clue};
+///
+/// #[derive(Default)]
+/// struct Calls(usize);
+///
+/// impl<'a> Visitor<'a> for Calls {
+///     fn visit_token(&mut self, t: &'a ComplexToken) {
+///         if let ComplexToken::CALL(..) = t {
+///             self.0 += 1;
+///         }
+///         walk_token(self, t)
+///     }
+/// }
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let (expr, _) = Clue::new().parse_code("if f(1) { print(g(2)) }".to_owned())?;
+///     let mut calls = Calls::default();
+///     calls.visit_expression(&expr);
+///     assert_eq!(calls.0, 3);
+///
+///     Ok(())
+/// }
+/// ```
+pub trait Visitor<'a> {
+	/// Visits a list of complex tokens, which can be a whole block of code or a single expression.
+	fn visit_expression(&mut self, expr: &'a Expression) {
+		walk_expression(self, expr)
+	}
+
+	/// Visits a single complex token.
+	fn visit_token(&mut self, t: &'a ComplexToken) {
+		walk_token(self, t)
+	}
+
+	/// Visits the code block of a statement.
+	fn visit_block(&mut self, block: &'a CodeBlock) {
+		walk_block(self, block)
+	}
+
+	/// Visits the arguments and the code of a function or a lambda.
+	fn visit_function(&mut self, args: &'a FunctionArgs, code: &'a CodeBlock) {
+		walk_function(self, args, code)
+	}
+
+	/// Visits a case of a match statement.
+	fn visit_match_case(&mut self, case: &'a MatchCase) {
+		walk_match_case(self, case)
+	}
+}
+
+/// Visits every complex token of the expression.
+pub fn walk_expression<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, expr: &'a Expression) {
+	for t in expr {
+		visitor.visit_token(t)
+	}
+}
+
+/// Visits the code of the code block.
+pub fn walk_block<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, block: &'a CodeBlock) {
+	visitor.visit_expression(&block.code)
+}
+
+/// Visits the default values of the arguments and then the code of the function.
+pub fn walk_function<'a, V: Visitor<'a> + ?Sized>(
+	visitor: &mut V,
+	args: &'a FunctionArgs,
+	code: &'a CodeBlock,
+) {
+	for (_, default) in args {
+		if let Some((default, _)) = default {
+			visitor.visit_expression(default)
+		}
+	}
+	visitor.visit_block(code)
+}
+
+/// Visits the things to match, the internal code, the condition and then the code of the case.
+pub fn walk_match_case<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, case: &'a MatchCase) {
+	let (conditions, internal_expr, extraif, code) = case;
+	for condition in conditions {
+		visitor.visit_expression(condition)
+	}
+	visitor.visit_expression(internal_expr);
+	if let Some(extraif) = extraif {
+		visitor.visit_expression(extraif)
+	}
+	visitor.visit_block(code)
+}
+
+/// Visits everything inside the complex token, in the order it appears in the source code.
+pub fn walk_token<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, t: &'a ComplexToken) {
+	match t {
+		VARIABLE { values, .. } => values
+			.iter()
+			.for_each(|value| visitor.visit_expression(value)),
+		ALTER { names, values, .. } => {
+			names.iter().for_each(|name| visitor.visit_expression(name));
+			values
+				.iter()
+				.for_each(|value| visitor.visit_expression(value))
+		}
+		TABLE { values, metas, .. } => {
+			for (key, value, _) in values {
+				if let Some(key) = key {
+					visitor.visit_expression(key)
+				}
+				visitor.visit_expression(value)
+			}
+			metas
+				.iter()
+				.for_each(|(_, value, _)| visitor.visit_expression(value))
+		}
+		FUNCTION {
+			name, args, code, ..
+		} => {
+			visitor.visit_expression(name);
+			visitor.visit_function(args, code)
+		}
+		LAMBDA { args, code, .. } => visitor.visit_function(args, code),
+		IF_STATEMENT {
+			condition,
+			code,
+			next,
+			..
+		} => {
+			visitor.visit_expression(condition);
+			visitor.visit_block(code);
+			if let Some(next) = next {
+				visitor.visit_token(next)
+			}
+		}
+		MATCH_BLOCK {
+			value, branches, ..
+		} => {
+			visitor.visit_expression(value);
+			branches
+				.iter()
+				.for_each(|case| visitor.visit_match_case(case))
+		}
+		WHILE_LOOP {
+			condition, code, ..
+		} => {
+			visitor.visit_expression(condition);
+			visitor.visit_block(code)
+		}
+		LOOP_UNTIL {
+			condition, code, ..
+		} => {
+			visitor.visit_block(code);
+			visitor.visit_expression(condition)
+		}
+		FOR_LOOP {
+			start,
+			end,
+			alter,
+			code,
+			..
+		} => {
+			visitor.visit_expression(start);
+			visitor.visit_expression(end);
+			visitor.visit_expression(alter);
+			visitor.visit_block(code)
+		}
+		FOR_FUNC_LOOP { expr, code, .. } => {
+			visitor.visit_expression(expr);
+			visitor.visit_block(code)
+		}
+		TRY_CATCH { totry, catch, .. } => {
+			visitor.visit_block(totry);
+			if let Some(catch) = catch {
+				visitor.visit_block(catch)
+			}
+		}
+		EXTERN_GLOBAL {
+			args: Some(args), ..
+		} => {
+			for (_, default) in args {
+				if let Some((default, _)) = default {
+					visitor.visit_expression(default)
+				}
+			}
+		}
+		IDENT { expr, .. } | EXPR(expr, _) => visitor.visit_expression(expr),
+		CALL(args, _) => args.iter().for_each(|arg| visitor.visit_expression(arg)),
+		DO_BLOCK(code, _) => visitor.visit_block(code),
+		RETURN_EXPR(Some(exprs), _) => exprs.iter().for_each(|expr| visitor.visit_expression(expr)),
+		EXTERN_GLOBAL { args: None, .. }
+		| RETURN_EXPR(None, _)
+		| SYMBOL(..)
+		| CONTINUE_LOOP(_)
+		| BREAK_LOOP(_) => {}
+	}
+}
+
+/// A traversal of the AST that can change it, like [`Visitor`].
+///
+/// # Example
+/// ```rust
+/// use clue_core::{diagnostic::Diagnostic, parser::*, Clue};
+///
+/// struct Rename;
+///
+/// impl VisitorMut for Rename {
+///     fn visit_token_mut(&mut self, t: &mut ComplexToken) {
+///         match t {
+///             ComplexToken::SYMBOL(name, _) if name == "print" => *name = String::from("log"),
+///             t => walk_token_mut(self, t),
+///         }
+///     }
+/// }
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let (mut expr, _) = Clue::new().parse_code("{ print(1) }".to_owned())?;
+///     Rename.visit_expression_mut(&mut expr);
+///     assert_eq!(clue_core::printer::print_ast(&expr), "{\n\tlog(1)\n}\n");
+///
+///     Ok(())
+/// }
+/// ```
+pub trait VisitorMut {
+	/// Visits a list of complex tokens, which can be a whole block of code or a single expression.
+	fn visit_expression_mut(&mut self, expr: &mut Expression) {
+		walk_expression_mut(self, expr)
+	}
+
+	/// Visits a single complex token.
+	fn visit_token_mut(&mut self, t: &mut ComplexToken) {
+		walk_token_mut(self, t)
+	}
+
+	/// Visits the code block of a statement.
+	fn visit_block_mut(&mut self, block: &mut CodeBlock) {
+		walk_block_mut(self, block)
+	}
+
+	/// Visits the arguments and the code of a function or a lambda.
+	fn visit_function_mut(&mut self, args: &mut FunctionArgs, code: &mut CodeBlock) {
+		walk_function_mut(self, args, code)
+	}
+
+	/// Visits a case of a match statement.
+	fn visit_match_case_mut(&mut self, case: &mut MatchCase) {
+		walk_match_case_mut(self, case)
+	}
+}
+
+/// Visits every complex token of the expression.
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expression) {
+	for t in expr {
+		visitor.visit_token_mut(t)
+	}
+}
+
+/// Visits the code of the code block.
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut CodeBlock) {
+	visitor.visit_expression_mut(&mut block.code)
+}
+
+/// Visits the default values of the arguments and then the code of the function.
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(
+	visitor: &mut V,
+	args: &mut FunctionArgs,
+	code: &mut CodeBlock,
+) {
+	for (_, default) in args {
+		if let Some((default, _)) = default {
+			visitor.visit_expression_mut(default)
+		}
+	}
+	visitor.visit_block_mut(code)
+}
+
+/// Visits the things to match, the internal code, the condition and then the code of the case.
+pub fn walk_match_case_mut<V: VisitorMut + ?Sized>(visitor: &mut V, case: &mut MatchCase) {
+	let (conditions, internal_expr, extraif, code) = case;
+	for condition in conditions {
+		visitor.visit_expression_mut(condition)
+	}
+	visitor.visit_expression_mut(internal_expr);
+	if let Some(extraif) = extraif {
+		visitor.visit_expression_mut(extraif)
+	}
+	visitor.visit_block_mut(code)
+}
+
+/// Visits everything inside the complex token, in the order it appears in the source code.
+pub fn walk_token_mut<V: VisitorMut + ?Sized>(visitor: &mut V, t: &mut ComplexToken) {
+	match t {
+		VARIABLE { values, .. } => values
+			.iter_mut()
+			.for_each(|value| visitor.visit_expression_mut(value)),
+		ALTER { names, values, .. } => {
+			names
+				.iter_mut()
+				.for_each(|name| visitor.visit_expression_mut(name));
+			values
+				.iter_mut()
+				.for_each(|value| visitor.visit_expression_mut(value))
+		}
+		TABLE { values, metas, .. } => {
+			for (key, value, _) in values {
+				if let Some(key) = key {
+					visitor.visit_expression_mut(key)
+				}
+				visitor.visit_expression_mut(value)
+			}
+			metas
+				.iter_mut()
+				.for_each(|(_, value, _)| visitor.visit_expression_mut(value))
+		}
+		FUNCTION {
+			name, args, code, ..
+		} => {
+			visitor.visit_expression_mut(name);
+			visitor.visit_function_mut(args, code)
+		}
+		LAMBDA { args, code, .. } => visitor.visit_function_mut(args, code),
+		IF_STATEMENT {
+			condition,
+			code,
+			next,
+			..
+		} => {
+			visitor.visit_expression_mut(condition);
+			visitor.visit_block_mut(code);
+			if let Some(next) = next {
+				visitor.visit_token_mut(next)
+			}
+		}
+		MATCH_BLOCK {
+			value, branches, ..
+		} => {
+			visitor.visit_expression_mut(value);
+			branches
+				.iter_mut()
+				.for_each(|case| visitor.visit_match_case_mut(case))
+		}
+		WHILE_LOOP {
+			condition, code, ..
+		} => {
+			visitor.visit_expression_mut(condition);
+			visitor.visit_block_mut(code)
+		}
+		LOOP_UNTIL {
+			condition, code, ..
+		} => {
+			visitor.visit_block_mut(code);
+			visitor.visit_expression_mut(condition)
+		}
+		FOR_LOOP {
+			start,
+			end,
+			alter,
+			code,
+			..
+		} => {
+			visitor.visit_expression_mut(start);
+			visitor.visit_expression_mut(end);
+			visitor.visit_expression_mut(alter);
+			visitor.visit_block_mut(code)
+		}
+		FOR_FUNC_LOOP { expr, code, .. } => {
+			visitor.visit_expression_mut(expr);
+			visitor.visit_block_mut(code)
+		}
+		TRY_CATCH { totry, catch, .. } => {
+			visitor.visit_block_mut(totry);
+			if let Some(catch) = catch {
+				visitor.visit_block_mut(catch)
+			}
+		}
+		EXTERN_GLOBAL {
+			args: Some(args), ..
+		} => {
+			for (_, default) in args {
+				if let Some((default, _)) = default {
+					visitor.visit_expression_mut(default)
+				}
+			}
+		}
+		IDENT { expr, .. } | EXPR(expr, _) => visitor.visit_expression_mut(expr),
+		CALL(args, _) => args
+			.iter_mut()
+			.for_each(|arg| visitor.visit_expression_mut(arg)),
+		DO_BLOCK(code, _) => visitor.visit_block_mut(code),
+		RETURN_EXPR(Some(exprs), _) => exprs
+			.iter_mut()
+			.for_each(|expr| visitor.visit_expression_mut(expr)),
+		EXTERN_GLOBAL { args: None, .. }
+		| RETURN_EXPR(None, _)
+		| SYMBOL(..)
+		| CONTINUE_LOOP(_)
+		| BREAK_LOOP(_) => {}
+	}
+}
+
+/// Makes the bare returns of a struct constructor return the new struct,
+/// without changing the returns of the functions defined inside it
+struct ConstructorReturns;
+
+impl VisitorMut for ConstructorReturns {
+	fn visit_token_mut(&mut self, t: &mut ComplexToken) {
+		match t {
+			RETURN_EXPR(exprs @ None, span) => {
+				let this = IDENT {
+					expr: vec_deque![SYMBOL(String::from("self"), *span)],
+					line: span.start.line,
+					span: *span,
+				};
+				*exprs = Some(vec![vec_deque![this]])
+			}
+			_ => walk_token_mut(self, t),
+		}
+	}
+
+	fn visit_function_mut(&mut self, _: &mut FunctionArgs, _: &mut CodeBlock) {}
+}
+
+/// Renames the variable `from` to `to`, without renaming the members and keys called like it
+/// and what is inside the functions with an argument that shadows it
+pub(crate) struct Rename<'a> {
+	pub from: &'a str,
+	pub to: &'a str,
+}
+
+impl VisitorMut for Rename<'_> {
+	fn visit_expression_mut(&mut self, expr: &mut Expression) {
+		let mut indexed = false;
+		for t in expr {
+			if let SYMBOL(lexeme, _) = t {
+				if !indexed && lexeme == self.from {
+					*lexeme = self.to.to_owned();
+				}
+				indexed = matches!(lexeme.as_str(), "." | ":");
+			} else {
+				self.visit_token_mut(t);
+				indexed = false;
+			}
+		}
+	}
+
+	fn visit_token_mut(&mut self, t: &mut ComplexToken) {
+		match t {
+			TABLE { values, metas, .. } => {
+				for (key, value, _) in values {
+					if let Some(key) = key.as_mut().filter(|key| single_name(key).is_none()) {
+						self.visit_expression_mut(key)
+					}
+					self.visit_expression_mut(value)
+				}
+				metas
+					.iter_mut()
+					.for_each(|(_, value, _)| self.visit_expression_mut(value))
+			}
+			_ => walk_token_mut(self, t),
+		}
+	}
+
+	fn visit_function_mut(&mut self, args: &mut FunctionArgs, code: &mut CodeBlock) {
+		if args.iter().all(|(arg, _)| arg != self.from) {
+			walk_function_mut(self, args, code)
+		}
+	}
+}
+
+/// Whether the expression reads the variable `name`, see [`Rename`]
+fn references(expr: &Expression, name: &str) -> bool {
+	let mut renamed = expr.clone();
+	Rename { from: name, to: "" }.visit_expression_mut(&mut renamed);
+	renamed != *expr
 }
-*/
+
 struct ParserInfo<'a> {
 	options: &'a Options,
 	current: usize,
@@ -295,15 +979,13 @@ struct ParserInfo<'a> {
 	internal_stack: Vec<Cell<Expression>>,
 	statics: String,
 	compiler: Compiler<'a>,
-	//locals: LocalsList,
+	errors: Vec<Diagnostic>,
+	static_names: Vec<String>,
+	symbols: SymbolTable,
 }
 
 impl<'a> ParserInfo<'a> {
-	fn new(
-		tokens: Vec<Token>, /* , locals: LocalsList */
-		filename: &'a String,
-		options: &'a Options,
-	) -> ParserInfo<'a> {
+	fn new(tokens: Vec<Token>, filename: &'a String, options: &'a Options) -> ParserInfo<'a> {
 		ParserInfo {
 			current: 0,
 			size: tokens.len() - 1,
@@ -314,11 +996,24 @@ impl<'a> ParserInfo<'a> {
 			internal_stack: Vec::new(),
 			statics: String::new(),
 			compiler: Compiler::new(options, filename),
+			errors: Vec::new(),
+			static_names: Vec::new(),
+			symbols: SymbolTable::new(),
 			options,
-			// locals,
 		}
 	}
 
+	fn add_symbol(
+		&mut self,
+		name: impl Into<String>,
+		kind: SymbolKind,
+		local: bool,
+		span: Span,
+	) -> &mut Symbol {
+		self.symbols.push(Symbol::new(name, kind, local, span));
+		self.symbols.symbols.last_mut().unwrap()
+	}
+
 	/*
 		fn warning(&self, msg: impl Into<String>, line: usize) {
 			println!(
@@ -330,35 +1025,66 @@ impl<'a> ParserInfo<'a> {
 		}
 	*/
 
-	fn error(&mut self, msg: impl Into<String>, line: usize, column: usize) -> String {
-		eprintln!("Error in {}:{line}:{column}!", self.filename);
-		msg.into()
+	fn error(&mut self, code: &str, msg: impl Into<String>, at: Position) -> Box<Diagnostic> {
+		Box::new(Diagnostic::error(
+			code,
+			msg,
+			self.filename.as_str(),
+			Span::range(at, at),
+		))
 	}
 
-	fn expected(&mut self, expected: &str, got: &str, line: usize, column: usize) -> String {
+	fn expected(&mut self, expected: &str, got: &str, at: Position) -> Box<Diagnostic> {
 		self.error(
+			"expected",
 			format_clue!("Expected '", expected, "', got '", got, "'"),
-			line,
-			column,
+			at,
 		)
 	}
 
-	fn expected_before(
-		&mut self,
-		expected: &str,
-		before: &str,
-		line: usize,
-		column: usize,
-	) -> String {
+	fn expected_before(&mut self, expected: &str, before: &str, at: Position) -> Box<Diagnostic> {
 		self.error(
+			"expected",
 			format_clue!("Expected '", expected, "' before '", before, "'"),
-			line,
-			column,
+			at,
+		)
+	}
+
+	fn unexpected(&mut self, str: &str, at: Position) -> Box<Diagnostic> {
+		self.error(
+			"unexpected-token",
+			format_clue!("Unexpected token '", str, "'"),
+			at,
 		)
 	}
 
-	fn unexpected(&mut self, str: &str, line: usize, column: usize) -> String {
-		self.error(format_clue!("Unexpected token '", str, "'"), line, column)
+	fn too_many_errors(&self) -> bool {
+		self.options
+			.env_maxerrors
+			.map_or(false, |max| self.errors.len() >= max.get())
+	}
+
+	fn synchronize(&mut self) {
+		self.internal_stack.clear();
+		let mut cscope = 0usize;
+		while !self.ended() {
+			match self.peek(0).kind() {
+				CURLY_BRACKET_OPEN => cscope += 1,
+				CURLY_BRACKET_CLOSED if cscope > 0 => cscope -= 1,
+				CURLY_BRACKET_CLOSED => {
+					self.advance();
+					return;
+				}
+				LOCAL | GLOBAL | STATIC | METHOD | FN | ENUM | STRUCT | EXTERN | IF | MATCH
+				| WHILE | UNTIL | LOOP | FOR | CONTINUE | BREAK | RETURN | TRY
+					if cscope == 0 =>
+				{
+					return
+				}
+				_ => {}
+			}
+			self.advance();
+		}
 	}
 
 	const fn ended(&self) -> bool {
@@ -409,18 +1135,18 @@ impl<'a> ParserInfo<'a> {
 		&mut self,
 		expected: TokenType,
 		error: &str,
-	) -> Result<BorrowedToken, String> {
+	) -> Result<BorrowedToken, Box<Diagnostic>> {
 		let t = self.advance();
 		if t.kind() != expected {
-			return Err(self.expected(error, &t.lexeme(), t.line(), t.column()));
+			return Err(self.expected(error, &t.lexeme(), t.span().start));
 		}
 		Ok(t)
 	}
 
-	fn assert_compare(&mut self, expected: TokenType, error: &str) -> Result<(), String> {
+	fn assert_compare(&mut self, expected: TokenType, error: &str) -> Result<(), Box<Diagnostic>> {
 		if !self.compare(expected) {
 			let t = self.peek(0);
-			return Err(self.expected(error, &t.lexeme(), t.line(), t.column()));
+			return Err(self.expected(error, &t.lexeme(), t.span().start));
 		}
 		Ok(())
 	}
@@ -430,41 +1156,28 @@ impl<'a> ParserInfo<'a> {
 		tocheck: &BorrowedToken,
 		end: OptionalEnd,
 		iftrue: T,
-	) -> Result<T, String> {
+	) -> Result<T, Box<Diagnostic>> {
 		if let Some((kind, lexeme)) = end {
 			if tocheck.kind() != kind {
-				return Err(self.expected(
-					lexeme,
-					&tocheck.lexeme(),
-					tocheck.line(),
-					tocheck.column(),
-				));
+				return Err(self.expected(lexeme, &tocheck.lexeme(), tocheck.span().start));
 			}
 		}
 		Ok(iftrue)
 	}
 
-	fn assert(&mut self, expected: TokenType, error: &str) -> Result<(), String> {
+	fn assert(&mut self, expected: TokenType, error: &str) -> Result<(), Box<Diagnostic>> {
 		if !self.advance_if(expected) {
 			let t = self.peek(0);
-			return Err(self.expected(error, &t.lexeme(), t.line(), t.column()));
+			return Err(self.expected(error, &t.lexeme(), t.span().start));
 		}
 		Ok(())
 	}
-	/*
-		fn assert_variable(&mut self, mut variable: Iter<ComplexToken>) -> Result<LuaType, String> {
-			let mut scope: &LocalsList = &self.locals;
-			let mut luatype = LuaType::NIL;
-			while let Some(locals) = scope {
-				if let Some(t) = variable.next() {
-					// TODO
-				} else {
-					break;
-				}
-			}
-			Ok(luatype)
-		}
-	*/
+
+	/// Returns the span going from `start` to the end of the last token that was read
+	fn span_from(&self, start: Span) -> Span {
+		let end = self.look_back(0).span().end;
+		Span::range(start.start, cmp::max(start.end, end))
+	}
 
 	fn get_next_internal_var(&mut self) -> String {
 		let var = format_clue!("_internal", self.internal_var_id.to_string());
@@ -472,7 +1185,7 @@ impl<'a> ParserInfo<'a> {
 		var
 	}
 
-	fn build_call(&mut self) -> Result<Vec<Expression>, String> {
+	fn build_call(&mut self) -> Result<Vec<Expression>, Box<Diagnostic>> {
 		let args: Vec<Expression> = if self.advance_if(ROUND_BRACKET_CLOSED) {
 			Vec::new()
 		} else {
@@ -481,7 +1194,7 @@ impl<'a> ParserInfo<'a> {
 		Ok(args)
 	}
 
-	fn find_expressions(&mut self, end: OptionalEnd) -> Result<Vec<Expression>, String> {
+	fn find_expressions(&mut self, end: OptionalEnd) -> Result<Vec<Expression>, Box<Diagnostic>> {
 		let mut exprs: Vec<Expression> = Vec::new();
 		loop {
 			let expr = self.build_expression(None)?;
@@ -493,10 +1206,11 @@ impl<'a> ParserInfo<'a> {
 		}
 	}
 
-	fn build_table(&mut self) -> Result<ComplexToken, String> {
+	fn build_table(&mut self) -> Result<ComplexToken, Box<Diagnostic>> {
 		let mut values: Vec<(Option<Expression>, Expression, usize)> = Vec::new();
 		let mut metas: Vec<(String, Expression, usize)> = Vec::new();
 		let mut metatable: Option<String> = None;
+		let open = self.look_back(0).span();
 		while !self.advance_if(CURLY_BRACKET_CLOSED) {
 			let start = self.current;
 			let mut qscope = 1u8;
@@ -519,14 +1233,7 @@ impl<'a> ParserInfo<'a> {
 					iskey = true;
 					true
 				}
-				EOF => {
-					return Err(self.expected_before(
-						"}",
-						"<end>",
-						self.peek(0).line(),
-						self.peek(0).column(),
-					))
-				}
+				EOF => return Err(self.expected_before("}", "<end>", self.peek(0).span().start)),
 				_ => true,
 			} {
 				self.current += 1;
@@ -542,7 +1249,7 @@ impl<'a> ParserInfo<'a> {
 			let pn = self.advance();
 			match pn.kind() {
 				IDENTIFIER => {
-					name = Ok(vec_deque![SYMBOL(pn.lexeme())]);
+					name = Ok(vec_deque![SYMBOL(pn.lexeme(), pn.span())]);
 				}
 				SQUARE_BRACKET_OPEN => {
 					let mut qscope = 1u8;
@@ -560,26 +1267,27 @@ impl<'a> ParserInfo<'a> {
 							return Err(self.expected_before(
 								"]",
 								"<end>",
-								self.peek(0).line(),
-								self.peek(0).column(),
+								self.peek(0).span().start,
 							))
 						}
 						_ => true,
 					} {}
 					self.current = start;
+					let key = self.build_expression(Some((SQUARE_BRACKET_CLOSED, "]")))?;
+					let key_span = expr_span(&key);
 					name = Ok(vec_deque![
-						SYMBOL(String::from("[")),
-						EXPR(self.build_expression(Some((SQUARE_BRACKET_CLOSED, "]")))?),
-						SYMBOL(String::from("]"))
+						SYMBOL(String::from("["), pn.span()),
+						EXPR(key, key_span),
+						SYMBOL(String::from("]"), self.look_back(0).span())
 					]);
 				}
 				META => {
 					if self.advance_if(WITH) {
 						if !metas.is_empty() {
 							return Err(self.error(
+								"invalid-metatable",
 								"An external metatable cannot be used if the table already set its own metamethods",
-								pn.line(),
-								pn.column()
+								pn.span().start
 							));
 						}
 						metatable = Some(self.assert_advance(IDENTIFIER, "<name>")?.lexeme());
@@ -589,9 +1297,9 @@ impl<'a> ParserInfo<'a> {
 
 					if metatable.is_some() {
 						return Err(self.error(
+								"invalid-metatable",
 								"Metamethods cannot be set if the table already uses an external metatable",
-								pn.line(),
-								pn.column()
+								pn.span().start
 							));
 					}
 					let name_token = self.advance();
@@ -603,9 +1311,9 @@ impl<'a> ParserInfo<'a> {
 								"__usedindex"
 							} else {
 								return Err(self.error(
+								"invalid-metamethod",
 								"The 'usedindex' metamethod can only be used with --target=blua",
-								name_token.line(),
-								name_token.column()
+								name_token.span().start
 							));
 							}
 						}
@@ -634,17 +1342,16 @@ impl<'a> ParserInfo<'a> {
 							return Err(self.expected(
 								"<meta name>",
 								&name_token.lexeme(),
-								name_token.line(),
-								name_token.column(),
+								name_token.span().start,
 							));
 						}
 					}))
 				}
-				_ => return Err(self.expected("<name>", &pn.lexeme(), pn.line(), pn.column())),
+				_ => return Err(self.expected("<name>", &pn.lexeme(), pn.span().start)),
 			}
 			if !self.advance_if(DEFINE) {
 				let t = self.peek(0);
-				return Err(self.expected("=", &t.lexeme(), t.line(), t.column()));
+				return Err(self.expected("=", &t.lexeme(), t.span().start));
 			}
 			let start = self.current;
 			let mut cscope = 0u8;
@@ -657,19 +1364,12 @@ impl<'a> ParserInfo<'a> {
 				ROUND_BRACKET_CLOSED => {
 					if cscope == 0 {
 						let t = self.peek(0);
-						return Err(self.expected_before("(", ")", t.line(), t.column()));
+						return Err(self.expected_before("(", ")", t.span().start));
 					}
 					cscope -= 1;
 					true
 				}
-				EOF => {
-					return Err(self.expected_before(
-						"}",
-						"<end>",
-						self.peek(0).line(),
-						self.peek(0).column(),
-					))
-				}
+				EOF => return Err(self.expected_before("}", "<end>", self.peek(0).span().start)),
 				_ => true,
 			} {
 				self.current += 1;
@@ -686,6 +1386,7 @@ impl<'a> ParserInfo<'a> {
 			values,
 			metas,
 			metatable,
+			span: self.span_from(open),
 		})
 	}
 
@@ -694,7 +1395,7 @@ impl<'a> ParserInfo<'a> {
 		t: &BorrowedToken,
 		notable: &mut bool,
 		checkback: Option<&Expression>,
-	) -> Result<(), String> {
+	) -> Result<(), Box<Diagnostic>> {
 		if match self.peek(0).kind() {
 			NUMBER | IDENTIFIER | STRING | TRUE | FALSE | MINUS | BIT_NOT | NIL | NOT | HASHTAG
 			| ROUND_BRACKET_OPEN | THREEDOTS | MATCH => false,
@@ -705,17 +1406,17 @@ impl<'a> ParserInfo<'a> {
 			_ => true,
 		} {
 			return Err(self.error(
+				"invalid-operator",
 				format!("Operator '{}' has invalid right hand token", t.lexeme()),
-				t.line(),
-				t.column(),
+				t.span().start,
 			));
 		}
 		if let Some(expr) = checkback {
 			if expr.is_empty() {
 				return Err(self.error(
+					"invalid-operator",
 					format!("Operator '{}' lacks a left hand token", t.lexeme()),
-					t.line(),
-					t.column(),
+					t.span().start,
 				));
 			} else if !matches!(
 				self.look_back(1).kind(),
@@ -726,9 +1427,9 @@ impl<'a> ParserInfo<'a> {
 					| THREEDOTS | CURLY_BRACKET_CLOSED
 			) {
 				return Err(self.error(
+					"invalid-operator",
 					format!("Operator '{}' has invalid left hand token", t.lexeme()),
-					t.line(),
-					t.column(),
+					t.span().start,
 				));
 			}
 		}
@@ -742,13 +1443,14 @@ impl<'a> ParserInfo<'a> {
 		fname: impl Into<String>,
 		end: OptionalEnd,
 		notable: &mut bool,
-	) -> Result<(), String> {
+	) -> Result<(), Box<Diagnostic>> {
 		self.check_operator(t, notable, Some(expr))?;
 		let mut arg1 = Expression::with_capacity(expr.len());
 		arg1.append(expr);
 		let arg2 = self.build_expression(end)?;
-		expr.push_back(SYMBOL(fname.into()));
-		expr.push_back(CALL(vec![arg1, arg2]));
+		let span = Span::range(expr_span(&arg1).start, expr_span(&arg2).end);
+		expr.push_back(SYMBOL(fname.into(), t.span()));
+		expr.push_back(CALL(vec![arg1, arg2], span));
 		self.current -= 1;
 		Ok(())
 	}
@@ -760,13 +1462,13 @@ impl<'a> ParserInfo<'a> {
 		fname: &str,
 		end: OptionalEnd,
 		notable: &mut bool,
-	) -> Result<bool, String> {
+	) -> Result<bool, Box<Diagnostic>> {
 		self.check_operator(t, notable, Some(expr))?;
 		Ok(if let Some(bit) = &self.options.env_jitbit {
 			self.build_function_op(t, expr, format!("{bit}.{fname}"), end, notable)?;
 			self.check_val()
 		} else {
-			expr.push_back(SYMBOL(t.lexeme()));
+			expr.push_back(SYMBOL(t.lexeme(), t.span()));
 			false
 		})
 	}
@@ -776,17 +1478,17 @@ impl<'a> ParserInfo<'a> {
 		t: &BorrowedToken,
 		expr: &mut Expression,
 		lexeme: &str,
-	) -> Result<(), String> {
+	) -> Result<(), Box<Diagnostic>> {
 		if !self.compare(IDENTIFIER)
 			|| matches!(self.look_back(0).kind(), IDENTIFIER | SQUARE_BRACKET_CLOSED)
 		{
 			return Err(self.error(
+				"invalid-index",
 				format!("'{}' should be used only when indexing", t.lexeme()),
-				self.peek(0).line(),
-				self.peek(0).column(),
+				self.peek(0).span().start,
 			));
 		}
-		expr.push_back(SYMBOL(lexeme.to_owned()));
+		expr.push_back(SYMBOL(lexeme.to_owned(), t.span()));
 		Ok(())
 	}
 
@@ -802,23 +1504,23 @@ impl<'a> ParserInfo<'a> {
 	}
 
 	fn get_prev_expr(&mut self) -> &mut Expression {
-		return match self.internal_stack.last_mut() {
+		match self.internal_stack.last_mut() {
 			Some(last) => last.get_mut(),
-			None => &mut self.expr
-		};
+			None => &mut self.expr,
+		}
 	}
 
 	fn use_internal_stack<T>(
 		&mut self,
-		f: impl FnOnce(&mut Self) -> Result<T, String>
-	) -> Result<(T, Expression), String> {
+		f: impl FnOnce(&mut Self) -> Result<T, Box<Diagnostic>>,
+	) -> Result<(T, Expression), Box<Diagnostic>> {
 		self.internal_stack.push(Cell::new(Expression::new()));
 		let result = f(self)?;
 		let code = self.internal_stack.pop().unwrap().into_inner();
 		Ok((result, code))
 	}
 
-	fn build_expression(&mut self, end: OptionalEnd) -> Result<Expression, String> {
+	fn build_expression(&mut self, end: OptionalEnd) -> Result<Expression, Box<Diagnostic>> {
 		let mut expr = Expression::with_capacity(16);
 		let notable = &mut true;
 		let start = self.current;
@@ -848,25 +1550,30 @@ impl<'a> ParserInfo<'a> {
 				PLUS | STAR | SLASH | PERCENTUAL | CARET | TWODOTS | EQUAL | BIGGER
 				| BIGGER_EQUAL | SMALLER | SMALLER_EQUAL => {
 					self.check_operator(&t, notable, Some(&expr))?;
-					expr.push_back(SYMBOL(t.lexeme()))
+					expr.push_back(SYMBOL(t.lexeme(), t.span()))
 				}
 				MINUS => {
 					self.check_operator(&t, notable, None)?;
-					expr.push_back(SYMBOL(if self.look_back(1).kind() == MINUS {
+					let lexeme = if self.look_back(1).kind() == MINUS {
 						format!(" {}", t.lexeme())
 					} else {
 						t.lexeme()
-					}))
+					};
+					expr.push_back(SYMBOL(lexeme, t.span()))
 				}
 				FLOOR_DIVISION => {
 					self.check_operator(&t, notable, Some(&expr))?;
 					let mut division = Expression::with_capacity(expr.len());
 					division.append(&mut expr);
-					division.push_back(SYMBOL(String::from('/')));
+					division.push_back(SYMBOL(String::from('/'), t.span()));
 					division.append(&mut self.build_expression(end)?);
-					expr.push_back(SYMBOL(String::from("math.floor")));
-					expr.push_back(CALL(vec![division]));
+					let span = expr_span(&division);
+					expr.push_back(SYMBOL(String::from("math.floor"), t.span()));
+					expr.push_back(CALL(vec![division], span));
 					self.current -= 1;
+					if self.check_val() {
+						break t;
+					}
 				}
 				BIT_AND => bitwise!(self, t, expr, "band", end, notable),
 				BIT_OR => bitwise!(self, t, expr, "bor", end, notable),
@@ -874,6 +1581,7 @@ impl<'a> ParserInfo<'a> {
 					//SAFETY: the token goes out of scope after BorrowedToken is used, so it stays valid
 					let t2 = if self.options.env_bitwise == BitwiseMode::Vanilla {
 						Token::new(t.kind(), '~', t.line(), t.column())
+							.with_offset(t.span().start.offset)
 					} else {
 						t.into_owned()
 					};
@@ -885,21 +1593,22 @@ impl<'a> ParserInfo<'a> {
 					self.check_operator(&t, notable, None)?;
 					if let Some(bit) = self.options.env_jitbit.clone() {
 						let arg = self.build_expression(end)?;
-						expr.push_back(SYMBOL(bit.clone() + ".bnot"));
-						expr.push_back(CALL(vec![arg]));
+						let span = Span::range(t.span().start, expr_span(&arg).end);
+						expr.push_back(SYMBOL(bit.clone() + ".bnot", t.span()));
+						expr.push_back(CALL(vec![arg], span));
 						self.current -= 1;
 						if self.check_val() {
 							break t;
 						}
 					} else {
-						expr.push_back(SYMBOL(t.lexeme()))
+						expr.push_back(SYMBOL(t.lexeme(), t.span()))
 					}
 				}
 				LEFT_SHIFT => bitwise!(self, t, expr, "lshift", end, notable),
 				RIGHT_SHIFT => bitwise!(self, t, expr, "rshift", end, notable),
 				NOT_EQUAL => {
 					self.check_operator(&t, notable, Some(&expr))?;
-					expr.push_back(SYMBOL(String::from("~=")))
+					expr.push_back(SYMBOL(String::from("~="), t.span()))
 				}
 				HASHTAG => {
 					if !matches!(
@@ -907,9 +1616,9 @@ impl<'a> ParserInfo<'a> {
 						IDENTIFIER | CURLY_BRACKET_OPEN | ROUND_BRACKET_OPEN
 					) {
 						let t = self.peek(0);
-						return Err(self.expected("<table>", &t.lexeme(), t.line(), t.column()));
+						return Err(self.expected("<table>", &t.lexeme(), t.span().start));
 					}
-					expr.push_back(SYMBOL(String::from("#")))
+					expr.push_back(SYMBOL(String::from("#"), t.span()))
 				}
 				/*SAFE_EXPRESSION => {
 					self.assert(ROUND_BRACKET_OPEN, "(")?;
@@ -918,19 +1627,19 @@ impl<'a> ParserInfo<'a> {
 				}*/
 				AND => {
 					self.check_operator(&t, notable, Some(&expr))?;
-					expr.push_back(SYMBOL(String::from(" and ")))
+					expr.push_back(SYMBOL(String::from(" and "), t.span()))
 				}
 				OR => {
 					self.check_operator(&t, notable, Some(&expr))?;
-					expr.push_back(SYMBOL(String::from(" or ")))
+					expr.push_back(SYMBOL(String::from(" or "), t.span()))
 				}
 				NOT => {
 					self.check_operator(&t, notable, None)?;
-					expr.push_back(SYMBOL(String::from("not ")))
+					expr.push_back(SYMBOL(String::from("not "), t.span()))
 				}
 				MATCH => {
 					let name = self.get_next_internal_var();
-					let ident = SYMBOL(name.clone());
+					let ident = SYMBOL(name.clone(), t.span());
 					let mut ctoken = self.build_match_block(name, &|i /* , _ */| {
 						let start = i.peek(0).line();
 						let (expr, mut code) = i.use_internal_stack(|i| i.build_expression(None))?;
@@ -938,29 +1647,43 @@ impl<'a> ParserInfo<'a> {
 						if matches!(i.look_back(0).kind(), CURLY_BRACKET_CLOSED | DEFAULT) {
 							i.current -= 1
 						}
+						let span = expr_span(&expr);
 						code.push_back(ALTER {
 							kind: DEFINE,
 							names: vec_deque![vec_deque![ident.clone()]],
 							values: vec![expr],
-							line: end
+							line: end,
+							span,
 						});
 						Ok(CodeBlock { start, code, end })
 					})?;
-					let MATCH_BLOCK {branches, line, ..} = &mut ctoken else {
+					let MATCH_BLOCK {
+						branches,
+						line,
+						span,
+						..
+					} = &mut ctoken
+					else {
 						unreachable!()
 					};
 					let last_branch = branches.last().unwrap();
 					if !(last_branch.0.is_empty() && last_branch.2.is_none()) {
-						branches.push((Vec::new(), Expression::new(), None, CodeBlock {
-							start: *line,
-							code: vec_deque![ALTER {
-								kind: DEFINE,
-								names: vec_deque![vec_deque![ident.clone()]],
-								values: vec![vec_deque![SYMBOL(String::from("nil"))]],
-								line: *line
-							}],
-							end: *line
-						}))
+						branches.push((
+							Vec::new(),
+							Expression::new(),
+							None,
+							CodeBlock {
+								start: *line,
+								code: vec_deque![ALTER {
+									kind: DEFINE,
+									names: vec_deque![vec_deque![ident.clone()]],
+									values: vec![vec_deque![SYMBOL(String::from("nil"), *span)]],
+									line: *line,
+									span: *span,
+								}],
+								end: *line,
+							},
+						))
 					}
 					self.get_prev_expr().push_back(ctoken);
 					expr.push_back(ident);
@@ -974,6 +1697,7 @@ impl<'a> ParserInfo<'a> {
 					let (rightexpr, mut code) = self.use_internal_stack(|i| i.build_expression(end))?;
 					self.current -= 1;
 					let name = self.get_next_internal_var();
+					let span = self.span_from(self.at(start).span());
 					let start = self.at(start).line();
 					let end = self.at(self.current).line();
 					let prev_expr = self.get_prev_expr();
@@ -982,22 +1706,29 @@ impl<'a> ParserInfo<'a> {
 						local: true,
 						names: vec![name.clone()],
 						values: vec![leftexpr],
+						types: Vec::new(),
+						span,
 					});
-					let name = SYMBOL(name);
+					let name = SYMBOL(name, span);
 					code.push_back(ALTER {
 						kind: DEFINE,
 						line: t.line(),
 						names: vec_deque![vec_deque![name.clone()]],
-						values: vec![rightexpr]
+						values: vec![rightexpr],
+						span,
 					});
 					prev_expr.push_back(IF_STATEMENT {
-						condition: vec_deque![name.clone(), SYMBOL(String::from(" == nil"))],
+						condition: vec_deque![
+							name.clone(),
+							SYMBOL(String::from(" == nil"), t.span())
+						],
 						code: CodeBlock {
 							start: t.line(),
 							code,
 							end,
 						},
 						next: None,
+						span,
 					});
 					expr.push_back(name);
 					if self.check_val() {
@@ -1016,6 +1747,7 @@ impl<'a> ParserInfo<'a> {
 					)?;
 					self.current -= 1;
 					let name = self.get_next_internal_var();
+					let span = self.span_from(self.at(start).span());
 					let start = self.at(start).line();
 					let end = self.at(self.current).line();
 					let prev_expr = self.get_prev_expr();
@@ -1024,19 +1756,25 @@ impl<'a> ParserInfo<'a> {
 						local: true,
 						names: vec![name.clone()],
 						values: Vec::new(),
+						types: Vec::new(),
+						span,
 					});
-					let name = SYMBOL(name);
+					let name = SYMBOL(name, span);
+					let true_span = expr_span(&exprtrue);
 					codetrue.push_back(ALTER {
 						kind: DEFINE,
 						line: t.line(),
 						names: vec_deque![vec_deque![name.clone()]],
-						values: vec![exprtrue]
+						values: vec![exprtrue],
+						span: true_span,
 					});
+					let false_span = expr_span(&exprfalse);
 					codefalse.push_back(ALTER {
 						kind: DEFINE,
 						line: t.line(),
 						names: vec_deque![vec_deque![name.clone()]],
-						values: vec![exprfalse]
+						values: vec![exprfalse],
+						span: false_span,
 					});
 					prev_expr.push_back(IF_STATEMENT {
 						condition,
@@ -1045,11 +1783,15 @@ impl<'a> ParserInfo<'a> {
 							code: codetrue,
 							end: t2.line(),
 						},
-						next: Some(Box::new(DO_BLOCK(CodeBlock {
-							start: t2.line(),
-							code: codefalse,
-							end,
-						}))),
+						next: Some(Box::new(DO_BLOCK(
+							CodeBlock {
+								start: t2.line(),
+								code: codefalse,
+								end,
+							},
+							Span::range(t2.span().start, false_span.end),
+						))),
+						span,
 					});
 					expr.push_back(name);
 					if self.check_val() {
@@ -1057,15 +1799,14 @@ impl<'a> ParserInfo<'a> {
 					}
 				}
 				THREEDOTS | NUMBER | TRUE | FALSE | NIL | STRING => {
-					expr.push_back(SYMBOL(t.lexeme()));
+					expr.push_back(SYMBOL(t.lexeme(), t.span()));
 					if self.check_val() {
 						break t;
 					}
 				}
 				ROUND_BRACKET_OPEN => {
-					expr.push_back(EXPR(
-						self.build_expression(Some((ROUND_BRACKET_CLOSED, ")")))?,
-					));
+					let inner = self.build_expression(Some((ROUND_BRACKET_CLOSED, ")")))?;
+					expr.push_back(EXPR(inner, self.span_from(t.span())));
 					if self.check_val() {
 						break t;
 					}
@@ -1078,15 +1819,18 @@ impl<'a> ParserInfo<'a> {
 					}
 				}
 				FN => {
-					let /*(*/args/*, types)*/ = if self.advance_if(ROUND_BRACKET_OPEN)
-						&& !self.advance_if(ROUND_BRACKET_CLOSED)
-					{
+					let (args, types) = if self.advance_if(ROUND_BRACKET_OPEN) {
 						self.build_function_args()?
 					} else {
-						/*(*/FunctionArgs::new()//, None)
+						ArgsAndTypes::default()
 					};
-					let code = self.build_function_block(/*types*/)?;
-					expr.push_back(LAMBDA { args, code });
+					let code = self.build_function_block()?;
+					expr.push_back(LAMBDA {
+						args,
+						types,
+						code,
+						span: self.span_from(t.span()),
+					});
 					if self.check_val() {
 						break t;
 					}
@@ -1099,23 +1843,24 @@ impl<'a> ParserInfo<'a> {
 			}
 		};
 		if expr.is_empty() {
-			return Err(self.expected("<expr>", &last.lexeme(), last.line(), last.column()));
+			return Err(self.expected("<expr>", &last.lexeme(), last.span().start));
 		}
 		self.assert_end(&self.look_back(0), end, expr)
 	}
 
-	fn build_name(&mut self) -> Result<Expression, String> {
+	fn build_name(&mut self) -> Result<Expression, Box<Diagnostic>> {
 		Ok(vec_deque![self.build_identifier()?])
 	}
 
-	fn build_identifier(&mut self) -> Result<ComplexToken, String> {
+	fn build_identifier(&mut self) -> Result<ComplexToken, Box<Diagnostic>> {
 		let line = self.look_back(0).line();
 		let (mut expr, safe_indexing) = self.build_identifier_internal()?;
+		let span = expr_span(&expr);
 		if safe_indexing {
-			expr.push_front(SYMBOL(String::from("(")));
-			expr.push_back(SYMBOL(String::from(")")));
+			expr.push_front(SYMBOL(String::from("("), span));
+			expr.push_back(SYMBOL(String::from(")"), span));
 		}
-		Ok(IDENT { expr, line })
+		Ok(IDENT { expr, line, span })
 	}
 
 	fn build_safe_index(
@@ -1130,19 +1875,23 @@ impl<'a> ParserInfo<'a> {
 		let mut safe_expr = Expression::with_capacity(expr.len());
 		safe_expr.append(expr);
 		let name = self.get_next_internal_var();
+		let span = expr_span(&safe_expr);
+		let t = self.look_back(0);
 		self.expr.push_back(VARIABLE {
 			local: true,
 			names: vec![name.clone()],
 			values: vec![safe_expr],
+			types: Vec::new(),
 			line: self.peek(0).line(),
+			span,
 		});
-		expr.push_back(SYMBOL(name.clone()));
-		expr.push_back(SYMBOL(String::from(" and ")));
-		expr.push_back(SYMBOL(name));
+		expr.push_back(SYMBOL(name.clone(), span));
+		expr.push_back(SYMBOL(String::from(" and "), t.span()));
+		expr.push_back(SYMBOL(name, span));
 		true
 	}
 
-	fn build_identifier_internal(&mut self) -> Result<(Expression, bool), String> {
+	fn build_identifier_internal(&mut self) -> Result<(Expression, bool), Box<Diagnostic>> {
 		let mut expr = Expression::with_capacity(8);
 		let mut safe_indexing = false;
 		self.current -= 1;
@@ -1150,7 +1899,7 @@ impl<'a> ParserInfo<'a> {
 			let t = self.advance();
 			match t.kind() {
 				IDENTIFIER => {
-					expr.push_back(SYMBOL(t.lexeme()));
+					expr.push_back(SYMBOL(t.lexeme(), t.span()));
 					if self.check_val() {
 						break;
 					}
@@ -1170,7 +1919,7 @@ impl<'a> ParserInfo<'a> {
 							let name = if t.kind() == DOUBLE_COLON {
 								let mut start = {
 									let mut start = Expression::with_capacity(2);
-									if let Some(SYMBOL(lexeme)) = expr.get(1) {
+									if let Some(SYMBOL(lexeme, _)) = expr.get(1) {
 										if lexeme == " and " {
 											start.push_back(expr[0].clone());
 											start.push_back(expr[1].clone());
@@ -1181,22 +1930,25 @@ impl<'a> ParserInfo<'a> {
 								let mut expr_self = Expression::with_capacity(expr.len());
 								expr_self.append(&mut expr);
 								let name = self.get_next_internal_var();
+								let span = expr_span(&expr_self);
 								self.expr.push_back(VARIABLE {
 									local: true,
 									names: vec![name.clone()],
 									values: vec![expr_self],
+									types: Vec::new(),
 									line,
+									span,
 								});
 								expr.append(&mut start);
-								expr.push_back(SYMBOL(name.clone()));
+								expr.push_back(SYMBOL(name.clone(), span));
 								name
 							} else {
-								let SYMBOL(name) = &expr[0] else {
+								let SYMBOL(name, _) = &expr[0] else {
 									unreachable!();
 								};
 								name.to_owned()
 							};
-							expr.push_back(SYMBOL(String::from(".")));
+							expr.push_back(SYMBOL(String::from("."), t.span()));
 							self.tokens
 								.insert(self.current + 2, Token::new(IDENTIFIER, name, line, 0));
 							if self.peek(3).kind() != ROUND_BRACKET_CLOSED {
@@ -1211,7 +1963,7 @@ impl<'a> ParserInfo<'a> {
 						}
 						_ => {
 							let t = self.peek(1);
-							return Err(self.expected("(", &t.lexeme(), t.line(), t.column()));
+							return Err(self.expected("(", &t.lexeme(), t.span().start));
 						}
 					}
 				}
@@ -1219,16 +1971,18 @@ impl<'a> ParserInfo<'a> {
 					safe_indexing |=
 						self.build_safe_index(SQUARE_BRACKET_OPEN, t.kind(), &mut expr);
 					let qexpr = self.build_expression(Some((SQUARE_BRACKET_CLOSED, "]")))?;
-					expr.push_back(SYMBOL(String::from("[(")));
-					expr.push_back(EXPR(qexpr));
-					expr.push_back(SYMBOL(String::from(")]")));
+					let span = expr_span(&qexpr);
+					expr.push_back(SYMBOL(String::from("[("), t.span()));
+					expr.push_back(EXPR(qexpr, span));
+					expr.push_back(SYMBOL(String::from(")]"), self.look_back(0).span()));
 					if self.check_val() {
 						break;
 					}
 				}
 				ROUND_BRACKET_OPEN | SAFE_CALL => {
 					safe_indexing |= self.build_safe_index(ROUND_BRACKET_OPEN, t.kind(), &mut expr);
-					expr.push_back(CALL(self.build_call()?));
+					let args = self.build_call()?;
+					expr.push_back(CALL(args, self.span_from(t.span())));
 					if self.check_val() {
 						break;
 					}
@@ -1239,7 +1993,7 @@ impl<'a> ParserInfo<'a> {
 		Ok((expr, safe_indexing))
 	}
 
-	fn get_code_block_start(&mut self) -> Result<usize, String> {
+	fn get_code_block_start(&mut self) -> Result<usize, Box<Diagnostic>> {
 		let t = self.advance();
 		if t.kind() != CURLY_BRACKET_OPEN {
 			self.current -= 2;
@@ -1247,29 +2001,36 @@ impl<'a> ParserInfo<'a> {
 			if t2.kind() == CURLY_BRACKET_OPEN {
 				Ok(t2.line())
 			} else {
-				Err(self.expected("{", &t.lexeme(), t.line(), t.column()))
+				Err(self.expected("{", &t.lexeme(), t.span().start))
 			}
 		} else {
 			Ok(t.line())
 		}
 	}
 
-	fn parse_code_block(
-		&mut self,
-		mut tokens: Vec<Token>,
-		//locals: LocalsList,
-	) -> Result<Expression, String> {
+	fn parse_code_block(&mut self, mut tokens: Vec<Token>) -> Result<Expression, Box<Diagnostic>> {
 		if tokens.is_empty() {
 			Ok(Expression::new())
 		} else {
-			tokens.push(self.tokens.last().unwrap().clone());
-			let (ctokens, statics) = parse_tokens(tokens, self.filename, self.options)?;
-			self.statics += &statics;
-			Ok(ctokens)
+			// the block ends at its closing bracket, not at the end of the file
+			let close = self.look_back(0).span().start;
+			tokens.push(Token::new(EOF, "<end>", close.line, close.column).with_offset(close.offset));
+			match ParserInfo::new(tokens, self.filename, self.options).parse() {
+				Ok(mut i) => {
+					self.statics += &i.take_statics();
+					self.static_names.append(&mut i.static_names);
+					self.symbols.append(&mut i.symbols);
+					Ok(i.expr)
+				}
+				Err(errors) => {
+					self.errors.extend(errors);
+					Ok(Expression::new())
+				}
+			}
 		}
 	}
 
-	fn build_code_block(&mut self /* , locals: LocalsList */) -> Result<CodeBlock, String> {
+	fn build_code_block(&mut self) -> Result<CodeBlock, Box<Diagnostic>> {
 		let start = self.get_code_block_start()?;
 		let mut tokens: Vec<Token> = Vec::new();
 		let mut cscope = 1u8;
@@ -1285,37 +2046,24 @@ impl<'a> ParserInfo<'a> {
 						break;
 					}
 				}
-				EOF => return Err(self.expected_before("}", "<end>", t.line(), t.column())),
+				EOF => return Err(self.expected_before("}", "<end>", t.span().start)),
 				_ => {}
 			}
 			tokens.push(t.into_owned());
 		}
-		let code = self.parse_code_block(tokens /* , locals */)?;
+		let code = self.parse_code_block(tokens)?;
 		Ok(CodeBlock { start, code, end })
 	}
 
-	fn build_function_block(
-		&mut self,
-		//args: Option<Vec<(String, LuaType)>>,
-	) -> Result<CodeBlock, String> {
-		/*
-		if let Some(args) = args {
-			self.build_code_block({
-				let mut locals = self.locals.clone().unwrap();
-				for (name, luatype) in args {
-					locals.insert(name, luatype);
-				}
-				Some(locals)
-			})
-		} else {*/
-		self.build_code_block(/*self.locals.clone()*/)
-		//}
+	fn build_function_block(&mut self) -> Result<CodeBlock, Box<Diagnostic>> {
+		self.build_code_block()
 	}
 
-	fn build_loop_block(&mut self) -> Result<CodeBlock, String> {
+	fn build_loop_block(&mut self) -> Result<CodeBlock, Box<Diagnostic>> {
 		let mut hascontinue: Option<String> = None;
 		let mut is_in_other_loop = false;
 		let start = self.get_code_block_start()?;
+		let open = self.look_back(0).span();
 		let mut tokens: Vec<Token> = Vec::new();
 		let mut cscope = 1u8;
 		let end: usize;
@@ -1344,44 +2092,54 @@ impl<'a> ParserInfo<'a> {
 						continue;
 					}
 				}
-				EOF => return Err(self.expected_before("}", "<end>", t.line(), t.column())),
+				EOF => return Err(self.expected_before("}", "<end>", t.span().start)),
 				_ => {}
 			}
 			tokens.push(t.into_owned());
 		}
-		let mut code = self.parse_code_block(tokens /* , self.locals.clone() */)?;
+		let close = self.look_back(0).span();
+		let span = self.span_from(open);
+		let mut code = self.parse_code_block(tokens)?;
 		if let Some(name) = hascontinue {
 			use ContinueMode::*;
 			match self.options.env_continue {
 				Simple => {}
-				Goto | LuaJIT => code.push_back(SYMBOL(String::from("::continue::"))),
+				Goto | LuaJIT => code.push_back(SYMBOL(String::from("::continue::"), close)),
 				MoonScript => {
 					code.push_back(ALTER {
 						kind: DEFINE,
-						names: vec_deque![vec_deque![SYMBOL(name.clone())]],
-						values: vec![vec_deque![SYMBOL(String::from("true"))]],
+						names: vec_deque![vec_deque![SYMBOL(name.clone(), close)]],
+						values: vec![vec_deque![SYMBOL(String::from("true"), close)]],
 						line: end,
+						span: close,
 					});
 					code = vec_deque![
 						VARIABLE {
 							local: true,
 							names: vec![name.clone()],
-							values: vec![vec_deque![SYMBOL(String::from("false"))]],
-							line: start
+							values: vec![vec_deque![SYMBOL(String::from("false"), open)]],
+							types: Vec::new(),
+							line: start,
+							span: open,
 						},
 						LOOP_UNTIL {
-							condition: vec_deque![SYMBOL(String::from("true"))],
+							condition: vec_deque![SYMBOL(String::from("true"), span)],
 							code: CodeBlock { start, code, end },
-							line: start
+							line: start,
+							span,
 						},
 						IF_STATEMENT {
-							condition: vec_deque![SYMBOL(String::from("not ")), SYMBOL(name)],
+							condition: vec_deque![
+								SYMBOL(String::from("not "), close),
+								SYMBOL(name, close)
+							],
 							code: CodeBlock {
 								start: end,
-								code: vec_deque![BREAK_LOOP],
+								code: vec_deque![BREAK_LOOP(close)],
 								end
 							},
-							next: None
+							next: None,
+							span: close,
 						}
 					]
 				}
@@ -1390,7 +2148,7 @@ impl<'a> ParserInfo<'a> {
 		Ok(CodeBlock { start, code, end })
 	}
 
-	fn build_identifier_list(&mut self) -> Result<Vec<String>, String> {
+	fn build_identifier_list(&mut self) -> Result<Vec<String>, Box<Diagnostic>> {
 		let mut idents: Vec<String> = Vec::new();
 		loop {
 			let t = self.assert_advance(IDENTIFIER, "<name>")?;
@@ -1401,35 +2159,92 @@ impl<'a> ParserInfo<'a> {
 		}
 	}
 
-	fn build_function_args(&mut self) -> Result</* ArgsAndTypes */ FunctionArgs, String> {
-		let mut args = FunctionArgs::new();
-		/*let mut types: Option<Vec<(String, LuaType)>> = if self.locals.is_some() {
-			Some(Vec::new())
-		} else {
-			None
-		};*/
-		while {
-			let name = {
-				let t = self.advance();
+	fn build_type(&mut self) -> Result<Option<LuaType>, Box<Diagnostic>> {
+		if !self.advance_if(COLON) {
+			return Ok(None);
+		}
+		let mut types = Vec::new();
+		loop {
+			let t = self.advance();
+			types.push(match t.kind() {
+				NIL => LuaType::NIL,
+				FN => LuaType::FUNCTION,
+				IDENTIFIER => match t.lexeme().as_str() {
+					"any" => LuaType::ANY,
+					"bool" | "boolean" => LuaType::BOOLEAN,
+					"number" => LuaType::NUMBER,
+					"string" => LuaType::STRING,
+					"table" => LuaType::TABLE,
+					"userdata" => LuaType::USERDATA,
+					"thread" => LuaType::THREAD,
+					name => {
+						return Err(self.error(
+							"unknown-type",
+							format!("Unknown type '{name}'"),
+							t.span().start,
+						))
+					}
+				},
+				_ => return Err(self.expected("<type>", &t.lexeme(), t.span().start)),
+			});
+			if self.advance_if(QUESTION_MARK) {
+				types.push(LuaType::NIL);
+			}
+			if !self.advance_if(BIT_OR) {
+				break;
+			}
+		}
+		Ok(Some(if types.len() == 1 {
+			types.remove(0)
+		} else {
+			LuaType::UNION(types)
+		}))
+	}
+
+	fn build_typed_identifier_list(
+		&mut self,
+		local: bool,
+	) -> Result<TypedIdentifiers, Box<Diagnostic>> {
+		let mut idents: Vec<String> = Vec::new();
+		let mut types: Vec<Option<LuaType>> = Vec::new();
+		loop {
+			let t = self.assert_advance(IDENTIFIER, "<name>")?;
+			self.add_symbol(t.lexeme(), SymbolKind::Variable, local, t.span());
+			idents.push(t.lexeme());
+			types.push(self.build_type()?);
+			if !self.advance_if(COMMA) {
+				if types.iter().all(Option::is_none) {
+					types.clear();
+				}
+				break Ok((idents, types));
+			}
+		}
+	}
+
+	fn build_function_args(&mut self) -> Result<ArgsAndTypes, Box<Diagnostic>> {
+		let mut args = FunctionArgs::new();
+		let mut types: Vec<Option<LuaType>> = Vec::new();
+		if self.advance_if(ROUND_BRACKET_CLOSED) {
+			let returns = self.build_type()?;
+			return Ok((args, (types, returns)));
+		}
+		while {
+			let name = {
+				let t = self.advance();
 				match t.kind() {
 					IDENTIFIER => t,
 					THREEDOTS => {
 						self.assert_compare(ROUND_BRACKET_CLOSED, ")")?;
 						t
 					}
-					_ => return Err(self.expected("<name>", &t.lexeme(), t.line(), t.column())),
+					_ => return Err(self.expected("<name>", &t.lexeme(), t.span().start)),
 				}
 			};
-			/*if let Some(types) = &mut types {
-				types.push((
-					name.lexeme(),
-					if name.kind() == THREEDOTS {
-						LuaType::ANY
-					} else {
-						self.build_type()?
-					},
-				))
-			}*/
+			types.push(if name.kind() == THREEDOTS {
+				None
+			} else {
+				self.build_type()?
+			});
 			let t = self.advance();
 			match t.kind() {
 				COMMA => {
@@ -1439,60 +2254,72 @@ impl<'a> ParserInfo<'a> {
 				DEFINE => {
 					let default = self.build_expression(None)?;
 					args.push((name.lexeme(), Some((default, name.line()))));
-					let notended = self.peek(0).kind() != CURLY_BRACKET_OPEN;
-					if notended {
-						match self.look_back(0).kind() {
-							COMMA => {}
-							ROUND_BRACKET_CLOSED => self.current -= 1,
-							_ => {
-								let t = self.peek(0);
-								return Err(self.expected(")", &t.lexeme(), t.line(), t.column()));
-							}
+					match self.look_back(0).kind() {
+						COMMA => true,
+						ROUND_BRACKET_CLOSED => false,
+						_ => {
+							let t = self.peek(0);
+							return Err(self.expected(")", &t.lexeme(), t.span().start));
 						}
 					}
-					notended
 				}
 				ROUND_BRACKET_CLOSED => {
 					args.push((name.lexeme(), None));
 					false
 				}
-				_ => return Err(self.expected(")", &t.lexeme(), t.line(), t.column())),
+				_ => return Err(self.expected(")", &t.lexeme(), t.span().start)),
 			}
 		} {}
-		Ok(/* (args, types) */ args)
+		let returns = self.build_type()?;
+		Ok((args, (types, returns)))
 	}
 
-	fn build_elseif_chain(&mut self, condition: Option<Expression>) -> Result<ComplexToken, String> {
+	fn build_elseif_chain(
+		&mut self,
+		condition: Option<Expression>,
+	) -> Result<ComplexToken, Box<Diagnostic>> {
+		let keyword = match &condition {
+			Some(condition) => expr_span(condition),
+			None => self.look_back(0).span(),
+		};
 		let condition = match condition {
 			Some(condition) => condition,
 			None => {
 				if self.advance_if(LOCAL) {
-					let start = self.look_back(0).line();
+					let local = self.look_back(0).span();
+					let start = local.start.line;
 					let destructure = self.advance_if(CURLY_BRACKET_OPEN);
-					let (vars, mut code) = self.use_internal_stack(
-						|i| i.build_variables(true, start, destructure)
-					)?;
+					let (vars, mut code) =
+						self.use_internal_stack(|i| i.build_variables(true, local, destructure))?;
 					let (condition, end) = {
-						let VARIABLE {names, line: end, ..} = &vars else {
+						let VARIABLE {
+							names,
+							line: end,
+							span,
+							..
+						} = &vars
+						else {
 							unreachable!()
 						};
 						let mut condition = Expression::with_capacity(names.len());
 						let mut names = names.iter();
 						let first = names.next().unwrap();
-						condition.push_back(SYMBOL(format_clue!(first, " ~= nil")));
+						condition.push_back(SYMBOL(format_clue!(first, " ~= nil"), *span));
 						for name in names {
-							condition.push_back(SYMBOL(format_clue!(" and ", name, " ~= nil")))
+							condition
+								.push_back(SYMBOL(format_clue!(" and ", name, " ~= nil"), *span))
 						}
 						(condition, *end)
 					};
 					code.push_back(vars);
 					code.push_back(self.build_elseif_chain(Some(condition))?);
-					return Ok(DO_BLOCK(CodeBlock { start, code, end }))
+					let span = self.span_from(keyword);
+					return Ok(DO_BLOCK(CodeBlock { start, code, end }, span));
 				}
 				self.build_expression(Some((CURLY_BRACKET_OPEN, "{")))?
 			}
 		};
-		let code = self.build_code_block(/*self.locals.clone()*/)?;
+		let code = self.build_code_block()?;
 		Ok(IF_STATEMENT {
 			condition,
 			code,
@@ -1500,19 +2327,21 @@ impl<'a> ParserInfo<'a> {
 				let t = self.advance();
 				match t.kind() {
 					ELSEIF => Some(Box::new(self.build_elseif_chain(None)?)),
-					ELSE => Some(Box::new(DO_BLOCK(
-						self.build_code_block(/*self.locals.clone()*/)?,
-					))),
+					ELSE => {
+						let block = self.build_code_block()?;
+						Some(Box::new(DO_BLOCK(block, self.span_from(t.span()))))
+					}
 					_ => {
 						self.current -= 1;
 						None
 					}
 				}
 			},
+			span: self.span_from(keyword),
 		})
 	}
 
-	fn build_enums(&mut self, local: bool) -> Result<Expression, String> {
+	fn build_enums(&mut self, local: bool) -> Result<Expression, Box<Diagnostic>> {
 		self.current += 1;
 		self.assert(CURLY_BRACKET_OPEN, "{")?;
 		let mut enums = Expression::new();
@@ -1522,98 +2351,272 @@ impl<'a> ParserInfo<'a> {
 				break;
 			}
 			let name = self.assert_advance(IDENTIFIER, "<name>")?;
+			self.add_symbol(name.lexeme(), SymbolKind::EnumEntry, local, name.span());
 			let t = self.advance();
 			let value = match t.kind() {
 				CURLY_BRACKET_CLOSED => {
 					self.current -= 1;
 					n += 1;
-					SYMBOL(n.to_string())
+					SYMBOL(n.to_string(), name.span())
 				}
 				COMMA => {
 					n += 1;
-					SYMBOL(n.to_string())
+					SYMBOL(n.to_string(), name.span())
 				}
 				DEFINE => {
 					let t = self.advance();
 					if t.kind() != NUMBER {
 						return Err(self.error(
+							"invalid-enum",
 							"Enums values should be a non-float number ranging from -32768 to 32767.",
-							t.line(),
-							t.column()
+							t.span().start
 						));
 					}
-					n = check!(t.lexeme().parse());
+					n = t.lexeme().parse().map_err(|e: std::num::ParseIntError| {
+						self.error("invalid-enum", e.to_string(), t.span().start)
+					})?;
 					self.advance_if(COMMA);
-					SYMBOL(n.to_string())
+					SYMBOL(n.to_string(), t.span())
 				}
-				_ => return Err(self.expected("}", &t.lexeme(), t.line(), t.column())),
+				_ => return Err(self.expected("}", &t.lexeme(), t.span().start)),
 			};
+			let span = Span::range(name.span().start, value.span().end);
 			enums.push_back(VARIABLE {
 				line: name.line(),
 				local,
 				names: vec![name.lexeme()],
 				values: vec![vec_deque![value]],
+				types: Vec::new(),
+				span,
 			});
 		}
-		/*if let Some(locals) = &mut self.locals {
-			for r#enum in &enums {
-				if let VARIABLE { names, .. } = r#enum {
-					locals.insert(names[0].clone(), LuaType::NUMBER);
+		Ok(enums)
+	}
+
+	fn build_struct_function(
+		&mut self,
+		name: String,
+		kind: SymbolKind,
+		local: bool,
+		span: Span,
+	) -> Result<(ArgsAndTypes, CodeBlock), Box<Diagnostic>> {
+		self.assert(ROUND_BRACKET_OPEN, "(")?;
+		let args = self.build_function_args()?;
+		self.add_symbol(name, kind, local, span).args = arg_names(&args.0);
+		let code = self.build_function_block()?;
+		Ok((args, code))
+	}
+
+	fn build_struct(&mut self, local: bool) -> Result<Expression, Box<Diagnostic>> {
+		let keyword = self.look_back(0).span();
+		let line = self.advance().line();
+		let t = self.assert_advance(IDENTIFIER, "<name>")?;
+		let name = t.lexeme();
+		let name_span = t.span();
+		self.add_symbol(t.lexeme(), SymbolKind::Struct, local, t.span());
+		self.assert(CURLY_BRACKET_OPEN, "{")?;
+		let mut fields: Vec<(Option<Expression>, Expression, usize)> = Vec::new();
+		let mut constructor: Option<((ArgsAndTypes, CodeBlock), Span)> = None;
+		let mut functions = Expression::new();
+		while !self.advance_if(CURLY_BRACKET_CLOSED) {
+			let t = self.advance();
+			match t.kind() {
+				IDENTIFIER => {
+					let value = if self.advance_if(DEFINE) {
+						let value = self.build_expression(None)?;
+						self.current -= 1;
+						value
+					} else {
+						vec_deque![SYMBOL(String::from("nil"), t.span())]
+					};
+					let key = vec_deque![SYMBOL(t.lexeme(), t.span())];
+					fields.push((Some(key), value, t.line()));
+				}
+				CONSTRUCTOR => {
+					if constructor.is_some() {
+						return Err(self.error(
+							"invalid-struct",
+							format!("Struct '{name}' already has a constructor"),
+							t.span().start,
+						));
+					}
+					let symbol_name = format_clue!(name, ".new");
+					let function = self.build_struct_function(
+						symbol_name,
+						SymbolKind::Function,
+						local,
+						t.span(),
+					)?;
+					constructor = Some((function, self.span_from(t.span())));
 				}
+				FN | METHOD => {
+					let (index, kind) = if t.kind() == METHOD {
+						(":", SymbolKind::Method)
+					} else {
+						(".", SymbolKind::Function)
+					};
+					let start = t.span();
+					let t = self.assert_advance(IDENTIFIER, "<name>")?;
+					let fname = t.lexeme();
+					let symbol_name = format_clue!(name, index, fname);
+					let ((args, types), code) =
+						self.build_struct_function(symbol_name, kind, local, t.span())?;
+					functions.push_back(FUNCTION {
+						local: false,
+						name: vec_deque![
+							SYMBOL(name.clone(), name_span),
+							SYMBOL(index.to_owned(), t.span()),
+							SYMBOL(fname, t.span())
+						],
+						args,
+						types,
+						code,
+						span: self.span_from(start),
+					});
+				}
+				EOF => return Err(self.expected_before("}", "<end>", t.span().start)),
+				_ => return Err(self.expected("}", &t.lexeme(), t.span().start)),
 			}
-		}*/
-		Ok(enums)
+			self.advance_if(COMMA);
+		}
+		let span = self.span_from(keyword);
+		let (((mut args, types), mut code), constructor_span) = constructor.unwrap_or_else(|| {
+			(
+				(
+					ArgsAndTypes::default(),
+					CodeBlock {
+						start: line,
+						code: Expression::new(),
+						end: line,
+					},
+				),
+				span,
+			)
+		});
+		// the defaults of the fields are created before the arguments can shadow what they use,
+		// so those arguments are renamed and only get their names back after `self`
+		let mut renamed = Vec::new();
+		for (i, (arg, _)) in args.iter_mut().enumerate() {
+			if arg != "..." && fields.iter().any(|(_, value, _)| references(value, arg)) {
+				let internal = self.get_next_internal_var();
+				let kind = types.0.get(i).cloned().flatten();
+				renamed.push((std::mem::replace(arg, internal.clone()), internal, kind));
+			}
+		}
+		if !renamed.is_empty() {
+			for (from, to, _) in &renamed {
+				for (_, default) in &mut args {
+					if let Some((default, _)) = default {
+						Rename { from, to }.visit_expression_mut(default);
+					}
+				}
+			}
+			let mut kinds: Vec<Option<LuaType>> = Vec::new();
+			let (names, values) = renamed
+				.into_iter()
+				.map(|(from, to, kind)| {
+					kinds.push(kind);
+					(from, vec_deque![SYMBOL(to, constructor_span)])
+				})
+				.unzip();
+			if kinds.iter().all(Option::is_none) {
+				kinds.clear();
+			}
+			code.code.push_front(VARIABLE {
+				local: true,
+				names,
+				values,
+				types: kinds,
+				line: code.start,
+				span: constructor_span,
+			});
+		}
+		code.code.push_front(VARIABLE {
+			local: true,
+			names: vec![String::from("self")],
+			values: vec![vec_deque![TABLE {
+				values: fields,
+				metas: Vec::new(),
+				metatable: Some(name.clone()),
+				span,
+			}]],
+			types: Vec::new(),
+			line: code.start,
+			span: constructor_span,
+		});
+		ConstructorReturns.visit_block_mut(&mut code);
+		if !matches!(code.code.back(), Some(RETURN_EXPR(..))) {
+			let this = vec_deque![SYMBOL(String::from("self"), constructor_span)];
+			code.code
+				.push_back(RETURN_EXPR(Some(vec![this]), constructor_span));
+		}
+		let mut result = vec_deque![
+			VARIABLE {
+				local,
+				names: vec![name.clone()],
+				values: vec![vec_deque![TABLE {
+					values: Vec::new(),
+					metas: Vec::new(),
+					metatable: None,
+					span,
+				}]],
+				types: Vec::new(),
+				line,
+				span,
+			},
+			ALTER {
+				kind: DEFINE,
+				names: vec_deque![vec_deque![
+					SYMBOL(name.clone(), name_span),
+					SYMBOL(String::from("."), name_span),
+					SYMBOL(String::from("__index"), name_span)
+				]],
+				values: vec![vec_deque![SYMBOL(name.clone(), name_span)]],
+				line,
+				span,
+			},
+			FUNCTION {
+				local: false,
+				name: vec_deque![
+					SYMBOL(name, name_span),
+					SYMBOL(String::from("."), name_span),
+					SYMBOL(String::from("new"), name_span)
+				],
+				args,
+				types,
+				code,
+				span: constructor_span,
+			}
+		];
+		result.append(&mut functions);
+		Ok(result)
 	}
 
-	fn build_function(&mut self, local: bool) -> Result<ComplexToken, String> {
+	fn build_function(&mut self, local: bool) -> Result<ComplexToken, Box<Diagnostic>> {
+		let keyword = self.look_back(0).span();
 		self.current += 1;
 		let t = self.assert_advance(IDENTIFIER, "<name>")?;
-		let name = vec_deque![SYMBOL(t.lexeme())];
+		let name = vec_deque![SYMBOL(t.lexeme(), t.span())];
 		self.assert(ROUND_BRACKET_OPEN, "(")?;
-		let /*(*/args/*, types)*/ = if !self.advance_if(ROUND_BRACKET_CLOSED) {
-			self.build_function_args()?
-		} else {
-			/*(*/FunctionArgs::new()//, None)
-		};
-		let code = self.build_function_block(/*types*/)?;
-		/*if self.locals.is_some() {
-			self.add_variable(t.lexeme(), LuaType::NIL);
-		}*/
+		let (args, types) = self.build_function_args()?;
+		let symbol = self.add_symbol(t.lexeme(), SymbolKind::Function, local, t.span());
+		symbol.args = arg_names(&args);
+		let code = self.build_function_block()?;
 		Ok(FUNCTION {
 			local,
 			name,
 			args,
+			types,
 			code,
+			span: self.span_from(keyword),
 		})
 	}
-	/*
-		fn add_variable(&mut self, name: String, luatype: LuaType) {
-			if let Some(locals) = &mut self.locals {
-				locals.insert(name, luatype);
-			}
-		}
-
-		fn build_type(&mut self) -> Result<LuaType, String> {
-			if self.advance_if(COLON) {
-				Ok(LuaType::ANY) //PLACEHOLDER
-			} else {
-				Ok(LuaType::NIL)
-			}
-		}
-	*/
-	/*fn build_variable(&mut self) -> Result</*(*/ String /*, LuaType)*/, String> {
-		let name = self.assert_advance(IDENTIFIER, "<name>")?.lexeme();
-		/*if self.locals.is_some() {
-			let luatype = self.build_type()?;
-			self.add_variable(name.to_string(), luatype.clone());
-			Ok((name, luatype))
-		} else {*/
-		Ok(/*(*/ name /*, LuaType::ANY)*/)
-		//}
-	}*/
 
 	#[allow(clippy::type_complexity)]
-	fn build_destructure_table(&mut self) -> Result<(Vec<String>, Vec<String>, Vec<String>), String> {
+	fn build_destructure_table(
+		&mut self,
+		local: bool,
+	) -> Result<(Vec<String>, Vec<String>, Vec<String>), Box<Diagnostic>> {
 		let mut names = Vec::new();
 		let mut key_names = Vec::new();
 		let name = self.get_next_internal_var();
@@ -1623,6 +2626,7 @@ impl<'a> ParserInfo<'a> {
 			&mut key_names,
 			&mut internal_names,
 			name + ".",
+			local,
 		)?;
 		Ok((names, key_names, internal_names))
 	}
@@ -1633,7 +2637,8 @@ impl<'a> ParserInfo<'a> {
 		key_names: &mut Vec<String>,
 		internal_names: &mut Vec<String>,
 		key_start: String,
-	) -> Result<(), String> {
+		local: bool,
+	) -> Result<(), Box<Diagnostic>> {
 		loop {
 			let t = self.assert_advance(IDENTIFIER, "<name>")?;
 			names.push(if self.advance_if(ARROW) {
@@ -1646,6 +2651,7 @@ impl<'a> ParserInfo<'a> {
 						key_names,
 						internal_names,
 						name + ".",
+						local,
 					)?;
 					if self.advance_if(COMMA) {
 						continue;
@@ -1654,9 +2660,12 @@ impl<'a> ParserInfo<'a> {
 						break Ok(());
 					}
 				} else {
-					self.assert_advance(IDENTIFIER, "<name>")?.lexeme()
+					let t = self.assert_advance(IDENTIFIER, "<name>")?;
+					self.add_symbol(t.lexeme(), SymbolKind::Variable, local, t.span());
+					t.lexeme()
 				}
 			} else {
+				self.add_symbol(t.lexeme(), SymbolKind::Variable, local, t.span());
 				t.lexeme()
 			});
 			key_names.push(format_clue!(key_start, t.lexeme()));
@@ -1672,6 +2681,7 @@ impl<'a> ParserInfo<'a> {
 		internal_names: Vec<String>,
 		values: Vec<Expression>,
 		line: usize,
+		span: Span,
 	) {
 		let prev_expr = self.get_prev_expr();
 		let mut names = internal_names.into_iter();
@@ -1679,14 +2689,18 @@ impl<'a> ParserInfo<'a> {
 			local: true,
 			names: vec![names.next().unwrap()],
 			values,
+			types: Vec::new(),
 			line,
+			span,
 		});
 		while let (Some(prev_name), Some(name)) = (names.next(), names.next()) {
 			prev_expr.push_back(VARIABLE {
 				local: true,
 				names: vec![name.clone()],
-				values: vec![vec_deque![SYMBOL(prev_name)]],
+				values: vec![vec_deque![SYMBOL(prev_name, span)]],
+				types: Vec::new(),
 				line,
+				span,
 			});
 		}
 	}
@@ -1694,14 +2708,16 @@ impl<'a> ParserInfo<'a> {
 	fn build_variables(
 		&mut self,
 		local: bool,
-		line: usize,
+		keyword: Span,
 		destructure: bool,
-	) -> Result<ComplexToken, String> {
-		let (names, destructure) = if destructure {
-			let (names, key_names, internal_names) = self.build_destructure_table()?;
-			(names, Some((key_names, internal_names)))
+	) -> Result<ComplexToken, Box<Diagnostic>> {
+		let line = keyword.start.line;
+		let (names, types, destructure) = if destructure {
+			let (names, key_names, internal_names) = self.build_destructure_table(local)?;
+			(names, Vec::new(), Some((key_names, internal_names)))
 		} else {
-			(self.build_identifier_list()?, None)
+			let (names, types) = self.build_typed_identifier_list(local)?;
+			(names, types, None)
 		};
 		let check = self.advance().kind();
 		let mut values: Vec<Expression> = if check != DEFINE {
@@ -1711,34 +2727,43 @@ impl<'a> ParserInfo<'a> {
 			if local {
 				Vec::new()
 			} else {
-				/*if let Some(_locals) = &self.locals {
-					//println!("{:?}", locals);
-				} else {
-					self.warning("Defining external globals will not do anything if you don't have type checking enabled!", line)
-				}*/
 				self.current -= 1;
-				return Ok(SYMBOL(String::new()));
+				return Ok(SYMBOL(String::new(), keyword));
 			}
 		} else {
 			self.find_expressions(None)?
 		};
 		self.current -= 1;
+		let span = self.span_from(keyword);
 		if let Some((key_names, internal_names)) = destructure {
-			self.build_table_destructuring(internal_names, values, line);
+			self.build_table_destructuring(internal_names, values, line, span);
 			values = Vec::new();
 			for key_name in key_names {
-				values.push(vec_deque![SYMBOL(key_name)])
+				values.push(vec_deque![SYMBOL(key_name, span)])
 			}
 		}
 		Ok(VARIABLE {
 			local,
 			names,
 			values,
+			types,
 			line,
+			span,
 		})
 	}
 
-	fn compile_static(&mut self, expr: Expression) -> Result<(), String> {
+	fn compile_static(&mut self, expr: Expression) -> Result<(), Box<Diagnostic>> {
+		for t in &expr {
+			match t {
+				VARIABLE { names, .. } => self.static_names.extend(names.iter().cloned()),
+				FUNCTION { name, .. } => {
+					if let Some(SYMBOL(name, _)) = name.front() {
+						self.static_names.push(name.clone())
+					}
+				}
+				_ => {}
+			}
+		}
 		let code = self.compiler.compile_tokens(0, expr)?;
 		self.statics += &(code + "\n");
 		Ok(())
@@ -1747,8 +2772,11 @@ impl<'a> ParserInfo<'a> {
 	fn build_match_block(
 		&mut self,
 		name: String,
-		func: &impl Fn(&mut ParserInfo<'a> /* , LocalsList */) -> Result<CodeBlock, String>,
-	) -> Result<ComplexToken, String> {
+		func: &impl Fn(
+			&mut ParserInfo<'a>, /* , LocalsList */
+		) -> Result<CodeBlock, Box<Diagnostic>>,
+	) -> Result<ComplexToken, Box<Diagnostic>> {
+		let keyword = self.look_back(0).span();
 		let line = self.peek(0).line();
 		let value = self.build_expression(Some((CURLY_BRACKET_OPEN, "{")))?;
 		let mut branches: Vec<MatchCase> = Vec::new();
@@ -1759,12 +2787,12 @@ impl<'a> ParserInfo<'a> {
 					ARROW => {
 						if branches.is_empty() {
 							return Err(self.error(
+								"invalid-match",
 								"The default case (with no extra if) of a match block must be the last case, not the first",
-								t.line(),
-								t.column()
+								t.span().start
 							));
 						}
-						branches.push((Vec::new(), Expression::new(), None, func(self /* , self.locals.clone() */)?));
+						branches.push((Vec::new(), Expression::new(), None, func(self)?));
 						self.assert(CURLY_BRACKET_CLOSED, "}")?;
 						false
 					}
@@ -1772,15 +2800,10 @@ impl<'a> ParserInfo<'a> {
 						let (extra_if, internal_expr) = self.use_internal_stack(|i|
 							i.build_expression(Some((ARROW, "=>")))
 						)?;
-						branches.push((
-							Vec::new(),
-							internal_expr,
-							Some(extra_if),
-							func(self /* , self.locals.clone() */)?,
-						));
+						branches.push((Vec::new(), internal_expr, Some(extra_if), func(self)?));
 						!self.advance_if(CURLY_BRACKET_CLOSED)
 					}
-					_ => return Err(self.expected("=>", &t.lexeme(), t.line(), t.column())),
+					_ => return Err(self.expected("=>", &t.lexeme(), t.span().start)),
 				}
 			} else {
 				let ((expr, extra_if), internal_expr) = self.use_internal_stack(|i| {
@@ -1789,7 +2812,7 @@ impl<'a> ParserInfo<'a> {
 					let extra_if = match t.kind() {
 						ARROW => None,
 						IF => Some(i.build_expression(Some((ARROW, "=>")))?),
-						_ => return Err(i.expected("=>", &t.lexeme(), t.line(), t.column()))
+						_ => return Err(i.expected("=>", &t.lexeme(), t.span().start))
 					};
 					Ok((expr, extra_if))
 				})?;
@@ -1797,7 +2820,7 @@ impl<'a> ParserInfo<'a> {
 				let mut current = Expression::with_capacity(3);
 				for ctoken in expr {
 					match ctoken {
-						SYMBOL(lexeme) if lexeme == " or " => {
+						SYMBOL(lexeme, _) if lexeme == " or " => {
 							conditions.push(current.clone());
 							current.clear();
 						}
@@ -1807,12 +2830,7 @@ impl<'a> ParserInfo<'a> {
 				if !current.is_empty() {
 					conditions.push(current);
 				}
-				branches.push((
-					conditions,
-					internal_expr,
-					extra_if,
-					func(self /* , self.locals.clone() */)?
-				));
+				branches.push((conditions, internal_expr, extra_if, func(self)?));
 				!self.advance_if(CURLY_BRACKET_CLOSED)
 			}
 		} {}
@@ -1821,10 +2839,11 @@ impl<'a> ParserInfo<'a> {
 			value,
 			branches,
 			line,
+			span: self.span_from(keyword),
 		})
 	}
 
-	fn parse_token_local_global(&mut self, t: &BorrowedToken) -> Result<(), String> {
+	fn parse_token_local_global(&mut self, t: &BorrowedToken) -> Result<(), Box<Diagnostic>> {
 		let local = t.kind() == LOCAL;
 		match self.peek(0).kind() {
 			FN => {
@@ -1835,16 +2854,21 @@ impl<'a> ParserInfo<'a> {
 				let enums = &mut self.build_enums(local)?;
 				self.expr.append(enums);
 			}
+			STRUCT => {
+				let r#struct = &mut self.build_struct(local)?;
+				self.expr.append(r#struct);
+			}
 			_ => {
 				let destructure = self.advance_if(CURLY_BRACKET_OPEN);
-				let vars = self.build_variables(local, t.line(), destructure)?;
+				let vars = self.build_variables(local, t.span(), destructure)?;
 				self.expr.push_back(vars);
 			}
 		}
 		Ok(())
 	}
 
-	fn parse_token_static(&mut self, t: &BorrowedToken) -> Result<(), String> {
+	fn parse_token_static(&mut self, t: &BorrowedToken) -> Result<(), Box<Diagnostic>> {
+		let first_symbol = self.symbols.symbols.len();
 		match self.peek(0).kind() {
 			FN => {
 				let function = vec_deque![self.build_function(true)?];
@@ -1854,15 +2878,24 @@ impl<'a> ParserInfo<'a> {
 				let enums = self.build_enums(true)?;
 				self.compile_static(enums)?;
 			}
+			STRUCT => {
+				let r#struct = self.build_struct(true)?;
+				self.compile_static(r#struct)?;
+			}
 			_ => {
-				let vars = vec_deque![self.build_variables(true, t.line(), false)?];
+				let vars = vec_deque![self.build_variables(true, t.span(), false)?];
 				self.compile_static(vars)?;
 			}
 		}
+		for symbol in &mut self.symbols.symbols[first_symbol..] {
+			symbol.is_static = true;
+		}
 		Ok(())
 	}
 
-	fn parse_token_method(&mut self) -> Result<(), String> {
+	fn parse_token_method(&mut self) -> Result<(), Box<Diagnostic>> {
+		let keyword = self.look_back(0).span();
+		let span = self.peek(0).span();
 		let name = {
 			let mut expr = Expression::with_capacity(4);
 			loop {
@@ -1871,54 +2904,61 @@ impl<'a> ParserInfo<'a> {
 					IDENTIFIER => {
 						let nt = self.peek(0);
 						if nt.kind() == IDENTIFIER {
-							return Err(self.unexpected(&nt.lexeme(), nt.line(), nt.column()));
+							return Err(self.unexpected(&nt.lexeme(), nt.span().start));
 						}
-						expr.push_back(SYMBOL(t.lexeme()))
+						expr.push_back(SYMBOL(t.lexeme(), t.span()))
 					}
 					DOT => self.check_index(&t, &mut expr, ".")?,
 					DOUBLE_COLON => {
 						self.check_index(&t, &mut expr, ":")?;
 						let t = self.peek(1);
 						if t.kind() != ROUND_BRACKET_OPEN {
-							return Err(self.expected("(", &t.lexeme(), t.line(), t.column()));
+							return Err(self.expected("(", &t.lexeme(), t.span().start));
 						}
 					}
 					ROUND_BRACKET_OPEN => break,
-					_ => return Err(self.expected("(", &t.lexeme(), t.line(), t.column())),
+					_ => return Err(self.expected("(", &t.lexeme(), t.span().start)),
 				}
 			}
 			expr
 		};
-		let /*(*/args/*, types)*/ = if !self.advance_if(ROUND_BRACKET_CLOSED) {
-			self.build_function_args()?
+		let (args, types) = self.build_function_args()?;
+		let symbol_name: String = name
+			.iter()
+			.filter_map(|t| match t {
+				SYMBOL(part, _) => Some(part.as_str()),
+				_ => None,
+			})
+			.collect();
+		let kind = if symbol_name.contains(':') {
+			SymbolKind::Method
 		} else {
-			/*(*/FunctionArgs::new()//, None)
+			SymbolKind::Function
 		};
-		let code = self.build_function_block(/*types*/)?;
+		self.add_symbol(symbol_name, kind, false, span).args = arg_names(&args);
+		let code = self.build_function_block()?;
 		//ADD FUNCTION FOR ADDING VALUES INSIDE TABLES MAYBE?
 		self.expr.push_back(FUNCTION {
 			local: false,
 			name,
 			args,
+			types,
 			code,
+			span: self.span_from(keyword),
 		});
 		Ok(())
 	}
 
-	fn parse_token_identifier(&mut self, t: &BorrowedToken) -> Result<(), String> {
+	fn parse_token_identifier(&mut self, t: &BorrowedToken) -> Result<(), Box<Diagnostic>> {
 		let start = self.current - 1;
 		let (mut first_expr, safe_indexing) = self.build_identifier_internal()?;
-		if let CALL(_) = first_expr.back().unwrap() {
+		if let CALL(..) = first_expr.back().unwrap() {
 			let line = self.at(start).line();
+			let span = expr_span(&first_expr);
 			if safe_indexing {
-				let name = SYMBOL({
-					let SYMBOL(name) = first_expr.pop_front().unwrap() else {
-						unreachable!()
-					};
-					first_expr.pop_front();
-					first_expr.pop_front();
-					name
-				});
+				let name = first_expr.pop_front().unwrap();
+				first_expr.pop_front();
+				first_expr.pop_front();
 				first_expr.push_front(name.clone());
 				self.expr.push_back(IF_STATEMENT {
 					condition: vec_deque![name],
@@ -1926,17 +2966,20 @@ impl<'a> ParserInfo<'a> {
 						start: line,
 						code: vec_deque![IDENT {
 							expr: first_expr,
-							line
+							line,
+							span,
 						}],
 						end: line,
 					},
 					next: None,
+					span,
 				});
 				self.current -= 1;
 			} else {
 				self.expr.push_back(IDENT {
 					expr: first_expr,
 					line,
+					span,
 				});
 				self.current -= 1;
 				self.advance_if(SEMICOLON);
@@ -1944,9 +2987,9 @@ impl<'a> ParserInfo<'a> {
 			return Ok(());
 		} else if safe_indexing {
 			return Err(self.error(
+				"invalid-assignment",
 				"Safe indexing cannot be used when altering variables",
-				t.line(),
-				t.column(),
+				t.span().start,
 			));
 		}
 		let mut names = vec_deque![first_expr];
@@ -1960,14 +3003,20 @@ impl<'a> ParserInfo<'a> {
 		let checkt = self.look_back(0);
 		let check = checkt.kind();
 		if check < DEFINE || check > MODULATE {
-			return Err(self.expected("=", &checkt.lexeme(), checkt.line(), checkt.column()));
+			return Err(self.expected("=", &checkt.lexeme(), checkt.span().start));
 		}
+		let operator = checkt.span();
 		let values = self.find_expressions(None)?;
+		let span = Span::range(
+			t.span().start,
+			values.last().map_or(operator, expr_span).end,
+		);
 		if check == DEFINE_COALESCE {
 			for value in values {
 				if let Some(name) = names.pop_front() {
+					let span = Span::range(expr_span(&name).start, expr_span(&value).end);
 					let mut condition = name.clone();
-					condition.push_back(SYMBOL(String::from(" == nil")));
+					condition.push_back(SYMBOL(String::from(" == nil"), operator));
 					self.expr.push_back(IF_STATEMENT {
 						condition,
 						code: CodeBlock {
@@ -1976,11 +3025,13 @@ impl<'a> ParserInfo<'a> {
 								kind: DEFINE,
 								names: vec_deque![name],
 								values: vec![value],
-								line: t.line()
+								line: t.line(),
+								span,
 							}],
 							end: t.line(),
 						},
 						next: None,
+						span,
 					});
 				} else {
 					break;
@@ -1992,15 +3043,17 @@ impl<'a> ParserInfo<'a> {
 				line: t.line(),
 				names,
 				values,
+				span,
 			});
 		}
 		self.current -= 1;
 		Ok(())
 	}
 
-	fn parse_token_round_bracket_open(&mut self) -> Result<(), String> {
+	fn parse_token_round_bracket_open(&mut self) -> Result<(), Box<Diagnostic>> {
+		let open = self.look_back(0).span();
 		let expr = self.build_expression(Some((ROUND_BRACKET_CLOSED, ")")))?;
-		self.expr.push_back(EXPR(expr));
+		self.expr.push_back(EXPR(expr, self.span_from(open)));
 		self.current += 1;
 		let call = self.build_identifier()?;
 		self.expr.push_back(call);
@@ -2009,67 +3062,96 @@ impl<'a> ParserInfo<'a> {
 		Ok(())
 	}
 
-	fn parse_token_curly_bracket_open(&mut self) -> Result<(), String> {
+	fn parse_token_curly_bracket_open(&mut self) -> Result<(), Box<Diagnostic>> {
 		self.current -= 1;
-		let block = self.build_code_block(/*self.locals.clone()*/)?;
-		self.expr.push_back(DO_BLOCK(block));
+		let open = self.peek(0).span();
+		let block = self.build_code_block()?;
+		self.expr.push_back(DO_BLOCK(block, self.span_from(open)));
 		Ok(())
 	}
 
-	fn parse_token_if(&mut self) -> Result<(), String> {
+	fn parse_token_if(&mut self) -> Result<(), Box<Diagnostic>> {
 		let ctoken = self.build_elseif_chain(None)?;
 		self.expr.push_back(ctoken);
 		Ok(())
 	}
 
-	fn parse_token_match(&mut self) -> Result<(), String> {
+	fn parse_token_match(&mut self) -> Result<(), Box<Diagnostic>> {
 		let name = self.get_next_internal_var();
 		let ctoken = self.build_match_block(name, &ParserInfo::build_code_block)?;
 		self.expr.push_back(ctoken);
 		Ok(())
 	}
 
-	fn parse_token_while(&mut self, line: usize) -> Result<(), String> {
+	fn parse_token_while(&mut self, keyword: Span) -> Result<(), Box<Diagnostic>> {
 		let condition = self.build_expression(Some((CURLY_BRACKET_OPEN, "{")))?;
 		let code = self.build_loop_block()?;
-		self.expr.push_back(WHILE_LOOP { condition, code, line });
+		self.expr.push_back(WHILE_LOOP {
+			condition,
+			code,
+			line: keyword.start.line,
+			span: self.span_from(keyword),
+		});
 		Ok(())
 	}
 
-	fn parse_token_until(&mut self, line: usize) -> Result<(), String> {
+	fn parse_token_until(&mut self, keyword: Span) -> Result<(), Box<Diagnostic>> {
 		let mut condition = self.build_expression(Some((CURLY_BRACKET_OPEN, "{")))?;
-		condition.push_front(SYMBOL(String::from("not (")));
-		condition.push_back(SYMBOL(String::from(")")));
+		let span = expr_span(&condition);
+		condition.push_front(SYMBOL(String::from("not ("), span));
+		condition.push_back(SYMBOL(String::from(")"), span));
 		let code = self.build_loop_block()?;
-		self.expr.push_back(WHILE_LOOP { condition, code, line });
+		self.expr.push_back(WHILE_LOOP {
+			condition,
+			code,
+			line: keyword.start.line,
+			span: self.span_from(keyword),
+		});
 		Ok(())
 	}
 
-	fn parse_token_loop(&mut self, line: usize) -> Result<(), String> {
+	fn parse_token_loop(&mut self, keyword: Span) -> Result<(), Box<Diagnostic>> {
 		let code = self.build_loop_block()?;
 		let t = self.advance();
 		match t.kind() {
 			UNTIL => {
 				let condition = self.build_expression(None)?;
-				self.expr.push_back(LOOP_UNTIL { condition, code, line: t.line() })
+				let span = Span::range(keyword.start, expr_span(&condition).end);
+				self.expr.push_back(LOOP_UNTIL {
+					condition,
+					code,
+					line: t.line(),
+					span,
+				})
 			}
 			WHILE => {
 				let mut condition = self.build_expression(None)?;
-				condition.push_front(SYMBOL(String::from("not (")));
-				condition.push_back(SYMBOL(String::from(")")));
-				self.expr.push_back(LOOP_UNTIL { condition, code, line: t.line() })
+				let span = expr_span(&condition);
+				condition.push_front(SYMBOL(String::from("not ("), span));
+				condition.push_back(SYMBOL(String::from(")"), span));
+				self.expr.push_back(LOOP_UNTIL {
+					condition,
+					code,
+					line: t.line(),
+					span: Span::range(keyword.start, span.end),
+				})
+			}
+			_ => {
+				let span = Span::range(keyword.start, self.look_back(1).span().end);
+				self.expr.push_back(WHILE_LOOP {
+					condition: vec_deque![SYMBOL(String::from("true"), keyword)],
+					code,
+					line: keyword.start.line,
+					span,
+				})
 			}
-			_ => self.expr.push_back(WHILE_LOOP {
-				condition: vec_deque![SYMBOL(String::from("true"))],
-				code,
-				line,
-			}),
 		}
 		self.current -= 1;
 		Ok(())
 	}
 
-	fn parse_token_for(&mut self, line: usize) -> Result<(), String> {
+	fn parse_token_for(&mut self, keyword: Span) -> Result<(), Box<Diagnostic>> {
+		let line = keyword.start.line;
 		if self.peek(1).kind() == DEFINE {
 			let iterator = self.assert_advance(IDENTIFIER, "<name>")?.lexeme();
 			self.current += 1;
@@ -2080,10 +3162,10 @@ impl<'a> ParserInfo<'a> {
 			let alter = match t.kind() {
 				CURLY_BRACKET_OPEN => {
 					self.current -= 1;
-					vec_deque![SYMBOL(String::from("1"))]
+					vec_deque![SYMBOL(String::from("1"), keyword)]
 				}
 				COMMA => self.build_expression(Some((CURLY_BRACKET_OPEN, "{")))?,
-				_ => return Err(self.expected(",", &t.lexeme(), t.line(), t.column())),
+				_ => return Err(self.expected(",", &t.lexeme(), t.span().start)),
 			};
 			let code = self.build_loop_block()?;
 			self.expr.push_back(FOR_LOOP {
@@ -2093,31 +3175,28 @@ impl<'a> ParserInfo<'a> {
 				alter,
 				code,
 				line,
+				span: self.span_from(keyword),
 			})
 		} else {
 			let iterators = self.build_identifier_list()?;
-			let expr = match self.advance().kind() {
+			let t = self.advance();
+			let expr = match t.kind() {
 				OF => {
-					let mut expr = vec_deque![SYMBOL(String::from("pairs("))];
+					let mut expr = vec_deque![SYMBOL(String::from("pairs("), t.span())];
 					expr.append(&mut self.build_expression(Some((CURLY_BRACKET_OPEN, "{")))?);
-					expr.push_back(SYMBOL(String::from(")")));
+					expr.push_back(SYMBOL(String::from(")"), t.span()));
 					expr
 				}
 				IN => {
-					let mut expr = vec_deque![SYMBOL(String::from("ipairs("))];
+					let mut expr = vec_deque![SYMBOL(String::from("ipairs("), t.span())];
 					expr.append(&mut self.build_expression(Some((CURLY_BRACKET_OPEN, "{")))?);
-					expr.push_back(SYMBOL(String::from(")")));
+					expr.push_back(SYMBOL(String::from(")"), t.span()));
 					expr
 				}
 				WITH => self.build_expression(Some((CURLY_BRACKET_OPEN, "{")))?,
 				_ => {
 					let t = self.peek(0);
-					return Err(self.expected(
-						"of', 'in' or 'with",
-						&t.lexeme(),
-						t.line(),
-						t.column(),
-					));
+					return Err(self.expected("of', 'in' or 'with", &t.lexeme(), t.span().start));
 				}
 			};
 			let code = self.build_loop_block()?;
@@ -2126,39 +3205,46 @@ impl<'a> ParserInfo<'a> {
 				expr,
 				code,
 				line,
+				span: self.span_from(keyword),
 			});
 		}
 		Ok(())
 	}
 
-	fn parse_token_continue(&mut self) -> Result<(), String> {
-		self.expr.push_back(CONTINUE_LOOP);
+	fn parse_token_continue(&mut self) -> Result<(), Box<Diagnostic>> {
+		self.expr.push_back(CONTINUE_LOOP(self.look_back(0).span()));
 		self.advance_if(SEMICOLON);
 		Ok(())
 	}
 
-	fn parse_token_break(&mut self) -> Result<(), String> {
-		self.expr.push_back(BREAK_LOOP);
+	fn parse_token_break(&mut self) -> Result<(), Box<Diagnostic>> {
+		self.expr.push_back(BREAK_LOOP(self.look_back(0).span()));
 		self.advance_if(SEMICOLON);
 		Ok(())
 	}
 
-	fn parse_token_return(&mut self) -> Result<(), String> {
+	fn parse_token_return(&mut self) -> Result<(), Box<Diagnostic>> {
+		let keyword = self.look_back(0).span();
 		let exprs = if self.ended() || self.advance_if(SEMICOLON) {
 			None
 		} else {
 			Some(self.find_expressions(None)?)
 		};
-		self.expr.push_back(RETURN_EXPR(exprs));
+		let span = match exprs.as_ref().and_then(|exprs| exprs.last()) {
+			Some(last) => Span::range(keyword.start, expr_span(last).end),
+			None => keyword,
+		};
+		self.expr.push_back(RETURN_EXPR(exprs, span));
 		if !self.ended() {
 			let t = self.look_back(0);
-			return Err(self.expected("<end>", &t.lexeme(), t.line(), t.column()))
+			return Err(self.expected("<end>", &t.lexeme(), t.span().start));
 		}
 		Ok(())
 	}
 
-	fn parse_token_try(&mut self) -> Result<(), String> {
-		let totry = self.build_code_block(/*self.locals.clone()*/)?;
+	fn parse_token_try(&mut self) -> Result<(), Box<Diagnostic>> {
+		let keyword = self.look_back(0).span();
+		let totry = self.build_code_block()?;
 		let error: Option<String>;
 		let catch = if self.advance_if(CATCH) {
 			let t = self.advance();
@@ -2168,7 +3254,7 @@ impl<'a> ParserInfo<'a> {
 				error = None;
 				self.current -= 1;
 			}
-			Some(self.build_code_block(/*self.locals.clone()*/)?)
+			Some(self.build_code_block()?)
 		} else {
 			error = None;
 			None
@@ -2177,34 +3263,113 @@ impl<'a> ParserInfo<'a> {
 			totry,
 			error,
 			catch,
+			span: self.span_from(keyword),
+		});
+		Ok(())
+	}
+
+	fn parse(mut self) -> Result<Self, Vec<Diagnostic>> {
+		while !self.ended() {
+			let t = self.advance();
+			let result = match t.kind() {
+				LOCAL | GLOBAL => self.parse_token_local_global(&t),
+				STATIC => self.parse_token_static(&t),
+				METHOD => self.parse_token_method(),
+				IDENTIFIER => self.parse_token_identifier(&t),
+				ROUND_BRACKET_OPEN => self.parse_token_round_bracket_open(),
+				CURLY_BRACKET_OPEN => self.parse_token_curly_bracket_open(),
+				IF => self.parse_token_if(),
+				MATCH => self.parse_token_match(),
+				WHILE => self.parse_token_while(t.span()),
+				UNTIL => self.parse_token_until(t.span()),
+				LOOP => self.parse_token_loop(t.span()),
+				FOR => self.parse_token_for(t.span()),
+				CONTINUE => self.parse_token_continue(),
+				BREAK => self.parse_token_break(),
+				RETURN => self.parse_token_return(),
+				TRY => self.parse_token_try(),
+				EXTERN => self.parse_token_extern(),
+				FN | ENUM | STRUCT => self.parse_token_fn_enum(&t),
+				EOF => break,
+				_ => Err(self.expected("<end>", &t.lexeme(), t.span().start)),
+			};
+			if let Err(error) = result {
+				self.errors.push(*error);
+				if self.too_many_errors() {
+					break;
+				}
+				self.synchronize();
+			} else if self.too_many_errors() {
+				// the errors of a nested block can reach the limit too
+				break;
+			}
+		}
+
+		if !self.errors.is_empty() {
+			self.errors.sort_by_key(|error| error.span);
+			if let Some(max) = self.options.env_maxerrors {
+				self.errors.truncate(max.get());
+			}
+			return Err(self.errors);
+		}
+		Ok(self)
+	}
+
+	fn take_statics(&mut self) -> String {
+		let statics = std::mem::take(&mut self.statics);
+		if !statics.is_empty() && self.options.env_debug {
+			format!("--statics defined in \"{}\":\n{}\n", self.filename, statics)
+		} else {
+			statics
+		}
+	}
+
+	fn parse_token_extern(&mut self) -> Result<(), Box<Diagnostic>> {
+		let keyword = self.look_back(0).span();
+		let function = self.advance_if(FN);
+		let t = self.assert_advance(IDENTIFIER, "<name>")?;
+		let args = if function {
+			self.assert(ROUND_BRACKET_OPEN, "(")?;
+			Some(self.build_function_args()?.0)
+		} else {
+			None
+		};
+		self.advance_if(SEMICOLON);
+		self.expr.push_back(EXTERN_GLOBAL {
+			name: t.lexeme(),
+			args,
+			line: t.line(),
+			span: self.span_from(keyword),
 		});
 		Ok(())
 	}
 
-	fn parse_token_fn_enum(&mut self, t: &BorrowedToken) -> Result<(), String> {
+	fn parse_token_fn_enum(&mut self, t: &BorrowedToken) -> Result<(), Box<Diagnostic>> {
 		Err(self.error(
+			"missing-modifier",
 			format!(
 				"'{}' must have 'local', 'global' or 'static' beforehand",
 				t.lexeme()
 			),
-			t.line(),
-			t.column(),
+			t.span().start,
 		))
 	}
 }
 
 /// Parses a list of tokens into an expression
 /// Takes a list of [`Token`]s, a filename, and [`Options`]
-/// Returns an expression and statics as a string
+/// Returns an expression, statics as a string and the warnings of the global and type checks
 ///
 /// # Errors
-/// Returns an [`Err`] containing the error message if an unexpected [`Token`] is found.
+/// Returns an [`Err`] containing the [`Diagnostic`]s of the errors found if an unexpected [`Token`] is found.
+/// After an error the parser skips to the next statement and keeps going, so every syntax error
+/// of the file is reported at once, up to [`Options::env_maxerrors`] errors.
 ///
 /// # Examples
 /// ```
-/// use clue_core::{env::Options, parser::*, preprocessor::*, scanner::*};
+/// use clue_core::{diagnostic::Diagnostic, env::Options, parser::*, preprocessor::*, scanner::*};
 ///
-/// fn main() -> Result<(), String> {
+/// fn main() -> Result<(), Vec<Diagnostic>> {
 ///     let options = Options::default();
 ///     let filename = String::from("fizzbuzz.clue");
 ///     let mut code = include_str!("../../examples/fizzbuzz.clue").to_owned();
@@ -2218,51 +3383,444 @@ impl<'a> ParserInfo<'a> {
 ///     )?;
 ///     let codes = preprocess_codes(0, codes, &variables, &filename)?;
 ///     let tokens = scan_code(codes, &filename)?;
-///     let (expr, statics) = parse_tokens(tokens, &filename, &options)?;
+///     let (expr, statics, warnings) = parse_tokens(tokens, &filename, &options)?;
+///     assert!(warnings.is_empty());
 ///
 ///     Ok(())
 /// }
 /// ```
 pub fn parse_tokens(
 	tokens: Vec<Token>,
-	//locals: Option<AHashMap<String, LuaType>>,
 	filename: &String,
 	options: &Options,
-) -> Result<(Expression, String), String> {
-	let mut i = ParserInfo::new(tokens /* , locals */, filename, options);
-	while !i.ended() {
-		let t = i.advance();
-		match t.kind() {
-			LOCAL | GLOBAL => i.parse_token_local_global(&t)?,
-			STATIC => i.parse_token_static(&t)?,
-			METHOD => i.parse_token_method()?,
-			IDENTIFIER => i.parse_token_identifier(&t)?,
-			ROUND_BRACKET_OPEN => i.parse_token_round_bracket_open()?,
-			CURLY_BRACKET_OPEN => i.parse_token_curly_bracket_open()?,
-			IF => i.parse_token_if()?,
-			MATCH => i.parse_token_match()?,
-			WHILE => i.parse_token_while(t.line())?,
-			UNTIL => i.parse_token_until(t.line())?,
-			LOOP => i.parse_token_loop(t.line())?,
-			FOR => i.parse_token_for(t.line())?,
-			CONTINUE => i.parse_token_continue()?,
-			BREAK => i.parse_token_break()?,
-			RETURN => i.parse_token_return()?,
-			TRY => i.parse_token_try()?,
-			FN | ENUM => i.parse_token_fn_enum(&t)?,
-			EOF => break,
-			_ => return Err(i.expected("<end>", &t.lexeme(), t.line(), t.column())),
-		}
-	}
-
-	//println!("LOCALS = {:#?}", i.locals);
-
-	Ok((
-		i.expr,
-		if !i.statics.is_empty() && options.env_debug {
-			format!("--statics defined in \"{}\":\n{}\n", i.filename, i.statics)
-		} else {
-			i.statics
-		},
-	))
+) -> Result<(Expression, String, Vec<Diagnostic>), Vec<Diagnostic>> {
+	let (expr, statics, _, warnings) = parse_tokens_with_symbols(tokens, filename, options)?;
+	Ok((expr, statics, warnings))
+}
+
+/// Parses a list of tokens like [`parse_tokens`], also returning the [`SymbolTable`]
+/// of the variables, functions, methods, structs and enum entries declared in them
+///
+/// # Errors
+/// Returns an [`Err`] containing the [`Diagnostic`]s of the errors found, like [`parse_tokens`]
+///
+/// # Examples
+/// ```
+/// use clue_core::{code::Code, diagnostic::Diagnostic, env::Options, parser::*, scanner::*, symbols::*};
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let options = Options::default();
+///     let filename = String::from("main.clue");
+///     let code = "static count = 0\nmethod Counter::add(n) {}";
+///
+///     let tokens = scan_code(Code::from((code, 1, 1)), &filename)?;
+///     let (_, _, symbols, _) = parse_tokens_with_symbols(tokens, &filename, &options)?;
+///
+///     assert!(symbols.get("count").unwrap().is_static);
+///     assert_eq!(symbols.get("Counter:add").unwrap().kind, SymbolKind::Method);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn parse_tokens_with_symbols(
+	tokens: Vec<Token>,
+	filename: &String,
+	options: &Options,
+) -> Result<(Expression, String, SymbolTable, Vec<Diagnostic>), Vec<Diagnostic>> {
+	let mut i = ParserInfo::new(tokens, filename, options).parse()?;
+
+	let mut diagnostics = Vec::new();
+	if options.env_checkglobals || options.std() != LuaSTD::NONE {
+		diagnostics = check_globals(&i.expr, &i.static_names, filename, options);
+	}
+	diagnostics.append(&mut check_types(&i.expr, filename, options.env_types));
+	if diagnostics.iter().any(Diagnostic::is_error) {
+		return Err(diagnostics);
+	}
+
+	let statics = i.take_statics();
+	Ok((i.expr, statics, i.symbols, diagnostics))
+}
+
+pub(crate) fn expr_span(expr: &Expression) -> Span {
+	match (expr.front(), expr.back()) {
+		(Some(first), Some(last)) => Span::range(first.span().start, last.span().end),
+		_ => Span::default(),
+	}
+}
+
+fn arg_names(args: &FunctionArgs) -> Vec<String> {
+	args.iter().map(|(name, _)| name.clone()).collect()
+}
+
+/// Globals that are not part of the standard library but are usually provided anyway
+const HOST_GLOBALS: &[&str] = &["_ENV", "arg", "import"];
+
+fn is_name(lexeme: &str) -> bool {
+	let mut chars = lexeme.chars();
+	chars
+		.next()
+		.map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+		&& chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+		&& !matches!(lexeme, "nil" | "true" | "false" | "and" | "or" | "not")
+}
+
+fn single_name(expr: &Expression) -> Option<&String> {
+	match expr.front() {
+		Some(SYMBOL(name, _)) if expr.len() == 1 => Some(name),
+		Some(IDENT { expr, .. }) if expr.len() == 1 => single_name(expr),
+		_ => None,
+	}
+}
+
+struct GlobalsCollector {
+	globals: AHashSet<String>,
+}
+
+impl<'a> Visitor<'a> for GlobalsCollector {
+	fn visit_token(&mut self, t: &'a ComplexToken) {
+		match t {
+			VARIABLE {
+				local: false,
+				names,
+				..
+			} => self.globals.extend(names.iter().cloned()),
+			ALTER { names, .. } => self
+				.globals
+				.extend(names.iter().filter_map(single_name).cloned()),
+			FUNCTION {
+				local: false, name, ..
+			} => {
+				if let Some(name) = single_name(name) {
+					self.globals.insert(name.clone());
+				}
+			}
+			EXTERN_GLOBAL { name, .. } => {
+				self.globals.insert(name.clone());
+			}
+			_ => {}
+		}
+		walk_token(self, t)
+	}
+}
+
+struct GlobalsChecker<'a> {
+	filename: &'a String,
+	undeclared: bool,
+	std: LuaSTD,
+	manifest: &'static AHashSet<&'static str>,
+	known: &'static AHashSet<&'static str>,
+	globals: AHashSet<String>,
+	scopes: Vec<Vec<String>>,
+	diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> GlobalsChecker<'a> {
+	fn declare(&mut self, name: &str) {
+		if let Some(scope) = self.scopes.last_mut() {
+			scope.push(name.to_owned())
+		}
+	}
+
+	fn is_declared(&self, name: &str) -> bool {
+		self.globals.contains(name)
+			|| self
+				.scopes
+				.iter()
+				.any(|scope| scope.iter().any(|local| local == name))
+	}
+
+	fn check_global(&mut self, name: &str, member: Option<(&String, Span)>, span: Span) {
+		if !self.known.contains(name) {
+			if self.undeclared && !HOST_GLOBALS.contains(&name) {
+				self.diagnostics.push(
+					Diagnostic::error(
+						"undeclared-global",
+						format!("Global '{name}' was never declared"),
+						self.filename.as_str(),
+						span,
+					)
+					.with_note(
+						"Declare it with 'global' or with 'extern' if it is provided by the host environment",
+					),
+				);
+			}
+			return;
+		}
+		if self.std == LuaSTD::NONE {
+			return;
+		}
+		let (missing, span) = match member {
+			_ if !self.manifest.contains(name) => (name.to_owned(), span),
+			Some((member, member_span)) => {
+				let path = format_clue!(name, ".", member);
+				if !self.known.contains(path.as_str()) || self.manifest.contains(path.as_str()) {
+					return;
+				}
+				(path, Span::range(span.start, member_span.end))
+			}
+			None => return,
+		};
+		self.diagnostics.push(Diagnostic::warning(
+			"missing-std",
+			format!(
+				"'{missing}' is not part of the standard library of {}",
+				self.std
+			),
+			self.filename.as_str(),
+			span,
+		));
+	}
+
+	fn check_block(&mut self, block: &CodeBlock, declared: &[&String]) {
+		self.scopes
+			.push(declared.iter().map(|name| name.to_string()).collect());
+		self.visit_block(block);
+		self.scopes.pop();
+	}
+}
+
+impl<'a, 'b> Visitor<'b> for GlobalsChecker<'a> {
+	fn visit_expression(&mut self, expr: &'b Expression) {
+		let mut indexed = false;
+		for (i, t) in expr.iter().enumerate() {
+			if let SYMBOL(lexeme, span) = t {
+				if !indexed && is_name(lexeme) && !self.is_declared(lexeme) {
+					let member = match (expr.get(i + 1), expr.get(i + 2)) {
+						(Some(SYMBOL(dot, _)), Some(SYMBOL(member, member_span))) if dot == "." => {
+							Some((member, *member_span))
+						}
+						_ => None,
+					};
+					self.check_global(lexeme, member, *span);
+				}
+				indexed = matches!(lexeme.as_str(), "." | ":");
+			} else {
+				self.visit_token(t);
+				indexed = false;
+			}
+		}
+	}
+
+	fn visit_token(&mut self, t: &'b ComplexToken) {
+		match t {
+			VARIABLE {
+				local: true, names, ..
+			} => {
+				walk_token(self, t);
+				names.iter().for_each(|name| self.declare(name))
+			}
+			ALTER { names, values, .. } => {
+				for name in names {
+					if single_name(name).is_none() {
+						self.visit_expression(name)
+					}
+				}
+				values.iter().for_each(|value| self.visit_expression(value))
+			}
+			FUNCTION {
+				local,
+				name,
+				args,
+				code,
+				..
+			} => {
+				if *local {
+					if let Some(name) = single_name(name) {
+						self.declare(name)
+					}
+				} else if single_name(name).is_none() {
+					self.visit_expression(name)
+				}
+				self.scopes.push(Vec::new());
+				if name.iter().any(|t| matches!(t, SYMBOL(s, _) if s == ":")) {
+					self.declare("self");
+				}
+				self.visit_function(args, code);
+				self.scopes.pop();
+			}
+			TABLE { values, metas, .. } => {
+				for (key, value, _) in values {
+					if let Some(key) = key {
+						if single_name(key).is_none() {
+							self.visit_expression(key)
+						}
+					}
+					self.visit_expression(value)
+				}
+				metas
+					.iter()
+					.for_each(|(_, value, _)| self.visit_expression(value))
+			}
+			MATCH_BLOCK {
+				name,
+				value,
+				branches,
+				..
+			} => {
+				self.visit_expression(value);
+				self.declare(name);
+				branches.iter().for_each(|case| self.visit_match_case(case))
+			}
+			LOOP_UNTIL {
+				condition, code, ..
+			} => {
+				self.scopes.push(Vec::new());
+				self.visit_expression(&code.code);
+				self.visit_expression(condition);
+				self.scopes.pop();
+			}
+			FOR_LOOP {
+				iterator,
+				start,
+				end,
+				alter,
+				code,
+				..
+			} => {
+				self.visit_expression(start);
+				self.visit_expression(end);
+				self.visit_expression(alter);
+				self.check_block(code, &[iterator]);
+			}
+			FOR_FUNC_LOOP {
+				iterators,
+				expr,
+				code,
+				..
+			} => {
+				self.visit_expression(expr);
+				let iterators: Vec<&String> = iterators.iter().collect();
+				self.check_block(code, &iterators);
+			}
+			TRY_CATCH {
+				totry,
+				error,
+				catch,
+				..
+			} => {
+				self.visit_block(totry);
+				if let Some(catch) = catch {
+					match error {
+						Some(error) => self.check_block(catch, &[error]),
+						None => self.visit_block(catch),
+					}
+				}
+			}
+			_ => walk_token(self, t),
+		}
+	}
+
+	fn visit_block(&mut self, block: &'b CodeBlock) {
+		self.scopes.push(Vec::new());
+		walk_block(self, block);
+		self.scopes.pop();
+	}
+
+	fn visit_function(&mut self, args: &'b FunctionArgs, code: &'b CodeBlock) {
+		self.scopes.push(Vec::new());
+		for (name, default) in args {
+			if let Some((default, _)) = default {
+				self.visit_expression(default)
+			}
+			self.declare(name);
+		}
+		self.visit_block(code);
+		self.scopes.pop();
+	}
+
+	fn visit_match_case(&mut self, case: &'b MatchCase) {
+		self.scopes.push(Vec::new());
+		walk_match_case(self, case);
+		self.scopes.pop();
+	}
+}
+
+/// Reports every read of a global that was never declared with `global`, `static`, `extern`
+/// or assigned anywhere in the file, ignoring the globals of Lua's standard library,
+/// if `env_checkglobals` is enabled, and warns about every reference to a member of the standard library
+/// that is missing in the one returned by [`Options::std`]
+fn check_globals(
+	expr: &Expression,
+	statics: &[String],
+	filename: &String,
+	options: &Options,
+) -> Vec<Diagnostic> {
+	let std = options.std();
+	let mut collector = GlobalsCollector {
+		globals: statics.iter().cloned().collect(),
+	};
+	collector.visit_expression(expr);
+	let mut checker = GlobalsChecker {
+		filename,
+		undeclared: options.env_checkglobals,
+		std,
+		manifest: stdlib::manifest(std),
+		known: stdlib::all_members(),
+		globals: collector.globals,
+		scopes: vec![Vec::new()],
+		diagnostics: Vec::new(),
+	};
+	checker.visit_expression(expr);
+	checker.diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::Clue;
+	use std::num::NonZeroUsize;
+
+	#[test]
+	fn constructors_return_the_struct() {
+		let code = "local struct S {\n\tx,\n\tconstructor(a) {\n\t\tif !a { return }\n\t\tself.x = fn() { return }\n\t\treturn\n\t}\n}";
+		let code = Clue::new().compile_code(code.to_owned()).unwrap();
+		assert!(code.contains("if not a then\n\t\treturn self;\n\tend"));
+		assert!(code.contains("self.x = function()\n\t\treturn;\n\tend;"));
+		assert!(code.ends_with("end;\n\treturn self;\nend"), "{code}");
+		assert_eq!(code.matches("return self;").count(), 2);
+
+		let code = "local struct S { constructor() { return 1 } }";
+		let code = Clue::new().compile_code(code.to_owned()).unwrap();
+		assert!(!code.contains("return self"));
+	}
+
+	#[test]
+	fn field_defaults_ignore_constructor_arguments() {
+		let code = "local x = 5\nlocal struct S { a = x, b = {x = x.y}, constructor(x, y = x) { self.c = x + y } }";
+		let code = Clue::new().compile_code(code.to_owned()).unwrap();
+		assert!(code.contains("function S.new(_internal0, y)"), "{code}");
+		assert!(code.contains("y = _internal0"));
+		assert!(code.contains("a = x, \n\t\tb = {\n\t\t\tx = x.y\n\t\t}"));
+		assert!(code.contains("local x = _internal0;\n\tself.c = x+y;"));
+	}
+
+	#[test]
+	fn offsets_are_in_the_source_file() {
+		let code = "@define X 1\n@macro M(x) { $x + $x }\nlocal a = $M!(22)\nlocal b = $X";
+		let clue = Clue::new();
+		let tokens = clue.scan_code(code.to_owned()).unwrap();
+		let b = tokens.iter().find(|t| t.lexeme == "b").unwrap();
+		assert_eq!((b.line, b.column, b.offset), (4, 7, 60));
+
+		let errors = clue.parse_code(format!("{code}\nlocal = 3")).unwrap_err();
+		assert_eq!(errors[0].span.start.offset, 73);
+	}
+
+	#[test]
+	fn nested_errors_are_sorted_and_limited() {
+		let mut clue = Clue::new();
+		let code = "if x {\n\tprint(1\n}\nlocal = 2\n\n\n";
+		let errors = clue.compile_code(code.to_owned()).unwrap_err();
+		let positions: Vec<_> = errors
+			.iter()
+			.map(|error| (error.span.start.line, error.span.start.column))
+			.collect();
+		assert_eq!(positions, [(3, 1), (4, 7)]);
+
+		clue.max_errors(NonZeroUsize::new(2));
+		let code = "local = 1\nif x {\n\tlocal = 2\n\tlocal = 3\n}";
+		let errors = clue.compile_code(code.to_owned()).unwrap_err();
+		assert_eq!(errors.len(), 2);
+		assert_eq!(errors[1].span.start.line, 3);
+	}
 }
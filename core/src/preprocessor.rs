@@ -1,30 +1,26 @@
 //! The preprocessor is the first step in the compilation process.
 //! It is responsible for removing comments and expanding macros and directives.
 //!
-//! It exposes three functions: [`preprocess_code`], [`preprocess_codes`] and [`preprocess_variables`]
+//! It exposes three functions: [`preprocess_code`], [`preprocess_codes`] and [`preprocess_variables`],
+//! together with [`variable_references`] and [`used_variables`] to find the variables some code depends on
 
 use crate::{
-	check,
 	code::{Code, CodeChar},
-	env::Options,
+	diagnostic::{Diagnostic, Span},
+	env::{Options, OutputMode},
 	format_clue,
 };
 use ahash::AHashMap;
 use std::{
 	cmp,
-	collections::{VecDeque, HashMap},
-	env,
-	fs,
+	collections::{BTreeSet, VecDeque},
+	env, fs,
 	iter::{Peekable, Rev},
 	path::PathBuf,
 	str::{self, Split},
-	u8::{self, MAX},
 };
 use utf8_decode::decode;
 
-#[cfg(feature = "lsp")]
-use serde_json::json;
-
 macro_rules! pp_if {
 	($code:ident, $ifname:ident, $prev:ident) => {{
 		let check = $code.$ifname(b'{')?;
@@ -36,6 +32,9 @@ macro_rules! pp_if {
 pub type PPVars = AHashMap<Code, PPVar>;
 /// A list of code segments and its size.
 pub type PPCode = (VecDeque<(Code, bool)>, usize);
+/// The preprocessed code, its variables, the line and the byte where preprocessing stopped
+/// and the notes of the preprocessor.
+pub type PPOutput = (PPCode, PPVars, usize, usize, Vec<Diagnostic>);
 
 #[derive(Debug, Clone)]
 /// A preprocessor variable or macro.
@@ -65,13 +64,30 @@ pub enum PPVar {
 	VarArgs(PPCode),
 }
 
-fn error(msg: impl Into<String>, line: usize, column: usize, filename: &String) -> String {
-	eprintln!("Error in {filename}:{line}:{column}!");
-	msg.into()
+fn error(
+	code: &str,
+	msg: impl Into<String>,
+	line: usize,
+	column: usize,
+	filename: &str,
+) -> Box<Diagnostic> {
+	Box::new(Diagnostic::error(
+		code,
+		msg,
+		filename,
+		Span::new(line, column),
+	))
 }
 
-fn expected(expected: &str, got: &str, line: usize, column: usize, filename: &String) -> String {
+fn expected(
+	expected: &str,
+	got: &str,
+	line: usize,
+	column: usize,
+	filename: &str,
+) -> Box<Diagnostic> {
 	error(
+		"expected",
 		format_clue!("Expected '", expected, "', got '", got, "'"),
 		line,
 		column,
@@ -84,9 +100,10 @@ fn expected_before(
 	before: &str,
 	line: usize,
 	column: usize,
-	filename: &String,
-) -> String {
+	filename: &str,
+) -> Box<Diagnostic> {
 	error(
+		"expected",
 		format_clue!("Expected '", expected, "' before '", before, "'"),
 		line,
 		column,
@@ -112,16 +129,19 @@ struct CodeFile<'a> {
 	peeked: Option<CodeChar>,
 	line: usize,
 	column: usize,
+	offset: usize,
 	filename: &'a String,
 	last_if: bool,
 	cscope: u8,
 	ends: Vec<u8>,
+	notes: Vec<Diagnostic>,
 }
 
 impl<'a> CodeFile<'a> {
 	fn new(
 		code: &'a mut [u8],
 		line: usize,
+		offset: usize,
 		filename: &'a String,
 		cscope: u8,
 		options: &'a Options,
@@ -135,23 +155,29 @@ impl<'a> CodeFile<'a> {
 			peeked: None,
 			line,
 			column: 1,
+			offset,
 			filename,
 			last_if: true,
 			cscope,
 			ends: Vec::new(),
+			notes: Vec::new(),
 		}
 	}
 
-	fn is_ascii(&mut self, c: Option<CodeChar>) -> Result<Option<CodeChar>, String> {
+	fn is_ascii(&mut self, c: Option<CodeChar>) -> Result<Option<CodeChar>, Box<Diagnostic>> {
 		match c {
 			None => Ok(None),
 			Some(c) if c.0.is_ascii() => Ok(Some(c)),
-			Some((_, line, column)) => {
-				let c = check!(decode(
-					&mut self.code[self.read - 1..cmp::min(self.read + 3, self.code.len())].iter().copied()
+			Some((_, line, column, _)) => {
+				let c = decode(
+					&mut self.code[self.read - 1..cmp::min(self.read + 3, self.code.len())]
+						.iter()
+						.copied(),
 				)
-				.unwrap());
+				.unwrap()
+				.unwrap_or('\u{FFFD}');
 				Err(error(
+					"invalid-character",
 					format!("Invalid character '{c}'"),
 					line,
 					column,
@@ -223,14 +249,14 @@ impl<'a> CodeFile<'a> {
 					}
 					_ => {}
 				}
-				Some((*current, line, column))
+				Some((*current, line, column, self.offset + self.read - 1))
 			} else {
 				None
 			}
 		}
 	}
 
-	fn read_char(&mut self) -> Result<Option<CodeChar>, String> {
+	fn read_char(&mut self) -> Result<Option<CodeChar>, Box<Diagnostic>> {
 		let c = self.read_char_unchecked();
 		self.is_ascii(c)
 	}
@@ -242,45 +268,41 @@ impl<'a> CodeFile<'a> {
 		self.peeked
 	}
 
-	fn peek_char(&mut self) -> Result<Option<CodeChar>, String> {
+	fn peek_char(&mut self) -> Result<Option<CodeChar>, Box<Diagnostic>> {
 		let c = self.peek_char_unchecked();
 		self.is_ascii(c)
 	}
 
-	fn assert_char(&mut self, wanted_c: u8) -> Result<(), String> {
+	fn assert_char(&mut self, wanted_c: u8) -> Result<(), Box<Diagnostic>> {
 		match self.read_char()? {
-			None => {
-				return Err(expected_before(
-					&String::from_utf8_lossy(&[wanted_c]),
-					"<end>",
-					self.line,
-					self.column,
-					self.filename,
-				))
-			}
-			Some((c, line, column)) if c != wanted_c => {
-				return Err(expected(
-					&String::from_utf8_lossy(&[wanted_c]),
-					&String::from_utf8_lossy(&[c]),
-					line,
-					column,
-					self.filename,
-				))
-			}
+			None => Err(expected_before(
+				&String::from_utf8_lossy(&[wanted_c]),
+				"<end>",
+				self.line,
+				self.column,
+				self.filename,
+			)),
+			Some((c, line, column, _)) if c != wanted_c => Err(expected(
+				&String::from_utf8_lossy(&[wanted_c]),
+				&String::from_utf8_lossy(&[c]),
+				line,
+				column,
+				self.filename,
+			)),
 			_ => Ok(()),
 		}
 	}
 
-	fn assert_reach(&mut self, wanted_c: u8) -> Result<(), String> {
+	fn assert_reach(&mut self, wanted_c: u8) -> Result<(), Box<Diagnostic>> {
 		self.skip_whitespace();
 		self.assert_char(wanted_c)
 	}
 
 	fn read(
 		&mut self,
-		mut get: impl FnMut(&mut Self) -> Result<Option<CodeChar>, String>,
+		mut get: impl FnMut(&mut Self) -> Result<Option<CodeChar>, Box<Diagnostic>>,
 		mut check: impl FnMut(&mut Self, CodeChar) -> bool,
-	) -> Result<Code, String> {
+	) -> Result<Code, Box<Diagnostic>> {
 		let mut code = Code::new();
 		while let Some(c) = get(self)? {
 			if check(self, c) {
@@ -300,7 +322,7 @@ impl<'a> CodeFile<'a> {
 		.to_string()
 	}
 
-	fn read_identifier(&mut self) -> Result<Code, String> {
+	fn read_identifier(&mut self) -> Result<Code, Box<Diagnostic>> {
 		self.read(Self::peek_char, |code, (c, ..)| {
 			if c.is_ascii_alphanumeric() || c == b'_' {
 				code.read_char_unchecked().unwrap();
@@ -311,14 +333,20 @@ impl<'a> CodeFile<'a> {
 		})
 	}
 
-	fn read_string(&mut self, c: CodeChar) -> Result<Code, String> {
+	fn read_string(&mut self, c: CodeChar) -> Result<Code, Box<Diagnostic>> {
 		self.comment = CommentState::String;
 		let mut skip_next = false;
 		self.read(
 			|code| {
 				let stringc = code.read_char_unchecked();
 				if stringc.is_none() {
-					Err(error("Unterminated string", c.1, c.2, self.filename))
+					Err(error(
+						"unterminated-string",
+						"Unterminated string",
+						c.1,
+						c.2,
+						self.filename,
+					))
 				} else {
 					Ok(stringc)
 				}
@@ -345,8 +373,8 @@ impl<'a> CodeFile<'a> {
 	fn read_until_with(
 		&mut self,
 		end: u8,
-		f: impl FnMut(&mut Self) -> Result<Option<CodeChar>, String>,
-	) -> Result<Option<Code>, String> {
+		f: impl FnMut(&mut Self) -> Result<Option<CodeChar>, Box<Diagnostic>>,
+	) -> Result<Option<Code>, Box<Diagnostic>> {
 		let mut reached = false;
 		let result = self.read(f, |_, (c, ..)| {
 			if c == end {
@@ -359,7 +387,7 @@ impl<'a> CodeFile<'a> {
 		Ok(reached.then_some(result))
 	}
 
-	fn read_until(&mut self, end: u8) -> Result<Code, String> {
+	fn read_until(&mut self, end: u8) -> Result<Code, Box<Diagnostic>> {
 		self.read_until_with(end, Self::read_char)?.ok_or_else(|| {
 			expected_before(
 				&(end as char).to_string(),
@@ -371,7 +399,7 @@ impl<'a> CodeFile<'a> {
 		})
 	}
 
-	fn read_macro_args(&mut self) -> Result<Code, String> {
+	fn read_macro_args(&mut self) -> Result<Code, Box<Diagnostic>> {
 		let mut args = Code::new();
 		args.push(self.read_char_unchecked().unwrap());
 		while let Some(c) = self.peek_char()? {
@@ -398,18 +426,26 @@ impl<'a> CodeFile<'a> {
 		))
 	}
 
-	fn read_macro_block(&mut self) -> Result<(PPCode, PPVars), String> {
+	fn read_macro_block(&mut self) -> Result<(PPCode, PPVars), Box<Diagnostic>> {
 		let line = self.line;
+		let offset = self.offset + self.read;
 		let len = self.code.len();
 		let block = &mut self.code[self.read..len];
-		let (block, ppvars, line, read) =
-			preprocess_code(block, line, true, self.filename, &Options::default())?;
+		let (block, ppvars, line, read, mut notes) = preprocess_code_from(
+			block,
+			line,
+			offset,
+			true,
+			self.filename,
+			&Options::default(),
+		)?;
 		self.line = line;
 		self.read += read;
+		self.notes.append(&mut notes);
 		Ok((block, ppvars))
 	}
 
-	fn skip_block(&mut self) -> Result<(), String> {
+	fn skip_block(&mut self) -> Result<(), Box<Diagnostic>> {
 		while let Some(c) = self.read_char()? {
 			match c.0 {
 				b'{' => self.skip_block()?,
@@ -429,7 +465,7 @@ impl<'a> CodeFile<'a> {
 		))
 	}
 
-	fn keep_block(&mut self, to_keep: bool) -> Result<(), String> {
+	fn keep_block(&mut self, to_keep: bool) -> Result<(), Box<Diagnostic>> {
 		self.last_if = to_keep;
 		if to_keep {
 			self.ends.push(self.cscope);
@@ -440,12 +476,12 @@ impl<'a> CodeFile<'a> {
 		}
 	}
 
-	fn ifos(&mut self, end: u8) -> Result<bool, String> {
+	fn ifos(&mut self, end: u8) -> Result<bool, Box<Diagnostic>> {
 		let checked_os = self.read_until(end)?.trim();
 		Ok(checked_os == self.options.env_targetos)
 	}
 
-	fn iflua(&mut self, end: u8) -> Result<bool, String> {
+	fn iflua(&mut self, end: u8) -> Result<bool, Box<Diagnostic>> {
 		use crate::env::LuaVersion::*;
 		let checked_lua_version = self.read_until(end)?.trim();
 		let Some(target) = self.options.env_target else {
@@ -461,19 +497,25 @@ impl<'a> CodeFile<'a> {
 		)
 	}
 
-	fn ifdef(&mut self, end: u8) -> Result<bool, String> {
-		let to_check = self.read_until(end)?.trim();
-		Ok(env::var_os(to_check.to_string()).is_some())
+	fn ifdef(&mut self, end: u8) -> Result<bool, Box<Diagnostic>> {
+		let to_check = self.read_until(end)?.trim().to_string();
+		Ok(self.options.env_defines.contains_key(&to_check) || env::var_os(to_check).is_some())
 	}
 
-	fn ifndef(&mut self, end: u8) -> Result<bool, String> {
+	fn ifndef(&mut self, end: u8) -> Result<bool, Box<Diagnostic>> {
 		self.ifdef(end).map(|ok| !ok)
 	}
 
-	fn ifcmp(&mut self, end: u8) -> Result<bool, String> {
-		let Some(to_compare1) = env::var_os(self.read_identifier()?.to_string()) else {
+	fn ifcmp(&mut self, end: u8) -> Result<bool, Box<Diagnostic>> {
+		let name = self.read_identifier()?.to_string();
+		let Some(to_compare1) = env::var_os(&name).or_else(|| {
+			self.options
+				.env_defines
+				.get(&name)
+				.map(|value| value.into())
+		}) else {
 			self.read_until(end)?;
-			return Ok(false)
+			return Ok(false);
 		};
 		self.skip_whitespace();
 		let comparison = [
@@ -504,7 +546,7 @@ impl<'a> CodeFile<'a> {
 		})
 	}
 
-	fn bool_op(&mut self, b: bool) -> Result<bool, String> {
+	fn bool_op(&mut self, b: bool) -> Result<bool, Box<Diagnostic>> {
 		let mut result = !b;
 		loop {
 			if self.r#if()? == b {
@@ -520,7 +562,7 @@ impl<'a> CodeFile<'a> {
 		}
 	}
 
-	fn r#if(&mut self) -> Result<bool, String> {
+	fn r#if(&mut self) -> Result<bool, Box<Diagnostic>> {
 		let check = {
 			let function = self.read_identifier()?.to_string();
 			self.assert_char(b'(')?;
@@ -549,6 +591,7 @@ impl<'a> CodeFile<'a> {
 				}
 				_ => {
 					return Err(error(
+						"unknown-function",
 						format!("Unknown function '{function}'"),
 						self.line,
 						self.column,
@@ -561,22 +604,28 @@ impl<'a> CodeFile<'a> {
 		Ok(check)
 	}
 
-	fn get_version_number(&self, version: &mut Split<char>, default : &str) -> Result<u8, String> {
+	fn get_version_number(
+		&self,
+		version: &mut Split<char>,
+		default: &str,
+	) -> Result<u8, Box<Diagnostic>> {
 		let num = match version.next() {
 			None => {
 				return Err(error(
+					"invalid-version",
 					"Incomplete version (must be 'X.Y.Z')",
 					self.line,
 					self.column,
 					self.filename,
 				))
 			}
-			Some(num) if num == "*" => default,
+			Some("*") => default,
 			Some(num) => num,
 		};
 		match num.parse::<u8>() {
 			Ok(num) => Ok(num),
 			Err(_) => Err(error(
+				"invalid-version",
 				"Invalid version (must be 'X.Y.Z')",
 				self.line,
 				self.column,
@@ -586,18 +635,19 @@ impl<'a> CodeFile<'a> {
 	}
 }
 
-/// Reads a file and gives back the a list of preprocessed code blocks and the variables
+/// Reads a file and gives back the a list of preprocessed code blocks, the variables
+/// and the notes of the preprocessor, like the ones about deprecated syntax
 ///
 /// # Errors
-/// If the file cannot be read or the code cannot be preprocessed it will return an [`Err`] with the error message
+/// If the file cannot be read or the code cannot be preprocessed it will return an [`Err`] with a [`Diagnostic`] describing the error
 ///
 /// # Examples
 /// ```
-/// use clue_core::{env::Options, preprocessor::read_file};
+/// use clue_core::{diagnostic::Diagnostic, env::Options, preprocessor::read_file};
 ///
-/// fn main() -> Result<(), String> {
+/// fn main() -> Result<(), Box<Diagnostic>> {
 ///     let options = Options::default();
-///     let (code, vars) = read_file(
+///     let (code, vars, notes) = read_file(
 ///         "../examples/macro.clue",
 ///         &String::from("macro.clue"),
 ///         &options,
@@ -610,21 +660,41 @@ pub fn read_file(
 	path: impl Into<PathBuf>,
 	filename: &String,
 	options: &Options,
-) -> Result<(PPCode, PPVars), String> {
-	let result = preprocess_code(&mut check!(fs::read(path.into())), 1, false, filename, options)?;
-	Ok((result.0, result.1))
+) -> Result<(PPCode, PPVars, Vec<Diagnostic>), Box<Diagnostic>> {
+	let mut code = fs::read(path.into())
+		.map_err(|e| Diagnostic::error("io", e.to_string(), filename.as_str(), Span::default()))?;
+	let (codes, variables, _, _, notes) = preprocess_code(&mut code, 1, false, filename, options)?;
+	Ok((codes, variables, notes))
+}
+
+/// Returns the variables defined by [`Options::env_defines`], parsed like the values of `@define`
+pub(crate) fn defined_variables(options: &Options) -> PPVars {
+	options
+		.env_defines
+		.iter()
+		.map(|(name, value)| {
+			let value = Code::from((value.trim(), 0, 0));
+			let variable = if value.iter().any(|(c, ..)| *c == b'$') {
+				PPVar::ToProcess(value)
+			} else {
+				PPVar::Simple(value)
+			};
+			(Code::from((name.as_str(), 0, 0)), variable)
+		})
+		.collect()
 }
 
 /// Preprocesses code and gives back the a list of preprocessed code blocks and the variable
+/// followed by the line and the byte the preprocessor stopped at and its notes
 ///
 /// # Errors
-/// If the code cannot be preprocessed it will return an [`Err`] with the error message
+/// If the code cannot be preprocessed it will return an [`Err`] with a [`Diagnostic`] describing the error
 ///
 /// # Examples
 /// ```
-/// use clue_core::{preprocessor::preprocess_code, env::Options};
+/// use clue_core::{diagnostic::Diagnostic, preprocessor::preprocess_code, env::Options};
 ///
-/// fn main() -> Result<(), String> {
+/// fn main() -> Result<(), Box<Diagnostic>> {
 ///   let options = Options::default();
 ///   let mut code = include_str!("../../examples/macro.clue").to_owned();
 ///
@@ -632,20 +702,36 @@ pub fn read_file(
 ///
 ///   Ok(())
 /// }
-#[allow(clippy::blocks_in_if_conditions)]
 pub fn preprocess_code(
 	code: &mut [u8],
 	line: usize,
 	is_block: bool,
 	filename: &String,
 	options: &Options,
-) -> Result<(PPCode, PPVars, usize, usize), String> {
+) -> Result<PPOutput, Box<Diagnostic>> {
+	preprocess_code_from(code, line, 0, is_block, filename, options)
+}
+
+/// Like [`preprocess_code`], for code that starts at the given byte of its file
+#[allow(clippy::blocks_in_conditions)]
+fn preprocess_code_from(
+	code: &mut [u8],
+	line: usize,
+	offset: usize,
+	is_block: bool,
+	filename: &String,
+	options: &Options,
+) -> Result<PPOutput, Box<Diagnostic>> {
 	let mut output_dir: Option<PathBuf> = None;
 	let mut finalcode = VecDeque::new();
 	let mut currentcode = Code::with_capacity(code.len());
 	let mut size = 0;
-	let mut code = CodeFile::new(code, line, filename, is_block as u8, options);
-	let mut variables = PPVars::new();
+	let mut code = CodeFile::new(code, line, offset, filename, is_block as u8, options);
+	let mut variables = if is_block {
+		PPVars::new()
+	} else {
+		defined_variables(options)
+	};
 	let mut pseudos: Option<VecDeque<Code>> = None;
 	let mut bitwise = false;
 	while let Some(c) = code.read_char()? {
@@ -681,18 +767,16 @@ pub fn preprocess_code(
 						code.keep_block(!code.last_if)?;
 					}
 					"import" => {
-						if output_dir.is_none() {
+						if output_dir.is_none() && options.env_mode == OutputMode::Bundle {
 							output_dir = Some(match options.env_outputname.as_ref() {
-								Some(output_dir) => output_dir
-									.parent()
-									.map_or_else(
-										|| output_dir.to_path_buf(),
-										|output_dir| output_dir.to_path_buf()
-									),
-								None => check!(env::current_dir())
+								Some(output_dir) => output_dir.parent().map_or_else(
+									|| output_dir.to_path_buf(),
+									|output_dir| output_dir.to_path_buf(),
+								),
+								None => env::current_dir()
+									.map_err(|e| error("io", e.to_string(), c.1, c.2, filename))?,
 							})
 						}
-						let output_dir = output_dir.as_ref().unwrap();
 						let str_start = code.read_char_unchecked();
 						let module = match str_start {
 							Some((b'\'' | b'"' | b'`', ..)) => {
@@ -704,16 +788,21 @@ pub fn preprocess_code(
 						}.to_string();
 						let name = code.read_line();
 						let name = name.trim();
-						let mut dirs = module.split('.');
-						let mut module_path = output_dir.join(dirs.next().unwrap());
-						for dir in dirs {
-							module_path.push(dir);
-						}
-						module_path.set_extension("lua");
-						let function = if module_path.exists() {
-							"require"
-						} else {
-							"import"
+						let function = match &output_dir {
+							Some(output_dir) => {
+								let mut dirs = module.split('.');
+								let mut module_path = output_dir.join(dirs.next().unwrap());
+								for dir in dirs {
+									module_path.push(dir);
+								}
+								module_path.set_extension("lua");
+								if module_path.exists() {
+									"require"
+								} else {
+									"import"
+								}
+							}
+							None => "require",
 						};
 						let (name, start) = match name.strip_prefix("=>") {
 							Some(name) =>{
@@ -724,17 +813,18 @@ pub fn preprocess_code(
 										"<empty>",
 										code.line,
 										code.column,
-										filename
-									))
+										filename,
+									));
 								}
-								if trimmed_name.contains(|c| matches!(c, '$' | '@')) {
-									let (codes, new_variables, ..) = preprocess_code(
+								if trimmed_name.contains(['$', '@']) {
+									let (codes, new_variables, _, _, mut notes) = preprocess_code(
 										unsafe { trimmed_name.as_bytes_mut() },
 										code.line,
 										false,
 										filename,
 										options
 									)?;
+									code.notes.append(&mut notes);
 									for (key, value) in new_variables {
 										variables.insert(key, value);
 									}
@@ -745,7 +835,7 @@ pub fn preprocess_code(
 										filename
 									)?.to_string();
 								}
-								let start = if trimmed_name.contains(|c| matches!(c, '.' | '[')) {
+								let start = if trimmed_name.contains(['.', '[']) {
 									""
 								} else {
 									"local "
@@ -758,18 +848,13 @@ pub fn preprocess_code(
 							}.trim().to_string(), "local ")
 						};
 						if name.is_empty() {
-							return Err(expected(
-								"<file name>",
-								"<empty>",
-								c.1,
-								c.2,
-								filename
-							))
+							return Err(expected("<file name>", "<empty>", c.1, c.2, filename));
 						}
 						currentcode.append(Code::from((
 							format_clue!(start, name, " = ", function, "(\"", module, "\")"),
 							c.1,
 							c.2,
+							c.3,
 						)));
 					}
 					"version" => {
@@ -783,9 +868,12 @@ pub fn preprocess_code(
 							};
 						if let Some(v) = full_wanted_version.strip_prefix(">=") {
 							wanted_version = v;
-							println!(
-								"Note: \"@version directives should no longer start with '>='\""
-							);
+							code.notes.push(Diagnostic::note(
+								"deprecated-version-syntax",
+								"@version directives should no longer start with '>='",
+								filename.as_str(),
+								Span::new(c.1, c.2),
+							));
 						}
 						let wanted_version_iter = &mut wanted_version.split('.');
 						const CURRENT_MAJOR: &str = env!("CARGO_PKG_VERSION_MAJOR");
@@ -801,6 +889,7 @@ pub fn preprocess_code(
 						|| check(&current_minor, &wanted_minor)
 						|| check(&current_patch, &wanted_patch) {
 							return Err(error(
+								"incompatible-version",
 								if full_wanted_version.starts_with('=') {
 									format_clue!(
 										"This code is only compatible with version '",
@@ -816,7 +905,7 @@ pub fn preprocess_code(
 								},
 								c.1,
 								c.2,
-								filename
+								filename,
 							));
 						}
 					}
@@ -849,7 +938,7 @@ pub fn preprocess_code(
 							let mut args = Vec::new();
 							loop {
 								code.skip_whitespace();
-								if let Some((b'.', line, column)) = code.peek_char_unchecked() {
+								if let Some((b'.', line, column, _)) = code.peek_char_unchecked() {
 									if code.read(CodeFile::peek_char, |code, (c, ..)| {
 										if c == b'.' {
 											code.read_char_unchecked();
@@ -874,7 +963,7 @@ pub fn preprocess_code(
 										break (false, args);
 									}
 									let (got, line, column) = match code.read_char_unchecked() {
-										Some((c, line, column)) => {
+										Some((c, line, column, _)) => {
 											((c as char).to_string(), line, column)
 										}
 										None => (String::from("<end>"), code.line, code.column),
@@ -901,10 +990,19 @@ pub fn preprocess_code(
 							},
 						);
 					}
-					"error" => return Err(error(code.read_line(), c.1, c.2, filename)),
+					"error" => {
+						return Err(error(
+							"error-directive",
+							code.read_line(),
+							c.1,
+							c.2,
+							filename,
+						))
+					}
 					"print" => println!("{}", code.read_line()),
 					_ => {
 						return Err(error(
+							"unknown-directive",
 							format!("Unknown directive '{directive_name}'"),
 							c.1,
 							c.2,
@@ -921,7 +1019,7 @@ pub fn preprocess_code(
 				size += currentcode.len() + 8;
 				finalcode.push_back((currentcode, false));
 				let name = format_clue!("_vararg", variables.len().to_string());
-				finalcode.push_back((Code::from((format_clue!("$", name), c.1, c.2)), true));
+				finalcode.push_back((Code::from((format_clue!("$", name), c.1, c.2, c.3)), true));
 				code.read_char_unchecked();
 				let (vararg_code, ppvars) = code.read_macro_block()?;
 				variables.extend(ppvars);
@@ -938,7 +1036,7 @@ pub fn preprocess_code(
 					};
 					if pseudos.is_none() {
 						let tocheck = code.code[code.checked..code.read].iter().rev().peekable();
-						pseudos = Some(read_pseudos(tocheck, c.1, c.2));
+						pseudos = Some(read_pseudos(tocheck, c.1, c.2, c.3));
 						code.checked = code.read;
 					}
 					match pseudos.as_ref().unwrap().get(n - 1) {
@@ -971,7 +1069,7 @@ pub fn preprocess_code(
 				true
 			}
 			b'&' | b'|' => {
-				if code.peek_char_unchecked().unwrap_or((b'\0', 0, 0)).0 == c.0 {
+				if code.peek_char_unchecked().unwrap_or((b'\0', 0, 0, 0)).0 == c.0 {
 					currentcode.push(code.read_char_unchecked().unwrap());
 				} else {
 					bitwise = true;
@@ -1064,30 +1162,23 @@ pub fn preprocess_code(
 		size += currentcode.len();
 		finalcode.push_back((currentcode, false))
 	}
-	if bitwise && options.env_jitbit.is_some() {
-		let bit = options.env_jitbit.as_ref().unwrap();
-		let mut loader = Code::from((format_clue!("local ", bit, " = require(\"", bit, "\");"), 1, 1));
+	if let (true, Some(bit)) = (bitwise, &options.env_jitbit) {
+		let mut loader = Code::from((
+			format_clue!("local ", bit, " = require(\"", bit, "\");"),
+			1,
+			1,
+		));
 		let first = finalcode.pop_front().unwrap();
 		loader.append(first.0);
 		finalcode.push_front((loader, first.1));
 	}
-	#[cfg(feature = "lsp")]
-	if options.env_symbols {
-		use PPVar::*;
-		let mut str_variables = HashMap::new();
-		for (name, variable) in &variables {
-			let (name, value) = match variable {
-				Simple(value) | ToProcess(value) => (format_clue!('$', name), value.to_string()),
-				_ => unimplemented!()
-			};
-			str_variables.insert(name, value);
-		}
-		println!("{}", json!({
-			"type": "PPVars",
-			"value": str_variables
-		}));
-	}
-	Ok(((finalcode, size), variables, code.line, code.read))
+	Ok((
+		(finalcode, size),
+		variables,
+		code.line,
+		code.read,
+		code.notes,
+	))
 }
 
 fn skip_whitespace_backwards(code: &mut Peekable<Rev<std::slice::Iter<u8>>>) {
@@ -1104,6 +1195,7 @@ fn read_pseudos(
 	mut code: Peekable<Rev<std::slice::Iter<u8>>>,
 	line: usize,
 	column: usize,
+	offset: usize,
 ) -> VecDeque<Code> {
 	let mut newpseudos = VecDeque::new();
 	while {
@@ -1150,7 +1242,7 @@ fn read_pseudos(
 			if let Some(c) = code.peek() {
 				match c {
 					b'\'' | b'"' | b'`' => {
-						name.push_start((*code.next().unwrap(), line, column));
+						name.push_start((*code.next().unwrap(), line, column, offset));
 						if !matches!(code.peek(), Some(b'\\')) {
 							in_string = !in_string;
 						}
@@ -1173,7 +1265,7 @@ fn read_pseudos(
 				false
 			}
 		} {
-			name.push_start((*code.next().unwrap(), line, column))
+			name.push_start((*code.next().unwrap(), line, column, offset))
 		}
 		newpseudos.push_front(name);
 		skip_whitespace_backwards(&mut code);
@@ -1194,9 +1286,9 @@ fn read_pseudos(
 ///
 /// # Examples
 /// ```
-/// use clue_core::{env::Options, preprocessor::*};
+/// use clue_core::{diagnostic::Diagnostic, env::Options, preprocessor::*};
 ///
-/// fn main() -> Result<(), String> {
+/// fn main() -> Result<(), Box<Diagnostic>> {
 ///     let options = Options::default();
 ///     let filename = String::from("macro.clue");
 ///     let mut code = include_str!("../../examples/macro.clue").to_owned();
@@ -1218,7 +1310,7 @@ pub fn preprocess_codes(
 	codes: PPCode,
 	variables: &PPVars,
 	filename: &String,
-) -> Result<Code, String> {
+) -> Result<Code, Box<Diagnostic>> {
 	let (mut codes, size) = codes;
 	if codes.len() == 1 {
 		Ok(codes.pop_back().unwrap().0)
@@ -1246,9 +1338,9 @@ pub fn preprocess_codes(
 /// See [`preprocess_codes`]
 ///
 /// ```
-/// use clue_core::{code::Code, env::Options, preprocessor::*};
+/// use clue_core::{code::Code, diagnostic::Diagnostic, env::Options, preprocessor::*};
 ///
-/// fn main() -> Result<(), String> {
+/// fn main() -> Result<(), Box<Diagnostic>> {
 ///     let options = Options::default();
 ///     let filename = String::from("macro.clue");
 ///     let mut code = include_str!("../../examples/macro.clue").to_owned();
@@ -1279,7 +1371,7 @@ pub fn preprocess_variables(
 	//mut chars: Peekable<Iter<CodeChar>>,
 	variables: &PPVars,
 	filename: &String,
-) -> Result<Code, String> {
+) -> Result<Code, Box<Diagnostic>> {
 	let mut result = Code::with_capacity(size);
 	let mut chars = code.iter().peekable();
 	while let Some(c) = chars.next() {
@@ -1296,14 +1388,15 @@ pub fn preprocess_variables(
 					name
 				};
 				if let Ok(value) = env::var(name.to_string()) {
-					result.push((b'"', c.1, c.2));
+					result.push((b'"', c.1, c.2, c.3));
 					for strc in value.as_bytes() {
-						result.push((*strc, c.1, c.2));
+						result.push((*strc, c.1, c.2, c.3));
 					}
-					result.push((b'"', c.1, c.2));
+					result.push((b'"', c.1, c.2, c.3));
 				} else if let Some(value) = variables.get(&name) {
-					if stacklevel == MAX {
+					if stacklevel == u8::MAX {
 						return Err(error(
+							"recursion-limit",
 							"Too many variables called (likely recursive)",
 							c.1,
 							c.2,
@@ -1333,6 +1426,7 @@ pub fn preprocess_variables(
 								if !is_called || !matches!(chars.next(), Some((b'(', ..))) {
 									let name = name.to_string();
 									return Err(error(
+										"macro-not-called",
 										format!(
 											"Macro not called (replace '${name}{}' with '${name}!()')",
 											if is_called {
@@ -1354,7 +1448,9 @@ pub fn preprocess_variables(
 									let mut cscope = 1u8;
 									let end = loop {
 										let Some(c) = chars.next() else {
-											return Err(expected_before(")", "<end>", c.1, c.2, filename))
+											return Err(expected_before(
+												")", "<end>", c.1, c.2, filename,
+											));
 										};
 										match c.0 {
 											b'(' => cscope += 1,
@@ -1392,13 +1488,14 @@ pub fn preprocess_variables(
 									} else if *vararg {
 										varargs += 1;
 										let mut arg_name = Code::with_capacity(varargs + 1);
-										arg_name.push((b'_', c.1, c.2));
+										arg_name.push((b'_', c.1, c.2, c.3));
 										for _ in 0..varargs {
-											arg_name.push((b'v', c.1, c.2));
+											arg_name.push((b'v', c.1, c.2, c.3));
 										}
 										macro_variables.insert(arg_name, value);
 									} else {
 										return Err(error(
+											"macro-arguments",
 											"Too many arguments passed to macro",
 											c.1,
 											c.2,
@@ -1411,10 +1508,8 @@ pub fn preprocess_variables(
 								}
 								if let Some(missed) = args.next() {
 									return Err(error(
-										format!(
-											"Missing argument '{}' for macro",
-											missed.to_string()
-										),
+										"macro-arguments",
+										format!("Missing argument '{missed}' for macro"),
 										c.1,
 										c.2,
 										filename,
@@ -1448,6 +1543,7 @@ pub fn preprocess_variables(
 					});
 				} else {
 					return Err(error(
+						"undefined-variable",
 						format_clue!("Value '", name.to_string(), "' not found"),
 						c.1,
 						c.2,
@@ -1474,3 +1570,73 @@ pub fn preprocess_variables(
 	}
 	Ok(result)
 }
+
+fn find_references(code: &Code, names: &mut BTreeSet<String>) {
+	let mut chars = code.iter().peekable();
+	while let Some((c, ..)) = chars.next() {
+		if *c == b'$' {
+			let mut name = String::new();
+			while let Some((c, ..)) =
+				chars.next_if(|(c, ..)| c.is_ascii_alphanumeric() || *c == b'_')
+			{
+				name.push(*c as char)
+			}
+			names.insert(name);
+		}
+	}
+}
+
+/// Returns the names of the variables and macros used by the given code blocks
+/// Only the code blocks themselves are searched, see [`used_variables`] to also search the
+/// variables they use
+///
+/// # Examples
+/// ```
+/// use clue_core::{diagnostic::Diagnostic, env::Options, preprocessor::*};
+///
+/// fn main() -> Result<(), Box<Diagnostic>> {
+///     let options = Options::default();
+///     let filename = String::from("main.clue");
+///     let mut code = b"@define A 1\n@define B $A\nprint($B)".to_vec();
+///     let (codes, variables, ..) = preprocess_code(&mut code, 1, false, &filename, &options)?;
+///
+///     let references = variable_references(&codes);
+///     assert_eq!(Vec::from_iter(&references), ["B"]);
+///     assert_eq!(Vec::from_iter(used_variables(&references, &variables)), ["A", "B"]);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn variable_references(codes: &PPCode) -> BTreeSet<String> {
+	let mut names = BTreeSet::new();
+	for (code, uses_vars) in &codes.0 {
+		if *uses_vars {
+			find_references(code, &mut names)
+		}
+	}
+	names
+}
+
+/// Returns the given names together with the names used by the values of the variables and
+/// macros they refer to, which are every variable that changes the code that uses the given names
+///
+/// # Examples
+/// See [`variable_references`]
+pub fn used_variables(names: &BTreeSet<String>, variables: &PPVars) -> BTreeSet<String> {
+	let mut found = names.clone();
+	let mut queue: Vec<String> = names.iter().cloned().collect();
+	while let Some(name) = queue.pop() {
+		let mut used = BTreeSet::new();
+		match variables.get(&Code::from((name.as_str(), 0, 0))) {
+			Some(PPVar::ToProcess(code)) => find_references(code, &mut used),
+			Some(PPVar::Macro { code, .. }) => used = variable_references(code),
+			_ => continue,
+		}
+		for name in used {
+			if found.insert(name.clone()) {
+				queue.push(name)
+			}
+		}
+	}
+	found
+}
@@ -0,0 +1,1409 @@
+//! The `printer` module turns an AST back into Clue code.
+//!
+//! The AST is built to be compiled to Lua, so the printer translates what the parser emitted for Lua
+//! back into Clue: `and` becomes `&&`, `~=` becomes `!=`, `a:b()` becomes `a::b()` and so on.
+//! The statements the parser adds to lower `??`, `? :`, `?.`, `match` expressions, destructuring,
+//! `if local` and structs are recognized and printed as the syntax they came from, so parsing the
+//! printed code gives back the same AST.
+//! Statements are printed one per line, indented with tabs, keeping the blank lines between them
+//! when the spans of the AST tell where they were.
+//!
+//! It exposes a single function, [`print_ast`].
+
+use crate::{
+	parser::{
+		walk_token, CodeBlock, ComplexToken, ComplexToken::*, Expression, FunctionArgs,
+		FunctionTypes, LuaType, Rename, Visitor, VisitorMut,
+	},
+	scanner::TokenType::*,
+};
+use ahash::AHashMap;
+
+fn binary_operator(lexeme: &str) -> Option<&str> {
+	match lexeme.trim() {
+		"and" => Some("&&"),
+		"or" => Some("||"),
+		"~=" => Some("!="),
+		"~" => Some("^^"),
+		lexeme @ ("+" | "-" | "*" | "/" | "%" | "^" | ".." | "==" | "!=" | "<" | "<=" | ">"
+		| ">=" | "&" | "|" | "^^" | "<<" | ">>") => Some(lexeme),
+		_ => None,
+	}
+}
+
+fn unary_operator(lexeme: &str) -> Option<&str> {
+	match lexeme.trim() {
+		"not" => Some("!"),
+		lexeme @ ("-" | "#" | "~") => Some(lexeme),
+		_ => None,
+	}
+}
+
+/// Turns a Lua long string (e.g. `[==[text]==]`) into a Clue raw string (e.g. `` `text` ``)
+fn raw_string(lexeme: &str) -> Option<String> {
+	let level = lexeme.strip_prefix('[')?.find('[')?;
+	if !lexeme[1..=level].bytes().all(|c| c == b'=') || lexeme.len() < level * 2 + 4 {
+		return None;
+	}
+	let content = &lexeme[level + 2..lexeme.len() - level - 2];
+	Some(format!("`{}`", content.replace('`', "\\`")))
+}
+
+fn type_name(luatype: &LuaType) -> String {
+	match luatype {
+		LuaType::FUNCTION => String::from("fn"),
+		LuaType::UNION(types) => types
+			.iter()
+			.map(type_name)
+			.collect::<Vec<String>>()
+			.join(" | "),
+		luatype => luatype.to_string(),
+	}
+}
+
+fn is_comment(t: &ComplexToken) -> bool {
+	matches!(t, SYMBOL(lexeme, _) if lexeme.starts_with("//") || lexeme.starts_with("/*"))
+}
+
+fn is_internal(name: &str) -> bool {
+	name.starts_with("_internal")
+}
+
+/// Finds the line where a statement ends, which its span misses when it ends with a raw string
+/// or a comment that spans multiple lines
+struct LastLine(usize);
+
+impl<'a> Visitor<'a> for LastLine {
+	fn visit_token(&mut self, t: &'a ComplexToken) {
+		if let SYMBOL(lexeme, span) = t {
+			self.0 = self.0.max(span.start.line + lexeme.matches('\n').count());
+		}
+		walk_token(self, t)
+	}
+}
+
+fn last_line(t: &ComplexToken) -> usize {
+	let end = t.span().end.line;
+	if end == 0 {
+		return 0;
+	}
+	let mut last = LastLine(end);
+	last.visit_token(t);
+	last.0
+}
+
+fn is_symbol(t: Option<&ComplexToken>, symbol: &str) -> bool {
+	matches!(t, Some(SYMBOL(lexeme, _)) if lexeme == symbol)
+}
+
+/// Whether the tokens are what the parser emits for `a?.b`: `_internalN and _internalN.b`
+fn is_safe_chain(tokens: &[&ComplexToken]) -> bool {
+	match tokens {
+		[SYMBOL(name, _), SYMBOL(and, _), SYMBOL(same, _), ..] => {
+			is_internal(name) && and == " and " && name == same
+		}
+		_ => false,
+	}
+}
+
+/// Whether the expression is an identifier not wrapped in an [`IDENT`], like the values the
+/// parser stores in the internal variables of `?.`
+fn is_raw_identifier(expr: &Expression) -> bool {
+	matches!(expr.front(), Some(SYMBOL(..)))
+		&& expr.iter().all(|t| match t {
+			SYMBOL(lexeme, _) => {
+				matches!(lexeme.as_str(), "." | ":" | "[(" | ")]")
+					|| binary_operator(lexeme).is_none() && unary_operator(lexeme).is_none()
+			}
+			CALL(..) | EXPR(..) => true,
+			_ => false,
+		})
+}
+
+/// Returns the value a block assigns to the internal variable `name` with its last statement,
+/// which is how the parser lowers the branches of `??`, `? :` and `match` expressions
+fn assigned_value<'a>(block: &'a CodeBlock, name: &str) -> Option<&'a Expression> {
+	match block.code.back() {
+		Some(ALTER {
+			kind: DEFINE,
+			names,
+			values,
+			..
+		}) if names.len() == 1 && values.len() == 1 => match names[0].iter().collect::<Vec<_>>()[..] {
+			[SYMBOL(assigned, _)] if assigned == name => Some(&values[0]),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// Whether a condition is `name == nil` for the internal variable `name`
+fn is_nil_check(condition: &Expression, name: &str) -> bool {
+	condition.len() == 2
+		&& is_symbol(condition.front(), name)
+		&& is_symbol(condition.get(1), " == nil")
+}
+
+/// Returns the condition of a loop lowered from `until` or `loop { } while`, which is `not (...)`
+fn negated_condition(condition: &Expression) -> Option<impl Iterator<Item = &ComplexToken>> {
+	(condition.len() > 2
+		&& is_symbol(condition.front(), "not (")
+		&& is_symbol(condition.back(), ")"))
+	.then(|| condition.range(1..condition.len() - 1))
+}
+
+/// Returns the variables and the `if` of a `DO_BLOCK` lowered from `if local`
+fn if_local(block: &CodeBlock) -> Option<(usize, &ComplexToken, &ComplexToken)> {
+	let len = block.code.len();
+	match (block.code.get(len.wrapping_sub(2)), block.code.back()) {
+		(Some(vars @ VARIABLE { local: true, .. }), Some(t @ IF_STATEMENT { condition, .. }))
+			if matches!(condition.front(), Some(SYMBOL(check, _)) if check.ends_with(" ~= nil")) =>
+		{
+			Some((len - 2, vars, t))
+		}
+		_ => None,
+	}
+}
+
+struct Printer {
+	output: String,
+	indent: usize,
+	internals: AHashMap<String, String>,
+	tables: AHashMap<String, (String, String)>,
+}
+
+impl Printer {
+	fn new_line(&mut self) {
+		self.output.push('\n');
+		for _ in 0..self.indent {
+			self.output.push('\t');
+		}
+	}
+
+	fn render(&mut self, print: impl FnOnce(&mut Self)) -> String {
+		let output = std::mem::take(&mut self.output);
+		print(self);
+		std::mem::replace(&mut self.output, output)
+	}
+
+	fn print_list<T>(&mut self, list: &[T], mut print: impl FnMut(&mut Self, &T)) {
+		for (i, element) in list.iter().enumerate() {
+			if i > 0 {
+				self.output += ", ";
+			}
+			print(self, element);
+		}
+	}
+
+	/// Records the statements the parser added to lower an expression, to print the expression
+	/// in their place when its internal variable is used
+	/// Returns how many statements were recorded, 0 if the statement is not one of them
+	fn hoist(&mut self, code: &Expression, i: usize) -> usize {
+		match &code[i] {
+			VARIABLE {
+				local: true,
+				names,
+				values,
+				types,
+				..
+			} if names.len() == 1 && is_internal(&names[0]) && types.is_empty() => {
+				let name = &names[0];
+				if let (
+					1,
+					Some(IF_STATEMENT {
+						condition,
+						code: block,
+						next: None,
+						..
+					}),
+				) = (values.len(), code.get(i + 1))
+				{
+					if let Some(right) =
+						assigned_value(block, name).filter(|_| is_nil_check(condition, name))
+					{
+						let left = self.render(|printer| printer.print_expression(&values[0]));
+						self.hoist_all(&block.code, block.code.len() - 1);
+						let right = self.render(|printer| printer.print_expression(right));
+						self.internals
+							.insert(name.clone(), format!("{left} ?? {right}"));
+						return 2;
+					}
+				}
+				if let (
+					0,
+					Some(IF_STATEMENT {
+						condition,
+						code: truthy,
+						next: Some(next),
+						..
+					}),
+				) = (values.len(), code.get(i + 1))
+				{
+					if let DO_BLOCK(falsy, _) = next.as_ref() {
+						if let (Some(t), Some(f)) =
+							(assigned_value(truthy, name), assigned_value(falsy, name))
+						{
+							let ternary = self.render(|printer| {
+								printer.print_expression(condition);
+								printer.output += " ? ";
+								printer.hoist_all(&truthy.code, truthy.code.len() - 1);
+								printer.print_expression(t);
+								printer.output += " : ";
+								printer.hoist_all(&falsy.code, falsy.code.len() - 1);
+								printer.print_expression(f);
+							});
+							self.internals.insert(name.clone(), ternary);
+							return 2;
+						}
+					}
+				}
+				if values.len() == 1 {
+					if let [SYMBOL(key, _)] = values[0].iter().collect::<Vec<_>>()[..] {
+						if let Some((table, key)) = key.split_once('.') {
+							if self.internals.contains_key(table) || self.tables.contains_key(table)
+							{
+								self.tables
+									.insert(name.clone(), (table.to_owned(), key.to_owned()));
+								return 1;
+							}
+						}
+					}
+				}
+				if values.is_empty() {
+					return 0;
+				}
+				let value = self.render(|printer| match &values[..] {
+					[value]
+						if is_raw_identifier(value)
+							|| is_safe_chain(&value.iter().collect::<Vec<_>>()) =>
+					{
+						printer.print_identifier(value)
+					}
+					values => printer.print_expressions(values),
+				});
+				self.internals.insert(name.clone(), value);
+				1
+			}
+			MATCH_BLOCK {
+				name,
+				value,
+				branches,
+				..
+			} if is_internal(name)
+				&& branches
+					.iter()
+					.all(|(.., block)| assigned_value(block, name).is_some()) =>
+			{
+				let mut branches = &branches[..];
+				if let [rest @ .., (conditions, _, None, block)] = branches {
+					let nil = assigned_value(block, name).map_or(false, |value| {
+						value.len() == 1 && is_symbol(value.front(), "nil")
+					});
+					if conditions.is_empty() && block.code.len() == 1 && nil {
+						branches = rest;
+					}
+				}
+				let text = self.render(|printer| {
+					printer.output += "match ";
+					printer.print_expression(value);
+					printer.output += " {";
+					printer.indent += 1;
+					for (i, (conditions, internal, extraif, block)) in branches.iter().enumerate() {
+						printer.hoist_all(internal, internal.len());
+						printer.new_line();
+						printer.print_match_case(conditions, extraif);
+						printer.hoist_all(&block.code, block.code.len() - 1);
+						if let Some(value) = assigned_value(block, name) {
+							printer.print_expression(value);
+						}
+						if i + 1 < branches.len() {
+							printer.output.push(',');
+						}
+					}
+					printer.indent -= 1;
+					printer.new_line();
+					printer.output.push('}');
+				});
+				self.internals.insert(name.clone(), text);
+				1
+			}
+			_ => 0,
+		}
+	}
+
+	fn hoist_all(&mut self, code: &Expression, end: usize) {
+		let mut i = 0;
+		while i < end {
+			i += self.hoist(code, i).max(1);
+		}
+	}
+
+	fn print_statements(&mut self, code: &Expression, nested: bool) {
+		let mut last: Option<usize> = None;
+		let mut i = 0;
+		while i < code.len() {
+			let hoisted = self.hoist(code, i);
+			if hoisted > 0 {
+				i += hoisted;
+				continue;
+			}
+			let t = &code[i];
+			let span = t.span();
+			if i > 0 && matches!((&code[i - 1], t), (EXPR(..), IDENT { .. })) {
+				self.print_statement(t);
+				i += 1;
+				continue;
+			}
+			if let Some(last) = last {
+				if last != 0 && span.start.line == last && is_comment(t) {
+					self.output.push(' ');
+					self.print_statement(t);
+					i += 1;
+					continue;
+				}
+				if last != 0 && span.start.line > last + 1 {
+					self.output.push('\n');
+				}
+			}
+			if nested || last.is_some() {
+				self.new_line();
+			}
+			let printed = self.print_struct(code, i).unwrap_or_else(|| {
+				self.print_statement(t);
+				1
+			});
+			i += printed;
+			last = Some(last_line(&code[i - 1]));
+		}
+	}
+
+	fn print_block(&mut self, block: &CodeBlock) {
+		if block.code.is_empty() {
+			self.output += "{}";
+			return;
+		}
+		let internals = std::mem::take(&mut self.internals);
+		let tables = std::mem::take(&mut self.tables);
+		self.output.push('{');
+		self.indent += 1;
+		self.print_statements(&block.code, true);
+		self.indent -= 1;
+		self.new_line();
+		self.output.push('}');
+		self.internals = internals;
+		self.tables = tables;
+	}
+
+	fn print_expressions(&mut self, exprs: &[Expression]) {
+		self.print_list(exprs, |printer, expr| printer.print_expression(expr));
+	}
+
+	fn print_expression(&mut self, expr: &Expression) {
+		if let (Some(SYMBOL(floor, span)), Some(CALL(args, _)), 2) =
+			(expr.front(), expr.get(1), expr.len())
+		{
+			if let (true, [division]) = (floor == "math.floor", &args[..]) {
+				let slash = division.iter().position(
+					|t| matches!(t, SYMBOL(lexeme, slash) if lexeme == "/" && slash == span),
+				);
+				if let Some(slash) = slash {
+					self.print_expression(&division.range(..slash).cloned().collect());
+					self.output += " /_ ";
+					self.print_expression(&division.range(slash + 1..).cloned().collect());
+					return;
+				}
+			}
+		}
+		let mut operand = false;
+		for t in expr {
+			if let SYMBOL(lexeme, _) = t {
+				if operand {
+					if let Some(operator) = binary_operator(lexeme) {
+						self.output += " ";
+						self.output += operator;
+						self.output += " ";
+						operand = false;
+						continue;
+					}
+				} else if let Some(operator) = unary_operator(lexeme) {
+					self.output += operator;
+					continue;
+				}
+			}
+			self.print_token(t);
+			operand = true;
+		}
+	}
+
+	fn print_token(&mut self, t: &ComplexToken) {
+		match t {
+			SYMBOL(lexeme, _) => {
+				if let Some(text) = self.internals.remove(lexeme) {
+					self.output += &text;
+				} else if let Some(string) = raw_string(lexeme) {
+					self.output += &string;
+				} else {
+					self.output += lexeme;
+				}
+			}
+			IDENT { expr, .. } => self.print_identifier(expr),
+			EXPR(expr, _) => {
+				self.output.push('(');
+				self.print_expression(expr);
+				self.output.push(')');
+			}
+			CALL(args, _) => {
+				self.output.push('(');
+				self.print_expressions(args);
+				self.output.push(')');
+			}
+			TABLE {
+				values,
+				metas,
+				metatable,
+				span,
+			} => {
+				let multiline = values.iter().any(|(.., line)| *line != span.start.line)
+					|| metas.iter().any(|(.., line)| *line != span.start.line);
+				let mut entries = Vec::with_capacity(values.len() + metas.len() + 1);
+				for (key, value, _) in values {
+					entries.push((None, key.as_ref(), value));
+				}
+				for (name, value, _) in metas {
+					entries.push((Some(name.trim_start_matches('_')), None, value));
+				}
+				self.output.push('{');
+				self.indent += 1;
+				for (i, (meta, key, value)) in entries.iter().enumerate() {
+					if multiline {
+						self.new_line();
+					} else if i > 0 {
+						self.output.push(' ');
+					}
+					if let Some(name) = meta {
+						self.output += "meta ";
+						self.output += name;
+						self.output += " = ";
+					} else if let Some(key) = key {
+						match (key.front(), key.get(1), key.len()) {
+							(Some(SYMBOL(open, _)), Some(EXPR(key, _)), 3) if open == "[" => {
+								self.output.push('[');
+								self.print_expression(key);
+								self.output.push(']');
+							}
+							_ => self.print_expression(key),
+						}
+						self.output += " = ";
+					}
+					self.print_expression(value);
+					if i + 1 < entries.len() || metatable.is_some() {
+						self.output.push(',');
+					}
+				}
+				if let Some(metatable) = metatable {
+					if multiline {
+						self.new_line();
+					} else if !entries.is_empty() {
+						self.output.push(' ');
+					}
+					self.output += "meta with ";
+					self.output += metatable;
+				}
+				self.indent -= 1;
+				if multiline {
+					self.new_line();
+				}
+				self.output.push('}');
+			}
+			LAMBDA {
+				args, types, code, ..
+			} => {
+				self.output += "fn";
+				self.print_function(args, types, code);
+			}
+			t => self.print_statement(t),
+		}
+	}
+
+	fn print_identifier<'b>(&mut self, expr: impl IntoIterator<Item = &'b ComplexToken>) {
+		let tokens: Vec<&ComplexToken> = expr.into_iter().collect();
+		let tokens = match &tokens[..] {
+			[SYMBOL(open, _), inner @ .., SYMBOL(close, _)]
+				if open == "(" && close == ")" && is_safe_chain(inner) =>
+			{
+				inner
+			}
+			tokens => tokens,
+		};
+		let tokens = if is_safe_chain(tokens) {
+			self.print_token(tokens[0]);
+			self.output.push('?');
+			&tokens[3..]
+		} else {
+			tokens
+		};
+		for t in tokens {
+			match t {
+				SYMBOL(lexeme, _) => match lexeme.as_str() {
+					"[(" => self.output.push('['),
+					")]" => self.output.push(']'),
+					":" => self.output += "::",
+					_ => self.print_token(t),
+				},
+				EXPR(expr, _) => self.print_expression(expr),
+				t => self.print_token(t),
+			}
+		}
+	}
+
+	fn print_function(&mut self, args: &FunctionArgs, types: &FunctionTypes, code: &CodeBlock) {
+		let (arg_types, returns) = types;
+		self.output.push('(');
+		for (i, (arg, default)) in args.iter().enumerate() {
+			if i > 0 {
+				self.output += ", ";
+			}
+			self.output += arg;
+			if let Some(Some(luatype)) = arg_types.get(i) {
+				self.output += ": ";
+				self.output += &type_name(luatype);
+			}
+			if let Some((default, _)) = default {
+				self.output += " = ";
+				self.print_expression(default);
+			}
+		}
+		self.output.push(')');
+		if let Some(returns) = returns {
+			self.output += ": ";
+			self.output += &type_name(returns);
+		}
+		self.output.push(' ');
+		self.print_block(code);
+	}
+
+	fn print_variables(
+		&mut self,
+		names: &[String],
+		values: &[Expression],
+		types: &[Option<LuaType>],
+	) {
+		if !self.print_destructuring(names, values) {
+			for (i, name) in names.iter().enumerate() {
+				if i > 0 {
+					self.output += ", ";
+				}
+				self.output += name;
+				if let Some(Some(luatype)) = types.get(i) {
+					self.output += ": ";
+					self.output += &type_name(luatype);
+				}
+			}
+			if !values.is_empty() {
+				self.output += " = ";
+				self.print_expressions(values);
+			}
+		}
+	}
+
+	/// Prints `{a, b => c, d => {e}} = t` if the variables are a lowered table destructuring
+	fn print_destructuring(&mut self, names: &[String], values: &[Expression]) -> bool {
+		let mut entries = Vec::with_capacity(names.len());
+		for (name, value) in names.iter().zip(values) {
+			let [SYMBOL(key, _)] = value.iter().collect::<Vec<_>>()[..] else {
+				return false;
+			};
+			let Some((table, key)) = key.split_once('.') else {
+				return false;
+			};
+			let mut path = vec![table];
+			while let Some((parent, _)) = self.tables.get(*path.last().unwrap_or(&table)) {
+				path.push(parent);
+			}
+			path.reverse();
+			if !self.internals.contains_key(path[0]) || names.len() != values.len() {
+				return false;
+			}
+			entries.push((name, key, path));
+		}
+		let Some(root) = entries.first().map(|(.., path)| path[0].to_owned()) else {
+			return false;
+		};
+		if entries.iter().any(|(.., path)| path[0] != root) {
+			return false;
+		}
+		let mut open = vec![root.as_str()];
+		let mut separate = false;
+		let mut destructuring = String::from("{");
+		for (name, key, path) in &entries {
+			let common = open.iter().zip(path).take_while(|(a, b)| a == b).count();
+			while open.len() > common {
+				open.pop();
+				destructuring.push('}');
+				separate = true;
+			}
+			for table in &path[common..] {
+				if separate {
+					destructuring += ", ";
+				}
+				if let Some((_, key)) = self.tables.get(*table) {
+					destructuring += key;
+				}
+				destructuring += " => {";
+				open.push(table);
+				separate = false;
+			}
+			if separate {
+				destructuring += ", ";
+			}
+			destructuring += key;
+			if name != key {
+				destructuring += " => ";
+				destructuring += name;
+			}
+			separate = true;
+		}
+		for _ in open {
+			destructuring.push('}');
+		}
+		self.output += &destructuring;
+		self.output += " = ";
+		if let Some(value) = self.internals.remove(&root) {
+			self.output += &value;
+		}
+		true
+	}
+
+	fn print_match_case(&mut self, conditions: &[Expression], extraif: &Option<Expression>) {
+		if conditions.is_empty() {
+			self.output += "default";
+		}
+		for (i, condition) in conditions.iter().enumerate() {
+			if i > 0 {
+				self.output += " || ";
+			}
+			self.print_expression(condition);
+		}
+		if let Some(extraif) = extraif {
+			self.output += " if ";
+			self.print_expression(extraif);
+		}
+		self.output += " => ";
+	}
+
+	/// Prints an `if` statement, or the `DO_BLOCK` of an `if local` statement
+	fn print_if(&mut self, t: &ComplexToken) {
+		let (code, next) = match t {
+			IF_STATEMENT {
+				condition,
+				code,
+				next,
+				..
+			} => {
+				self.output += "if ";
+				self.print_expression(condition);
+				(code, next)
+			}
+			DO_BLOCK(block, _) => match if_local(block) {
+				Some((
+					prelude,
+					VARIABLE {
+						names,
+						values,
+						types,
+						..
+					},
+					IF_STATEMENT { code, next, .. },
+				)) => {
+					self.hoist_all(&block.code, prelude);
+					self.output += "if local ";
+					self.print_variables(names, values, types);
+					(code, next)
+				}
+				_ => return,
+			},
+			_ => return,
+		};
+		self.output.push(' ');
+		self.print_block(code);
+		match next.as_deref() {
+			Some(DO_BLOCK(block, _)) if if_local(block).is_none() => {
+				self.output += " else ";
+				self.print_block(block);
+			}
+			Some(next) => {
+				self.output += " else";
+				self.print_if(next);
+			}
+			None => {}
+		}
+	}
+
+	/// Prints a struct if the statements at `i` are a lowered struct
+	/// Returns how many statements the struct was made of
+	fn print_struct(&mut self, code: &Expression, i: usize) -> Option<usize> {
+		let (
+			Some(VARIABLE {
+				local,
+				names,
+				values,
+				..
+			}),
+			Some(ALTER {
+				names: index,
+				values: this,
+				..
+			}),
+			Some(FUNCTION {
+				name: new,
+				args,
+				types,
+				code: constructor,
+				..
+			}),
+		) = (code.get(i), code.get(i + 1), code.get(i + 2))
+		else {
+			return None;
+		};
+		let [name] = &names[..] else {
+			return None;
+		};
+		let empty = matches!(&values[..], [table] if matches!(
+			table.iter().collect::<Vec<_>>()[..],
+			[TABLE { values, metas, metatable: None, .. }] if values.is_empty() && metas.is_empty()
+		));
+		let is_index = |expr: &Expression, key: &str| {
+			expr.len() == 3
+				&& is_symbol(expr.front(), name)
+				&& is_symbol(expr.get(1), ".")
+				&& is_symbol(expr.get(2), key)
+		};
+		let fields = match (constructor.code.front(), constructor.code.back()) {
+			(
+				Some(VARIABLE {
+					names: this_name,
+					values: this_value,
+					..
+				}),
+				Some(RETURN_EXPR(..)),
+			) => match (&this_name[..], &this_value[..]) {
+				([this_name], [this_value]) if this_name == "self" => match this_value.front() {
+					Some(TABLE {
+						values,
+						metatable: Some(metatable),
+						..
+					}) if metatable == name => values,
+					_ => return None,
+				},
+				_ => return None,
+			},
+			_ => return None,
+		};
+		if !empty
+			|| index.len() != 1
+			|| !is_index(&index[0], "__index")
+			|| !matches!(&this[..], [this] if this.len() == 1 && is_symbol(this.front(), name))
+			|| !is_index(new, "new")
+		{
+			return None;
+		}
+		self.output += if *local {
+			"local struct "
+		} else {
+			"global struct "
+		};
+		self.output += name;
+		self.output += " {";
+		self.indent += 1;
+		for (key, value, _) in fields {
+			self.new_line();
+			if let Some(key) = key {
+				self.print_identifier(key);
+			}
+			if !(value.len() == 1 && is_symbol(value.front(), "nil")) {
+				self.output += " = ";
+				self.print_expression(value);
+			}
+			self.output.push(',');
+		}
+		// the constructor returns the struct unless it ends with a return of its own
+		let returns_self = matches!(
+			constructor.code.back(),
+			Some(RETURN_EXPR(Some(exprs), _))
+				if matches!(&exprs[..], [this] if this.len() == 1 && is_symbol(this.front(), "self"))
+		);
+		// the arguments used by the defaults of the fields were renamed and then given back their names
+		let mut args = args.clone();
+		let restored = match constructor.code.get(1) {
+			Some(VARIABLE {
+				local: true,
+				names,
+				values,
+				..
+			}) if names.len() == values.len()
+				&& values.iter().all(|value| {
+					matches!(value.iter().collect::<Vec<_>>()[..], [SYMBOL(internal, _)]
+						if is_internal(internal) && args.iter().any(|(arg, _)| arg == internal))
+				}) =>
+			{
+				for (name, value) in names.iter().zip(values) {
+					let Some(SYMBOL(internal, _)) = value.front() else {
+						continue;
+					};
+					for (arg, default) in &mut args {
+						if arg == internal {
+							*arg = name.clone();
+						}
+						if let Some((default, _)) = default {
+							Rename {
+								from: internal,
+								to: name,
+							}
+							.visit_expression_mut(default);
+						}
+					}
+				}
+				1
+			}
+			_ => 0,
+		};
+		let body = constructor
+			.code
+			.range(1 + restored..constructor.code.len() - returns_self as usize);
+		if body.len() > 0 || !args.is_empty() || types.1.is_some() {
+			self.new_line();
+			self.output += "constructor";
+			let code = CodeBlock {
+				start: constructor.start,
+				code: body.cloned().collect(),
+				end: constructor.end,
+			};
+			self.print_function(&args, types, &code);
+		}
+		let mut printed = 3;
+		while let Some(FUNCTION {
+			local: false,
+			name: function,
+			args,
+			types,
+			code: body,
+			..
+		}) = code.get(i + printed)
+		{
+			let (Some(SYMBOL(index, _)), Some(SYMBOL(function, _)), true) = (
+				function.get(1),
+				function.get(2),
+				function.len() == 3 && is_symbol(function.front(), name),
+			) else {
+				break;
+			};
+			self.new_line();
+			self.output += if index == ":" { "method " } else { "fn " };
+			self.output += function;
+			self.print_function(args, types, body);
+			printed += 1;
+		}
+		self.indent -= 1;
+		self.new_line();
+		self.output.push('}');
+		Some(printed)
+	}
+
+	fn print_statement(&mut self, t: &ComplexToken) {
+		match t {
+			VARIABLE {
+				local,
+				names,
+				values,
+				types,
+				..
+			} => {
+				self.output += if *local { "local " } else { "global " };
+				self.print_variables(names, values, types);
+			}
+			ALTER {
+				kind,
+				names,
+				values,
+				..
+			} => {
+				for (i, name) in names.iter().enumerate() {
+					if i > 0 {
+						self.output += ", ";
+					}
+					self.print_identifier(name);
+				}
+				self.output += match kind {
+					DEFINE_AND => " &&= ",
+					DEFINE_OR => " ||= ",
+					DEFINE_COALESCE => " ??= ",
+					INCREASE => " += ",
+					DECREASE => " -= ",
+					MULTIPLY => " *= ",
+					DIVIDE => " /= ",
+					EXPONENTIATE => " ^= ",
+					CONCATENATE => " ..= ",
+					MODULATE => " %= ",
+					_ => " = ",
+				};
+				self.print_expressions(values);
+			}
+			FUNCTION {
+				local,
+				name,
+				args,
+				types,
+				code,
+				..
+			} => {
+				self.output += match (*local, name.len()) {
+					(true, _) => "local fn ",
+					(false, 1) => "global fn ",
+					(false, _) => "method ",
+				};
+				self.print_identifier(name);
+				self.print_function(args, types, code);
+			}
+			IF_STATEMENT {
+				condition,
+				code,
+				next: None,
+				..
+			} if matches!(&code.code.iter().collect::<Vec<_>>()[..], [ALTER { kind: DEFINE, names, values, .. }]
+				if names.len() == 1 && values.len() == 1 && condition.len() == names[0].len() + 1
+					&& is_symbol(condition.back(), " == nil")
+					&& condition.range(..names[0].len()).eq(names[0].iter())) =>
+			{
+				let Some(ALTER { names, values, .. }) = code.code.front() else {
+					return;
+				};
+				self.print_identifier(&names[0]);
+				self.output += " ??= ";
+				self.print_expressions(values);
+			}
+			IF_STATEMENT {
+				condition,
+				code,
+				next: None,
+				..
+			} if matches!((&condition.iter().collect::<Vec<_>>()[..], code.code.front(), code.code.len()),
+				([SYMBOL(name, _)], Some(IDENT { expr, .. }), 1)
+					if self.internals.contains_key(name) && is_symbol(expr.front(), name)) =>
+			{
+				let Some(IDENT { expr, .. }) = code.code.front() else {
+					return;
+				};
+				self.print_token(&expr[0]);
+				self.output.push('?');
+				self.print_identifier(expr.range(1..));
+			}
+			IF_STATEMENT { .. } => self.print_if(t),
+			MATCH_BLOCK {
+				value, branches, ..
+			} => {
+				self.output += "match ";
+				self.print_expression(value);
+				self.output += " {";
+				self.indent += 1;
+				for (conditions, internal, extraif, code) in branches {
+					self.hoist_all(internal, internal.len());
+					self.new_line();
+					self.print_match_case(conditions, extraif);
+					self.print_block(code);
+				}
+				self.indent -= 1;
+				self.new_line();
+				self.output.push('}');
+			}
+			WHILE_LOOP {
+				condition, code, ..
+			} => {
+				if condition.len() == 1 && is_symbol(condition.front(), "true") {
+					self.output += "loop ";
+				} else if let Some(condition) = negated_condition(condition) {
+					self.output += "until ";
+					self.print_expression(&condition.cloned().collect());
+					self.output.push(' ');
+				} else {
+					self.output += "while ";
+					self.print_expression(condition);
+					self.output.push(' ');
+				}
+				self.print_block(code);
+			}
+			LOOP_UNTIL {
+				condition, code, ..
+			} => {
+				self.output += "loop ";
+				self.print_block(code);
+				if let Some(condition) = negated_condition(condition) {
+					self.output += " while ";
+					self.print_expression(&condition.cloned().collect());
+				} else {
+					self.output += " until ";
+					self.print_expression(condition);
+				}
+			}
+			FOR_LOOP {
+				iterator,
+				start,
+				end,
+				alter,
+				code,
+				..
+			} => {
+				self.output += "for ";
+				self.output += iterator;
+				self.output += " = ";
+				self.print_expression(start);
+				self.output += ", ";
+				self.print_expression(end);
+				if !(alter.len() == 1 && is_symbol(alter.front(), "1")) {
+					self.output += ", ";
+					self.print_expression(alter);
+				}
+				self.output.push(' ');
+				self.print_block(code);
+			}
+			FOR_FUNC_LOOP {
+				iterators,
+				expr,
+				code,
+				..
+			} => {
+				self.output += "for ";
+				self.output += &iterators.join(", ");
+				let keyword = if is_symbol(expr.back(), ")") {
+					match expr.front() {
+						Some(SYMBOL(open, _)) if open == "pairs(" => Some(" of "),
+						Some(SYMBOL(open, _)) if open == "ipairs(" => Some(" in "),
+						_ => None,
+					}
+				} else {
+					None
+				};
+				match keyword {
+					Some(keyword) => {
+						self.output += keyword;
+						self.print_expression(&expr.range(1..expr.len() - 1).cloned().collect());
+					}
+					None => {
+						self.output += " with ";
+						self.print_expression(expr);
+					}
+				}
+				self.output.push(' ');
+				self.print_block(code);
+			}
+			TRY_CATCH {
+				totry,
+				catch,
+				error,
+				..
+			} => {
+				self.output += "try ";
+				self.print_block(totry);
+				if let Some(catch) = catch {
+					self.output += " catch ";
+					if let Some(error) = error {
+						self.output += error;
+						self.output.push(' ');
+					}
+					self.print_block(catch);
+				}
+			}
+			EXTERN_GLOBAL { name, args, .. } => {
+				self.output += "extern ";
+				match args {
+					Some(args) => {
+						self.output += "fn ";
+						self.output += name;
+						self.output.push('(');
+						self.print_list(args, |printer, (arg, _)| printer.output += arg);
+						self.output.push(')');
+					}
+					None => self.output += name,
+				}
+			}
+			DO_BLOCK(block, _) if if_local(block).is_some() => self.print_if(t),
+			DO_BLOCK(code, _) => self.print_block(code),
+			RETURN_EXPR(exprs, _) => {
+				self.output += "return";
+				if let Some(exprs) = exprs {
+					self.output.push(' ');
+					self.print_expressions(exprs);
+				}
+			}
+			CONTINUE_LOOP(_) => self.output += "continue",
+			BREAK_LOOP(_) => self.output += "break",
+			IDENT { expr, .. } => self.print_identifier(expr),
+			SYMBOL(lexeme, _) => self.output += lexeme,
+			t => self.print_token(t),
+		}
+	}
+}
+
+/// Prints an AST as Clue code, with a statement per line and a tab per indentation level
+/// Lines that only contain a comment can be kept by adding them to the AST as [`SYMBOL`]s
+///
+/// The AST should come from a parser using the default [`Options`](crate::env::Options):
+/// what other options make the parser emit (e.g. `bit.band` calls for `--jitbit`) is printed as is
+///
+/// # Example
+/// ```rust
+/// use clue_core::{diagnostic::Diagnostic, printer::print_ast, Clue};
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let clue = Clue::new();
+///     let (expr, _) = clue.parse_code("local t = {1, 2}\nif !t[1] && #t != 2 { t::f() }".to_owned())?;
+///
+///     assert_eq!(print_ast(&expr), "local t = {1, 2}\nif !t[1] && #t != 2 {\n\tt::f()\n}\n");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn print_ast(expr: &Expression) -> String {
+	let mut printer = Printer {
+		output: String::new(),
+		indent: 0,
+		internals: AHashMap::default(),
+		tables: AHashMap::default(),
+	};
+	printer.print_statements(expr, false);
+	if !printer.output.is_empty() {
+		printer.output.push('\n');
+	}
+	printer.output
+}
+
+#[cfg(test)]
+mod tests {
+	use super::print_ast;
+	use crate::{
+		diagnostic::Span,
+		parser::{
+			walk_function_mut, walk_token_mut, CodeBlock, ComplexToken, ComplexToken::*,
+			Expression, FunctionArgs, VisitorMut,
+		},
+		Clue,
+	};
+
+	/// Forgets where every token was, which printing can't keep
+	struct Normalize;
+
+	impl VisitorMut for Normalize {
+		fn visit_token_mut(&mut self, t: &mut ComplexToken) {
+			match t {
+				VARIABLE { line, span, .. }
+				| ALTER { line, span, .. }
+				| MATCH_BLOCK { line, span, .. }
+				| WHILE_LOOP { line, span, .. }
+				| LOOP_UNTIL { line, span, .. }
+				| FOR_LOOP { line, span, .. }
+				| FOR_FUNC_LOOP { line, span, .. }
+				| IDENT { line, span, .. } => {
+					*line = 0;
+					*span = Span::default();
+				}
+				TABLE {
+					values,
+					metas,
+					span,
+					..
+				} => {
+					values.iter_mut().for_each(|(.., line)| *line = 0);
+					metas.iter_mut().for_each(|(.., line)| *line = 0);
+					*span = Span::default();
+				}
+				EXTERN_GLOBAL {
+					args, line, span, ..
+				} => {
+					if let Some(args) = args {
+						forget_default_lines(args);
+					}
+					*line = 0;
+					*span = Span::default();
+				}
+				FUNCTION { span, .. }
+				| LAMBDA { span, .. }
+				| IF_STATEMENT { span, .. }
+				| TRY_CATCH { span, .. }
+				| SYMBOL(_, span)
+				| CALL(_, span)
+				| EXPR(_, span)
+				| DO_BLOCK(_, span)
+				| RETURN_EXPR(_, span)
+				| CONTINUE_LOOP(span)
+				| BREAK_LOOP(span) => *span = Span::default(),
+			}
+			walk_token_mut(self, t)
+		}
+
+		fn visit_block_mut(&mut self, block: &mut CodeBlock) {
+			block.start = 0;
+			block.end = 0;
+			self.visit_expression_mut(&mut block.code)
+		}
+
+		fn visit_function_mut(&mut self, args: &mut FunctionArgs, code: &mut CodeBlock) {
+			forget_default_lines(args);
+			walk_function_mut(self, args, code)
+		}
+	}
+
+	fn forget_default_lines(args: &mut FunctionArgs) {
+		for (_, default) in args {
+			if let Some((_, line)) = default {
+				*line = 0;
+			}
+		}
+	}
+
+	fn parse(code: &str) -> Expression {
+		let (mut expr, _) = Clue::new()
+			.parse_code(code.to_owned())
+			.unwrap_or_else(|errors| panic!("{code}\n{errors:?}"));
+		Normalize.visit_expression_mut(&mut expr);
+		expr
+	}
+
+	fn assert_round_trip(code: &str) {
+		let expr = parse(code);
+		let printed = print_ast(&expr);
+		let reparsed = parse(&printed);
+		assert!(
+			reparsed == expr,
+			"{code}\n--- printed as ---\n{printed}\n--- which prints as ---\n{}",
+			print_ast(&reparsed)
+		);
+	}
+
+	#[test]
+	fn examples() {
+		for entry in std::fs::read_dir("../examples").unwrap() {
+			let path = entry.unwrap().path();
+			if path
+				.extension()
+				.map_or(false, |extension| extension == "clue")
+			{
+				assert_round_trip(&std::fs::read_to_string(path).unwrap());
+			}
+		}
+	}
+
+	#[test]
+	fn constructs() {
+		for code in [
+			"local a, b: number, c = 1, 2\nglobal d: string? = \"x\" .. 'y'",
+			"local x = a /_ b + 1\nx, y.z[1] = 1, 2\ny.z[1] += x /_ 2",
+			"local t = {1, [k] = 2, x = {y = fn(a: number = 0): fn { return }}, meta index = t}\nlocal u = {meta with t}",
+			"local v = a ?? b ?? c\nlocal w = c ? d : e ? f : g\nx ??= fn() {}",
+			"local m = match v {\n\t1 => a,\n\t2 || 3 if a => b ?? c,\n\tdefault => c\n}\nprint(match x { 1 => 2 })",
+			"match x {\n\t1 => { print(1) }\n\tdefault => { print(match y { 1 => 2 }) }\n}",
+			"print(a?.b.c, a?.b?.c, a?[1])\na?.b()\nlocal z = a.b?.c::d()",
+			"if local x, y = f() {\n\tprint(x)\n} elseif local z = g() {\n\tprint(z)\n} elseif a {} else {}",
+			"local {a, b => c, d => {e, f => {g}}} = t\nglobal {h} = f()",
+			"local struct S {\n\tx,\n\ty = 2,\n\tconstructor(a) { self.x = a },\n\tfn f() {},\n\tmethod g() { return self.x }\n}\nglobal struct T { z }",
+			"local struct R {\n\tconstructor(a) {\n\t\tif a { return }\n\t\treturn a\n\t}\n}",
+			"local x = 1\nlocal struct Q {\n\ta = x,\n\tconstructor(x, y = x) { self.b = x + y }\n}",
+			"until a {}\nloop { break } while b\nloop { continue }\nloop {} until c\nwhile !d {}",
+			"for i = 1, 10 {}\nfor i = 10, 1, -1 {}\nfor k, v of t {}\nfor i, v in t {}\nfor x with f(t) {}",
+			"try { error() } catch e { print(e) }\ntry {}\nextern fn f(a, b)\nextern g",
+			"local fn f(...) { return ... }\nmethod a.b::c() {}\nglobal fn d() { { return 1, 2 } }",
+			"local x = -(-a) - -b + #t .. ~c & d | e ^^ f << 1 >> 2\n(f)(1)\nf::g().h = `raw`",
+			"local x = !(a && b) || c == d != e < f <= g > h >= i % j ^ k",
+		] {
+			assert_round_trip(code);
+		}
+	}
+
+	/// A xorshift generator, to build the same programs on every run
+	struct Random(u64);
+
+	impl Random {
+		fn next(&mut self, max: usize) -> usize {
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 7;
+			self.0 ^= self.0 << 17;
+			(self.0 % max as u64) as usize
+		}
+
+		fn pick<'a>(&mut self, options: &[&'a str]) -> &'a str {
+			options[self.next(options.len())]
+		}
+
+		fn expression(&mut self, depth: usize) -> String {
+			let atom = self.pick(&[
+				"1", "2.5", "\"s\"", "true", "nil", "x", "t.k", "t[1]", "#t", "({})",
+			]);
+			if depth == 0 {
+				return atom.to_owned();
+			}
+			let a = self.expression(depth - 1);
+			let b = self.expression(depth - 1);
+			match self.next(10) {
+				0 => format!(
+					"{a} {} {b}",
+					self.pick(&["+", "-", "*", "..", "==", "<", "&&", "||", "&", "<<"])
+				),
+				1 => format!("{} {a}", self.pick(&["!", "-", "~"])),
+				2 => format!("({a})"),
+				3 => format!("f({a}, {b})"),
+				4 => format!("({{{a}, k = {b}}})"),
+				5 => format!("{a} ?? {b}"),
+				6 => format!("{a} ? {b} : {atom}"),
+				7 => format!("match {a} {{ 1 => {b}, default => {atom} }}"),
+				8 => format!("t?.k.{}", self.pick(&["a", "b"])),
+				_ => format!("(fn(a, b = {a}) {{ return {b} }})"),
+			}
+		}
+
+		fn statement(&mut self, depth: usize) -> String {
+			let expr = self.expression(2);
+			let block = if depth == 0 {
+				String::from("{}")
+			} else {
+				let statements: Vec<String> = (0..self.next(3))
+					.map(|_| self.statement(depth - 1))
+					.collect();
+				format!("{{\n{}\n}}", statements.join("\n"))
+			};
+			match self.next(10) {
+				0 => format!("local x, y = {expr}"),
+				1 => format!(
+					"t.k {} {expr}",
+					self.pick(&["=", "+=", "..=", "??=", "||="])
+				),
+				2 => format!("if {expr} {block} elseif x {block} else {block}"),
+				3 => format!("while {expr} {block}"),
+				4 => format!("for i = 1, {expr} {block}"),
+				5 => format!("for k, v of t.{} {block}", self.pick(&["a", "b"])),
+				6 => format!("local fn f(a, ...) {block}"),
+				7 => format!("match {expr} {{ 1 => {block} default => {block} }}"),
+				8 => format!("if local z = {expr} {block}"),
+				_ => format!("print({expr})"),
+			}
+		}
+	}
+
+	#[test]
+	fn random_programs() {
+		let mut random = Random(0x2545_f491_4f6c_dd1d);
+		for _ in 0..500 {
+			let statements: Vec<String> = (0..5).map(|_| random.statement(2)).collect();
+			assert_round_trip(&statements.join("\n"));
+		}
+	}
+
+	#[test]
+	fn blank_lines_are_kept() {
+		for code in [
+			"local a = 1\n\nprint(a)\n",
+			"local s = `multi\nline`\nprint(s)\n",
+			"local s = `multi\n\nline`\n\nprint(s)\n",
+		] {
+			let (expr, _) = Clue::new().parse_code(code.to_owned()).unwrap();
+			assert_eq!(print_ast(&expr), code);
+		}
+	}
+}
@@ -0,0 +1,568 @@
+//! The project compiler compiles every `.clue` file of a directory in parallel.
+//!
+//! It works in two phases: first every file is preprocessed and the preprocessor variables
+//! of all the files are merged, then every file is compiled using the merged variables.
+//! This is what the CLI uses to compile directories, but it's available to any other build tool.
+//! [`compile_project_cached`] also stores the compiled modules in a [`Cache`] to skip the unchanged ones
+//! the next time the project is compiled.
+
+use crate::cache::{hash_source, same_variable, Cache, CachedModule};
+use crate::code::Code;
+use crate::compiler::Compiler;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::env::{Options, OutputMode};
+use crate::format_clue;
+use crate::parser::parse_tokens;
+use crate::preprocessor::{
+	defined_variables, preprocess_code, preprocess_codes, used_variables, variable_references,
+	PPCode, PPVar, PPVars,
+};
+use crate::scanner::scan_code;
+use crate::sourcemap::SourceMap;
+use ahash::AHashMap;
+use crossbeam_queue::SegQueue;
+use flume::Sender;
+use std::cmp;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// The function used to compile every preprocessed file of a project
+/// Takes the preprocessed code, the merged variables, the name of the file, the starting scope and the options
+/// Returns the compiled code, its [`SourceMap`] (empty if [`Options::env_sourcemap`] is disabled),
+/// the statics and the warnings of the file
+///
+/// [`compile_code`] is the default, but a custom one can be used to inspect the intermediate results
+pub type CompileFn = fn(
+	PPCode,
+	&PPVars,
+	&String,
+	usize,
+	&Options,
+) -> Result<(String, SourceMap, String, Vec<Diagnostic>), Vec<Diagnostic>>;
+
+type CodeQueue = SegQueue<(PPCode, String, String)>;
+
+struct PreprocessorAnalyzerData {
+	errored: bool,
+	skipped: bool,
+	source: u64,
+	codes: (PPCode, String, String),
+	variables: PPVars,
+	diagnostics: Vec<Diagnostic>,
+}
+
+struct ThreadData {
+	errored: bool,
+	module: CompiledModule,
+	diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A compiled file of a project
+pub struct CompiledModule {
+	/// The name of the module, which is the path of the file relative to the directory
+	/// without the extension and with dots as separators
+	pub name: String,
+
+	/// The compiled Lua code of the file
+	pub code: String,
+
+	/// The [`SourceMap`] of the code, which is empty if [`Options::env_sourcemap`] is disabled
+	pub sourcemap: SourceMap,
+
+	/// The statics defined in the file
+	pub statics: String,
+}
+
+#[derive(Debug, Clone, Default)]
+/// The result of compiling a project
+pub struct ProjectOutput {
+	/// The modules that were compiled successfully, sorted by name
+	/// so that the output doesn't depend on the order the threads finish in
+	pub modules: Vec<CompiledModule>,
+
+	/// The errors of every file that failed to compile and the warnings of the compiled ones,
+	/// the modules taken from the cache don't report their warnings again
+	pub diagnostics: Vec<Diagnostic>,
+
+	/// How many files failed to compile
+	pub failed: usize,
+
+	/// How many modules were taken from the cache instead of being compiled
+	pub cached: usize,
+}
+
+impl ProjectOutput {
+	/// Returns `true` if every file of the project was compiled successfully
+	pub fn is_ok(&self) -> bool {
+		self.failed == 0
+	}
+
+	/// Wraps every module into the `_modules` table of the bundle,
+	/// returning its content, the [`SourceMap`] of the content and the statics of every module
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::project::{CompiledModule, ProjectOutput};
+	///
+	/// let output = ProjectOutput {
+	///     modules: vec![CompiledModule {
+	///         name: String::from("main"),
+	///         code: String::from("print(1)"),
+	///         ..Default::default()
+	///     }],
+	///     ..Default::default()
+	/// };
+	/// let (bundle, ..) = output.bundle();
+	/// assert!(bundle.contains("[\"main\"] = function(...)\nprint(1)"));
+	/// ```
+	pub fn bundle(self) -> (String, SourceMap, String) {
+		let mut output = String::with_capacity(self.modules.len() * 512) + "\n";
+		let mut sourcemap = SourceMap::default();
+		let mut statics = String::with_capacity(512);
+		for module in self.modules {
+			output += &format_clue!("\t[\"", module.name, "\"] = function(...)\n");
+			sourcemap.append(&output, module.sourcemap);
+			output += &format_clue!(module.code, "\n\tend,\n");
+			statics += &module.statics;
+		}
+		(output, sourcemap, statics)
+	}
+}
+
+/// Compiles a preprocessed file without printing anything
+/// This is the default [`CompileFn`] of [`compile_project`]
+///
+/// # Errors
+/// If an error occurs while compiling the file, an [`Err`] containing a [`Diagnostic`] for each error found will be returned
+pub fn compile_code(
+	codes: PPCode,
+	variables: &PPVars,
+	name: &String,
+	scope: usize,
+	options: &Options,
+) -> Result<(String, SourceMap, String, Vec<Diagnostic>), Vec<Diagnostic>> {
+	let code = preprocess_codes(0, codes, variables, name)?;
+	let tokens = scan_code(code, name)?;
+	let (ctokens, statics, warnings) = parse_tokens(tokens, name, options)?;
+	let compiler = Compiler::new(options, name);
+	let (code, sourcemap) = if options.env_sourcemap {
+		compiler.compile_tokens_with_source_map(scope, ctokens)?
+	} else {
+		(
+			compiler.compile_tokens(scope, ctokens)?,
+			SourceMap::default(),
+		)
+	};
+	Ok((code, sourcemap, statics, warnings))
+}
+
+fn check_for_files(
+	path: PathBuf,
+	rpath: String,
+) -> Result<SegQueue<(PathBuf, String)>, std::io::Error> {
+	let files = SegQueue::new();
+	for entry in fs::read_dir(&path)? {
+		let entry = entry?;
+		let name = entry
+			.path()
+			.file_name()
+			.unwrap()
+			.to_string_lossy()
+			.into_owned();
+		let filepath = path.join(&name);
+		let realname = rpath.clone() + &name;
+		if filepath.is_dir() {
+			for file in check_for_files(filepath, realname + ".")? {
+				files.push(file)
+			}
+		} else if filepath
+			.extension()
+			.map_or(false, |extension| extension == "clue")
+		{
+			files.push((filepath, realname));
+		}
+	}
+	Ok(files)
+}
+
+fn wait_threads(threads: Vec<JoinHandle<()>>) {
+	for thread in threads {
+		thread.join().expect("Join shouldn't panic");
+	}
+}
+
+/// Compiles every file of the given directory in parallel using the given [`CompileFn`]
+/// `rpath` is prepended to the name of every module
+///
+/// # Errors
+/// If the directory can't be read, an [`Err`] containing a [`Diagnostic`] will be returned
+/// The errors of the single files are stored in the returned [`ProjectOutput`] instead
+///
+/// # Example
+/// ```rust
+/// use clue_core::{diagnostic::Diagnostic, env::Options, project::*};
+///
+/// fn main() -> Result<(), Box<Diagnostic>> {
+///     let output = compile_project("../examples/", String::new(), Options::default(), compile_code)?;
+///     assert!(output.is_ok());
+///
+///     Ok(())
+/// }
+/// ```
+pub fn compile_project(
+	path: impl AsRef<Path>,
+	rpath: String,
+	options: Options,
+	compile: CompileFn,
+) -> Result<ProjectOutput, Box<Diagnostic>> {
+	compile_files(path.as_ref(), rpath, options, compile, None)
+}
+
+/// Compiles every file of the given directory like [`compile_project`], but takes the modules
+/// that didn't change from the given [`Cache`] and stores the newly compiled ones in it
+/// Modules of files that don't exist anymore are removed from the cache, which has to be saved
+/// with [`Cache::save`] afterwards
+///
+/// # Errors
+/// If the directory can't be read, an [`Err`] containing a [`Diagnostic`] will be returned
+/// The errors of the single files are stored in the returned [`ProjectOutput`] instead
+///
+/// # Example
+/// ```rust
+/// use clue_core::{cache::Cache, diagnostic::Diagnostic, env::Options, project::*};
+///
+/// fn main() -> Result<(), Box<Diagnostic>> {
+///     let options = Options::default();
+///     let mut cache = Cache::load("project_cache_example", &options);
+///     let output = compile_project_cached("../examples/", String::new(), options.clone(), compile_code, &mut cache)?;
+///     assert_eq!(output.cached, 0);
+///
+///     let output = compile_project_cached("../examples/", String::new(), options, compile_code, &mut cache)?;
+///     assert_eq!(output.cached, output.modules.len());
+///
+///     Ok(())
+/// }
+/// ```
+pub fn compile_project_cached(
+	path: impl AsRef<Path>,
+	rpath: String,
+	options: Options,
+	compile: CompileFn,
+	cache: &mut Cache,
+) -> Result<ProjectOutput, Box<Diagnostic>> {
+	compile_files(path.as_ref(), rpath, options, compile, Some(cache))
+}
+
+fn module_name(realname: &str) -> &str {
+	realname.strip_suffix(".clue").unwrap()
+}
+
+fn read_source(
+	filepath: impl AsRef<Path>,
+	filename: &str,
+) -> Result<(Vec<u8>, u64), Box<Diagnostic>> {
+	let code = fs::read(filepath)
+		.map_err(|e| Diagnostic::error("io", e.to_string(), filename, Span::default()))?;
+	let source = hash_source(&code);
+	Ok((code, source))
+}
+
+fn compile_files(
+	path: &Path,
+	rpath: String,
+	options: Options,
+	compile: CompileFn,
+	mut cache: Option<&mut Cache>,
+) -> Result<ProjectOutput, Box<Diagnostic>> {
+	let found = check_for_files(path.to_path_buf(), rpath).map_err(|e| {
+		Diagnostic::error(
+			"io",
+			e.to_string(),
+			path.to_string_lossy().into_owned(),
+			Span::default(),
+		)
+	})?;
+	let files = SegQueue::new();
+	let mut realnames = Vec::with_capacity(found.len());
+	for (filepath, realname) in found {
+		realnames.push(realname.clone());
+		files.push((filepath, realname));
+	}
+	let files_len = files.len();
+	let threads_count = cmp::min(files_len, num_cpus::get() * 2);
+	let codes = SegQueue::new();
+	let files = Arc::new(files);
+	let options = Arc::new(options);
+	let mut output = ProjectOutput::default();
+	let mut preprocessed = Vec::with_capacity(files_len);
+
+	// the files that don't define any variable don't need to be preprocessed if they didn't change
+	let skippable: Arc<AHashMap<String, u64>> = Arc::new(match &cache {
+		Some(cache) => realnames
+			.iter()
+			.filter_map(|realname| {
+				let module = cache
+					.get(module_name(realname))
+					.filter(|module| !module.defines)?;
+				Some((realname.clone(), module.source))
+			})
+			.collect(),
+		None => AHashMap::default(),
+	});
+
+	let (tx, rx) = flume::unbounded();
+
+	let mut threads = Vec::with_capacity(threads_count);
+
+	for _ in 0..threads_count {
+		// this `.clone()` is used to create new pointers
+		// that can be used from inside the newly created thread
+		let files = files.clone();
+		let tx = tx.clone();
+		let options = options.clone();
+		let skippable = skippable.clone();
+
+		let thread = thread::spawn(move || preprocess_file_dir(files, tx, &options, &skippable));
+
+		threads.push(thread);
+	}
+
+	wait_threads(threads);
+
+	while let Ok(mut data) = rx.try_recv() {
+		output.diagnostics.append(&mut data.diagnostics);
+		if data.errored {
+			output.failed += 1;
+			continue;
+		}
+
+		preprocessed.push(data);
+	}
+
+	if !output.is_ok() {
+		return Ok(output);
+	}
+
+	// sorted so that a variable defined by multiple files always gets the same value
+	preprocessed.sort_unstable_by(|a, b| a.codes.2.cmp(&b.codes.2));
+	// files skipped by the cache aren't preprocessed, so the defined variables are added here too
+	let defined = defined_variables(&options);
+	let mut variables = defined.clone();
+	let mut defines = BTreeSet::new();
+	for data in &mut preprocessed {
+		// the variables of `env_defines` are in every file, but they can be redefined
+		let redefines = |(name, variable): (&Code, &PPVar)| {
+			defined
+				.get(name)
+				.map_or(true, |defined| !same_variable(variable, defined))
+		};
+		if data.variables.iter().any(redefines) {
+			defines.insert(data.codes.2.clone());
+		}
+		variables.extend(std::mem::take(&mut data.variables));
+	}
+	let mut dependencies = AHashMap::default();
+	for mut data in preprocessed {
+		let name = module_name(&data.codes.2).to_owned();
+		if let Some(cache) = &cache {
+			if let Some(module) = cache
+				.get(&name)
+				.filter(|module| module.source == data.source && module.is_valid(&variables))
+			{
+				output.modules.push(CompiledModule {
+					name,
+					code: module.code.clone(),
+					sourcemap: module.sourcemap.clone(),
+					statics: module.statics.clone(),
+				});
+				output.cached += 1;
+				continue;
+			}
+		}
+		if data.skipped {
+			// it didn't change, but a variable it uses did
+			let filename = &data.codes.1;
+			let preprocessed = read_source(filename, filename)
+				.and_then(|(mut code, _)| preprocess_code(&mut code, 1, false, filename, &options));
+			match preprocessed {
+				Ok((codes, _, _, _, mut notes)) => {
+					data.codes.0 = codes;
+					output.diagnostics.append(&mut notes);
+				}
+				Err(e) => {
+					output.failed += 1;
+					output.diagnostics.push(*e);
+					continue;
+				}
+			}
+		}
+		if cache.is_some() {
+			let used = used_variables(&variable_references(&data.codes.0), &variables);
+			let defines = defines.contains(&data.codes.2);
+			dependencies.insert(name, (data.source, defines, used));
+		}
+		codes.push(data.codes);
+	}
+	let variables = Arc::new(variables);
+
+	let mut threads = Vec::with_capacity(threads_count);
+	let (tx, rx) = flume::unbounded();
+	let codes = Arc::new(codes);
+
+	for _ in 0..threads_count {
+		let tx = tx.clone();
+		let options = options.clone();
+		let codes = codes.clone();
+		let variables = variables.clone();
+
+		let thread =
+			thread::spawn(move || compile_file_dir(tx, &options, codes, variables, compile));
+
+		threads.push(thread);
+	}
+
+	wait_threads(threads);
+
+	while let Ok(mut data) = rx.try_recv() {
+		output.diagnostics.append(&mut data.diagnostics);
+		if data.errored {
+			output.failed += 1;
+			continue;
+		}
+
+		if let (Some(cache), Some((source, defines, used))) =
+			(&mut cache, dependencies.remove(&data.module.name))
+		{
+			let module = &data.module;
+			let module = CachedModule::new(
+				source,
+				defines,
+				&used,
+				&variables,
+				module.code.clone(),
+				module.sourcemap.clone(),
+				module.statics.clone(),
+			);
+			cache.insert(data.module.name.clone(), module);
+		}
+		output.modules.push(data.module);
+	}
+
+	if let Some(cache) = cache {
+		let names: BTreeSet<&str> = realnames
+			.iter()
+			.map(|realname| module_name(realname))
+			.collect();
+		cache.retain(|name| names.contains(name));
+	}
+
+	output.modules.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+	output.diagnostics.sort_by(|a, b| a.file.cmp(&b.file));
+
+	Ok(output)
+}
+
+fn preprocess_file_dir(
+	files: Arc<SegQueue<(PathBuf, String)>>,
+	tx: Sender<PreprocessorAnalyzerData>,
+	options: &Options,
+	skippable: &AHashMap<String, u64>,
+) {
+	loop {
+		let (filename, filepath, realname) = match files.pop() {
+			None => break,
+			Some((filepath, realname)) => {
+				(filepath.to_string_lossy().into_owned(), filepath, realname)
+			}
+		};
+
+		let result = read_source(filepath, &filename).and_then(|(mut code, source)| {
+			if skippable.get(&realname) == Some(&source) {
+				return Ok((
+					Default::default(),
+					Default::default(),
+					Vec::new(),
+					source,
+					true,
+				));
+			}
+			let (codes, variables, _, _, notes) =
+				preprocess_code(&mut code, 1, false, &filename, options)?;
+			Ok((codes, variables, notes, source, false))
+		});
+		let (file_codes, file_variables, notes, source, skipped) = match result {
+			Ok(t) => t,
+			Err(e) => {
+				tx.send(PreprocessorAnalyzerData {
+					errored: true,
+					skipped: false,
+					source: 0,
+					codes: Default::default(),
+					variables: Default::default(),
+					diagnostics: vec![*e],
+				})
+				.unwrap();
+				continue;
+			}
+		};
+
+		tx.send(PreprocessorAnalyzerData {
+			errored: false,
+			skipped,
+			source,
+			codes: (file_codes, filename, realname),
+			variables: file_variables,
+			diagnostics: notes,
+		})
+		.unwrap();
+	}
+}
+
+fn compile_file_dir(
+	tx: Sender<ThreadData>,
+	options: &Options,
+	codes: Arc<CodeQueue>,
+	variables: Arc<AHashMap<Code, PPVar>>,
+	compile: CompileFn,
+) {
+	loop {
+		let (codes, filename, realname) = match codes.pop() {
+			None => break,
+			Some(codes) => codes,
+		};
+
+		let scope = match options.env_mode {
+			OutputMode::Bundle => 2,
+			OutputMode::Files => 0,
+		};
+		let (code, sourcemap, statics, warnings) =
+			match compile(codes, &variables, &filename, scope, options) {
+				Ok(t) => t,
+				Err(e) => {
+					tx.send(ThreadData {
+						errored: true,
+						module: CompiledModule::default(),
+						diagnostics: e,
+					})
+					.unwrap();
+					continue;
+				}
+			};
+
+		tx.send(ThreadData {
+			errored: false,
+			module: CompiledModule {
+				name: module_name(&realname).to_owned(),
+				code,
+				sourcemap,
+				statics,
+			},
+			diagnostics: warnings,
+		})
+		.unwrap();
+	}
+}
@@ -2,18 +2,21 @@
 //! into a list of tokens
 //!
 //! It exposes a single function, [`scan_code`], which takes a [`Code`] and returns a [`Vec`] of [`Token`]
+//! Tools that need to give back the original code can use [`scan_code_lossless`] instead,
+//! which scans code that was not preprocessed and keeps its comments
 
 #![allow(non_camel_case_types)]
 #![allow(clippy::upper_case_acronyms)]
 
 use crate::{
 	code::{Code, CodeChars},
+	diagnostic::{Diagnostic, Position, Span},
 	format_clue,
 };
 
 use self::TokenType::*;
 use phf::phf_map;
-use std::fmt;
+use std::{cmp, fmt};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -78,19 +81,46 @@ pub struct Token {
 
 	/// The column where the token is located.
 	pub column: usize,
+
+	/// The byte offset of the token from the start of its source file.
+	pub offset: usize,
 }
 
 impl Token {
 	/// Creates a new [`Token`] given its [`TokenType`], its literal token, the line and column where it is located.
 	/// The literal token is the literal value of the token, e.g. for `1` it's `"1"`, for `local` it's `"local"` and for `+` it's `"+"`.
+	/// Its byte offset is 0, use [`Token::with_offset`] to set it.
 	pub fn new(kind: TokenType, lexeme: impl Into<String>, line: usize, column: usize) -> Self {
 		Self {
 			kind,
 			lexeme: lexeme.into(),
 			line,
 			column,
+			offset: 0,
 		}
 	}
+
+	/// Returns the token with its byte offset set to `offset`.
+	pub const fn with_offset(mut self, offset: usize) -> Self {
+		self.offset = offset;
+		self
+	}
+
+	/// Returns the [`Span`] of the token, which ends right after its last character.
+	pub fn span(&self) -> Span {
+		Span::range(
+			Position {
+				line: self.line,
+				column: self.column,
+				offset: self.offset,
+			},
+			Position {
+				line: self.line,
+				column: self.column + self.lexeme.len(),
+				offset: self.offset + self.lexeme.len(),
+			},
+		)
+	}
 }
 
 /// A token that has a raw pointer to a [`Token`].
@@ -130,6 +160,11 @@ impl BorrowedToken {
 		self.token().column
 	}
 
+	/// Returns the [`Span`] of the token.
+	pub fn span(&self) -> Span {
+		self.token().span()
+	}
+
 	/// Clones the inner [`Token`] and returns it.
 	pub fn into_owned(&self) -> Token {
 		self.token().clone()
@@ -139,37 +174,44 @@ impl BorrowedToken {
 struct CodeInfo<'a> {
 	line: usize,
 	column: usize,
+	offset: usize,
 	start: usize,
 	current: usize,
 	size: usize,
 	code: CodeChars,
-	read: Vec<(char, usize, usize)>,
+	read: Vec<(char, usize, usize, usize)>,
 	filename: &'a String,
 	tokens: Vec<Token>,
 	last: TokenType,
-	errored: bool,
+	errors: Vec<Diagnostic>,
 }
 
 impl<'a> CodeInfo<'a> {
 	fn new(code: Code, filename: &'a String) -> Self {
 		let size = code.len() + 2;
-		let mut code = code.chars();
-		let mut read = Vec::with_capacity(size);
-		read.push((code.next_unwrapped(), code.line(), code.column()));
-		read.push((code.next_unwrapped(), code.line(), code.column()));
-		Self {
+		let mut info = Self {
 			line: 1,
 			column: 1,
+			offset: 0,
 			start: 0,
 			current: 0,
 			size,
-			code,
-			read,
+			code: code.chars(),
+			read: Vec::with_capacity(size),
 			filename,
 			tokens: Vec::new(),
 			last: EOF,
-			errored: false,
-		}
+			errors: Vec::new(),
+		};
+		info.read_next();
+		info.read_next();
+		info
+	}
+
+	fn read_next(&mut self) {
+		let c = self.code.next_unwrapped();
+		self.read
+			.push((c, self.code.line(), self.code.column(), self.code.offset()));
 	}
 
 	const fn ended(&self) -> bool {
@@ -181,11 +223,7 @@ impl<'a> CodeInfo<'a> {
 	}
 
 	fn advance(&mut self) -> char {
-		self.read.push((
-			self.code.next_unwrapped(),
-			self.code.line(),
-			self.code.column(),
-		));
+		self.read_next();
 		let (prev, line, ..) = self.read[self.current];
 		self.line = line;
 		let read = self.code.bytes_read();
@@ -233,31 +271,37 @@ impl<'a> CodeInfo<'a> {
 
 	fn add_literal_token(&mut self, kind: TokenType, literal: String) {
 		self.tokens
-			.push(Token::new(kind, literal, self.line, self.column));
+			.push(Token::new(kind, literal, self.line, self.column).with_offset(self.offset));
 	}
 
 	fn add_token(&mut self, kind: TokenType) {
 		let lexeme: String = self.substr(self.start, self.current);
 		self.last = kind;
 		self.tokens
-			.push(Token::new(kind, lexeme, self.line, self.column));
+			.push(Token::new(kind, lexeme, self.line, self.column).with_offset(self.offset));
 	}
 
-	fn warning(&mut self, message: impl Into<String>) {
-		eprintln!(
-			"Error in {}:{}:{}!\nError: \"{}\"\n",
-			self.filename,
-			self.line,
-			self.column,
-			message.into()
-		);
-		self.errored = true;
+	fn error(&mut self, code: &str, message: impl Into<String>) {
+		let position = Position {
+			line: self.line,
+			column: self.column,
+			offset: self.offset,
+		};
+		self.errors.push(Diagnostic::error(
+			code,
+			message,
+			self.filename.as_str(),
+			Span::range(position, position),
+		));
 	}
 
 	fn reserved(&mut self, keyword: &str, msg: &str) -> TokenType {
-		self.warning(format!(
-			"'{keyword}' is a reserved keyword in Lua and it cannot be used as a variable, {msg}",
-		));
+		self.error(
+			"reserved-keyword",
+			format!(
+				"'{keyword}' is a reserved keyword in Lua and it cannot be used as a variable, {msg}"
+			),
+		);
 		IDENTIFIER
 	}
 
@@ -280,7 +324,7 @@ impl<'a> CodeInfo<'a> {
 					if c == '-' && self.peek(2).is_ascii_digit() {
 						self.advance();
 					} else {
-						self.warning("Malformed number");
+						self.error("malformed-number", "Malformed number");
 					}
 				}
 				self.advance();
@@ -289,7 +333,7 @@ impl<'a> CodeInfo<'a> {
 				}
 			}
 		} else if self.current == start {
-			self.warning("Malformed number");
+			self.error("malformed-number", "Malformed number");
 		}
 		let llcheck = self.substr(self.current, self.current + 2);
 		if llcheck == "LL" {
@@ -298,7 +342,7 @@ impl<'a> CodeInfo<'a> {
 			if self.peek(2) == 'L' {
 				self.current += 3;
 			} else {
-				self.warning("Malformed number");
+				self.error("malformed-number", "Malformed number");
 			}
 		}
 		self.add_token(NUMBER);
@@ -312,7 +356,7 @@ impl<'a> CodeInfo<'a> {
 			}
 		}
 		if self.ended() {
-			self.warning("Unterminated string");
+			self.error("unterminated-string", "Unterminated string");
 			false
 		} else {
 			true
@@ -385,8 +429,63 @@ impl<'a> CodeInfo<'a> {
 		}
 	}
 
+	fn scan_token(&mut self, c: char) {
+		if self.scan_char(&SYMBOLS, c) {
+			return;
+		}
+		if c.is_ascii_digit() {
+			if c == '0' {
+				match self.peek(0) {
+					'x' | 'X' => {
+						self.current += 1;
+						self.read_number(
+							|c| {
+								c.is_ascii_digit()
+									|| ('a'..='f').contains(c) || ('A'..='F').contains(c)
+							},
+							false,
+						);
+					}
+					'b' | 'B' => {
+						self.current += 1;
+						self.read_number(|&c| c == '0' || c == '1', false);
+					}
+					_ => self.read_number(char::is_ascii_digit, true),
+				}
+			} else {
+				self.read_number(char::is_ascii_digit, true);
+			}
+		} else if c.is_ascii_alphabetic() || c == '_' {
+			let ident = self.read_identifier();
+			let kind = if let Some(keyword) = KEYWORDS.get(ident.as_bytes()) {
+				match keyword {
+					KeywordType::Lua(kind) => *kind,
+					KeywordType::Reserved(e) => self.reserved(&ident, e),
+					_ if matches!(self.last, DOT | SAFE_DOT | DOUBLE_COLON | SAFE_DOUBLE_COLON) => {
+						IDENTIFIER
+					}
+					KeywordType::Just(kind) => *kind,
+				}
+			} else {
+				IDENTIFIER
+			};
+			self.add_token(kind);
+		} else {
+			self.error(
+				"unexpected-character",
+				format!("Unexpected character '{c}'"),
+			);
+		}
+	}
+
+	fn skip_line(&mut self) {
+		while !matches!(self.peek(0), '\n' | '\0') {
+			self.advance();
+		}
+	}
+
 	fn update_column(&mut self) {
-		self.column = self.read[self.current].2
+		(_, _, self.column, self.offset) = self.read[self.current];
 	}
 }
 
@@ -394,7 +493,7 @@ impl<'a> CodeInfo<'a> {
 enum SymbolType {
 	Just(TokenType),
 	Function(fn(&mut CodeInfo)),
-	Symbols(SymbolsMap, TokenType),
+	Symbols(&'static SymbolsMap, TokenType),
 }
 
 impl fmt::Debug for SymbolType {
@@ -414,7 +513,6 @@ impl fmt::Debug for SymbolType {
 enum KeywordType {
 	Just(TokenType),
 	Lua(TokenType),
-	Error(&'static str),
 	Reserved(&'static str),
 }
 
@@ -429,10 +527,10 @@ const SYMBOLS: SymbolsMap = generate_map(&[
 	(
 		'.',
 		SymbolType::Symbols(
-			generate_map(&[(
+			&generate_map(&[(
 				'.',
 				SymbolType::Symbols(
-					generate_map(&[
+					&generate_map(&[
 						('.', SymbolType::Just(THREEDOTS)),
 						('=', SymbolType::Just(CONCATENATE)),
 					]),
@@ -445,20 +543,20 @@ const SYMBOLS: SymbolsMap = generate_map(&[
 	(';', SymbolType::Just(SEMICOLON)),
 	(
 		'+',
-		SymbolType::Symbols(generate_map(&[('=', SymbolType::Just(INCREASE))]), PLUS),
+		SymbolType::Symbols(&generate_map(&[('=', SymbolType::Just(INCREASE))]), PLUS),
 	),
 	(
 		'-',
-		SymbolType::Symbols(generate_map(&[('=', SymbolType::Just(DECREASE))]), MINUS),
+		SymbolType::Symbols(&generate_map(&[('=', SymbolType::Just(DECREASE))]), MINUS),
 	),
 	(
 		'*',
-		SymbolType::Symbols(generate_map(&[('=', SymbolType::Just(MULTIPLY))]), STAR),
+		SymbolType::Symbols(&generate_map(&[('=', SymbolType::Just(MULTIPLY))]), STAR),
 	),
 	(
 		'^',
 		SymbolType::Symbols(
-			generate_map(&[
+			&generate_map(&[
 				('=', SymbolType::Just(EXPONENTIATE)),
 				('^', SymbolType::Just(BIT_XOR)),
 			]),
@@ -469,7 +567,7 @@ const SYMBOLS: SymbolsMap = generate_map(&[
 	(
 		'/',
 		SymbolType::Symbols(
-			generate_map(&[
+			&generate_map(&[
 				('=', SymbolType::Just(DIVIDE)),
 				('_', SymbolType::Just(FLOOR_DIVISION)),
 			]),
@@ -479,19 +577,19 @@ const SYMBOLS: SymbolsMap = generate_map(&[
 	(
 		'%',
 		SymbolType::Symbols(
-			generate_map(&[('=', SymbolType::Just(MODULATE))]),
+			&generate_map(&[('=', SymbolType::Just(MODULATE))]),
 			PERCENTUAL,
 		),
 	),
 	(
 		'!',
-		SymbolType::Symbols(generate_map(&[('=', SymbolType::Just(NOT_EQUAL))]), NOT),
+		SymbolType::Symbols(&generate_map(&[('=', SymbolType::Just(NOT_EQUAL))]), NOT),
 	),
 	('~', SymbolType::Just(BIT_NOT)),
 	(
 		'=',
 		SymbolType::Symbols(
-			generate_map(&[
+			&generate_map(&[
 				('=', SymbolType::Just(EQUAL)),
 				('>', SymbolType::Just(ARROW)),
 			]),
@@ -501,7 +599,7 @@ const SYMBOLS: SymbolsMap = generate_map(&[
 	(
 		'<',
 		SymbolType::Symbols(
-			generate_map(&[
+			&generate_map(&[
 				('=', SymbolType::Just(SMALLER_EQUAL)),
 				('<', SymbolType::Just(LEFT_SHIFT)),
 			]),
@@ -511,7 +609,7 @@ const SYMBOLS: SymbolsMap = generate_map(&[
 	(
 		'>',
 		SymbolType::Symbols(
-			generate_map(&[
+			&generate_map(&[
 				('=', SymbolType::Just(BIGGER_EQUAL)),
 				('>', SymbolType::Just(RIGHT_SHIFT)),
 			]),
@@ -521,7 +619,7 @@ const SYMBOLS: SymbolsMap = generate_map(&[
 	(
 		'?',
 		SymbolType::Symbols(
-			generate_map(&[
+			&generate_map(&[
 				('.', SymbolType::Just(SAFE_DOT)),
 				(
 					':',
@@ -537,7 +635,7 @@ const SYMBOLS: SymbolsMap = generate_map(&[
 				(
 					'?',
 					SymbolType::Symbols(
-						generate_map(&[('=', SymbolType::Just(DEFINE_COALESCE))]),
+						&generate_map(&[('=', SymbolType::Just(DEFINE_COALESCE))]),
 						COALESCE,
 					),
 				),
@@ -549,9 +647,9 @@ const SYMBOLS: SymbolsMap = generate_map(&[
 	(
 		'&',
 		SymbolType::Symbols(
-			generate_map(&[(
+			&generate_map(&[(
 				'&',
-				SymbolType::Symbols(generate_map(&[('=', SymbolType::Just(DEFINE_AND))]), AND),
+				SymbolType::Symbols(&generate_map(&[('=', SymbolType::Just(DEFINE_AND))]), AND),
 			)]),
 			BIT_AND,
 		),
@@ -559,7 +657,7 @@ const SYMBOLS: SymbolsMap = generate_map(&[
 	(
 		':',
 		SymbolType::Symbols(
-			generate_map(&[
+			&generate_map(&[
 				(':', SymbolType::Just(DOUBLE_COLON)),
 			]),
 			COLON,
@@ -568,9 +666,9 @@ const SYMBOLS: SymbolsMap = generate_map(&[
 	(
 		'|',
 		SymbolType::Symbols(
-			generate_map(&[(
+			&generate_map(&[(
 				'|',
-				SymbolType::Symbols(generate_map(&[('=', SymbolType::Just(DEFINE_OR))]), OR),
+				SymbolType::Symbols(&generate_map(&[('=', SymbolType::Just(DEFINE_OR))]), OR),
 			)]),
 			BIT_OR,
 		),
@@ -618,22 +716,43 @@ static KEYWORDS: phf::Map<&'static [u8], KeywordType> = phf_map! {
 	b"catch" => KeywordType::Just(CATCH),
 	b"match" => KeywordType::Just(MATCH),
 	b"default" => KeywordType::Just(DEFAULT),
-	b"constructor" => KeywordType::Error("'constructor' is reserved for Clue 4.0 and cannnot be used."),
-	b"struct" => KeywordType::Error("'struct' is reserved for Clue 4.0 and cannot be used"),
-	b"extern" =>KeywordType::Error("'extern' is reserved for Clue 4.0 and cannot be used"),
+	b"constructor" => KeywordType::Just(CONSTRUCTOR),
+	b"struct" => KeywordType::Just(STRUCT),
+	b"extern" => KeywordType::Just(EXTERN),
 };
 
+/// Returns every keyword of Clue, sorted alphabetically
+/// Lua keywords that are not valid in Clue (e.g. `function` or `end`) are not included
+///
+/// # Example
+/// ```
+/// use clue_core::scanner::keywords;
+///
+/// let keywords = keywords();
+/// assert!(keywords.contains(&"fn"));
+/// assert!(!keywords.contains(&"function"));
+/// ```
+pub fn keywords() -> Vec<&'static str> {
+	let mut keywords: Vec<&'static str> = KEYWORDS
+		.entries()
+		.filter(|(_, keyword)| !matches!(keyword, KeywordType::Reserved(_)))
+		.map(|(keyword, _)| std::str::from_utf8(keyword).expect("keywords should be valid UTF-8"))
+		.collect();
+	keywords.sort_unstable();
+	keywords
+}
+
 /// Scans the code and returns a [`Vec`] of [`Token`]s
 /// It takes a preprocessed code and a filename as arguments
 ///
 /// # Errors
-/// If the code is invalid, it will return an [`Err`] with the error message
+/// If the code is invalid, it will return an [`Err`] with a [`Diagnostic`] for every error found
 ///
 /// # Examples
 /// ```
-/// use clue_core::{env::Options, preprocessor::*, scanner::*};
+/// use clue_core::{diagnostic::Diagnostic, env::Options, preprocessor::*, scanner::*};
 ///
-/// fn main() -> Result<(), String> {
+/// fn main() -> Result<(), Vec<Diagnostic>> {
 ///     let options = Options::default();
 ///     let filename = String::from("fizzbuzz.clue");
 ///     let mut code = include_str!("../../examples/fizzbuzz.clue").to_owned();
@@ -651,72 +770,269 @@ static KEYWORDS: phf::Map<&'static [u8], KeywordType> = phf_map! {
 ///     Ok(())
 /// }
 /// ```
-pub fn scan_code(code: Code, filename: &String) -> Result<Vec<Token>, String> {
+pub fn scan_code(code: Code, filename: &String) -> Result<Vec<Token>, Vec<Diagnostic>> {
 	let mut i: CodeInfo = CodeInfo::new(code, filename);
 	while !i.ended() && i.peek(0) != '\0' {
 		i.start = i.current;
 		i.update_column();
 		let c = i.advance();
-		if !i.scan_char(&SYMBOLS, c) {
-			if c.is_whitespace() {
-				continue;
-			} else if c.is_ascii_digit() {
-				if c == '0' {
+		if !c.is_whitespace() {
+			i.scan_token(c);
+		}
+	}
+	if !i.errors.is_empty() {
+		return Err(i.errors);
+	}
+	i.add_literal_token(EOF, String::from("<end>"));
+	Ok(i.tokens)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// The kind of a [`Trivia`]
+pub enum TriviaKind {
+	/// Spaces, tabs and any other whitespace except newlines
+	Whitespace,
+
+	/// A single `\n`
+	Newline,
+
+	/// A `//` or `/* */` comment
+	Comment,
+
+	/// A preprocessor directive, from the `@` to the end of the line
+	Directive,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A piece of code that only matters to the preprocessor and is never seen by [`scan_code`]
+pub struct Trivia {
+	/// What the trivia is.
+	pub kind: TriviaKind,
+
+	/// The code the trivia is made of, exactly as it's written.
+	pub text: String,
+
+	/// Where the trivia is in the source code.
+	pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A [`Token`] with the [`Trivia`] around it, returned by [`scan_code_lossless`]
+pub struct LosslessToken {
+	/// The trivia between the previous token's trailing trivia and this token.
+	/// Comments on the lines above a declaration are here.
+	pub leading: Vec<Trivia>,
+
+	/// The token, whose lexeme is exactly what's written in the code.
+	pub token: Token,
+
+	/// The trivia after the token up to the end of its line, excluding the newline.
+	pub trailing: Vec<Trivia>,
+}
+
+impl LosslessToken {
+	/// Returns the comments of the leading and trailing trivia of the token.
+	pub fn comments(&self) -> impl Iterator<Item = &Trivia> {
+		self.leading
+			.iter()
+			.chain(&self.trailing)
+			.filter(|trivia| trivia.kind == TriviaKind::Comment)
+	}
+}
+
+impl fmt::Display for LosslessToken {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for trivia in &self.leading {
+			f.write_str(&trivia.text)?;
+		}
+		f.write_str(&self.token.lexeme)?;
+		for trivia in &self.trailing {
+			f.write_str(&trivia.text)?;
+		}
+		Ok(())
+	}
+}
+
+enum Lexeme {
+	Token(Token),
+	Trivia(Trivia),
+}
+
+fn end_position(start: Position, text: &str) -> Position {
+	let (line, column) = match text.rfind('\n') {
+		Some(last) => (start.line + text.matches('\n').count(), text.len() - last),
+		None => (start.line, start.column + text.len()),
+	};
+	Position {
+		line,
+		column,
+		offset: start.offset + text.len(),
+	}
+}
+
+fn scan_lexemes(code: &str, filename: &String) -> Result<Vec<Lexeme>, Vec<Diagnostic>> {
+	let mut source = Code::with_capacity(code.len());
+	let (mut line, mut column) = (1, 1);
+	for (offset, c) in code.bytes().enumerate() {
+		source.push((c, line, column, offset));
+		if c == b'\n' {
+			line += 1;
+			column = 1;
+		} else {
+			column += 1;
+		}
+	}
+	let mut i: CodeInfo = CodeInfo::new(source, filename);
+	let mut lexemes = Vec::new();
+	while !i.ended() && i.peek(0) != '\0' {
+		i.start = i.current;
+		i.update_column();
+		let c = i.advance();
+		let trivia = match c {
+			'\n' => Some(TriviaKind::Newline),
+			'/' if i.peek(0) == '/' => {
+				i.skip_line();
+				Some(TriviaKind::Comment)
+			}
+			'/' if i.peek(0) == '*' => {
+				i.advance();
+				loop {
 					match i.peek(0) {
-						'x' | 'X' => {
-							i.current += 1;
-							i.read_number(
-								|c| {
-									c.is_ascii_digit()
-										|| ('a'..='f').contains(c) || ('A'..='F').contains(c)
-								},
-								false,
-							);
+						'\0' => {
+							i.error("unterminated-comment", "Unterminated comment");
+							break;
 						}
-						'b' | 'B' => {
-							i.current += 1;
-							i.read_number(|&c| c == '0' || c == '1', false);
-						}
-						_ => i.read_number(char::is_ascii_digit, true),
-					}
-				} else {
-					i.read_number(char::is_ascii_digit, true);
-				}
-			} else if c.is_ascii_alphabetic() || c == '_' {
-				let ident = i.read_identifier();
-				let kind = if let Some(keyword) = KEYWORDS.get(ident.as_bytes()) {
-					match keyword {
-						KeywordType::Lua(kind) => *kind,
-						KeywordType::Reserved(e) => i.reserved(&ident, e),
-						_ if matches!(
-							i.last,
-							DOT | SAFE_DOT | DOUBLE_COLON | SAFE_DOUBLE_COLON
-						) =>
-						{
-							IDENTIFIER
+						'*' if i.peek(1) == '/' => {
+							i.advance();
+							i.advance();
+							break;
 						}
-						KeywordType::Just(kind) => *kind,
-						KeywordType::Error(e) => {
-							i.warning(*e);
-							IDENTIFIER
+						_ => {
+							i.advance();
 						}
 					}
-				} else {
-					IDENTIFIER
+				}
+				Some(TriviaKind::Comment)
+			}
+			'@' => {
+				i.skip_line();
+				Some(TriviaKind::Directive)
+			}
+			'$' => {
+				i.read_identifier();
+				i.compare('!');
+				i.add_token(IDENTIFIER);
+				None
+			}
+			c if c.is_whitespace() => {
+				while i.peek(0) != '\n' && i.peek(0).is_whitespace() {
+					i.advance();
+				}
+				Some(TriviaKind::Whitespace)
+			}
+			c => {
+				i.scan_token(c);
+				None
+			}
+		};
+		let end = i
+			.read
+			.get(i.current)
+			.map_or(code.len(), |(.., offset)| *offset);
+		let text = &code[i.offset..cmp::min(end, code.len())];
+		let (_, line, column, offset) = i.read[i.start];
+		match trivia {
+			Some(kind) => {
+				let start = Position {
+					line,
+					column,
+					offset,
 				};
-				i.add_token(kind);
-			} else {
-				i.warning(format!("Unexpected character '{c}'").as_str());
+				lexemes.push(Lexeme::Trivia(Trivia {
+					kind,
+					text: text.to_owned(),
+					span: Span::range(start, end_position(start, text)),
+				}))
+			}
+			None => {
+				for mut token in i.tokens.drain(..) {
+					token.lexeme = text.to_owned();
+					token.line = line;
+					lexemes.push(Lexeme::Token(token));
+				}
 			}
 		}
 	}
-	if i.errored {
-		return Err(String::from(
-			"Cannot continue until the above errors are fixed",
-		));
+	if !i.errors.is_empty() {
+		return Err(i.errors);
 	}
-	i.add_literal_token(EOF, String::from("<end>"));
-	Ok(i.tokens)
+	let end = end_position(Position::new(1, 1), code);
+	lexemes.push(Lexeme::Token(
+		Token::new(EOF, "", end.line, end.column).with_offset(end.offset),
+	));
+	Ok(lexemes)
+}
+
+/// Scans code that was not preprocessed without losing anything, for tools like formatters
+/// Whitespace, comments and preprocessor directives are kept as the [`Trivia`] of the tokens around them,
+/// so joining every [`LosslessToken`] gives back the original code
+/// Preprocessor variables and macro calls (e.g. `$x` or `$add!`) are scanned as identifiers
+/// and the lexeme of the last token, an [`EOF`] whose leading trivia is everything after the last real token, is empty
+///
+/// # Errors
+/// If the code is invalid, it will return an [`Err`] with a [`Diagnostic`] for every error found
+///
+/// # Example
+/// ```
+/// use clue_core::{diagnostic::Diagnostic, scanner::*};
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let filename = String::from("main.clue");
+///     let code = "// the answer\nlocal x = 42 // not 41\n";
+///     let tokens = scan_code_lossless(code, &filename)?;
+///
+///     let local = &tokens[0];
+///     assert_eq!(local.token.kind, TokenType::LOCAL);
+///     assert_eq!(local.leading[0].text, "// the answer");
+///     let number = &tokens[3];
+///     assert_eq!(number.comments().next().unwrap().text, "// not 41");
+///     assert_eq!(tokens.iter().map(ToString::to_string).collect::<String>(), code);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn scan_code_lossless(
+	code: &str,
+	filename: &String,
+) -> Result<Vec<LosslessToken>, Vec<Diagnostic>> {
+	let mut tokens: Vec<LosslessToken> = Vec::new();
+	let mut leading = Vec::new();
+	let mut trailing = true;
+	for lexeme in scan_lexemes(code, filename)? {
+		match lexeme {
+			Lexeme::Trivia(trivia) => match tokens.last_mut() {
+				Some(last) if trailing && trivia.kind != TriviaKind::Newline => {
+					last.trailing.push(trivia)
+				}
+				_ => {
+					trailing = false;
+					leading.push(trivia)
+				}
+			},
+			Lexeme::Token(token) => {
+				tokens.push(LosslessToken {
+					leading: std::mem::take(&mut leading),
+					token,
+					trailing: Vec::new(),
+				});
+				trailing = true;
+			}
+		}
+	}
+	Ok(tokens)
 }
 
 #[cfg(test)]
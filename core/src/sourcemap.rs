@@ -0,0 +1,218 @@
+//! The `sourcemap` module contains the [`SourceMap`] struct, which maps the lines of the generated
+//! Lua code back to the Clue code they were compiled from.
+//!
+//! The [`Compiler`] records where every statement starts in the output while compiling it, and
+//! [`Compiler::compile_tokens_with_source_map`] turns those offsets into a [`SourceMap`], which can be
+//! moved along with its code when the code is put in a bigger file and then saved as a Source Map v3 file.
+//! The statics of a file are not mapped.
+//!
+//! [`Compiler`]: crate::compiler::Compiler
+//! [`Compiler::compile_tokens_with_source_map`]: crate::compiler::Compiler::compile_tokens_with_source_map
+
+use crate::diagnostic::{Position, Span};
+use std::fmt::Write;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A single position of the generated Lua code and the Clue code it was compiled from
+pub struct Mapping {
+	/// The position in the generated Lua code
+	pub generated: Position,
+
+	/// The name of the Clue file
+	pub source: String,
+
+	/// The position in the Clue file
+	pub original: Position,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Maps positions of the generated Lua code to the Clue code they were compiled from
+///
+/// # Example
+/// ```rust
+/// use clue_core::{diagnostic::Position, Clue};
+///
+/// let clue = Clue::new();
+/// let (code, map) = clue
+///     .compile_code_with_source_map("local a = 1\n\nprint(a)".to_owned())
+///     .unwrap();
+///
+/// assert_eq!(code, "local a = 1;\nprint(a);");
+/// let mapping = map.lookup(Position::new(2, 1)).unwrap();
+/// assert_eq!(mapping.original.line, 3);
+/// ```
+pub struct SourceMap {
+	/// Every mapping, sorted by their position in the generated code
+	pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+	/// Builds the [`SourceMap`] of `code` from the byte offsets where the statements start in it
+	/// and the spans of the statements in the file called `source`
+	pub(crate) fn new(code: &str, source: &str, offsets: &[(usize, Span)]) -> Self {
+		let mut offsets = offsets.to_vec();
+		offsets.sort_by_key(|(offset, _)| *offset);
+		let mut mappings = Vec::with_capacity(offsets.len());
+		let mut offsets = offsets.into_iter().peekable();
+		let (mut line, mut column) = (1, 1);
+		for (i, c) in code.char_indices().chain([(code.len(), '\0')]) {
+			while let Some((_, span)) = offsets.next_if(|(offset, _)| *offset <= i) {
+				mappings.push(Mapping {
+					generated: Position {
+						line,
+						column,
+						offset: i,
+					},
+					source: source.to_owned(),
+					original: span.start,
+				});
+			}
+			if c == '\n' {
+				line += 1;
+				column = 1;
+			} else {
+				column += 1;
+			}
+		}
+		Self { mappings }
+	}
+
+	/// Moves every mapping as if the code it maps was put after `code`
+	pub fn move_after(&mut self, code: &str) {
+		let lines = code.matches('\n').count();
+		let columns = code[code.rfind('\n').map_or(0, |i| i + 1)..]
+			.chars()
+			.count();
+		for mapping in &mut self.mappings {
+			if mapping.generated.line == 1 {
+				mapping.generated.column += columns;
+			}
+			mapping.generated.line += lines;
+			mapping.generated.offset += code.len();
+		}
+	}
+
+	/// Adds the mappings of `other`, whose code is being put after `code`,
+	/// which must be the code mapped by this [`SourceMap`]
+	pub fn append(&mut self, code: &str, mut other: SourceMap) {
+		other.move_after(code);
+		self.mappings.append(&mut other.mappings);
+	}
+
+	/// Returns the [`Mapping`] of the statement that contains the given position of the generated code, if any
+	pub fn lookup(&self, generated: Position) -> Option<&Mapping> {
+		let index = self.mappings.partition_point(|mapping| {
+			(mapping.generated.line, mapping.generated.column) <= (generated.line, generated.column)
+		});
+		if index > 0 {
+			return self.mappings.get(index - 1);
+		}
+		let first = self.mappings.first()?;
+		(first.generated.line == generated.line).then_some(first)
+	}
+
+	/// Returns the names of the Clue files used by the mappings, in order of appearance
+	pub fn sources(&self) -> Vec<&str> {
+		let mut sources: Vec<&str> = Vec::new();
+		for mapping in &self.mappings {
+			if !sources.contains(&mapping.source.as_str()) {
+				sources.push(&mapping.source);
+			}
+		}
+		sources
+	}
+
+	/// Converts the [`SourceMap`] to a Source Map v3 JSON string
+	/// `file` is the name of the generated Lua file
+	pub fn to_json(&self, file: &str) -> String {
+		let sources = self.sources();
+		let mut mappings = String::with_capacity(self.mappings.len() * 8);
+		let mut line = 1;
+		let mut first = true;
+		let (mut column, mut source, mut original_line, mut original_column) = (0, 0, 0, 0);
+		for mapping in &self.mappings {
+			while line < mapping.generated.line {
+				mappings.push(';');
+				line += 1;
+				column = 0;
+				first = true;
+			}
+			if !first {
+				mappings.push(',');
+			}
+			first = false;
+			let new_source = sources
+				.iter()
+				.position(|source| *source == mapping.source)
+				.unwrap_or_default() as i64;
+			let new_column = mapping.generated.column as i64 - 1;
+			let new_line = mapping.original.line as i64 - 1;
+			let new_original_column = mapping.original.column as i64 - 1;
+			encode_vlq(&mut mappings, new_column - column);
+			encode_vlq(&mut mappings, new_source - source);
+			encode_vlq(&mut mappings, new_line - original_line);
+			encode_vlq(&mut mappings, new_original_column - original_column);
+			column = new_column;
+			source = new_source;
+			original_line = new_line;
+			original_column = new_original_column;
+		}
+		let mut json = String::from("{\"version\":3,\"file\":");
+		write_json_string(&mut json, file);
+		json += ",\"sources\":[";
+		for (i, source) in sources.iter().enumerate() {
+			if i > 0 {
+				json.push(',');
+			}
+			write_json_string(&mut json, source);
+		}
+		json += "],\"names\":[],\"mappings\":\"";
+		json += &mappings;
+		json += "\"}";
+		json
+	}
+}
+
+fn encode_vlq(result: &mut String, value: i64) {
+	let mut value = if value < 0 {
+		((-value) << 1) | 1
+	} else {
+		value << 1
+	};
+	loop {
+		let mut digit = value & 0b11111;
+		value >>= 5;
+		if value > 0 {
+			digit |= 0b100000;
+		}
+		result.push(BASE64[digit as usize] as char);
+		if value == 0 {
+			break;
+		}
+	}
+}
+
+fn write_json_string(result: &mut String, string: &str) {
+	result.push('"');
+	for c in string.chars() {
+		match c {
+			'"' => result.push_str("\\\""),
+			'\\' => result.push_str("\\\\"),
+			'\n' => result.push_str("\\n"),
+			'\r' => result.push_str("\\r"),
+			'\t' => result.push_str("\\t"),
+			c if (c as u32) < 0x20 => {
+				let _ = write!(result, "\\u{:04x}", c as u32);
+			}
+			c => result.push(c),
+		}
+	}
+	result.push('"');
+}
@@ -0,0 +1,58 @@
+//! The `stdlib` module contains the manifests of the standard library of every supported version of Lua,
+//! which list every global and every member of the standard library tables (e.g. `print` or `table.unpack`).
+//!
+//! They are used to warn about references to members of the standard library that are missing
+//! in the one chosen with [`Options::std`](crate::env::Options::std).
+
+use crate::env::LuaSTD;
+use ahash::AHashSet;
+use once_cell::sync::Lazy;
+
+const LUAJIT: &str = include_str!("../std/luajit.txt");
+const LUA54: &str = include_str!("../std/lua54.txt");
+const LUA53: &str = include_str!("../std/lua53.txt");
+const LUA52: &str = include_str!("../std/lua52.txt");
+const LUA51: &str = include_str!("../std/lua51.txt");
+
+const MANIFESTS: [&str; 5] = [LUAJIT, LUA54, LUA53, LUA52, LUA51];
+
+// the manifests are parsed only the first time they are needed, instead of once per file
+static PARSED: Lazy<[AHashSet<&'static str>; 5]> =
+	Lazy::new(|| MANIFESTS.map(|manifest| parse_manifest(manifest).collect()));
+static ALL_MEMBERS: Lazy<AHashSet<&'static str>> =
+	Lazy::new(|| PARSED.iter().flatten().copied().collect());
+static EMPTY: Lazy<AHashSet<&'static str>> = Lazy::new(AHashSet::default);
+
+fn parse_manifest(manifest: &'static str) -> impl Iterator<Item = &'static str> {
+	manifest
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Returns the manifest of the given standard library
+/// BLUA uses the manifest of Lua 5.4, while [`LuaSTD::NONE`] has an empty manifest
+///
+/// # Example
+/// ```rust
+/// use clue_core::{env::LuaSTD, stdlib::manifest};
+///
+/// assert!(manifest(LuaSTD::LUA51).contains("unpack"));
+/// assert!(manifest(LuaSTD::LUA54).contains("table.unpack"));
+/// assert!(!manifest(LuaSTD::LUA54).contains("unpack"));
+/// ```
+pub fn manifest(std: LuaSTD) -> &'static AHashSet<&'static str> {
+	match std {
+		LuaSTD::NONE => &EMPTY,
+		LuaSTD::LUAJIT => &PARSED[0],
+		LuaSTD::LUA54 | LuaSTD::BLUA => &PARSED[1],
+		LuaSTD::LUA53 => &PARSED[2],
+		LuaSTD::LUA52 => &PARSED[3],
+		LuaSTD::LUA51 => &PARSED[4],
+	}
+}
+
+/// Returns every member of the standard library of any version of Lua
+pub fn all_members() -> &'static AHashSet<&'static str> {
+	&ALL_MEMBERS
+}
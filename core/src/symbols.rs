@@ -0,0 +1,220 @@
+//! The `symbols` module contains the [`SymbolTable`], which lists everything a file declares:
+//! variables, functions, methods, structs, enum entries and preprocessor variables and macros,
+//! each with the [`Span`] of its definition.
+//!
+//! It's meant to be used by editor tooling, which can get it as JSON with `--symbols`.
+
+use crate::{
+	code::Code,
+	diagnostic::{Position, Span},
+	preprocessor::{PPVar, PPVars},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// What a [`Symbol`] is
+pub enum SymbolKind {
+	/// A variable declared with `local`, `global` or `static`
+	Variable,
+
+	/// A function declared with `fn`, or with `method` without using `::`
+	Function,
+
+	/// A function declared with `method` using `::` or inside a struct
+	Method,
+
+	/// A struct
+	Struct,
+
+	/// An entry of an enum
+	EnumEntry,
+
+	/// A preprocessor variable declared with `@define`
+	Define,
+
+	/// A preprocessor macro declared with `@macro`
+	Macro,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Something declared in a file
+pub struct Symbol {
+	/// The name of the symbol, including the table it's stored in for methods (e.g. `Vector:length`)
+	pub name: String,
+
+	/// What the symbol is
+	pub kind: SymbolKind,
+
+	/// Whether the symbol is local to the file
+	pub local: bool,
+
+	/// Whether the symbol was declared with `static`
+	pub is_static: bool,
+
+	/// The arguments of functions, methods and macros
+	pub args: Vec<String>,
+
+	/// The value of preprocessor variables
+	pub value: Option<String>,
+
+	/// Where the name of the symbol is in the source code
+	pub span: Span,
+}
+
+impl Symbol {
+	/// Creates a new [`Symbol`] that is neither static nor a preprocessor variable
+	pub fn new(name: impl Into<String>, kind: SymbolKind, local: bool, span: Span) -> Self {
+		Self {
+			name: name.into(),
+			kind,
+			local,
+			is_static: false,
+			args: Vec::new(),
+			value: None,
+			span,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Every [`Symbol`] declared in a file, in the order they were declared
+///
+/// # Example
+/// ```rust
+/// use clue_core::{diagnostic::Diagnostic, symbols::SymbolKind, Clue};
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let clue = Clue::new();
+///     let table = clue.symbols("@macro double(x) { $x * 2 }\nlocal fn f(a) {}".to_owned())?;
+///
+///     let function = table.get("f").unwrap();
+///     assert_eq!(function.kind, SymbolKind::Function);
+///     assert_eq!(function.args, ["a"]);
+///     assert_eq!(function.span.start.line, 2);
+///     assert_eq!(table.get("double").unwrap().kind, SymbolKind::Macro);
+///
+///     Ok(())
+/// }
+/// ```
+pub struct SymbolTable {
+	/// The symbols of the table
+	pub symbols: Vec<Symbol>,
+}
+
+fn code_span(code: &Code) -> Span {
+	match (code.iter().next(), code.last()) {
+		(Some((_, line, column, offset)), Some((_, end_line, end_column, end_offset))) => {
+			Span::range(
+				Position {
+					line: *line,
+					column: *column,
+					offset: *offset,
+				},
+				Position {
+					line: *end_line,
+					column: end_column + 1,
+					offset: end_offset + 1,
+				},
+			)
+		}
+		_ => Span::default(),
+	}
+}
+
+impl SymbolTable {
+	/// Creates a new empty [`SymbolTable`]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Returns the first symbol with the given name
+	pub fn get(&self, name: &str) -> Option<&Symbol> {
+		self.symbols.iter().find(|symbol| symbol.name == name)
+	}
+
+	/// Adds a [`Symbol`] to the table
+	pub fn push(&mut self, symbol: Symbol) {
+		self.symbols.push(symbol)
+	}
+
+	/// Adds every symbol of another table to this one
+	pub fn append(&mut self, other: &mut SymbolTable) {
+		self.symbols.append(&mut other.symbols)
+	}
+
+	/// Adds every preprocessor variable and macro of the given [`PPVars`], sorted by name
+	pub fn add_ppvars(&mut self, variables: &PPVars) {
+		let mut symbols = Vec::with_capacity(variables.len());
+		for (name, variable) in variables {
+			let span = code_span(name);
+			let name = name.to_string();
+			symbols.push(match variable {
+				PPVar::Simple(value) | PPVar::ToProcess(value) => Symbol {
+					value: Some(value.to_string()),
+					..Symbol::new(name, SymbolKind::Define, true, span)
+				},
+				PPVar::Macro { args, vararg, .. } => {
+					let mut args: Vec<String> = args.iter().map(Code::to_string).collect();
+					if *vararg {
+						args.push(String::from("..."));
+					}
+					Symbol {
+						args,
+						..Symbol::new(name, SymbolKind::Macro, true, span)
+					}
+				}
+				// only exists while a variadic macro is being expanded
+				PPVar::VarArgs(_) => continue,
+			});
+		}
+		symbols.sort_by(|a, b| a.name.cmp(&b.name));
+		self.symbols.append(&mut symbols);
+	}
+
+	#[cfg(feature = "lsp")]
+	/// Returns the table as JSON, in the format printed by `--symbols`
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
+	///
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
+	///     let clue = Clue::new();
+	///     let table = clue.symbols("local x = 1".to_owned())?;
+	///     let json = table.to_json("main.clue");
+	///
+	///     assert!(json.starts_with("{\"file\":\"main.clue\""));
+	///     assert!(json.contains("\"name\":\"x\""));
+	///
+	///     Ok(())
+	/// }
+	/// ```
+	pub fn to_json(&self, filename: &str) -> String {
+		serde_json::json!({
+			"file": filename,
+			"symbols": self.symbols,
+		})
+		.to_string()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::Clue;
+
+	#[test]
+	fn spans_are_in_the_source_file() {
+		let code = "@define X 1\n@macro M(x) { $x + $x }\nlocal a = $M!(22)\nlocal b = $X";
+		let table = Clue::new().symbols(code.to_owned()).unwrap();
+		let offset = |name| table.get(name).unwrap().span.start.offset;
+		assert_eq!(offset("X"), 8);
+		assert_eq!(offset("M"), 19);
+		assert_eq!(offset("a"), 42);
+		assert_eq!(offset("b"), 60);
+	}
+}
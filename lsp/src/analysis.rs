@@ -0,0 +1,618 @@
+//! The `analysis` module turns the text of a document into the information the language server needs:
+//! the diagnostics of the compiler, the symbols declared in the document and where each name is declared.
+//!
+//! Declarations are found by looking at the tokens of the document, so they are available even if the code
+//! fails to parse, as long as it can be scanned.
+
+use std::ops::Range;
+
+use clue_core::{
+	diagnostic::{self, Severity},
+	env::TypesMode,
+	scanner::{Token, TokenType, TokenType::*},
+	stdlib, Clue,
+};
+use lsp_types::{
+	Diagnostic, DiagnosticSeverity, Location, NumberOrString, Position, SymbolInformation,
+	SymbolKind, Url,
+};
+
+/// A name declared in a document
+struct Declaration {
+	/// The name that was declared
+	name: String,
+
+	/// The index of the token of the name
+	token: usize,
+
+	/// The indexes of the tokens that can see the declaration
+	scope: Range<usize>,
+}
+
+/// A symbol shown in the outline of a document
+struct Symbol {
+	/// The name of the symbol, including the table it's stored in for functions like `a.b`
+	name: String,
+
+	/// What the symbol is
+	kind: SymbolKind,
+
+	/// The index of the first token of the name of the symbol
+	token: usize,
+
+	/// The name of the struct the symbol belongs to, if any
+	container: Option<String>,
+}
+
+/// An open document and the result of its analysis
+pub struct Document {
+	text: String,
+	line_starts: Vec<usize>,
+	tokens: Vec<Token>,
+	diagnostics: Vec<diagnostic::Diagnostic>,
+	declarations: Vec<Declaration>,
+	symbols: Vec<Symbol>,
+}
+
+impl Document {
+	/// Analyzes the given text, compiling it to find its diagnostics
+	/// Type annotations are checked too, with their mismatches reported as warnings
+	pub fn new(text: String) -> Self {
+		let mut clue = Clue::new();
+		clue.types_mode(TypesMode::WARN);
+		let line_starts = std::iter::once(0)
+			.chain(text.match_indices('\n').map(|(i, _)| i + 1))
+			.collect();
+		let (tokens, diagnostics) = match clue.scan_code(text.clone()) {
+			Ok(tokens) => {
+				let diagnostics = clue.check_tokens(tokens.clone());
+				(tokens, diagnostics)
+			}
+			Err(diagnostics) => (Vec::new(), diagnostics),
+		};
+		let (declarations, symbols) = DeclarationsFinder::new(&tokens).find();
+		Document {
+			text,
+			line_starts,
+			tokens,
+			diagnostics,
+			declarations,
+			symbols,
+		}
+	}
+
+	/// Returns the diagnostics of the document
+	pub fn diagnostics(&self) -> Vec<Diagnostic> {
+		self.diagnostics
+			.iter()
+			.map(|diagnostic| {
+				let span = diagnostic.span;
+				let start = self.position(span.start.line, span.start.column);
+				let end = if span.end == span.start {
+					let (line, offset) = self.offset(start);
+					let word = self.word_at(line, offset);
+					let end = if word.is_empty() {
+						offset + 1
+					} else {
+						word.end
+					};
+					self.position(span.start.line, end + 1)
+				} else {
+					self.position(span.end.line, span.end.column)
+				};
+				Diagnostic {
+					range: lsp_types::Range { start, end },
+					severity: Some(match diagnostic.severity {
+						Severity::Error => DiagnosticSeverity::ERROR,
+						Severity::Warning => DiagnosticSeverity::WARNING,
+						Severity::Note => DiagnosticSeverity::INFORMATION,
+					}),
+					code: Some(NumberOrString::String(diagnostic.code.clone())),
+					source: Some(String::from("clue")),
+					message: diagnostic.message.clone(),
+					..Default::default()
+				}
+			})
+			.collect()
+	}
+
+	/// Returns the functions, methods, structs, enums and statics declared in the document
+	#[allow(deprecated)]
+	pub fn symbols(&self, uri: &Url) -> Vec<SymbolInformation> {
+		self.symbols
+			.iter()
+			.map(|symbol| SymbolInformation {
+				name: symbol.name.clone(),
+				kind: symbol.kind,
+				tags: None,
+				deprecated: None,
+				location: Location::new(uri.clone(), self.token_range(symbol.token)),
+				container_name: symbol.container.clone(),
+			})
+			.collect()
+	}
+
+	/// Returns the range of the declaration of the name at the given position,
+	/// which can be either a local, a function or a preprocessor variable or macro
+	pub fn definition(&self, position: Position) -> Option<lsp_types::Range> {
+		if let Some((line, name)) = self.preprocessor_name(position) {
+			return self.preprocessor_definition(line, name);
+		}
+		let declaration = self.declaration(self.token_at(position)?)?;
+		Some(self.token_range(declaration.token))
+	}
+
+	/// Returns the markdown describing the name at the given position
+	pub fn hover(&self, position: Position) -> Option<String> {
+		let line = if let Some((line, name)) = self.preprocessor_name(position) {
+			self.preprocessor_definition(line, name)?.start.line as usize
+		} else {
+			let index = self.token_at(position)?;
+			match self.declaration(index) {
+				Some(declaration) => self.tokens[declaration.token].line - 1,
+				None => {
+					let name = &self.tokens[index].lexeme;
+					return stdlib::all_members()
+						.contains(name.as_str())
+						.then(|| format!("`{name}` (Lua standard library)"));
+				}
+			}
+		};
+		let declaration = self.line(line).trim().trim_end_matches('{').trim_end();
+		Some(format!("```clue\n{declaration}\n```"))
+	}
+
+	fn line(&self, line: usize) -> &str {
+		let start = self.line_starts[line];
+		let end = self
+			.line_starts
+			.get(line + 1)
+			.map_or(self.text.len(), |end| end - 1);
+		self.text[start..end].trim_end_matches('\r')
+	}
+
+	/// Converts the 1-based line and byte column used by the compiler to an LSP position
+	fn position(&self, line: usize, column: usize) -> Position {
+		let line = line.saturating_sub(1).min(self.line_starts.len() - 1);
+		let text = self.line(line);
+		let mut offset = column.saturating_sub(1).min(text.len());
+		while !text.is_char_boundary(offset) {
+			offset -= 1;
+		}
+		let character = text[..offset].encode_utf16().count();
+		Position::new(line as u32, character as u32)
+	}
+
+	/// Converts an LSP position to the line and byte offset in that line
+	fn offset(&self, position: Position) -> (usize, usize) {
+		let line = (position.line as usize).min(self.line_starts.len() - 1);
+		let mut character = 0;
+		for (offset, c) in self.line(line).char_indices() {
+			if character >= position.character as usize {
+				return (line, offset);
+			}
+			character += c.len_utf16();
+		}
+		(line, self.line(line).len())
+	}
+
+	/// Returns the range of bytes of the identifier around the given offset of a line
+	fn word_at(&self, line: usize, offset: usize) -> Range<usize> {
+		let text = self.line(line);
+		let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+		let start = text[..offset]
+			.rfind(|c| !is_word(c))
+			.map_or(0, |start| start + 1);
+		let end = text[offset..]
+			.find(|c| !is_word(c))
+			.map_or(text.len(), |end| offset + end);
+		start..end
+	}
+
+	fn token_range(&self, index: usize) -> lsp_types::Range {
+		let token = &self.tokens[index];
+		lsp_types::Range::new(
+			self.position(token.line, token.column),
+			self.position(token.line, token.column + token.lexeme.len()),
+		)
+	}
+
+	/// Returns the index of the identifier at the given position
+	fn token_at(&self, position: Position) -> Option<usize> {
+		let (line, offset) = self.offset(position);
+		let (line, column) = (line + 1, offset + 1);
+		self.tokens.iter().position(|token| {
+			token.kind == IDENTIFIER
+				&& token.line == line
+				&& (token.column..=token.column + token.lexeme.len()).contains(&column)
+		})
+	}
+
+	/// Returns the declaration visible from the identifier with the given index
+	fn declaration(&self, index: usize) -> Option<&Declaration> {
+		if index > 0
+			&& matches!(
+				self.tokens[index - 1].kind,
+				DOT | DOUBLE_COLON | SAFE_DOT | SAFE_DOUBLE_COLON
+			) {
+			return None;
+		}
+		let name = &self.tokens[index].lexeme;
+		self.declarations
+			.iter()
+			.filter(|declaration| declaration.name == *name && declaration.scope.contains(&index))
+			.max_by_key(|declaration| (declaration.scope.start, declaration.token))
+	}
+
+	/// Returns the line and the name of the preprocessor variable or macro at the given position, if any
+	fn preprocessor_name(&self, position: Position) -> Option<(usize, &str)> {
+		let (line, offset) = self.offset(position);
+		let word = self.word_at(line, offset);
+		let text = self.line(line);
+		if word.is_empty() {
+			return None;
+		}
+		let name = &text[word.clone()];
+		if text[..word.start].ends_with('$') || definition_name(text) == Some(name) {
+			Some((line, name))
+		} else {
+			None
+		}
+	}
+
+	/// Returns the range of the `@define` or `@macro` directive that defines `name`,
+	/// preferring the last one before the given line
+	fn preprocessor_definition(&self, line: usize, name: &str) -> Option<lsp_types::Range> {
+		let mut definition = None;
+		for i in 0..self.line_starts.len() {
+			let text = self.line(i);
+			if definition_name(text) != Some(name) {
+				continue;
+			}
+			let directive = text.find('@').unwrap();
+			let name_start = directive + text[directive..].find(char::is_whitespace).unwrap();
+			let offset = name_start + text[name_start..].find(name).unwrap();
+			definition = Some(lsp_types::Range::new(
+				self.position(i + 1, offset + 1),
+				self.position(i + 1, offset + name.len() + 1),
+			));
+			if i >= line {
+				break;
+			}
+		}
+		definition
+	}
+}
+
+/// Returns the name defined by a line containing a `@define` or `@macro` directive
+fn definition_name(line: &str) -> Option<&str> {
+	let directive = line.trim_start();
+	let name = directive
+		.strip_prefix("@define")
+		.or_else(|| directive.strip_prefix("@macro"))?;
+	if !name.starts_with(char::is_whitespace) {
+		return None;
+	}
+	let name = name.trim_start();
+	let end = name
+		.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+		.unwrap_or(name.len());
+	(end > 0).then(|| &name[..end])
+}
+
+/// Finds the declarations and the symbols of a list of tokens
+struct DeclarationsFinder<'a> {
+	tokens: &'a [Token],
+	block_ends: Vec<usize>,
+	enclosing: Vec<usize>,
+	structs: Vec<(String, Range<usize>)>,
+	declarations: Vec<Declaration>,
+	symbols: Vec<Symbol>,
+}
+
+impl<'a> DeclarationsFinder<'a> {
+	fn new(tokens: &'a [Token]) -> Self {
+		let len = tokens.len();
+		let mut block_ends = vec![len; len];
+		let mut enclosing = vec![len; len];
+		let mut opened = Vec::new();
+		for (i, token) in tokens.iter().enumerate() {
+			match token.kind {
+				CURLY_BRACKET_OPEN => opened.push(i),
+				CURLY_BRACKET_CLOSED => {
+					if let Some(start) = opened.pop() {
+						block_ends[start] = i;
+					}
+				}
+				_ => {}
+			}
+		}
+		let mut opened: Vec<usize> = Vec::new();
+		for (i, token) in tokens.iter().enumerate() {
+			enclosing[i] = opened.last().map_or(len, |start| block_ends[*start]);
+			match token.kind {
+				CURLY_BRACKET_OPEN => opened.push(i),
+				CURLY_BRACKET_CLOSED => {
+					opened.pop();
+				}
+				_ => {}
+			}
+		}
+		Self {
+			tokens,
+			block_ends,
+			enclosing,
+			structs: Vec::new(),
+			declarations: Vec::new(),
+			symbols: Vec::new(),
+		}
+	}
+
+	fn kind(&self, index: usize) -> TokenType {
+		self.tokens.get(index).map_or(EOF, |token| token.kind)
+	}
+
+	fn declare(&mut self, token: usize, scope: Range<usize>) {
+		self.declarations.push(Declaration {
+			name: self.tokens[token].lexeme.clone(),
+			token,
+			scope,
+		})
+	}
+
+	fn symbol(&mut self, name: String, kind: SymbolKind, token: usize) {
+		let container = self
+			.structs
+			.iter()
+			.rev()
+			.find(|(_, body)| body.contains(&token))
+			.map(|(name, _)| name.clone());
+		self.symbols.push(Symbol {
+			name,
+			kind,
+			token,
+			container,
+		})
+	}
+
+	/// Returns the scope of a declaration made at `token` by a statement starting with `modifier`
+	fn scope(&self, modifier: TokenType, token: usize) -> Range<usize> {
+		match modifier {
+			LOCAL => token..self.enclosing[token],
+			_ => 0..self.tokens.len(),
+		}
+	}
+
+	/// Returns the scope of the first block after the given token
+	fn next_block(&self, start: usize) -> Range<usize> {
+		match (start..self.tokens.len()).find(|i| self.kind(*i) == CURLY_BRACKET_OPEN) {
+			Some(block) => block..self.block_ends[block],
+			None => 0..0,
+		}
+	}
+
+	fn find(mut self) -> (Vec<Declaration>, Vec<Symbol>) {
+		for i in 0..self.tokens.len() {
+			match self.kind(i) {
+				LOCAL | GLOBAL | STATIC | EXTERN => self.find_modified(i),
+				FN | METHOD => self.find_function(i),
+				CONSTRUCTOR => self.find_parameters(i + 1),
+				FOR => {
+					let scope = self.next_block(i);
+					let mut i = i + 1;
+					while matches!(self.kind(i), IDENTIFIER | COMMA) {
+						if self.kind(i) == IDENTIFIER {
+							self.declare(i, scope.clone());
+						}
+						i += 1;
+					}
+				}
+				CATCH if self.kind(i + 1) == IDENTIFIER => {
+					let scope = self.next_block(i);
+					self.declare(i + 1, scope);
+				}
+				_ => {}
+			}
+		}
+		(self.declarations, self.symbols)
+	}
+
+	/// Finds what is declared by a statement starting with `local`, `global`, `static` or `extern`
+	fn find_modified(&mut self, i: usize) {
+		let modifier = self.kind(i);
+		match self.kind(i + 1) {
+			FN => {}
+			STRUCT if self.kind(i + 2) == IDENTIFIER => {
+				let name = self.tokens[i + 2].lexeme.clone();
+				self.declare(i + 2, self.scope(modifier, i + 2));
+				self.symbol(name.clone(), SymbolKind::STRUCT, i + 2);
+				let body = self.next_block(i + 2);
+				self.structs.push((name, body));
+			}
+			ENUM => {
+				let body = self.next_block(i + 1);
+				let mut expects_name = true;
+				for j in body.clone() {
+					match self.kind(j) {
+						IDENTIFIER if expects_name => {
+							self.declare(j, self.scope(modifier, j));
+							let name = self.tokens[j].lexeme.clone();
+							self.symbol(name, SymbolKind::ENUM_MEMBER, j);
+							expects_name = false;
+						}
+						COMMA => expects_name = true,
+						_ => {}
+					}
+				}
+			}
+			CURLY_BRACKET_OPEN => {
+				let mut j = i + 2;
+				while matches!(self.kind(j), IDENTIFIER | COMMA) {
+					if self.kind(j) == IDENTIFIER {
+						self.declare(j, self.scope(modifier, j));
+					}
+					j += 1;
+				}
+			}
+			_ => {
+				let mut j = i + 1;
+				while self.kind(j) == IDENTIFIER {
+					self.declare(j, self.scope(modifier, j));
+					if modifier == STATIC {
+						let name = self.tokens[j].lexeme.clone();
+						self.symbol(name, SymbolKind::VARIABLE, j);
+					}
+					j += 1;
+					if self.kind(j) == COLON {
+						j += 1;
+						while matches!(self.kind(j), IDENTIFIER | NIL | FN | QUESTION_MARK | BIT_OR)
+						{
+							j += 1;
+						}
+					}
+					if self.kind(j) != COMMA {
+						break;
+					}
+					j += 1;
+				}
+			}
+		}
+	}
+
+	/// Finds the name and the parameters of a function or a method
+	fn find_function(&mut self, i: usize) {
+		let modifier = match i.checked_sub(1).map(|i| self.kind(i)) {
+			Some(modifier @ (LOCAL | GLOBAL | STATIC | EXTERN)) => Some(modifier),
+			_ => None,
+		};
+		let mut j = i + 1;
+		let mut name = String::new();
+		let mut kind = match self.kind(i) {
+			METHOD => SymbolKind::METHOD,
+			_ => SymbolKind::FUNCTION,
+		};
+		while self.kind(j) == IDENTIFIER {
+			name += &self.tokens[j].lexeme;
+			match self.kind(j + 1) {
+				DOT => name.push('.'),
+				DOUBLE_COLON => {
+					name.push(':');
+					kind = SymbolKind::METHOD;
+				}
+				_ => {
+					j += 1;
+					break;
+				}
+			}
+			j += 2;
+		}
+		if !name.is_empty() {
+			if let (Some(modifier), true) = (modifier, j == i + 2) {
+				self.declare(i + 1, self.scope(modifier, i + 1));
+			}
+			self.symbol(name, kind, i + 1);
+		}
+		if modifier != Some(EXTERN) {
+			self.find_parameters(j);
+		}
+	}
+
+	/// Declares the parameters of the function whose arguments start at the given token
+	fn find_parameters(&mut self, start: usize) {
+		if self.kind(start) != ROUND_BRACKET_OPEN {
+			return;
+		}
+		let mut depth = 0;
+		let mut parameters = Vec::new();
+		let mut end = start;
+		for j in start..self.tokens.len() {
+			match self.kind(j) {
+				ROUND_BRACKET_OPEN => depth += 1,
+				ROUND_BRACKET_CLOSED => {
+					depth -= 1;
+					if depth == 0 {
+						end = j;
+						break;
+					}
+				}
+				IDENTIFIER
+					if depth == 1 && matches!(self.kind(j - 1), ROUND_BRACKET_OPEN | COMMA) =>
+				{
+					parameters.push(j)
+				}
+				_ => {}
+			}
+		}
+		let scope = self.next_block(end);
+		for parameter in parameters {
+			self.declare(parameter, scope.clone());
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Document;
+	use lsp_types::{DiagnosticSeverity, Position, Range, SymbolKind, Url};
+
+	#[test]
+	fn definitions() {
+		let document = Document::new(String::from(
+			"@define COUNT 3\nlocal fn add(x, y) {\n\treturn x + y\n}\nlocal x = add($COUNT, 2)\nprint(x)",
+		));
+		// `x` inside `add` is the parameter
+		assert_eq!(
+			document.definition(Position::new(2, 8)),
+			Some(Range::new(Position::new(1, 13), Position::new(1, 14)))
+		);
+		// `x` at the end is the local
+		assert_eq!(
+			document.definition(Position::new(5, 6)),
+			Some(Range::new(Position::new(4, 6), Position::new(4, 7)))
+		);
+		// `$COUNT` is the preprocessor variable
+		assert_eq!(
+			document.definition(Position::new(4, 17)),
+			Some(Range::new(Position::new(0, 8), Position::new(0, 13)))
+		);
+		assert!(document
+			.hover(Position::new(4, 11))
+			.unwrap()
+			.contains("local fn add(x, y)"));
+	}
+
+	#[test]
+	fn symbols_and_diagnostics() {
+		let uri = Url::parse("file:///test.clue").unwrap();
+		let document = Document::new(String::from(
+			"local struct Vector {\n\tmethod length() {}\n}\nstatic fn f() {}\nlocal enum { A, B = 3 }\nlocal x = }",
+		));
+		let symbols: Vec<_> = document
+			.symbols(&uri)
+			.into_iter()
+			.map(|symbol| (symbol.name, symbol.kind, symbol.container_name))
+			.collect();
+		assert_eq!(
+			symbols,
+			[
+				(String::from("Vector"), SymbolKind::STRUCT, None),
+				(
+					String::from("length"),
+					SymbolKind::METHOD,
+					Some(String::from("Vector"))
+				),
+				(String::from("f"), SymbolKind::FUNCTION, None),
+				(String::from("A"), SymbolKind::ENUM_MEMBER, None),
+				(String::from("B"), SymbolKind::ENUM_MEMBER, None),
+			]
+		);
+		let diagnostics = document.diagnostics();
+		assert_eq!(diagnostics.len(), 1);
+		assert_eq!(diagnostics[0].range.start, Position::new(5, 10));
+
+		let document = Document::new(String::from("local x: number = \"one\""));
+		let diagnostics = document.diagnostics();
+		assert_eq!(diagnostics.len(), 1);
+		assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+	}
+}
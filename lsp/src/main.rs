@@ -0,0 +1,199 @@
+//! The Clue language server
+//! It speaks the Language Server Protocol over stdio and provides diagnostics, document symbols,
+//! hover, go-to-definition and keyword completion for Clue files
+
+use std::{collections::HashMap, error::Error};
+
+use analysis::Document;
+use clue_core::scanner::keywords;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+	notification::{
+		DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+		Notification as NotificationTrait, PublishDiagnostics,
+	},
+	request::{Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as _},
+	CompletionItem, CompletionItemKind, CompletionOptions, CompletionResponse,
+	DocumentSymbolResponse, GotoDefinitionResponse, Hover, HoverContents, HoverProviderCapability,
+	Location, MarkupContent, MarkupKind, OneOf, PublishDiagnosticsParams, ServerCapabilities,
+	TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use serde_json::Value;
+
+mod analysis;
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
+/// The requests the server can respond to
+const REQUESTS: [&str; 4] = [
+	DocumentSymbolRequest::METHOD,
+	HoverRequest::METHOD,
+	GotoDefinition::METHOD,
+	Completion::METHOD,
+];
+
+fn capabilities() -> ServerCapabilities {
+	ServerCapabilities {
+		text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+		document_symbol_provider: Some(OneOf::Left(true)),
+		hover_provider: Some(HoverProviderCapability::Simple(true)),
+		definition_provider: Some(OneOf::Left(true)),
+		completion_provider: Some(CompletionOptions::default()),
+		..Default::default()
+	}
+}
+
+struct Server {
+	connection: Connection,
+	documents: HashMap<Url, Document>,
+}
+
+impl Server {
+	fn run(mut self) -> Result<()> {
+		while let Ok(message) = self.connection.receiver.recv() {
+			match message {
+				Message::Request(request) => {
+					if self.connection.handle_shutdown(&request)? {
+						return Ok(());
+					}
+					let response = self.handle_request(request);
+					self.connection.sender.send(response.into())?;
+				}
+				Message::Notification(notification) => self.handle_notification(notification)?,
+				Message::Response(_) => {}
+			}
+		}
+		Ok(())
+	}
+
+	fn handle_request(&self, request: Request) -> Response {
+		let id = request.id.clone();
+		if !REQUESTS.contains(&request.method.as_str()) {
+			let message = format!("Unsupported request {}", request.method);
+			return Response::new_err(id, ErrorCode::MethodNotFound as i32, message);
+		}
+		match self.respond(request) {
+			Ok(Some(result)) => Response::new_ok(id, result),
+			Ok(None) => Response::new_ok(id, Value::Null),
+			Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, error.to_string()),
+		}
+	}
+
+	fn respond(&self, request: Request) -> Result<Option<Value>> {
+		Ok(match request.method.as_str() {
+			DocumentSymbolRequest::METHOD => {
+				let (_, params) = extract::<DocumentSymbolRequest>(request)?;
+				let uri = params.text_document.uri;
+				self.documents.get(&uri).map(|document| {
+					serde_json::to_value(DocumentSymbolResponse::Flat(document.symbols(&uri)))
+				})
+			}
+			HoverRequest::METHOD => {
+				let (_, params) = extract::<HoverRequest>(request)?;
+				let params = params.text_document_position_params;
+				self.documents
+					.get(&params.text_document.uri)
+					.and_then(|document| document.hover(params.position))
+					.map(|value| {
+						serde_json::to_value(Hover {
+							contents: HoverContents::Markup(MarkupContent {
+								kind: MarkupKind::Markdown,
+								value,
+							}),
+							range: None,
+						})
+					})
+			}
+			GotoDefinition::METHOD => {
+				let (_, params) = extract::<GotoDefinition>(request)?;
+				let params = params.text_document_position_params;
+				let uri = params.text_document.uri;
+				self.documents
+					.get(&uri)
+					.and_then(|document| document.definition(params.position))
+					.map(|range| {
+						serde_json::to_value(GotoDefinitionResponse::Scalar(Location::new(
+							uri, range,
+						)))
+					})
+			}
+			Completion::METHOD => {
+				let items = keywords()
+					.into_iter()
+					.map(|keyword| CompletionItem {
+						label: keyword.to_owned(),
+						kind: Some(CompletionItemKind::KEYWORD),
+						..Default::default()
+					})
+					.collect();
+				Some(serde_json::to_value(CompletionResponse::Array(items)))
+			}
+			_ => unreachable!("unsupported requests are handled by handle_request"),
+		}
+		.transpose()?)
+	}
+
+	fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+		match notification.method.as_str() {
+			DidOpenTextDocument::METHOD => {
+				let params = notification
+					.extract::<<DidOpenTextDocument as NotificationTrait>::Params>(
+						DidOpenTextDocument::METHOD,
+					)?;
+				let document = params.text_document;
+				self.update(document.uri, document.text)
+			}
+			DidChangeTextDocument::METHOD => {
+				let params = notification
+					.extract::<<DidChangeTextDocument as NotificationTrait>::Params>(
+						DidChangeTextDocument::METHOD,
+					)?;
+				match params.content_changes.into_iter().last() {
+					Some(change) => self.update(params.text_document.uri, change.text),
+					None => Ok(()),
+				}
+			}
+			DidCloseTextDocument::METHOD => {
+				let params = notification
+					.extract::<<DidCloseTextDocument as NotificationTrait>::Params>(
+						DidCloseTextDocument::METHOD,
+					)?;
+				let uri = params.text_document.uri;
+				self.documents.remove(&uri);
+				self.publish(uri, Vec::new())
+			}
+			_ => Ok(()),
+		}
+	}
+
+	/// Analyzes the new text of a document and publishes its diagnostics
+	fn update(&mut self, uri: Url, text: String) -> Result<()> {
+		let document = Document::new(text);
+		let diagnostics = document.diagnostics();
+		self.documents.insert(uri.clone(), document);
+		self.publish(uri, diagnostics)
+	}
+
+	fn publish(&self, uri: Url, diagnostics: Vec<lsp_types::Diagnostic>) -> Result<()> {
+		let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+		let notification = Notification::new(PublishDiagnostics::METHOD.to_owned(), params);
+		self.connection.sender.send(notification.into())?;
+		Ok(())
+	}
+}
+
+fn extract<R: lsp_types::request::Request>(request: Request) -> Result<(RequestId, R::Params)> {
+	Ok(request.extract(R::METHOD)?)
+}
+
+fn main() -> Result<()> {
+	let (connection, io_threads) = Connection::stdio();
+	connection.initialize(serde_json::to_value(capabilities())?)?;
+	Server {
+		connection,
+		documents: HashMap::new(),
+	}
+	.run()?;
+	io_threads.join()?;
+	Ok(())
+}
@@ -4,13 +4,19 @@
 
 use clue_core::{
 	code::Code,
-	env::{BitwiseMode, ContinueMode, LuaVersion},
+	diagnostic::Diagnostic,
+	env::{BitwiseMode, ContinueMode, LuaSTD, LuaVersion, TypesMode},
 	parser::Expression,
 	scanner::Token,
 	Clue as ClueCore,
 };
+use std::num::NonZeroUsize;
 use wasm_bindgen::prelude::*;
 
+fn to_js_error(diagnostics: Vec<Diagnostic>) -> JsValue {
+	serde_wasm_bindgen::to_value(&diagnostics).unwrap_or_else(|err| JsValue::from(err.to_string()))
+}
+
 /// Returns the version of the `clue` crate.
 #[wasm_bindgen(js_name = "getVersion")]
 pub fn get_version() -> String {
@@ -134,6 +140,60 @@ impl Clue {
 	pub fn target_os(&mut self, os: String) {
 		self.inner.target_os(os);
 	}
+
+	/// Sets the `maxerrors` option
+	/// The `maxerrors` option is used to limit how many errors are reported for each file
+	/// If `max` is `undefined` or 0 then every error will be reported
+	#[wasm_bindgen(js_name = "maxErrors")]
+	pub fn max_errors(&mut self, max: Option<usize>) {
+		self.inner.max_errors(max.and_then(NonZeroUsize::new));
+	}
+
+	/// Sets the `checkglobals` option
+	/// If `checkglobals` is `true` then reading a global that was never declared will be an error
+	#[wasm_bindgen(js_name = "checkGlobals")]
+	pub fn check_globals(&mut self, check: bool) {
+		self.inner.check_globals(check);
+	}
+
+	/// Sets the `types` option
+	/// The `types` option is used to set how type annotations are checked
+	/// The `types` option can be set to `none`, `warn`, or `strict`
+	///
+	/// # Errors
+	/// If the `types` option is set to an invalid value then an error will be thrown
+	#[wasm_bindgen(js_name = "typesMode")]
+	pub fn types_mode(&mut self, mode: String) -> Result<(), String> {
+		self.inner.types_mode(match mode.as_str() {
+			"none" => TypesMode::NONE,
+			"warn" => TypesMode::WARN,
+			"strict" => TypesMode::STRICT,
+			_ => return Err(format!("Invalid types mode {}", mode)),
+		});
+		Ok(())
+	}
+
+	/// Sets the `std` option
+	/// The `std` option is used to set the standard library references are checked against
+	/// The `std` option can be set to `none`, `5.1`, `5.2`, `5.3`, `5.4`, `blua`, or `luajit`
+	/// If it's not set then the standard library of the target is used
+	///
+	/// # Errors
+	/// If the `std` option is set to an invalid value then an error will be thrown
+	pub fn std(&mut self, std: Option<String>) -> Result<(), String> {
+		self.inner.std(match std.as_deref() {
+			Some("none") => Some(LuaSTD::NONE),
+			Some("5.1") => Some(LuaSTD::LUA51),
+			Some("5.2") => Some(LuaSTD::LUA52),
+			Some("5.3") => Some(LuaSTD::LUA53),
+			Some("5.4") => Some(LuaSTD::LUA54),
+			Some("blua") => Some(LuaSTD::BLUA),
+			Some("luajit") => Some(LuaSTD::LUAJIT),
+			None => None,
+			Some(std) => return Err(format!("Invalid standard library {}", std)),
+		});
+		Ok(())
+	}
 }
 
 #[wasm_bindgen]
@@ -142,118 +202,121 @@ impl Clue {
 	/// Takes a string of code and returns a preprocessed version of the code
 	///
 	/// # Errors
-	/// If the code is invalid then an error will be thrown
+	/// If the code is invalid then an array of diagnostics will be thrown
 	#[wasm_bindgen(js_name = "preprocessCode")]
-	pub fn preprocess_code(&self, code: String) -> Result<JsValue, String> {
-		let preprocessed = self.inner.preprocess_code(code)?;
-		serde_wasm_bindgen::to_value(&preprocessed).map_err(|err| err.to_string())
+	pub fn preprocess_code(&self, code: String) -> Result<JsValue, JsValue> {
+		let preprocessed = self.inner.preprocess_code(code).map_err(to_js_error)?;
+		serde_wasm_bindgen::to_value(&preprocessed).map_err(|err| JsValue::from(err.to_string()))
 	}
 
 	/// Scans the given preprocessed code
 	/// Takes a Code object and returns an array of tokens
 	///
 	/// # Errors
-	/// If the code is invalid then an error will be thrown
+	/// If the code is invalid then an array of diagnostics will be thrown
 	#[wasm_bindgen(js_name = "scanPreprocessed")]
-	pub fn scan_preprocessed(&self, code: JsValue) -> Result<JsValue, String> {
-		let code: Code = serde_wasm_bindgen::from_value(code).map_err(|err| err.to_string())?;
-		let scanned = self.inner.scan_preprocessed(code)?;
-		serde_wasm_bindgen::to_value(&scanned).map_err(|err| err.to_string())
+	pub fn scan_preprocessed(&self, code: JsValue) -> Result<JsValue, JsValue> {
+		let code: Code =
+			serde_wasm_bindgen::from_value(code).map_err(|err| JsValue::from(err.to_string()))?;
+		let scanned = self.inner.scan_preprocessed(code).map_err(to_js_error)?;
+		serde_wasm_bindgen::to_value(&scanned).map_err(|err| JsValue::from(err.to_string()))
 	}
 
 	/// Scans the given code
 	/// Takes a string of code and returns an array of tokens
 	///
 	/// # Errors
-	/// If the code is invalid then an error will be thrown
+	/// If the code is invalid then an array of diagnostics will be thrown
 	#[wasm_bindgen(js_name = "scanCode")]
-	pub fn scan_code(&self, code: String) -> Result<JsValue, String> {
-		let scanned = self.inner.scan_code(code)?;
-		serde_wasm_bindgen::to_value(&scanned).map_err(|err| err.to_string())
+	pub fn scan_code(&self, code: String) -> Result<JsValue, JsValue> {
+		let scanned = self.inner.scan_code(code).map_err(to_js_error)?;
+		serde_wasm_bindgen::to_value(&scanned).map_err(|err| JsValue::from(err.to_string()))
 	}
 
 	/// Parses the given preprocessed code
 	/// Takes a Code object and returns an Expression object
 	///
 	/// # Errors
-	/// If the code is invalid then an error will be thrown
+	/// If the code is invalid then an array of diagnostics will be thrown
 	#[wasm_bindgen(js_name = "parsePreprocessed")]
-	pub fn parse_preprocessed(&self, code: JsValue) -> Result<JsValue, String> {
-		let code: Code = serde_wasm_bindgen::from_value(code).map_err(|err| err.to_string())?;
-		let parsed = self.inner.parse_preprocessed(code)?;
-		serde_wasm_bindgen::to_value(&parsed).map_err(|err| err.to_string())
+	pub fn parse_preprocessed(&self, code: JsValue) -> Result<JsValue, JsValue> {
+		let code: Code =
+			serde_wasm_bindgen::from_value(code).map_err(|err| JsValue::from(err.to_string()))?;
+		let parsed = self.inner.parse_preprocessed(code).map_err(to_js_error)?;
+		serde_wasm_bindgen::to_value(&parsed).map_err(|err| JsValue::from(err.to_string()))
 	}
 
 	/// Parses the given tokens
 	/// Takes an array of tokens and returns an Expression object
 	///
 	/// # Errors
-	/// If the code is invalid then an error will be thrown
+	/// If the code is invalid then an array of diagnostics will be thrown
 	#[wasm_bindgen(js_name = "parseTokens")]
-	pub fn parse_tokens(&self, tokens: JsValue) -> Result<JsValue, String> {
+	pub fn parse_tokens(&self, tokens: JsValue) -> Result<JsValue, JsValue> {
 		let tokens: Vec<Token> =
-			serde_wasm_bindgen::from_value(tokens).map_err(|err| err.to_string())?;
-		let parsed = self.inner.parse_tokens(tokens)?;
-		serde_wasm_bindgen::to_value(&parsed).map_err(|err| err.to_string())
+			serde_wasm_bindgen::from_value(tokens).map_err(|err| JsValue::from(err.to_string()))?;
+		let parsed = self.inner.parse_tokens(tokens).map_err(to_js_error)?;
+		serde_wasm_bindgen::to_value(&parsed).map_err(|err| JsValue::from(err.to_string()))
 	}
 
 	/// Parses the given code
 	/// Takes a string of code and returns an Expression object
 	///
 	/// # Errors
-	/// If the code is invalid then an error will be thrown
+	/// If the code is invalid then an array of diagnostics will be thrown
 	#[wasm_bindgen(js_name = "parseCode")]
-	pub fn parse_code(&self, code: String) -> Result<JsValue, String> {
-		let parsed = self.inner.parse_code(code)?;
-		serde_wasm_bindgen::to_value(&parsed).map_err(|err| err.to_string())
+	pub fn parse_code(&self, code: String) -> Result<JsValue, JsValue> {
+		let parsed = self.inner.parse_code(code).map_err(to_js_error)?;
+		serde_wasm_bindgen::to_value(&parsed).map_err(|err| JsValue::from(err.to_string()))
 	}
 
 	/// Compiles the given preprocessed code
 	/// Takes a Code object and returns a string of Lua code
 	///
 	/// # Errors
-	/// If the code is invalid then an error will be thrown
+	/// If the code is invalid then an array of diagnostics will be thrown
 	#[wasm_bindgen(js_name = "compilePreprocessed")]
-	pub fn compile_preprocessed(&self, code: JsValue) -> Result<String, String> {
-		let code: Code = serde_wasm_bindgen::from_value(code).map_err(|err| err.to_string())?;
+	pub fn compile_preprocessed(&self, code: JsValue) -> Result<String, JsValue> {
+		let code: Code =
+			serde_wasm_bindgen::from_value(code).map_err(|err| JsValue::from(err.to_string()))?;
 
-		self.inner.compile_preprocessed(code)
+		self.inner.compile_preprocessed(code).map_err(to_js_error)
 	}
 
 	/// Compiles the given tokens
 	/// Takes an array of tokens and returns a string of Lua code
 	///
 	/// # Errors
-	/// If the code is invalid then an error will be thrown
+	/// If the code is invalid then an array of diagnostics will be thrown
 	#[wasm_bindgen(js_name = "compileTokens")]
-	pub fn compile_tokens(&self, tokens: JsValue) -> Result<String, String> {
+	pub fn compile_tokens(&self, tokens: JsValue) -> Result<String, JsValue> {
 		let tokens: Vec<Token> =
-			serde_wasm_bindgen::from_value(tokens).map_err(|err| err.to_string())?;
+			serde_wasm_bindgen::from_value(tokens).map_err(|err| JsValue::from(err.to_string()))?;
 
-		self.inner.compile_tokens(tokens)
+		self.inner.compile_tokens(tokens).map_err(to_js_error)
 	}
 
 	/// Compiles the given parse result
 	/// Takes a tuple of an Expression object and the static variables and returns a string of Lua code
 	///
 	/// # Errors
-	/// If the code is invalid then an error will be thrown
+	/// If the code is invalid then an array of diagnostics will be thrown
 	#[wasm_bindgen(js_name = "compileAst")]
-	pub fn compile_ast(&self, parse_result: JsValue) -> Result<String, String> {
-		let parse_result: (Expression, String) =
-			serde_wasm_bindgen::from_value(parse_result).map_err(|err| err.to_string())?;
+	pub fn compile_ast(&self, parse_result: JsValue) -> Result<String, JsValue> {
+		let parse_result: (Expression, String) = serde_wasm_bindgen::from_value(parse_result)
+			.map_err(|err| JsValue::from(err.to_string()))?;
 
-		self.inner.compile_ast(parse_result)
+		self.inner.compile_ast(parse_result).map_err(to_js_error)
 	}
 
 	/// Compiles the given code
 	/// Takes a string of code and returns a string of Lua code
 	///
 	/// # Errors
-	/// If the code is invalid then an error will be thrown
+	/// If the code is invalid then an array of diagnostics will be thrown
 	#[wasm_bindgen(js_name = "compileCode")]
-	pub fn compile_code(&self, code: String) -> Result<String, String> {
-		self.inner.compile_code(code)
+	pub fn compile_code(&self, code: String) -> Result<String, JsValue> {
+		self.inner.compile_code(code).map_err(to_js_error)
 	}
 }
 